@@ -1,43 +1,307 @@
-//! This example demonstrates how to use the `odra-cli` tool to deploy and interact with a smart contract.
+//! Command line tool for deploying the CasperFlow protocol and driving it
+//! through operational scenarios against a local or live network.
 
-use casperflow_contracts::flipper::Flipper;
-use odra::host::{HostEnv, NoArgs};
+use casperflow_contracts::billing_engine::{BillingEngine, Error as BillingError};
+use casperflow_contracts::registry::{Component, Registry};
+use casperflow_contracts::stake_to_pay::{Error as StakeError, StakeToPay};
+use casperflow_contracts::subscription_manager::{Error as SubscriptionError, SubscriptionManager};
+use casperflow_contracts::time::Duration;
+use casperflow_contracts::usage_meter::UsageMeter;
+use odra::casper_types::U512;
+use odra::host::{HostEnv, HostRef, InstallConfig, NoArgs};
+use odra::prelude::{Address, OdraError};
 use odra::schema::casper_contract_schema::NamedCLType;
 use odra_cli::{
     deploy::DeployScript,
     scenario::{Args, Error, Scenario, ScenarioMetadata},
     CommandArg, ContractProvider, DeployedContractsContainer, DeployerExt,
-    OdraCli, 
+    OdraCli,
 };
 
-/// Deploys the `Flipper` and adds it to the container.
-pub struct FlipperDeployScript;
+/// Gas limit for installing each contract
+const DEPLOY_GAS: u64 = 350_000_000_000;
+/// Gas limit for wiring calls and scenario transactions
+const CALL_GAS: u64 = 10_000_000_000;
 
-impl DeployScript for FlipperDeployScript {
+/// Deploys the four protocol contracts and a `Registry`, registers each
+/// contract in the registry and points every contract at it.
+///
+/// Contracts already in the container are reused, so the script can be
+/// re-run to finish an interrupted deployment.
+pub struct ProtocolDeployScript;
+
+impl DeployScript for ProtocolDeployScript {
     fn deploy(
         &self,
         env: &HostEnv,
         container: &mut DeployedContractsContainer
     ) -> Result<(), odra_cli::deploy::Error> {
-        let _flipper = Flipper::load_or_deploy(
-            &env,
+        let mut registry = Registry::load_or_deploy_with_cfg(
+            env,
+            NoArgs,
+            InstallConfig::upgradable::<Registry>(),
+            container,
+            DEPLOY_GAS
+        )?;
+        let mut manager = SubscriptionManager::load_or_deploy_with_cfg(
+            env,
+            NoArgs,
+            InstallConfig::upgradable::<SubscriptionManager>(),
+            container,
+            DEPLOY_GAS
+        )?;
+        let mut meter = UsageMeter::load_or_deploy_with_cfg(
+            env,
+            NoArgs,
+            InstallConfig::upgradable::<UsageMeter>(),
+            container,
+            DEPLOY_GAS
+        )?;
+        let mut engine = BillingEngine::load_or_deploy_with_cfg(
+            env,
+            NoArgs,
+            InstallConfig::upgradable::<BillingEngine>(),
+            container,
+            DEPLOY_GAS
+        )?;
+        let mut stake_to_pay = StakeToPay::load_or_deploy_with_cfg(
+            env,
             NoArgs,
+            InstallConfig::upgradable::<StakeToPay>(),
             container,
-            350_000_000_000 // Adjust gas limit as needed
+            DEPLOY_GAS
         )?;
 
+        env.set_gas(CALL_GAS);
+        let components = [
+            (Component::SubscriptionManager, manager.address()),
+            (Component::UsageMeter, meter.address()),
+            (Component::BillingEngine, engine.address()),
+            (Component::StakeToPay, stake_to_pay.address()),
+        ];
+        for (component, address) in components {
+            // Replacing an existing entry only queues a timelocked change
+            if registry.get_address(component) != Some(address) {
+                registry.try_register(component, address)?;
+            }
+        }
+
+        let registry = registry.address();
+        if manager.get_registry().is_none() {
+            manager.try_set_registry(registry)?;
+        }
+        if meter.get_registry().is_none() {
+            meter.try_set_registry(registry)?;
+        }
+        if engine.get_registry().is_none() {
+            engine.try_set_registry(registry)?;
+        }
+        if stake_to_pay.get_registry().is_none() {
+            stake_to_pay.try_set_registry(registry)?;
+        }
+
         Ok(())
     }
 }
 
-/// Scenario that flips the state of the deployed `Flipper` contract a specified number of times.
-pub struct FlippingScenario;
+/// Creates a plan owned by the caller and authorizes the caller to record
+/// usage for it.
+pub struct CreatePlanScenario;
 
-impl Scenario for FlippingScenario {
+impl Scenario for CreatePlanScenario {
+    fn args(&self) -> Vec<CommandArg> {
+        vec![
+            CommandArg::new("name", "Plan name", NamedCLType::String),
+            CommandArg::new("base_price", "Price per billing cycle, in motes", NamedCLType::U512),
+            CommandArg::new("usage_price", "Price per usage unit, in motes", NamedCLType::U512),
+            CommandArg::new("cycle_days", "Billing cycle length in days", NamedCLType::U64),
+        ]
+    }
+
+    fn run(
+        &self,
+        env: &HostEnv,
+        container: &DeployedContractsContainer,
+        args: Args
+    ) -> Result<(), Error> {
+        let mut manager = container.contract_ref::<SubscriptionManager>(env)?;
+        let mut meter = container.contract_ref::<UsageMeter>(env)?;
+        let name = args.get_single::<String>("name")?;
+        let base_price = args.get_single::<U512>("base_price")?;
+        let usage_price = args.get_single::<U512>("usage_price")?;
+        let cycle = Duration::from_days(args.get_single::<u64>("cycle_days")?);
+
+        env.set_gas(CALL_GAS);
+        let plan_id = manager.try_create_plan(name, base_price, usage_price, cycle)?;
+        meter.try_authorize_recorder(plan_id, env.caller())?;
+
+        println!("Created plan {plan_id}");
+        Ok(())
+    }
+}
+
+impl ScenarioMetadata for CreatePlanScenario {
+    const NAME: &'static str = "create-plan";
+    const DESCRIPTION: &'static str =
+        "Creates a plan owned by the caller and authorizes the caller to record its usage";
+}
+
+/// Subscribes the caller to a plan, paying the first cycle from the wallet.
+pub struct SubscribeScenario;
+
+impl Scenario for SubscribeScenario {
+    fn args(&self) -> Vec<CommandArg> {
+        vec![
+            CommandArg::new("plan_id", "Plan to subscribe to", NamedCLType::U64),
+            CommandArg::new("auto_renew", "Renew automatically", NamedCLType::Bool),
+            CommandArg::new("payment_method", "0 = wallet, 1 = staked", NamedCLType::U8),
+        ]
+    }
+
+    fn run(
+        &self,
+        env: &HostEnv,
+        container: &DeployedContractsContainer,
+        args: Args
+    ) -> Result<(), Error> {
+        let manager = container.contract_ref::<SubscriptionManager>(env)?;
+        let plan_id = args.get_single::<u64>("plan_id")?;
+        let auto_renew = args.get_single::<bool>("auto_renew")?;
+        let payment_method = args.get_single::<u8>("payment_method")?;
+        let plan = manager
+            .get_plan(plan_id)
+            .ok_or::<OdraError>(SubscriptionError::PlanNotFound.into())?;
+
+        env.set_gas(CALL_GAS);
+        let subscription_id = manager
+            .with_tokens(plan.base_price)
+            .try_subscribe(plan_id, auto_renew, payment_method)?;
+
+        println!("Subscribed to plan {plan_id} as subscription {subscription_id}");
+        Ok(())
+    }
+}
+
+impl ScenarioMetadata for SubscribeScenario {
+    const NAME: &'static str = "subscribe";
+    const DESCRIPTION: &'static str =
+        "Subscribes the caller to a plan, paying the first cycle from the wallet";
+}
+
+/// Records usage for a subscription as the plan's recorder.
+pub struct RecordUsageScenario;
+
+impl Scenario for RecordUsageScenario {
+    fn args(&self) -> Vec<CommandArg> {
+        vec![
+            CommandArg::new("subscription_id", "Subscription the usage belongs to", NamedCLType::U64),
+            CommandArg::new("metric", "Metric name, e.g. api_calls", NamedCLType::String),
+            CommandArg::new("units", "Units of usage", NamedCLType::U64),
+        ]
+    }
+
+    fn run(
+        &self,
+        env: &HostEnv,
+        container: &DeployedContractsContainer,
+        args: Args
+    ) -> Result<(), Error> {
+        let manager = container.contract_ref::<SubscriptionManager>(env)?;
+        let mut meter = container.contract_ref::<UsageMeter>(env)?;
+        let subscription_id = args.get_single::<u64>("subscription_id")?;
+        let metric = args.get_single::<String>("metric")?;
+        let units = args.get_single::<u64>("units")?;
+        let subscription = manager
+            .get_subscription(subscription_id)
+            .ok_or::<OdraError>(SubscriptionError::SubscriptionNotFound.into())?;
+
+        env.set_gas(CALL_GAS);
+        let record_id = meter.try_record_usage(subscription_id, subscription.plan_id, metric, units)?;
+
+        println!("Recorded {units} units for subscription {subscription_id} (record {record_id})");
+        Ok(())
+    }
+}
+
+impl ScenarioMetadata for RecordUsageScenario {
+    const NAME: &'static str = "record-usage";
+    const DESCRIPTION: &'static str = "Records usage for a subscription";
+}
+
+/// Closes a subscription's current billing period and invoices it.
+pub struct RunBillingCycleScenario;
+
+impl Scenario for RunBillingCycleScenario {
+    fn args(&self) -> Vec<CommandArg> {
+        vec![CommandArg::new(
+            "subscription_id",
+            "Subscription to bill",
+            NamedCLType::U64,
+        )]
+    }
+
+    fn run(
+        &self,
+        env: &HostEnv,
+        container: &DeployedContractsContainer,
+        args: Args
+    ) -> Result<(), Error> {
+        let manager = container.contract_ref::<SubscriptionManager>(env)?;
+        let mut meter = container.contract_ref::<UsageMeter>(env)?;
+        let mut engine = container.contract_ref::<BillingEngine>(env)?;
+        let subscription_id = args.get_single::<u64>("subscription_id")?;
+        let subscription = manager
+            .get_subscription(subscription_id)
+            .ok_or::<OdraError>(SubscriptionError::SubscriptionNotFound.into())?;
+        let plan = manager
+            .get_plan(subscription.plan_id)
+            .ok_or::<OdraError>(SubscriptionError::PlanNotFound.into())?;
+
+        // Periods start at the subscription start until usage opens a later one
+        let period_start = meter
+            .get_current_period_start(subscription_id)
+            .unwrap_or(subscription.started_at);
+        let period_end = period_start + plan.billing_cycle;
+
+        env.set_gas(CALL_GAS);
+        let units = meter.try_close_period(subscription_id, period_end)?;
+        let invoice_id = engine.try_create_invoice(
+            subscription_id,
+            plan.id,
+            subscription.subscriber,
+            plan.merchant,
+            plan.base_price,
+            plan.usage_price,
+            units,
+            period_start,
+            period_end,
+        )?;
+        let invoice = engine
+            .get_invoice(invoice_id)
+            .ok_or::<OdraError>(BillingError::InvoiceNotFound.into())?;
+
+        println!(
+            "Invoice {invoice_id}: {units} units, total {} motes",
+            invoice.total_amount
+        );
+        Ok(())
+    }
+}
+
+impl ScenarioMetadata for RunBillingCycleScenario {
+    const NAME: &'static str = "run-billing-cycle";
+    const DESCRIPTION: &'static str =
+        "Closes a subscription's current billing period and creates its invoice";
+}
+
+/// Pays one of the caller's invoices from their staking rewards.
+pub struct PayFromStakeScenario;
+
+impl Scenario for PayFromStakeScenario {
     fn args(&self) -> Vec<CommandArg> {
         vec![CommandArg::new(
-            "number",
-            "The number of times to flip the state",
+            "invoice_id",
+            "Invoice to pay",
             NamedCLType::U64,
         )]
     }
@@ -48,31 +312,102 @@ impl Scenario for FlippingScenario {
         container: &DeployedContractsContainer,
         args: Args
     ) -> Result<(), Error> {
-        let mut contract = container.contract_ref::<Flipper>(env)?;
-        let n = args.get_single::<u64>("name")?;
+        let engine = container.contract_ref::<BillingEngine>(env)?;
+        let mut stake_to_pay = container.contract_ref::<StakeToPay>(env)?;
+        let invoice_id = args.get_single::<u64>("invoice_id")?;
+        let invoice = engine
+            .get_invoice(invoice_id)
+            .ok_or::<OdraError>(BillingError::InvoiceNotFound.into())?;
 
-        env.set_gas(50_000_000);
-        for _ in 0..n {
-            contract.try_flip()?;
+        if invoice.subscriber != env.caller() {
+            return Err(OdraError::from(StakeError::NotInvoiceSubscriber).into());
         }
 
+        env.set_gas(CALL_GAS);
+        stake_to_pay.try_pay_invoice_from_rewards(invoice_id)?;
+
+        println!(
+            "Paid invoice {invoice_id} ({} motes) from rewards; {} motes of rewards left",
+            invoice.total_amount,
+            stake_to_pay.get_available_rewards(env.caller())
+        );
+        Ok(())
+    }
+}
+
+impl ScenarioMetadata for PayFromStakeScenario {
+    const NAME: &'static str = "pay-from-stake";
+    const DESCRIPTION: &'static str = "Pays one of the caller's invoices from staking rewards";
+}
+
+/// Prints a merchant's subscriber, revenue and receivables figures.
+pub struct MerchantReportScenario;
+
+impl Scenario for MerchantReportScenario {
+    fn args(&self) -> Vec<CommandArg> {
+        vec![CommandArg::new(
+            "merchant",
+            "Merchant account",
+            NamedCLType::Key,
+        )]
+    }
+
+    fn run(
+        &self,
+        env: &HostEnv,
+        container: &DeployedContractsContainer,
+        args: Args
+    ) -> Result<(), Error> {
+        let manager = container.contract_ref::<SubscriptionManager>(env)?;
+        let engine = container.contract_ref::<BillingEngine>(env)?;
+        let merchant = args.get_single::<Address>("merchant")?;
+
+        let period = manager.current_analytics_period();
+        let stats = manager.get_merchant_stats(merchant);
+        let churn = manager.get_merchant_period_stats(merchant, period);
+        let revenue = engine.get_merchant_period_revenue(merchant, period);
+        let receivables = engine.get_merchant_receivables(merchant);
+
+        println!("Plans:                {}", manager.get_merchant_plan_count(merchant));
+        println!("Active subscribers:   {} of {}", stats.active_subscribers, stats.total_subscribers);
+        println!("MRR:                  {} motes", stats.mrr);
+        println!("ARPU:                 {} motes", manager.get_merchant_arpu(merchant));
+        println!("Period {period}:");
+        println!("  New / churned:      {} / {}", churn.new_subscribers, churn.churned_subscribers);
+        println!("  Churn:              {} bps", manager.get_merchant_churn_bps(merchant, period));
+        println!("  Invoiced:           {} motes", revenue.invoiced_amount);
+        println!("  Collected:          {} motes", revenue.collected_amount);
+        println!("Lifetime revenue:     {} motes", engine.get_merchant_revenue(merchant));
+        println!(
+            "Outstanding:          {} motes in {} invoices",
+            receivables.outstanding_amount, receivables.outstanding_invoices
+        );
         Ok(())
     }
 }
 
-impl ScenarioMetadata for FlippingScenario {
-    const NAME: &'static str = "flip";
+impl ScenarioMetadata for MerchantReportScenario {
+    const NAME: &'static str = "merchant-report";
     const DESCRIPTION: &'static str =
-        "Flips the state of the deployed flipper contract a specified number of times";
+        "Prints a merchant's subscribers, revenue and outstanding invoices";
 }
 
 /// Main function to run the CLI tool.
 pub fn main() {
     OdraCli::new()
-        .about("CLI tool for casperflow_contracts smart contract")
-        .deploy(FlipperDeployScript)
-        .contract::<Flipper>()
-        .scenario(FlippingScenario)
+        .about("CLI tool for the CasperFlow protocol contracts")
+        .deploy(ProtocolDeployScript)
+        .contract::<Registry>()
+        .contract::<SubscriptionManager>()
+        .contract::<UsageMeter>()
+        .contract::<BillingEngine>()
+        .contract::<StakeToPay>()
+        .scenario(CreatePlanScenario)
+        .scenario(SubscribeScenario)
+        .scenario(RecordUsageScenario)
+        .scenario(RunBillingCycleScenario)
+        .scenario(PayFromStakeScenario)
+        .scenario(MerchantReportScenario)
         .build()
         .run();
 }
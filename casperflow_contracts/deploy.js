@@ -4,7 +4,7 @@
 const fs = require('fs');
 const path = require('path');
 const https = require('https');
-const { Keys, RuntimeArgs, DeployUtil } = require('casper-js-sdk');
+const { CLValueBuilder, Keys, RuntimeArgs, DeployUtil } = require('casper-js-sdk');
 
 // Configuration
 const CHAIN_NAME = 'casper-test';
@@ -79,9 +79,15 @@ async function deploy() {
         CHAIN_NAME
     );
 
+    // Install as an upgradable package so fixes can ship without losing state
     const session = DeployUtil.ExecutableDeployItem.newModuleBytes(
         wasmData,
-        RuntimeArgs.fromMap({})
+        RuntimeArgs.fromMap({
+            odra_cfg_package_hash_key_name: CLValueBuilder.string('SubscriptionManager'),
+            odra_cfg_allow_key_override: CLValueBuilder.bool(false),
+            odra_cfg_is_upgradable: CLValueBuilder.bool(true),
+            odra_cfg_is_upgrade: CLValueBuilder.bool(false),
+        })
     );
 
     const payment = DeployUtil.standardPayment(PAYMENT_AMOUNT);
@@ -56,6 +56,11 @@ echo "Deploying StakeToPay..."
 STAKE_TO_PAY_HASH=$(cargo odra deploy -n testnet -c StakeToPay)
 echo "✅ StakeToPay deployed: $STAKE_TO_PAY_HASH"
 
+# Deploy Registry
+echo "Deploying Registry..."
+REGISTRY_HASH=$(cargo odra deploy -n testnet -c Registry)
+echo "✅ Registry deployed: $REGISTRY_HASH"
+
 echo ""
 echo "=================================="
 echo "🎉 All contracts deployed!"
@@ -65,8 +70,9 @@ echo "  SubscriptionManager: $SUBSCRIPTION_MANAGER_HASH"
 echo "  UsageMeter:          $USAGE_METER_HASH"
 echo "  BillingEngine:       $BILLING_ENGINE_HASH"
 echo "  StakeToPay:          $STAKE_TO_PAY_HASH"
+echo "  Registry:            $REGISTRY_HASH"
 echo ""
 echo "Next steps:"
 echo "  1. Update SDK with contract hashes"
-echo "  2. Set contract references (billing_engine, stake_to_pay)"
+echo "  2. Register each contract in the Registry and call set_registry on it"
 echo "  3. Run integration tests"
@@ -0,0 +1,264 @@
+//! Role-based access control shared by all CasperFlow contracts.
+//!
+//! [`AccessControl`] is composed into each contract as a submodule. It keeps
+//! a single owner, transferred in two steps, and a set of [`Role`] grants:
+//!
+//! - The owner implicitly holds every role, so handing over ownership hands
+//!   over full control in one step.
+//! - `Admin` holders grant and revoke roles on behalf of the owner.
+//! - Any holder may renounce their own role, e.g. when rotating a keeper key.
+
+use odra::prelude::*;
+
+/// Permission held by an account
+#[odra::odra_type]
+#[derive(Copy)]
+pub enum Role {
+    /// Grants/revokes roles and sets contract addresses
+    Admin,
+    /// Sets the protocol fee and its recipient
+    FeeManager,
+    /// Sets the StakeToPay reward rate
+    RateManager,
+    /// Runs billing operations such as failing unpaid invoices
+    Keeper,
+    /// Pauses and unpauses the protocol
+    Pauser,
+}
+
+/// Errors reverted by access checks (codes 500-599)
+#[odra::odra_error]
+pub enum Error {
+    /// Caller does not hold the required role
+    MissingRole = 500,
+    /// Caller is not the owner
+    NotOwner = 501,
+    /// Caller is not the pending owner
+    NotPendingOwner = 502,
+}
+
+/// Events
+pub mod events {
+    use super::*;
+
+    #[odra::event]
+    pub struct RoleGranted {
+        pub role: Role,
+        pub account: Address,
+        pub sender: Address,
+    }
+
+    /// Also emitted on renounce, with `sender == account`
+    #[odra::event]
+    pub struct RoleRevoked {
+        pub role: Role,
+        pub account: Address,
+        pub sender: Address,
+    }
+
+    #[odra::event]
+    pub struct OwnershipTransferStarted {
+        pub previous_owner: Address,
+        pub new_owner: Address,
+    }
+
+    #[odra::event]
+    pub struct OwnershipTransferred {
+        pub previous_owner: Option<Address>,
+        pub new_owner: Address,
+    }
+}
+
+/// Owner and role registry
+#[odra::module(events = [
+    events::RoleGranted,
+    events::RoleRevoked,
+    events::OwnershipTransferStarted,
+    events::OwnershipTransferred
+], errors = Error)]
+pub struct AccessControl {
+    /// Current owner
+    owner: Var<Option<Address>>,
+    /// Owner nominated by `transfer_ownership`, pending acceptance
+    pending_owner: Var<Option<Address>>,
+    /// (Role, Account) -> whether the role is granted
+    roles: Mapping<(Role, Address), bool>,
+}
+
+#[odra::module]
+impl AccessControl {
+    /// Set the initial owner
+    pub fn init(&mut self, owner: Address) {
+        self.owner.set(Some(owner));
+        self.env().emit_event(events::OwnershipTransferred {
+            previous_owner: None,
+            new_owner: owner,
+        });
+    }
+
+    // ============ ROLE FUNCTIONS ============
+
+    /// Grant `role` to `account` (owner or admin only)
+    pub fn grant_role(&mut self, role: Role, account: Address) {
+        let sender = self.env().caller();
+        self.require_role(Role::Admin);
+
+        if !self.roles.get(&(role, account)).unwrap_or_default() {
+            self.roles.set(&(role, account), true);
+            self.env().emit_event(events::RoleGranted { role, account, sender });
+        }
+    }
+
+    /// Revoke `role` from `account` (owner or admin only)
+    pub fn revoke_role(&mut self, role: Role, account: Address) {
+        let sender = self.env().caller();
+        self.require_role(Role::Admin);
+        self.remove_role(role, account, sender);
+    }
+
+    /// Give up a role held by the caller
+    pub fn renounce_role(&mut self, role: Role) {
+        let caller = self.env().caller();
+        self.remove_role(role, caller, caller);
+    }
+
+    // ============ OWNERSHIP FUNCTIONS ============
+
+    /// Nominate a new owner; takes effect once they call `accept_ownership`
+    pub fn transfer_ownership(&mut self, new_owner: Address) {
+        let caller = self.env().caller();
+        if Some(caller) != self.owner.get_or_default() {
+            self.env().revert(Error::NotOwner);
+        }
+
+        self.pending_owner.set(Some(new_owner));
+        self.env().emit_event(events::OwnershipTransferStarted {
+            previous_owner: caller,
+            new_owner,
+        });
+    }
+
+    /// Accept a pending ownership transfer
+    pub fn accept_ownership(&mut self) {
+        let caller = self.env().caller();
+        if Some(caller) != self.pending_owner.get_or_default() {
+            self.env().revert(Error::NotPendingOwner);
+        }
+
+        let previous_owner = self.owner.get_or_default();
+        self.owner.set(Some(caller));
+        self.pending_owner.set(None);
+        self.env().emit_event(events::OwnershipTransferred {
+            previous_owner,
+            new_owner: caller,
+        });
+    }
+
+    // ============ VIEW FUNCTIONS ============
+
+    /// Whether `account` holds `role`, directly or as owner
+    pub fn has_role(&self, role: Role, account: Address) -> bool {
+        Some(account) == self.owner.get_or_default()
+            || self.roles.get(&(role, account)).unwrap_or_default()
+    }
+
+    /// Get the current owner
+    pub fn owner(&self) -> Option<Address> {
+        self.owner.get_or_default()
+    }
+
+    /// Get the owner nominated by `transfer_ownership`, if any
+    pub fn pending_owner(&self) -> Option<Address> {
+        self.pending_owner.get_or_default()
+    }
+}
+
+impl AccessControl {
+    /// Revert unless the caller holds `role`
+    pub fn require_role(&self, role: Role) {
+        if !self.has_role(role, self.env().caller()) {
+            self.env().revert(Error::MissingRole);
+        }
+    }
+
+    fn remove_role(&mut self, role: Role, account: Address, sender: Address) {
+        if self.roles.get(&(role, account)).unwrap_or_default() {
+            self.roles.set(&(role, account), false);
+            self.env().emit_event(events::RoleRevoked { role, account, sender });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use odra::host::Deployer;
+
+    #[test]
+    fn test_grant_revoke_renounce() {
+        let env = odra_test::env();
+        let owner = env.get_account(0);
+        let admin = env.get_account(1);
+        let keeper = env.get_account(2);
+        let mut access = AccessControl::deploy(&env, AccessControlInitArgs { owner });
+
+        // The owner holds every role without a grant
+        assert!(access.has_role(Role::Pauser, owner));
+        assert!(!access.has_role(Role::Admin, admin));
+
+        access.grant_role(Role::Admin, admin);
+        assert!(env.emitted_event(
+            &access,
+            events::RoleGranted { role: Role::Admin, account: admin, sender: owner }
+        ));
+
+        // Admins manage roles too
+        env.set_caller(admin);
+        access.grant_role(Role::Keeper, keeper);
+        assert!(access.has_role(Role::Keeper, keeper));
+        access.revoke_role(Role::Keeper, keeper);
+        assert!(!access.has_role(Role::Keeper, keeper));
+
+        // Other accounts cannot
+        env.set_caller(keeper);
+        assert_eq!(
+            access.try_grant_role(Role::Keeper, keeper),
+            Err(Error::MissingRole.into())
+        );
+
+        env.set_caller(admin);
+        access.renounce_role(Role::Admin);
+        assert!(!access.has_role(Role::Admin, admin));
+        assert!(env.emitted_event(
+            &access,
+            events::RoleRevoked { role: Role::Admin, account: admin, sender: admin }
+        ));
+    }
+
+    #[test]
+    fn test_two_step_ownership() {
+        let env = odra_test::env();
+        let owner = env.get_account(0);
+        let new_owner = env.get_account(1);
+        let mut access = AccessControl::deploy(&env, AccessControlInitArgs { owner });
+
+        env.set_caller(new_owner);
+        assert_eq!(access.try_transfer_ownership(new_owner), Err(Error::NotOwner.into()));
+
+        env.set_caller(owner);
+        access.transfer_ownership(new_owner);
+        assert_eq!(access.owner(), Some(owner));
+        assert_eq!(access.pending_owner(), Some(new_owner));
+
+        // Only the nominee can accept
+        env.set_caller(env.get_account(2));
+        assert_eq!(access.try_accept_ownership(), Err(Error::NotPendingOwner.into()));
+
+        env.set_caller(new_owner);
+        access.accept_ownership();
+        assert_eq!(access.owner(), Some(new_owner));
+        assert_eq!(access.pending_owner(), None);
+        assert!(access.has_role(Role::Admin, new_owner));
+        assert!(!access.has_role(Role::Admin, owner));
+    }
+}
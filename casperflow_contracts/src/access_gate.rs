@@ -0,0 +1,310 @@
+//! Subscription gate for third-party contracts.
+//!
+//! Casper dApps can require an active CasperFlow subscription before running
+//! an entry point. [`AccessGate`] is composed into the dApp as a submodule:
+//!
+//! - `require_plan` / `require_merchant` revert unless the caller holds an
+//!   active, non-past-due subscription to a plan, or to any plan of a
+//!   merchant, as answered by the SubscriptionManager.
+//! - `require_plan_and_debit` also records usage against the subscription,
+//!   so metered calls are invoiced at the next renewal. The UsageMeter only
+//!   accepts records from authorized recorders, so the plan's merchant must
+//!   authorize the dApp contract itself.
+//!
+//! Contracts that only need the checks can call the SubscriptionManager
+//! through [`CasperFlowAccess`] instead of embedding the gate. Configuring the
+//! gate is gated by the parent contract; this module only stores addresses.
+
+use odra::prelude::*;
+
+use crate::subscription_manager::Subscription;
+
+/// Access checks answered by the SubscriptionManager
+#[odra::external_contract]
+pub trait CasperFlowAccess {
+    /// Whether `subscriber` holds an active, non-past-due subscription to a plan
+    fn has_access(&self, subscriber: Address, plan_id: u64) -> bool;
+    /// Whether `subscriber` holds an active, non-past-due subscription to any of a merchant's plans
+    fn has_merchant_access(&self, subscriber: Address, merchant: Address) -> bool;
+    /// A user's most recent subscription to a plan
+    fn get_user_plan_subscription(&self, user: Address, plan_id: u64) -> Option<Subscription>;
+}
+
+/// Usage recording entry point of the UsageMeter
+#[odra::external_contract]
+pub trait CasperFlowUsage {
+    /// Record usage for a subscription; the caller must be an authorized recorder for the plan
+    fn record_usage(&mut self, subscription_id: u64, plan_id: u64, metric: String, units: u64) -> u64;
+}
+
+/// Errors reverted by the gate (codes 1000-1099)
+#[odra::odra_error]
+pub enum Error {
+    /// Caller has no active, non-past-due subscription
+    NoAccess = 1000,
+    /// SubscriptionManager address has not been configured
+    SubscriptionManagerNotSet = 1001,
+    /// UsageMeter address has not been configured
+    UsageMeterNotSet = 1002,
+}
+
+/// Subscription check for a dApp's entry points
+#[odra::module(errors = Error)]
+pub struct AccessGate {
+    /// SubscriptionManager answering access checks
+    subscription_manager: Var<Option<Address>>,
+    /// UsageMeter debited by metered calls, if any
+    usage_meter: Var<Option<Address>>,
+}
+
+#[odra::module]
+impl AccessGate {
+    /// Whether `subscriber` may call entry points gated on `plan_id`
+    pub fn has_plan_access(&self, subscriber: Address, plan_id: u64) -> bool {
+        self.manager().has_access(subscriber, plan_id)
+    }
+
+    /// Whether `subscriber` may call entry points gated on `merchant`'s plans
+    pub fn has_merchant_access(&self, subscriber: Address, merchant: Address) -> bool {
+        self.manager().has_merchant_access(subscriber, merchant)
+    }
+
+    /// Get the SubscriptionManager answering access checks
+    pub fn get_subscription_manager(&self) -> Option<Address> {
+        self.subscription_manager.get_or_default()
+    }
+
+    /// Get the UsageMeter debited by metered calls
+    pub fn get_usage_meter(&self) -> Option<Address> {
+        self.usage_meter.get_or_default()
+    }
+}
+
+impl AccessGate {
+    /// Point the gate at the protocol contracts
+    pub fn init(&mut self, subscription_manager: Address, usage_meter: Option<Address>) {
+        self.subscription_manager.set(Some(subscription_manager));
+        self.usage_meter.set(usage_meter);
+    }
+
+    /// Set the SubscriptionManager; the caller must already be authorized by the parent
+    pub fn set_subscription_manager(&mut self, address: Address) {
+        self.subscription_manager.set(Some(address));
+    }
+
+    /// Set or clear the UsageMeter; the caller must already be authorized by the parent
+    pub fn set_usage_meter(&mut self, address: Option<Address>) {
+        self.usage_meter.set(address);
+    }
+
+    /// Revert unless the caller may use entry points gated on `plan_id`
+    pub fn require_plan(&self, plan_id: u64) {
+        if !self.has_plan_access(self.env().caller(), plan_id) {
+            self.env().revert(Error::NoAccess);
+        }
+    }
+
+    /// Revert unless the caller may use entry points gated on `merchant`'s plans
+    pub fn require_merchant(&self, merchant: Address) {
+        if !self.has_merchant_access(self.env().caller(), merchant) {
+            self.env().revert(Error::NoAccess);
+        }
+    }
+
+    /// Revert unless the caller may use entry points gated on `plan_id`, then
+    /// record `units` of `metric` against their subscription
+    ///
+    /// Returns the usage record ID.
+    pub fn require_plan_and_debit(&mut self, plan_id: u64, metric: String, units: u64) -> u64 {
+        self.require_plan(plan_id);
+        let subscription = self
+            .manager()
+            .get_user_plan_subscription(self.env().caller(), plan_id)
+            .unwrap_or_revert_with(&self.env(), Error::NoAccess);
+        let usage_meter = self
+            .get_usage_meter()
+            .unwrap_or_revert_with(&self.env(), Error::UsageMeterNotSet);
+        CasperFlowUsageContractRef::new(self.env(), usage_meter).record_usage(
+            subscription.id,
+            plan_id,
+            metric,
+            units,
+        )
+    }
+
+    /// Reference to the configured SubscriptionManager
+    fn manager(&self) -> CasperFlowAccessContractRef {
+        let address = self
+            .get_subscription_manager()
+            .unwrap_or_revert_with(&self.env(), Error::SubscriptionManagerNotSet);
+        CasperFlowAccessContractRef::new(self.env(), address)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::access::Role;
+    use crate::billing_engine::BillingEngine;
+    use crate::registry::{Component, Registry};
+    use crate::subscription_manager::{SubscriptionManager, SubscriptionManagerHostRef, DEFAULT_PAYMENT_GRACE};
+    use crate::time::Duration;
+    use crate::timelock::{Param, DEFAULT_TIMELOCK_DELAY};
+    use crate::usage_meter::{Error as UsageError, UsageMeter, UsageMeterHostRef, DEFAULT_LATE_USAGE_WINDOW};
+    use odra::casper_types::U512;
+    use odra::host::{Deployer, HostEnv, HostRef, NoArgs};
+
+    /// Third-party contract embedding the gate
+    #[odra::module]
+    struct GatedApi {
+        gate: SubModule<AccessGate>,
+        calls: Var<u32>,
+    }
+
+    #[odra::module]
+    impl GatedApi {
+        pub fn init(&mut self, subscription_manager: Address, usage_meter: Option<Address>) {
+            self.gate.init(subscription_manager, usage_meter);
+        }
+
+        pub fn premium_call(&mut self, plan_id: u64) {
+            self.gate.require_plan(plan_id);
+            self.calls.set(self.calls.get_or_default() + 1);
+        }
+
+        pub fn merchant_call(&mut self, merchant: Address) {
+            self.gate.require_merchant(merchant);
+            self.calls.set(self.calls.get_or_default() + 1);
+        }
+
+        pub fn metered_call(&mut self, plan_id: u64) -> u64 {
+            self.gate.require_plan_and_debit(plan_id, "api_calls".to_string(), 3)
+        }
+
+        delegate! {
+            to self.gate {
+                fn has_plan_access(&self, subscriber: Address, plan_id: u64) -> bool;
+                fn has_merchant_access(&self, subscriber: Address, merchant: Address) -> bool;
+            }
+        }
+    }
+
+    /// Deploy the protocol through a Registry and create a plan owned by account 0
+    fn setup(env: &HostEnv) -> (SubscriptionManagerHostRef, UsageMeterHostRef, u64) {
+        let mut registry = Registry::deploy(env, NoArgs);
+        let mut manager = SubscriptionManager::deploy(env, NoArgs);
+        let mut meter = UsageMeter::deploy(env, NoArgs);
+        let mut engine = BillingEngine::deploy(env, NoArgs);
+        registry.register(Component::SubscriptionManager, manager.address());
+        registry.register(Component::UsageMeter, meter.address());
+        registry.register(Component::BillingEngine, engine.address());
+        manager.set_registry(registry.address());
+        meter.set_registry(registry.address());
+        engine.set_registry(registry.address());
+
+        let plan_id = manager.create_plan(
+            "Pro".to_string(),
+            U512::from(1_000u64),
+            U512::from(10u64),
+            Duration::from_days(30),
+        );
+        (manager, meter, plan_id)
+    }
+
+    #[test]
+    fn test_gate_checks_subscription() {
+        let env = odra_test::env();
+        let (mut manager, mut meter, plan_id) = setup(&env);
+        let merchant = env.get_account(0);
+        let subscriber = env.get_account(1);
+        let stranger = env.get_account(2);
+        let mut api = GatedApi::deploy(
+            &env,
+            GatedApiInitArgs {
+                subscription_manager: manager.address(),
+                usage_meter: Some(meter.address()),
+            },
+        );
+
+        env.set_caller(subscriber);
+        let subscription_id = manager.with_tokens(U512::from(1_000u64)).subscribe(plan_id, true, 0);
+        api.premium_call(plan_id);
+        api.merchant_call(merchant);
+        assert_eq!(api.try_merchant_call(stranger), Err(Error::NoAccess.into()));
+
+        env.set_caller(stranger);
+        assert_eq!(api.try_premium_call(plan_id), Err(Error::NoAccess.into()));
+
+        // Debiting needs the dApp to be an authorized recorder
+        env.set_caller(subscriber);
+        assert!(api.try_metered_call(plan_id).is_err());
+
+        // Only the plan's merchant can authorize it
+        for account in [subscriber, stranger] {
+            env.set_caller(account);
+            assert_eq!(
+                meter.try_authorize_recorder(plan_id, api.address()),
+                Err(UsageError::NotAdminOrMerchant.into())
+            );
+        }
+        env.set_caller(merchant);
+        meter.authorize_recorder(plan_id, api.address());
+        env.set_caller(subscriber);
+        api.metered_call(plan_id);
+        api.metered_call(plan_id);
+        let period_start = meter.get_current_period_start(subscription_id).unwrap();
+        let usage = meter.get_period_usage(subscription_id, period_start).unwrap();
+        assert_eq!(usage.total_units, 6);
+
+        // Cancelling revokes access at once
+        manager.unsubscribe(subscription_id);
+        assert_eq!(api.try_premium_call(plan_id), Err(Error::NoAccess.into()));
+    }
+
+    #[test]
+    fn test_gate_withholds_access_when_past_due() {
+        let env = odra_test::env();
+        let (mut manager, _, plan_id) = setup(&env);
+        let cycle = Duration::from_days(30);
+        let api = GatedApi::deploy(
+            &env,
+            GatedApiInitArgs {
+                subscription_manager: manager.address(),
+                usage_meter: None,
+            },
+        );
+
+        env.set_caller(env.get_account(1));
+        let renewing = manager.with_tokens(U512::from(1_000u64)).subscribe(plan_id, true, 0);
+        env.set_caller(env.get_account(2));
+        let expiring = manager.with_tokens(U512::from(1_000u64)).subscribe(plan_id, false, 0);
+
+        // A non-renewing subscription lapses when its cycle ends; a renewing
+        // one waits for the keeper
+        env.advance_block_time(cycle.as_millis());
+        assert!(!api.has_plan_access(env.get_account(2), plan_id));
+        assert!(api.has_plan_access(env.get_account(1), plan_id));
+
+        env.set_caller(env.get_account(0));
+        let keeper = env.get_account(3);
+        manager.grant_role(Role::Keeper, keeper);
+        env.set_caller(keeper);
+        env.advance_block_time(DEFAULT_LATE_USAGE_WINDOW.as_millis());
+        assert_eq!(manager.renew_subscriptions(vec![renewing, expiring]).len(), 1);
+
+        // The unpaid renewal keeps access until the payment grace runs out
+        assert!(api.has_plan_access(env.get_account(1), plan_id));
+        env.advance_block_time(DEFAULT_PAYMENT_GRACE.as_millis());
+        assert!(!api.has_plan_access(env.get_account(1), plan_id));
+        assert!(!api.has_merchant_access(env.get_account(1), env.get_account(0)));
+
+        // Lengthening the grace restores it once the timelock has passed
+        env.set_caller(env.get_account(0));
+        manager.set_payment_grace(Duration::from_days(7));
+        let change = manager.get_pending_change(Param::PaymentGrace).unwrap();
+        assert!(!api.has_plan_access(env.get_account(1), plan_id));
+        env.advance_block_time(DEFAULT_TIMELOCK_DELAY.as_millis());
+        manager.execute_change(change.id);
+        assert!(api.has_plan_access(env.get_account(1), plan_id));
+    }
+}
@@ -0,0 +1,169 @@
+//! Aggregates backing the merchant analytics dashboard.
+//!
+//! SubscriptionManager keeps subscriber counts, MRR and churn per plan and
+//! per merchant; BillingEngine keeps receivables and collected revenue.
+//! Both are updated on every lifecycle event, so the dashboard can be served
+//! from views without replaying history.
+//!
+//! Time-bucketed figures use fixed analytics periods of [`ANALYTICS_PERIOD`],
+//! numbered from zero (`block_time / ANALYTICS_PERIOD`).
+
+use odra::casper_types::U512;
+
+use crate::time::{Duration, Timestamp};
+
+/// Length of an analytics period and of the "month" MRR is normalized to (30 days)
+pub const ANALYTICS_PERIOD: Duration = Duration::from_days(30);
+
+/// Analytics period containing `timestamp`
+pub fn period_of(timestamp: Timestamp) -> u64 {
+    timestamp.as_millis() / ANALYTICS_PERIOD.as_millis()
+}
+
+/// Normalize a per-cycle price to a monthly recurring amount
+pub fn monthly_amount(price: U512, billing_cycle: Duration) -> U512 {
+    if billing_cycle.is_zero() {
+        return U512::zero();
+    }
+    price * U512::from(ANALYTICS_PERIOD.as_millis()) / U512::from(billing_cycle.as_millis())
+}
+
+/// Running subscriber totals for a plan or merchant
+#[odra::odra_type]
+#[derive(Default)]
+pub struct SubscriberStats {
+    /// Currently active subscriptions
+    pub active_subscribers: u64,
+    /// Subscriptions ever created
+    pub total_subscribers: u64,
+    /// Monthly recurring revenue of the active subscriptions
+    pub mrr: U512,
+}
+
+impl SubscriberStats {
+    pub(crate) fn apply(&mut self, joined: bool, monthly: U512) {
+        if joined {
+            self.active_subscribers += 1;
+            self.total_subscribers += 1;
+            self.mrr = self.mrr + monthly;
+        } else {
+            self.active_subscribers -= 1;
+            self.mrr = self.mrr - monthly;
+        }
+    }
+
+    /// Average revenue per active subscriber, per month
+    pub fn arpu(&self) -> U512 {
+        if self.active_subscribers == 0 {
+            return U512::zero();
+        }
+        self.mrr / U512::from(self.active_subscribers)
+    }
+}
+
+/// Subscriber movement within one analytics period
+#[odra::odra_type]
+#[derive(Default)]
+pub struct ChurnStats {
+    /// Active subscribers when the period's first event was recorded
+    pub opening_subscribers: u64,
+    /// Subscriptions started in the period
+    pub new_subscribers: u64,
+    /// Subscriptions cancelled in the period
+    pub churned_subscribers: u64,
+}
+
+impl ChurnStats {
+    pub(crate) fn opening(active_subscribers: u64) -> Self {
+        Self {
+            opening_subscribers: active_subscribers,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn apply(&mut self, joined: bool) {
+        if joined {
+            self.new_subscribers += 1;
+        } else {
+            self.churned_subscribers += 1;
+        }
+    }
+
+    /// Churn rate in basis points of the opening subscriber base
+    pub fn churn_bps(&self) -> u64 {
+        let base = self.opening_subscribers + self.new_subscribers;
+        if base == 0 {
+            return 0;
+        }
+        self.churned_subscribers * 10_000 / base
+    }
+}
+
+/// Running invoice totals for a plan or merchant
+#[odra::odra_type]
+#[derive(Default)]
+pub struct ReceivableStats {
+    /// Sum of pending invoice totals
+    pub outstanding_amount: U512,
+    /// Number of pending invoices
+    pub outstanding_invoices: u64,
+    /// Sum of paid invoice totals
+    pub collected_amount: U512,
+    /// Sum of invoice totals marked failed
+    pub failed_amount: U512,
+}
+
+/// Invoice activity within one analytics period
+#[odra::odra_type]
+#[derive(Default)]
+pub struct RevenueStats {
+    /// Sum of invoices created in the period
+    pub invoiced_amount: U512,
+    /// Sum of invoices paid in the period
+    pub collected_amount: U512,
+    /// Sum of invoices failed in the period
+    pub failed_amount: U512,
+    /// Number of invoices paid in the period
+    pub paid_invoices: u64,
+}
+
+/// Invoice lifecycle transitions tracked by the receivable aggregates
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum InvoiceEvent {
+    Created,
+    Paid,
+    Failed,
+}
+
+impl ReceivableStats {
+    pub(crate) fn apply(&mut self, event: InvoiceEvent, amount: U512) {
+        match event {
+            InvoiceEvent::Created => {
+                self.outstanding_amount = self.outstanding_amount + amount;
+                self.outstanding_invoices += 1;
+            }
+            InvoiceEvent::Paid | InvoiceEvent::Failed => {
+                self.outstanding_amount = self.outstanding_amount - amount;
+                self.outstanding_invoices -= 1;
+                if event == InvoiceEvent::Paid {
+                    self.collected_amount = self.collected_amount + amount;
+                } else {
+                    self.failed_amount = self.failed_amount + amount;
+                }
+            }
+        }
+    }
+}
+
+impl RevenueStats {
+    pub(crate) fn apply(&mut self, event: InvoiceEvent, amount: U512) {
+        match event {
+            InvoiceEvent::Created => self.invoiced_amount = self.invoiced_amount + amount,
+            InvoiceEvent::Paid => {
+                self.collected_amount = self.collected_amount + amount;
+                self.paid_invoices += 1;
+            }
+            InvoiceEvent::Failed => self.failed_amount = self.failed_amount + amount,
+        }
+    }
+}
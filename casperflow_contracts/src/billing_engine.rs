@@ -0,0 +1,1267 @@
+//! Billing Engine Contract
+//!
+//! Calculates and processes billing for subscriptions.
+//! 
+//! Key features:
+//! - Calculate total bill (base price + usage * usage_price)
+//! - Apply usage adjustments from closed periods as credit/debit lines
+//! - Generate on-chain invoices
+//! - Process payments from wallet or staking rewards, refunding overpayments
+//! - Settle the first cycle of new subscriptions as a paid invoice
+//! - Handle subscription renewals
+//! - Maintains receivable and collected-revenue aggregates per plan and merchant
+
+use odra::prelude::*;
+use odra::casper_types::U512;
+
+use crate::access::{AccessControl, Role};
+use crate::analytics::{self, InvoiceEvent, ReceivableStats, RevenueStats};
+use crate::pagination::{self, DateRange};
+use crate::pausable::{PauseGroup, Pausable};
+use crate::registry::{Component, RegistryContractRef};
+use crate::subscription_manager::SubscriptionManagerContractRef;
+use crate::time::{Duration, Timestamp};
+use crate::timelock::{Error as TimelockError, Param, QueuedChange, Timelock};
+use crate::upgrade::{Migrate, MigrationProgress, RecordKind, Schema};
+use crate::usage_meter::UsageMeterContractRef;
+
+/// Invoice status
+#[odra::odra_type]
+#[derive(Copy)]
+pub enum InvoiceStatus {
+    Pending,
+    Paid,
+    Failed,
+    Cancelled,
+}
+
+/// On-chain invoice record
+#[odra::odra_type]
+pub struct Invoice {
+    /// Unique invoice ID
+    pub id: u64,
+    /// Subscription this invoice is for
+    pub subscription_id: u64,
+    /// Plan ID
+    pub plan_id: u64,
+    /// Subscriber address
+    pub subscriber: Address,
+    /// Merchant address
+    pub merchant: Address,
+    /// Base amount
+    pub base_amount: U512,
+    /// Usage amount
+    pub usage_amount: U512,
+    /// Debit line from usage adjustments to previously billed periods
+    pub adjustment_debit: U512,
+    /// Credit line applied from usage adjustments and carried-over credit
+    pub adjustment_credit: U512,
+    /// Total amount (base + usage + debit - credit)
+    pub total_amount: U512,
+    /// Units of usage
+    pub usage_units: u64,
+    /// Billing period start
+    pub period_start: Timestamp,
+    /// Billing period end
+    pub period_end: Timestamp,
+    /// Invoice created timestamp
+    pub created_at: Timestamp,
+    /// Invoice paid timestamp (`None` if not paid)
+    pub paid_at: Option<Timestamp>,
+    /// Payment method used (0 = wallet, 1 = staked)
+    pub payment_method: u8,
+    /// Status
+    pub status: InvoiceStatus,
+    /// Transaction hash of payment (if paid)
+    pub payment_tx: String,
+}
+
+impl Migrate for Invoice {
+    // Schema 1 is the first versioned layout
+    type Previous = Invoice;
+
+    fn migrate(previous: Invoice, _from_version: u32) -> Self {
+        previous
+    }
+}
+
+/// Filter for invoice queries; `None` fields match everything
+#[odra::odra_type]
+#[derive(Default)]
+pub struct InvoiceFilter {
+    /// Only invoices with this status
+    pub status: Option<InvoiceStatus>,
+    /// Only invoices for this plan
+    pub plan_id: Option<u64>,
+    /// Only invoices created within this range
+    pub created: DateRange,
+}
+
+impl InvoiceFilter {
+    fn matches(&self, invoice: &Invoice) -> bool {
+        self.status.map_or(true, |status| invoice.status == status)
+            && self.plan_id.map_or(true, |id| invoice.plan_id == id)
+            && self.created.contains(invoice.created_at)
+    }
+}
+
+/// Page of invoices returned by paginated queries
+#[odra::odra_type]
+pub struct InvoicePage {
+    pub items: Vec<Invoice>,
+    /// Cursor to pass to fetch the next page, `None` when exhausted
+    pub next_cursor: Option<u32>,
+}
+
+/// Errors reverted by the contract (codes 300-399)
+#[odra::odra_error]
+pub enum Error {
+    /// Caller is not the SubscriptionManager
+    NotSubscriptionManager = 301,
+    /// Caller is not the StakeToPay contract
+    NotStakeToPay = 302,
+    /// Invoice does not exist
+    InvoiceNotFound = 303,
+    /// Invoice is not awaiting payment
+    InvoiceNotPending = 304,
+    /// Caller is not the invoice's subscriber
+    NotSubscriber = 305,
+    /// Payer is not the invoice's subscriber
+    PayerMismatch = 306,
+    /// Attached tokens do not cover the invoice
+    InsufficientPayment = 307,
+    /// Protocol fee above the 10% cap
+    FeeTooHigh = 308,
+    /// Fee recipient has not been configured
+    FeeRecipientNotSet = 309,
+    /// SubscriptionManager address has not been configured
+    SubscriptionManagerNotSet = 310,
+    /// Plan does not exist on the SubscriptionManager
+    PlanNotFound = 311,
+}
+
+/// Events
+pub mod events {
+    use super::*;
+
+    #[odra::event]
+    pub struct InvoiceCreated {
+        pub invoice_id: u64,
+        pub subscription_id: u64,
+        pub total_amount: U512,
+    }
+
+    #[odra::event]
+    pub struct InvoicePaid {
+        pub invoice_id: u64,
+        pub amount: U512,
+        pub payment_method: u8,
+    }
+
+    #[odra::event]
+    pub struct InvoiceFailed {
+        pub invoice_id: u64,
+        pub reason: String,
+    }
+
+    #[odra::event]
+    pub struct PaymentProcessed {
+        pub from: Address,
+        pub to: Address,
+        pub amount: U512,
+    }
+
+    #[odra::event]
+    pub struct OverpaymentRefunded {
+        pub invoice_id: u64,
+        pub to: Address,
+        pub amount: U512,
+    }
+}
+
+/// Billing Engine Contract
+#[odra::module(events = [
+    events::InvoiceCreated,
+    events::InvoicePaid,
+    events::InvoiceFailed,
+    events::PaymentProcessed,
+    events::OverpaymentRefunded
+], errors = Error)]
+pub struct BillingEngine {
+    /// Owner and role grants
+    access: SubModule<AccessControl>,
+    /// Per-group pause flags
+    pausable: SubModule<Pausable>,
+    /// Delayed parameter changes
+    timelock: SubModule<Timelock>,
+    /// SubscriptionManager contract
+    subscription_manager: Var<Option<Address>>,
+    /// UsageMeter contract
+    usage_meter: Var<Option<Address>>,
+    /// StakeToPay contract
+    stake_to_pay: Var<Option<Address>>,
+    /// Invoice counter
+    invoice_counter: Var<u64>,
+    /// Invoice ID -> Invoice
+    invoices: Mapping<u64, Invoice>,
+    /// Subscription ID -> number of invoices
+    subscription_invoice_count: Mapping<u64, u32>,
+    /// (Subscription ID, Index) -> Invoice ID
+    subscription_invoices: Mapping<(u64, u32), u64>,
+    /// User -> number of invoices
+    user_invoice_count: Mapping<Address, u32>,
+    /// (User, Index) -> Invoice ID
+    user_invoices: Mapping<(Address, u32), u64>,
+    /// Merchant -> number of invoices
+    merchant_invoice_count: Mapping<Address, u32>,
+    /// (Merchant, Index) -> Invoice ID
+    merchant_invoices: Mapping<(Address, u32), u64>,
+    /// Merchant -> total revenue
+    merchant_revenue: Mapping<Address, U512>,
+    /// Subscription ID -> credit not yet absorbed by an invoice
+    subscription_credit: Mapping<u64, U512>,
+    /// Plan ID -> receivable totals
+    plan_receivables: Mapping<u64, ReceivableStats>,
+    /// Merchant -> receivable totals
+    merchant_receivables: Mapping<Address, ReceivableStats>,
+    /// (Plan ID, Analytics period) -> invoice activity
+    plan_period_revenue: Mapping<(u64, u64), RevenueStats>,
+    /// (Merchant, Analytics period) -> invoice activity
+    merchant_period_revenue: Mapping<(Address, u64), RevenueStats>,
+    /// Protocol fee percentage (in basis points, e.g., 100 = 1%)
+    protocol_fee_bps: Var<u64>,
+    /// Protocol fee recipient
+    fee_recipient: Var<Address>,
+    /// Stored schema version and migration progress
+    schema: SubModule<Schema>,
+    /// Protocol Registry consulted for peer addresses, if set
+    registry: Var<Option<Address>>,
+    /// Invoice ID -> issued by the SubscriptionManager
+    manager_invoices: Mapping<u64, bool>,
+}
+
+#[odra::module]
+impl BillingEngine {
+    /// Initialize the contract
+    pub fn init(&mut self) {
+        let caller = self.env().caller();
+        self.access.init(caller);
+        self.schema.init();
+        self.fee_recipient.set(caller);
+        self.invoice_counter.set(0);
+        self.protocol_fee_bps.set(100); // 1% default fee
+    }
+
+    // ============ BILLING FUNCTIONS ============
+
+    /// Create an invoice for a subscription's billing period (SubscriptionManager or keeper)
+    ///
+    /// Carried-forward usage adjustments are priced at the plan's stored usage
+    /// price, not `usage_price`.
+    pub fn create_invoice(
+        &mut self,
+        subscription_id: u64,
+        plan_id: u64,
+        subscriber: Address,
+        merchant: Address,
+        base_amount: U512,
+        usage_price: U512,
+        usage_units: u64,
+        period_start: Timestamp,
+        period_end: Timestamp,
+    ) -> u64 {
+        self.pausable.require_not_paused(PauseGroup::Invoicing);
+        let caller = self.env().caller();
+        let from_manager = Some(caller) == self.get_peer(Component::SubscriptionManager);
+        if !from_manager && !self.access.has_role(Role::Keeper, caller) {
+            self.env().revert(Error::NotSubscriptionManager);
+        }
+        let invoice_id = self.invoice_counter.get_or_default() + 1;
+        self.invoice_counter.set(invoice_id);
+        if from_manager {
+            self.manager_invoices.set(&invoice_id, true);
+        }
+
+        let usage_amount = usage_price * U512::from(usage_units);
+        let (adjustment_debit, adjustment_credit) =
+            self.apply_usage_adjustments(subscription_id, plan_id, base_amount + usage_amount);
+        let total_amount = base_amount + usage_amount + adjustment_debit - adjustment_credit;
+        let now = Timestamp::now(&self.env());
+        // Nothing is owed when credit covers the whole cycle
+        let (status, paid_at) = if total_amount.is_zero() {
+            (InvoiceStatus::Paid, Some(now))
+        } else {
+            (InvoiceStatus::Pending, None)
+        };
+
+        let invoice = Invoice {
+            id: invoice_id,
+            subscription_id,
+            plan_id,
+            subscriber,
+            merchant,
+            base_amount,
+            usage_amount,
+            adjustment_debit,
+            adjustment_credit,
+            total_amount,
+            usage_units,
+            period_start,
+            period_end,
+            created_at: now,
+            paid_at,
+            payment_method: 0,
+            status,
+            payment_tx: String::new(),
+        };
+
+        self.invoices.set(&invoice_id, invoice);
+
+        // Update indexes
+        self.add_to_subscription_invoices(subscription_id, invoice_id);
+        self.add_to_user_invoices(subscriber, invoice_id);
+        self.add_to_merchant_invoices(merchant, invoice_id);
+        self.record_invoice_event(plan_id, merchant, InvoiceEvent::Created, total_amount);
+
+        self.env().emit_event(events::InvoiceCreated {
+            invoice_id,
+            subscription_id,
+            total_amount,
+        });
+        if status == InvoiceStatus::Paid {
+            self.record_invoice_event(plan_id, merchant, InvoiceEvent::Paid, total_amount);
+            self.env().emit_event(events::InvoicePaid {
+                invoice_id,
+                amount: total_amount,
+                payment_method: 0,
+            });
+        }
+
+        invoice_id
+    }
+
+    /// Pay an invoice from wallet
+    ///
+    /// Any value attached above the invoice total is refunded to the caller.
+    #[odra(payable)]
+    pub fn pay_invoice(&mut self, invoice_id: u64) {
+        self.pausable.require_not_paused(PauseGroup::Payments);
+        let caller = self.env().caller();
+        let invoice = self
+            .invoices
+            .get(&invoice_id)
+            .unwrap_or_revert_with(&self.env(), Error::InvoiceNotFound);
+        if invoice.subscriber != caller {
+            self.env().revert(Error::NotSubscriber);
+        }
+
+        self.settle_wallet_payment(invoice, caller);
+    }
+
+    /// Invoice and settle the first billing cycle of a new subscription
+    /// (called by SubscriptionManager with the cycle's base price attached)
+    #[odra(payable)]
+    pub fn settle_initial_payment(
+        &mut self,
+        subscription_id: u64,
+        plan_id: u64,
+        subscriber: Address,
+        merchant: Address,
+        period_start: Timestamp,
+        period_end: Timestamp,
+    ) -> u64 {
+        self.pausable.require_not_paused(PauseGroup::Payments);
+        let caller = self.env().caller();
+        if Some(caller) != self.get_peer(Component::SubscriptionManager) {
+            self.env().revert(Error::NotSubscriptionManager);
+        }
+
+        let base_amount = self.env().attached_value();
+        let invoice_id = self.create_invoice(
+            subscription_id,
+            plan_id,
+            subscriber,
+            merchant,
+            base_amount,
+            U512::zero(),
+            0,
+            period_start,
+            period_end,
+        );
+        let invoice = self
+            .invoices
+            .get(&invoice_id)
+            .unwrap_or_revert_with(&self.env(), Error::InvoiceNotFound);
+        self.settle_wallet_payment(invoice, subscriber);
+
+        invoice_id
+    }
+
+    /// Pay invoice from staking rewards (called by StakeToPay contract with
+    /// the invoice total attached)
+    #[odra(payable)]
+    pub fn pay_invoice_from_staking(
+        &mut self,
+        invoice_id: u64,
+        payer: Address,
+    ) {
+        self.pausable.require_not_paused(PauseGroup::Payments);
+        let caller = self.env().caller();
+        let stake_to_pay = self.get_peer(Component::StakeToPay);
+        
+        if stake_to_pay != Some(caller) {
+            self.env().revert(Error::NotStakeToPay);
+        }
+
+        let mut invoice = self
+            .invoices
+            .get(&invoice_id)
+            .unwrap_or_revert_with(&self.env(), Error::InvoiceNotFound);
+        
+        if invoice.status != InvoiceStatus::Pending {
+            self.env().revert(Error::InvoiceNotPending);
+        }
+        if invoice.subscriber != payer {
+            self.env().revert(Error::PayerMismatch);
+        }
+        if self.env().attached_value() < invoice.total_amount {
+            self.env().revert(Error::InsufficientPayment);
+        }
+
+        // Mark as paid
+        invoice.status = InvoiceStatus::Paid;
+        invoice.paid_at = Some(Timestamp::now(&self.env()));
+        invoice.payment_method = 1; // Staked
+        self.invoices.set(&invoice_id, invoice.clone());
+
+        // Pay out the merchant share and protocol fee
+        let fee_bps = self.protocol_fee_bps.get_or_default();
+        let protocol_fee = (invoice.total_amount * U512::from(fee_bps)) / U512::from(10000);
+        let merchant_amount = invoice.total_amount - protocol_fee;
+        self.transfer_payment(invoice.merchant, merchant_amount, protocol_fee);
+
+        let current_revenue = self.merchant_revenue.get(&invoice.merchant).unwrap_or_default();
+        self.merchant_revenue.set(&invoice.merchant, current_revenue + merchant_amount);
+        self.record_invoice_event(invoice.plan_id, invoice.merchant, InvoiceEvent::Paid, invoice.total_amount);
+
+        self.env().emit_event(events::InvoicePaid {
+            invoice_id,
+            amount: invoice.total_amount,
+            payment_method: 1,
+        });
+
+        self.env().emit_event(events::PaymentProcessed {
+            from: payer,
+            to: invoice.merchant,
+            amount: merchant_amount,
+        });
+    }
+
+    /// Mark invoice as failed (keeper only)
+    pub fn fail_invoice(&mut self, invoice_id: u64, reason: String) {
+        self.access.require_role(Role::Keeper);
+
+        let mut invoice = self
+            .invoices
+            .get(&invoice_id)
+            .unwrap_or_revert_with(&self.env(), Error::InvoiceNotFound);
+        if invoice.status != InvoiceStatus::Pending {
+            self.env().revert(Error::InvoiceNotPending);
+        }
+        invoice.status = InvoiceStatus::Failed;
+        self.record_invoice_event(invoice.plan_id, invoice.merchant, InvoiceEvent::Failed, invoice.total_amount);
+        self.invoices.set(&invoice_id, invoice);
+
+        self.env().emit_event(events::InvoiceFailed {
+            invoice_id,
+            reason,
+        });
+    }
+
+    // ============ HELPER FUNCTIONS ============
+
+    /// Transfer a payment's merchant share and protocol fee
+    fn transfer_payment(&self, merchant: Address, merchant_amount: U512, protocol_fee: U512) {
+        if merchant_amount > U512::zero() {
+            self.env().transfer_tokens(&merchant, &merchant_amount);
+        }
+
+        let fee_recipient = self
+            .fee_recipient
+            .get()
+            .unwrap_or_revert_with(&self.env(), Error::FeeRecipientNotSet);
+        if protocol_fee > U512::zero() {
+            self.env().transfer_tokens(&fee_recipient, &protocol_fee);
+        }
+    }
+
+    /// Route a wallet payment to the merchant and fee recipient, mark the
+    /// invoice paid and refund any value attached above its total
+    fn settle_wallet_payment(&mut self, mut invoice: Invoice, payer: Address) {
+        let attached = self.env().attached_value();
+        if invoice.status != InvoiceStatus::Pending {
+            self.env().revert(Error::InvoiceNotPending);
+        }
+        if attached < invoice.total_amount {
+            self.env().revert(Error::InsufficientPayment);
+        }
+
+        // Calculate protocol fee
+        let fee_bps = self.protocol_fee_bps.get_or_default();
+        let protocol_fee = (invoice.total_amount * U512::from(fee_bps)) / U512::from(10000);
+        let merchant_amount = invoice.total_amount - protocol_fee;
+        self.transfer_payment(invoice.merchant, merchant_amount, protocol_fee);
+
+        // Refund overpayment
+        let excess = attached - invoice.total_amount;
+        if excess > U512::zero() {
+            self.env().transfer_tokens(&payer, &excess);
+            self.env().emit_event(events::OverpaymentRefunded {
+                invoice_id: invoice.id,
+                to: payer,
+                amount: excess,
+            });
+        }
+
+        // Update invoice
+        invoice.status = InvoiceStatus::Paid;
+        invoice.paid_at = Some(Timestamp::now(&self.env()));
+        invoice.payment_method = 0;
+        self.invoices.set(&invoice.id, invoice.clone());
+
+        // Update merchant revenue
+        let current_revenue = self.merchant_revenue.get(&invoice.merchant).unwrap_or_default();
+        self.merchant_revenue.set(&invoice.merchant, current_revenue + merchant_amount);
+        self.record_invoice_event(invoice.plan_id, invoice.merchant, InvoiceEvent::Paid, invoice.total_amount);
+
+        self.env().emit_event(events::InvoicePaid {
+            invoice_id: invoice.id,
+            amount: invoice.total_amount,
+            payment_method: 0,
+        });
+
+        self.env().emit_event(events::PaymentProcessed {
+            from: payer,
+            to: invoice.merchant,
+            amount: merchant_amount,
+        });
+    }
+
+    /// Settle carried-forward usage adjustments into (debit, credit) amounts.
+    ///
+    /// The credit applied is capped so the invoice total never goes negative;
+    /// any excess is kept as subscription credit for the next invoice.
+    fn apply_usage_adjustments(
+        &mut self,
+        subscription_id: u64,
+        plan_id: u64,
+        gross_amount: U512,
+    ) -> (U512, U512) {
+        let mut debit = U512::zero();
+        let mut credit = self.subscription_credit.get(&subscription_id).unwrap_or_default();
+
+        if let Some(usage_meter) = self.get_peer(Component::UsageMeter) {
+            let pending = UsageMeterContractRef::new(self.env(), usage_meter)
+                .settle_adjustments(subscription_id);
+            if pending.debit_units != 0 || pending.credit_units != 0 {
+                let usage_price = self.plan_usage_price(plan_id);
+                debit = usage_price * U512::from(pending.debit_units);
+                credit += usage_price * U512::from(pending.credit_units);
+            }
+        }
+
+        let billable = gross_amount + debit;
+        let applied_credit = if credit > billable { billable } else { credit };
+        self.subscription_credit.set(&subscription_id, credit - applied_credit);
+
+        (debit, applied_credit)
+    }
+
+    /// Get a plan's current usage price from the SubscriptionManager
+    fn plan_usage_price(&self, plan_id: u64) -> U512 {
+        let subscription_manager = self
+            .get_peer(Component::SubscriptionManager)
+            .unwrap_or_revert_with(&self.env(), Error::SubscriptionManagerNotSet);
+        SubscriptionManagerContractRef::new(self.env(), subscription_manager)
+            .get_plan(plan_id)
+            .unwrap_or_revert_with(&self.env(), Error::PlanNotFound)
+            .usage_price
+    }
+
+    fn add_to_subscription_invoices(&mut self, subscription_id: u64, invoice_id: u64) {
+        let index = self.subscription_invoice_count.get(&subscription_id).unwrap_or_default();
+        self.subscription_invoices.set(&(subscription_id, index), invoice_id);
+        self.subscription_invoice_count.set(&subscription_id, index + 1);
+    }
+
+    fn add_to_user_invoices(&mut self, user: Address, invoice_id: u64) {
+        let index = self.user_invoice_count.get(&user).unwrap_or_default();
+        self.user_invoices.set(&(user, index), invoice_id);
+        self.user_invoice_count.set(&user, index + 1);
+    }
+
+    fn add_to_merchant_invoices(&mut self, merchant: Address, invoice_id: u64) {
+        let index = self.merchant_invoice_count.get(&merchant).unwrap_or_default();
+        self.merchant_invoices.set(&(merchant, index), invoice_id);
+        self.merchant_invoice_count.set(&merchant, index + 1);
+    }
+
+    /// Update plan and merchant receivable aggregates for an invoice transition
+    fn record_invoice_event(&mut self, plan_id: u64, merchant: Address, event: InvoiceEvent, amount: U512) {
+        let period = analytics::period_of(Timestamp::now(&self.env()));
+
+        let mut plan_stats = self.plan_receivables.get(&plan_id).unwrap_or_default();
+        plan_stats.apply(event, amount);
+        self.plan_receivables.set(&plan_id, plan_stats);
+
+        let mut merchant_stats = self.merchant_receivables.get(&merchant).unwrap_or_default();
+        merchant_stats.apply(event, amount);
+        self.merchant_receivables.set(&merchant, merchant_stats);
+
+        let mut plan_period = self.plan_period_revenue.get(&(plan_id, period)).unwrap_or_default();
+        plan_period.apply(event, amount);
+        self.plan_period_revenue.set(&(plan_id, period), plan_period);
+
+        let mut merchant_period = self.merchant_period_revenue.get(&(merchant, period)).unwrap_or_default();
+        merchant_period.apply(event, amount);
+        self.merchant_period_revenue.set(&(merchant, period), merchant_period);
+    }
+
+    /// Collect a page of invoices whose IDs are looked up by list index
+    fn invoice_page(
+        &self,
+        total: u32,
+        cursor: u32,
+        limit: u32,
+        filter: &InvoiceFilter,
+        invoice_at: impl Fn(u32) -> Option<u64>,
+    ) -> InvoicePage {
+        let (items, next_cursor) = pagination::scan(total, cursor, limit, |index| {
+            invoice_at(index)
+                .and_then(|invoice_id| self.invoices.get(&invoice_id))
+                .filter(|invoice| filter.matches(invoice))
+        });
+        InvoicePage { items, next_cursor }
+    }
+
+    // ============ VIEW FUNCTIONS ============
+
+    /// Get invoice details
+    pub fn get_invoice(&self, invoice_id: u64) -> Option<Invoice> {
+        self.invoices.get(&invoice_id)
+    }
+
+    /// Whether an invoice was issued by the SubscriptionManager rather than a keeper
+    pub fn is_issued_by_manager(&self, invoice_id: u64) -> bool {
+        self.manager_invoices.get(&invoice_id).unwrap_or_default()
+    }
+
+    /// Get number of invoices for a subscription
+    pub fn get_subscription_invoice_count(&self, subscription_id: u64) -> u32 {
+        self.subscription_invoice_count.get(&subscription_id).unwrap_or_default()
+    }
+
+    /// Get a page of a subscription's invoices matching `filter`
+    pub fn get_subscription_invoices_page(
+        &self,
+        subscription_id: u64,
+        cursor: u32,
+        limit: u32,
+        filter: InvoiceFilter,
+    ) -> InvoicePage {
+        let total = self.get_subscription_invoice_count(subscription_id);
+        self.invoice_page(total, cursor, limit, &filter, |index| {
+            self.subscription_invoices.get(&(subscription_id, index))
+        })
+    }
+
+    /// Get number of invoices for a user
+    pub fn get_user_invoice_count(&self, user: Address) -> u32 {
+        self.user_invoice_count.get(&user).unwrap_or_default()
+    }
+
+    /// Get a page of a user's invoices matching `filter`
+    pub fn get_user_invoices_page(
+        &self,
+        user: Address,
+        cursor: u32,
+        limit: u32,
+        filter: InvoiceFilter,
+    ) -> InvoicePage {
+        let total = self.get_user_invoice_count(user);
+        self.invoice_page(total, cursor, limit, &filter, |index| {
+            self.user_invoices.get(&(user, index))
+        })
+    }
+
+    /// Get number of invoices for a merchant
+    pub fn get_merchant_invoice_count(&self, merchant: Address) -> u32 {
+        self.merchant_invoice_count.get(&merchant).unwrap_or_default()
+    }
+
+    /// Get a page of a merchant's invoices matching `filter`
+    pub fn get_merchant_invoices_page(
+        &self,
+        merchant: Address,
+        cursor: u32,
+        limit: u32,
+        filter: InvoiceFilter,
+    ) -> InvoicePage {
+        let total = self.get_merchant_invoice_count(merchant);
+        self.invoice_page(total, cursor, limit, &filter, |index| {
+            self.merchant_invoices.get(&(merchant, index))
+        })
+    }
+
+    /// Get merchant's total revenue
+    pub fn get_merchant_revenue(&self, merchant: Address) -> U512 {
+        self.merchant_revenue.get(&merchant).unwrap_or_default()
+    }
+
+    /// Get receivable totals for a plan
+    pub fn get_plan_receivables(&self, plan_id: u64) -> ReceivableStats {
+        self.plan_receivables.get(&plan_id).unwrap_or_default()
+    }
+
+    /// Get receivable totals for a merchant
+    pub fn get_merchant_receivables(&self, merchant: Address) -> ReceivableStats {
+        self.merchant_receivables.get(&merchant).unwrap_or_default()
+    }
+
+    /// Get a merchant's outstanding (pending) invoice total
+    pub fn get_merchant_outstanding(&self, merchant: Address) -> U512 {
+        self.get_merchant_receivables(merchant).outstanding_amount
+    }
+
+    /// Get a plan's invoice activity in an analytics period
+    pub fn get_plan_period_revenue(&self, plan_id: u64, period: u64) -> RevenueStats {
+        self.plan_period_revenue.get(&(plan_id, period)).unwrap_or_default()
+    }
+
+    /// Get a merchant's invoice activity in an analytics period
+    pub fn get_merchant_period_revenue(&self, merchant: Address, period: u64) -> RevenueStats {
+        self.merchant_period_revenue.get(&(merchant, period)).unwrap_or_default()
+    }
+
+    /// Get credit carried over to a subscription's next invoice
+    pub fn get_subscription_credit(&self, subscription_id: u64) -> U512 {
+        self.subscription_credit.get(&subscription_id).unwrap_or_default()
+    }
+
+    /// Get total number of invoices
+    pub fn total_invoices(&self) -> u64 {
+        self.invoice_counter.get_or_default()
+    }
+
+    /// Get protocol fee in basis points
+    pub fn get_protocol_fee_bps(&self) -> u64 {
+        self.protocol_fee_bps.get_or_default()
+    }
+
+    /// Get the protocol fee recipient
+    pub fn get_fee_recipient(&self) -> Address {
+        self.fee_recipient
+            .get()
+            .unwrap_or_revert_with(&self.env(), Error::FeeRecipientNotSet)
+    }
+
+    // ============ ADMIN FUNCTIONS ============
+
+    /// Set contract references (admin; timelocked once set)
+    pub fn set_subscription_manager(&mut self, address: Address) {
+        self.access.require_role(Role::Admin);
+        if self.subscription_manager.get_or_default().is_none() {
+            self.subscription_manager.set(Some(address));
+        } else {
+            self.timelock.queue_address(Param::SubscriptionManager, address);
+        }
+    }
+
+    pub fn set_usage_meter(&mut self, address: Address) {
+        self.access.require_role(Role::Admin);
+        if self.usage_meter.get_or_default().is_none() {
+            self.usage_meter.set(Some(address));
+        } else {
+            self.timelock.queue_address(Param::UsageMeter, address);
+        }
+    }
+
+    pub fn set_stake_to_pay(&mut self, address: Address) {
+        self.access.require_role(Role::Admin);
+        if self.stake_to_pay.get_or_default().is_none() {
+            self.stake_to_pay.set(Some(address));
+        } else {
+            self.timelock.queue_address(Param::StakeToPay, address);
+        }
+    }
+
+    /// Queue a new protocol fee (fee manager only)
+    pub fn set_protocol_fee_bps(&mut self, fee_bps: u64) {
+        self.access.require_role(Role::FeeManager);
+        // Max 10%
+        if fee_bps > 1000 {
+            self.env().revert(Error::FeeTooHigh);
+        }
+        self.timelock.queue_amount(Param::ProtocolFeeBps, fee_bps);
+    }
+
+    /// Queue a new fee recipient (fee manager only)
+    pub fn set_fee_recipient(&mut self, recipient: Address) {
+        self.access.require_role(Role::FeeManager);
+        self.timelock.queue_address(Param::FeeRecipient, recipient);
+    }
+
+    // ============ REGISTRY FUNCTIONS ============
+
+    /// Resolve peers through a Protocol Registry (admin; timelocked once set)
+    pub fn set_registry(&mut self, address: Address) {
+        self.access.require_role(Role::Admin);
+        if self.registry.get_or_default().is_none() {
+            self.registry.set(Some(address));
+        } else {
+            self.timelock.queue_address(Param::Registry, address);
+        }
+    }
+
+    /// Get the Protocol Registry address
+    pub fn get_registry(&self) -> Option<Address> {
+        self.registry.get_or_default()
+    }
+
+    /// Get the address of a peer contract: the registry's entry once a
+    /// registry is set, otherwise the address configured here
+    pub fn get_peer(&self, component: Component) -> Option<Address> {
+        if let Some(registry) = self.registry.get_or_default() {
+            return RegistryContractRef::new(self.env(), registry).get_address(component);
+        }
+        match component {
+            Component::SubscriptionManager => self.subscription_manager.get_or_default(),
+            Component::UsageMeter => self.usage_meter.get_or_default(),
+            Component::StakeToPay => self.stake_to_pay.get_or_default(),
+            _ => None,
+        }
+    }
+
+    // ============ UPGRADE FUNCTIONS ============
+
+    /// Hook run by Odra when a new version of the contract is installed (admin only)
+    pub fn upgrade(&mut self) {
+        self.access.require_role(Role::Admin);
+        self.schema.upgrade(&[(RecordKind::Invoice, self.invoice_counter.get_or_default())]);
+    }
+
+    /// Migrate the next `limit` invoices written before the last upgrade (keeper only)
+    pub fn migrate_invoices(&mut self, limit: u32) -> u32 {
+        self.access.require_role(Role::Keeper);
+        let (from_version, positions) = self.schema.next_batch(RecordKind::Invoice, limit);
+        for id in positions.clone().map(|position| position + 1) {
+            // Invoices are on their first layout, so `invoices` is also the legacy mapping
+            if let Some(previous) = self.invoices.get(&id) {
+                self.invoices.set(&id, Invoice::migrate(previous, from_version));
+            }
+        }
+        (positions.end - positions.start) as u32
+    }
+
+    delegate! {
+        to self.schema {
+            fn schema_version(&self) -> u32;
+            fn get_migration_progress(&self, kind: RecordKind) -> MigrationProgress;
+        }
+    }
+
+    // ============ GOVERNANCE FUNCTIONS ============
+
+    /// Apply a queued parameter change once its delay has passed (anyone)
+    pub fn execute_change(&mut self, change_id: u64) {
+        let change = self.timelock.take_ready(change_id);
+        match change.param {
+            Param::SubscriptionManager => self.subscription_manager.set(change.address),
+            Param::UsageMeter => self.usage_meter.set(change.address),
+            Param::StakeToPay => self.stake_to_pay.set(change.address),
+            Param::ProtocolFeeBps => self.protocol_fee_bps.set(change.amount),
+            Param::FeeRecipient => {
+                self.fee_recipient.set(change.address.unwrap_or_revert(&self.env()))
+            }
+            Param::Registry => self.registry.set(change.address),
+            Param::TimelockDelay => {}
+            _ => self.env().revert(TimelockError::UnsupportedParam),
+        }
+    }
+
+    /// Cancel a queued parameter change (admin only)
+    pub fn cancel_change(&mut self, change_id: u64) {
+        self.access.require_role(Role::Admin);
+        self.timelock.cancel(change_id);
+    }
+
+    /// Queue a new delay for parameter changes (admin only)
+    pub fn set_timelock_delay(&mut self, delay: Duration) {
+        self.access.require_role(Role::Admin);
+        self.timelock.queue_delay(delay);
+    }
+
+    delegate! {
+        to self.timelock {
+            fn get_queued_change(&self, change_id: u64) -> Option<QueuedChange>;
+            fn get_pending_change(&self, param: Param) -> Option<QueuedChange>;
+            fn get_timelock_delay(&self) -> Duration;
+        }
+    }
+
+    // ============ PAUSE FUNCTIONS ============
+
+    /// Pause a group of entry points (pauser only)
+    pub fn pause(&mut self, group: PauseGroup) {
+        self.access.require_role(Role::Pauser);
+        self.pausable.pause(group);
+    }
+
+    /// Resume a paused group of entry points (pauser only)
+    pub fn unpause(&mut self, group: PauseGroup) {
+        self.access.require_role(Role::Pauser);
+        self.pausable.unpause(group);
+    }
+
+    delegate! {
+        to self.pausable {
+            fn is_paused(&self, group: PauseGroup) -> bool;
+        }
+    }
+
+    // ============ ACCESS CONTROL ============
+
+    delegate! {
+        to self.access {
+            fn grant_role(&mut self, role: Role, account: Address);
+            fn revoke_role(&mut self, role: Role, account: Address);
+            fn renounce_role(&mut self, role: Role);
+            fn transfer_ownership(&mut self, new_owner: Address);
+            fn accept_ownership(&mut self);
+            fn has_role(&self, role: Role, account: Address) -> bool;
+            fn owner(&self) -> Option<Address>;
+            fn pending_owner(&self) -> Option<Address>;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::access::Error as AccessError;
+    use crate::timelock::DEFAULT_TIMELOCK_DELAY;
+    use crate::subscription_manager::SubscriptionManager;
+    use crate::time::Duration;
+    use crate::usage_meter::{reason_codes, UsageMeter, UsageMeterHostRef, DEFAULT_LATE_USAGE_WINDOW};
+    use odra::host::{Deployer, HostEnv, HostRef, NoArgs};
+
+    const CYCLE: Duration = Duration::from_days(30);
+
+    #[test]
+    fn test_create_invoice() {
+        let env = odra_test::env();
+        let mut contract = BillingEngine::deploy(&env, NoArgs);
+        
+        let subscriber = env.get_account(1);
+        let merchant = env.get_account(2);
+
+        let invoice_id = contract.create_invoice(
+            1,                             // subscription_id
+            1,                             // plan_id
+            subscriber,              // subscriber
+            merchant,                // merchant
+            U512::from(50_000_000_000u64), // base_amount (50 CSPR)
+            U512::from(1_000_000u64),      // usage_price (0.001 CSPR)
+            1000,                          // usage_units
+            Timestamp::ZERO,               // period_start
+            Timestamp::ZERO + CYCLE,       // period_end
+        );
+
+        assert_eq!(invoice_id, 1);
+        
+        let invoice = contract.get_invoice(invoice_id).unwrap();
+        // Total = 50 CSPR + (0.001 * 1000) = 50 + 1 = 51 CSPR
+        assert_eq!(invoice.total_amount, U512::from(51_000_000_000u64));
+        assert_eq!(invoice.status, InvoiceStatus::Pending);
+
+        // Only the SubscriptionManager or a keeper can invoice
+        env.set_caller(subscriber);
+        assert_eq!(
+            contract.try_create_invoice(
+                1, 1, subscriber, merchant,
+                U512::zero(), U512::zero(), 0, Timestamp::ZERO, Timestamp::ZERO + CYCLE,
+            ),
+            Err(Error::NotSubscriptionManager.into())
+        );
+        assert_eq!(contract.total_invoices(), 1);
+    }
+
+    #[test]
+    fn test_paginated_invoices() {
+        let env = odra_test::env();
+        let mut contract = BillingEngine::deploy(&env, NoArgs);
+
+        let subscriber = env.get_account(1);
+        let merchant = env.get_account(2);
+
+        for plan_id in [1u64, 2, 1, 2, 1] {
+            contract.create_invoice(
+                plan_id, plan_id, subscriber, merchant,
+                U512::from(1_000u64), U512::zero(), 0, Timestamp::ZERO, Timestamp::ZERO + CYCLE,
+            );
+        }
+        contract.fail_invoice(2, "Insufficient funds".to_string());
+
+        assert_eq!(contract.get_merchant_invoice_count(merchant), 5);
+        let page = contract.get_merchant_invoices_page(merchant, 0, 3, InvoiceFilter::default());
+        assert_eq!(page.items.len(), 3);
+        assert_eq!(page.next_cursor, Some(3));
+
+        let filter = InvoiceFilter { plan_id: Some(1), ..Default::default() };
+        let page = contract.get_user_invoices_page(subscriber, 0, 10, filter);
+        assert_eq!(page.items.len(), 3);
+        assert!(page.items.iter().all(|i| i.plan_id == 1));
+
+        let filter = InvoiceFilter { status: Some(InvoiceStatus::Failed), ..Default::default() };
+        let page = contract.get_subscription_invoices_page(2, 0, 10, filter);
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(page.items[0].id, 2);
+    }
+
+    #[test]
+    fn test_receivable_analytics() {
+        let env = odra_test::env();
+        let mut contract = BillingEngine::deploy(&env, NoArgs);
+
+        let subscriber = env.get_account(1);
+        let merchant = env.get_account(2);
+        let period = analytics::period_of(Timestamp::from_millis(env.block_time()));
+
+        for _ in 0..3 {
+            contract.create_invoice(
+                1, 1, subscriber, merchant,
+                U512::from(5_000_000_000u64), U512::zero(), 0, Timestamp::ZERO, Timestamp::ZERO + CYCLE,
+            );
+        }
+        assert_eq!(contract.get_merchant_outstanding(merchant), U512::from(15_000_000_000u64));
+
+        env.set_caller(subscriber);
+        contract.with_tokens(U512::from(5_000_000_000u64)).pay_invoice(1);
+        env.set_caller(env.get_account(0));
+        contract.fail_invoice(2, "Card declined".to_string());
+
+        let receivables = contract.get_merchant_receivables(merchant);
+        assert_eq!(receivables.outstanding_amount, U512::from(5_000_000_000u64));
+        assert_eq!(receivables.outstanding_invoices, 1);
+        assert_eq!(receivables.collected_amount, U512::from(5_000_000_000u64));
+        assert_eq!(receivables.failed_amount, U512::from(5_000_000_000u64));
+
+        let revenue = contract.get_plan_period_revenue(1, period);
+        assert_eq!(revenue.invoiced_amount, U512::from(15_000_000_000u64));
+        assert_eq!(revenue.paid_invoices, 1);
+
+        // A failed invoice cannot be failed again
+        assert_eq!(
+            contract.try_fail_invoice(2, "Retry".to_string()),
+            Err(Error::InvoiceNotPending.into())
+        );
+    }
+
+    #[test]
+    fn test_pay_invoice_refunds_overpayment() {
+        let env = odra_test::env();
+        let mut contract = BillingEngine::deploy(&env, NoArgs);
+
+        let subscriber = env.get_account(1);
+        let merchant = env.get_account(2);
+        let invoice_id = contract.create_invoice(
+            1, 1, subscriber, merchant,
+            U512::from(5_000_000_000u64), U512::zero(), 0, Timestamp::ZERO, Timestamp::ZERO + CYCLE,
+        );
+
+        env.set_caller(subscriber);
+        let merchant_balance = env.balance_of(&merchant);
+        contract.with_tokens(U512::from(8_000_000_000u64)).pay_invoice(invoice_id);
+
+        let invoice = contract.get_invoice(invoice_id).unwrap();
+        assert_eq!(invoice.status, InvoiceStatus::Paid);
+        assert!(invoice.paid_at.is_some());
+        assert_eq!(env.balance_of(&merchant), merchant_balance + U512::from(4_950_000_000u64));
+        assert_eq!(env.balance_of(&contract.address()), U512::zero());
+        assert_eq!(contract.get_merchant_receivables(merchant).collected_amount, U512::from(5_000_000_000u64));
+    }
+
+    #[test]
+    fn test_initial_payment_only_from_subscription_manager() {
+        let env = odra_test::env();
+        let mut contract = BillingEngine::deploy(&env, NoArgs);
+
+        let result = contract.with_tokens(U512::from(1_000u64)).try_settle_initial_payment(
+            1, 1, env.get_account(1), env.get_account(2), Timestamp::ZERO, Timestamp::ZERO + CYCLE,
+        );
+        assert_eq!(result, Err(Error::NotSubscriptionManager.into()));
+    }
+
+    #[test]
+    fn test_fee_settings_require_fee_manager() {
+        let env = odra_test::env();
+        let mut contract = BillingEngine::deploy(&env, NoArgs);
+        let fee_manager = env.get_account(1);
+
+        env.set_caller(fee_manager);
+        assert_eq!(
+            contract.try_set_protocol_fee_bps(200),
+            Err(AccessError::MissingRole.into())
+        );
+
+        env.set_caller(env.get_account(0));
+        contract.grant_role(Role::FeeManager, fee_manager);
+
+        // A fee manager can queue fees but not set contract addresses
+        env.set_caller(fee_manager);
+        contract.set_protocol_fee_bps(200);
+        assert!(contract.get_pending_change(Param::ProtocolFeeBps).is_some());
+        assert_eq!(
+            contract.try_set_usage_meter(fee_manager),
+            Err(AccessError::MissingRole.into())
+        );
+    }
+
+    #[test]
+    fn test_fee_change_waits_for_timelock() {
+        let env = odra_test::env();
+        let mut contract = BillingEngine::deploy(&env, NoArgs);
+
+        contract.set_protocol_fee_bps(200);
+        let change = contract.get_pending_change(Param::ProtocolFeeBps).unwrap();
+        assert_eq!(change.amount, 200);
+        assert_eq!(change.eta, Timestamp::from_millis(env.block_time()) + DEFAULT_TIMELOCK_DELAY);
+
+        // Nothing changes until the delay has passed
+        assert_eq!(
+            contract.try_execute_change(change.id),
+            Err(TimelockError::TimelockNotExpired.into())
+        );
+        assert_eq!(contract.get_protocol_fee_bps(), 100);
+
+        env.advance_block_time(DEFAULT_TIMELOCK_DELAY.as_millis());
+        contract.execute_change(change.id);
+        assert_eq!(contract.get_protocol_fee_bps(), 200);
+        assert!(contract.get_pending_change(Param::ProtocolFeeBps).is_none());
+        assert_eq!(
+            contract.try_execute_change(change.id),
+            Err(TimelockError::ChangeNotQueued.into())
+        );
+
+        // Cancelled changes can never execute
+        contract.set_protocol_fee_bps(300);
+        let change = contract.get_pending_change(Param::ProtocolFeeBps).unwrap();
+        contract.cancel_change(change.id);
+        env.advance_block_time(DEFAULT_TIMELOCK_DELAY.as_millis());
+        assert_eq!(
+            contract.try_execute_change(change.id),
+            Err(TimelockError::ChangeNotQueued.into())
+        );
+        assert_eq!(contract.get_protocol_fee_bps(), 200);
+    }
+
+    #[test]
+    fn test_only_address_replacement_is_timelocked() {
+        let env = odra_test::env();
+        let mut contract = BillingEngine::deploy(&env, NoArgs);
+        let first = env.get_account(1);
+        let second = env.get_account(2);
+
+        contract.set_usage_meter(first);
+        assert!(contract.get_pending_change(Param::UsageMeter).is_none());
+
+        contract.set_usage_meter(second);
+        let change = contract.get_pending_change(Param::UsageMeter).unwrap();
+        assert_eq!(change.address, Some(second));
+    }
+
+    /// Deploy an engine and meter wired to a SubscriptionManager with one metered subscription
+    fn setup_metered(env: &HostEnv) -> (BillingEngineHostRef, UsageMeterHostRef, u64, u64) {
+        let mut manager = SubscriptionManager::deploy(env, NoArgs);
+        let plan_id = manager.create_plan(
+            "Metered".to_string(),
+            U512::zero(),
+            U512::from(1_000_000u64),
+            CYCLE,
+        );
+        let sub_id = manager.subscribe(plan_id, true, 0);
+
+        let mut contract = BillingEngine::deploy(env, NoArgs);
+        let mut meter = UsageMeter::deploy(env, NoArgs);
+        meter.set_subscription_manager(manager.address());
+        contract.set_subscription_manager(manager.address());
+        contract.set_usage_meter(meter.address());
+        meter.set_billing_engine(contract.address());
+        (contract, meter, sub_id, plan_id)
+    }
+
+    #[test]
+    fn test_invoice_applies_usage_adjustments() {
+        let env = odra_test::env();
+        let (mut contract, mut meter, sub_id, plan_id) = setup_metered(&env);
+
+        let subscriber = env.get_account(1);
+        let merchant = env.get_account(2);
+        let usage_price = U512::from(1_000_000u64);
+
+        // Bill the first period
+        let record_id = meter.record_usage(sub_id, plan_id, "api_calls".to_string(), 1000);
+        let anchor = meter.get_period_schedule(sub_id).unwrap().anchor;
+        env.advance_block_time((CYCLE + DEFAULT_LATE_USAGE_WINDOW).as_millis());
+        let units = meter.close_period(sub_id, anchor + CYCLE);
+        contract.create_invoice(
+            sub_id, plan_id, subscriber, merchant,
+            U512::zero(), usage_price, units, anchor, anchor + CYCLE,
+        );
+
+        // Merchant discovers 400 calls were double-counted after close
+        meter.adjust_usage(record_id, plan_id, 400, true, reason_codes::DUPLICATE);
+
+        // Adjustments are priced at the plan's usage price, whatever the caller passes
+        let invoice_id = contract.create_invoice(
+            sub_id, plan_id, subscriber, merchant,
+            U512::from(1_000_000_000u64), U512::zero(), 0, anchor + CYCLE, anchor + CYCLE * 2,
+        );
+
+        let invoice = contract.get_invoice(invoice_id).unwrap();
+        assert_eq!(invoice.adjustment_credit, U512::from(400_000_000u64));
+        assert_eq!(invoice.adjustment_debit, U512::zero());
+        assert_eq!(invoice.total_amount, U512::from(600_000_000u64));
+        assert!(meter.get_pending_adjustments(sub_id).credit_units == 0);
+    }
+
+    #[test]
+    fn test_excess_credit_carries_over() {
+        let env = odra_test::env();
+        let (mut contract, mut meter, sub_id, plan_id) = setup_metered(&env);
+
+        let subscriber = env.get_account(1);
+        let merchant = env.get_account(2);
+        let usage_price = U512::from(1_000_000u64);
+
+        let record_id = meter.record_usage(sub_id, plan_id, "api_calls".to_string(), 1000);
+        let anchor = meter.get_period_schedule(sub_id).unwrap().anchor;
+        env.advance_block_time((CYCLE + DEFAULT_LATE_USAGE_WINDOW).as_millis());
+        meter.close_period(sub_id, anchor + CYCLE);
+        meter.adjust_usage(record_id, plan_id, 1000, true, reason_codes::METER_ERROR);
+
+        // 0.1 CSPR invoice absorbs 0.1 CSPR of the 1 CSPR credit
+        let invoice_id = contract.create_invoice(
+            sub_id, plan_id, subscriber, merchant,
+            U512::from(100_000_000u64), usage_price, 0, anchor + CYCLE, anchor + CYCLE * 2,
+        );
+
+        let invoice = contract.get_invoice(invoice_id).unwrap();
+        assert_eq!(invoice.total_amount, U512::zero());
+        assert_eq!(invoice.adjustment_credit, U512::from(100_000_000u64));
+        // Nothing is left to pay, so the invoice is settled on creation
+        assert_eq!(invoice.status, InvoiceStatus::Paid);
+        assert_eq!(invoice.paid_at, Some(invoice.created_at));
+        assert_eq!(contract.get_subscription_credit(sub_id), U512::from(900_000_000u64));
+    }
+}
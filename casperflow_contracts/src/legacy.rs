@@ -0,0 +1,91 @@
+//! Legacy SubscriptionManager Interface
+//!
+//! The first SubscriptionManager deployed to testnet used `u32` plan IDs and
+//! stored subscriptions as `(subscriber, plan_id) -> expiry`. The unified
+//! [`SubscriptionManager`](crate::subscription_manager::SubscriptionManager)
+//! reads that state through this interface when migrating plans and
+//! subscriptions (see `migrate_legacy_plan` / `migrate_legacy_subscription`).
+
+use odra::casper_types::U512;
+use odra::prelude::*;
+
+/// Read-only entry points of the legacy SubscriptionManager
+#[odra::external_contract]
+pub trait LegacySubscriptionManager {
+    /// Plan price in motes (zero if the plan does not exist)
+    fn get_plan_price(&self, plan_id: u32) -> U512;
+    /// Plan period in seconds
+    fn get_plan_period(&self, plan_id: u32) -> u64;
+    /// Plan name
+    fn get_plan_name(&self, plan_id: u32) -> String;
+    /// Plan merchant, `None` if the plan does not exist
+    fn get_plan_merchant(&self, plan_id: u32) -> Option<Address>;
+    /// Subscription expiry (zero if never subscribed)
+    ///
+    /// Computed by the legacy contract as millisecond block time plus the plan
+    /// period in seconds.
+    fn get_expiry(&self, subscriber: Address, plan_id: u32) -> u64;
+    /// Number of plans created
+    fn plan_count(&self) -> u32;
+}
+
+/// Stand-in for the legacy deployment used by migration tests
+#[cfg(test)]
+pub(crate) mod mock {
+    use odra::casper_types::U512;
+    use odra::prelude::*;
+
+    #[odra::module]
+    pub struct LegacyManagerMock {
+        plan_prices: Mapping<u32, U512>,
+        plan_periods: Mapping<u32, u64>,
+        plan_names: Mapping<u32, String>,
+        plan_merchants: Mapping<u32, Address>,
+        subscriptions: Mapping<(Address, u32), u64>,
+        plan_count: Var<u32>,
+    }
+
+    #[odra::module]
+    impl LegacyManagerMock {
+        pub fn create_plan(&mut self, price: U512, period_seconds: u64, name: String) -> u32 {
+            let plan_id = self.plan_count.get_or_default() + 1;
+            self.plan_prices.set(&plan_id, price);
+            self.plan_periods.set(&plan_id, period_seconds);
+            self.plan_names.set(&plan_id, name);
+            self.plan_merchants.set(&plan_id, self.env().caller());
+            self.plan_count.set(plan_id);
+            plan_id
+        }
+
+        #[odra(payable)]
+        pub fn subscribe(&mut self, plan_id: u32) {
+            let period = self.plan_periods.get(&plan_id).unwrap_or_default();
+            let expiry = self.env().get_block_time() + period;
+            self.subscriptions.set(&(self.env().caller(), plan_id), expiry);
+        }
+
+        pub fn get_plan_price(&self, plan_id: u32) -> U512 {
+            self.plan_prices.get(&plan_id).unwrap_or_default()
+        }
+
+        pub fn get_plan_period(&self, plan_id: u32) -> u64 {
+            self.plan_periods.get(&plan_id).unwrap_or_default()
+        }
+
+        pub fn get_plan_name(&self, plan_id: u32) -> String {
+            self.plan_names.get(&plan_id).unwrap_or_default()
+        }
+
+        pub fn get_plan_merchant(&self, plan_id: u32) -> Option<Address> {
+            self.plan_merchants.get(&plan_id)
+        }
+
+        pub fn get_expiry(&self, subscriber: Address, plan_id: u32) -> u64 {
+            self.subscriptions.get(&(subscriber, plan_id)).unwrap_or(0)
+        }
+
+        pub fn plan_count(&self) -> u32 {
+            self.plan_count.get_or_default()
+        }
+    }
+}
@@ -1,8 +1,75 @@
+//! CasperFlow Smart Contracts
+//!
+//! On-chain subscription and metered billing protocol for Casper blockchain.
+//!
+//! ## Contracts
+//!
+//! - [`SubscriptionManager`] - Manage subscription plans and user subscriptions
+//! - [`UsageMeter`] - Track and record API/compute usage metrics  
+//! - [`BillingEngine`] - Calculate and process billing (base + usage)
+//! - [`StakeToPay`] - Pay subscriptions using staking rewards
+//! - [`Registry`] - Canonical address of each component, by version
+//!
+//! Admin entry points are guarded by the roles in [`access`], and groups of
+//! entry points can be halted in an emergency through [`pausable`]. Fee,
+//! yield and address changes wait out the delay in [`timelock`]. All three are
+//! composed into every contract, along with the schema version tracked by
+//! [`upgrade`] for migrating records when an upgraded version is installed.
+//! Contracts pointed at a [`Registry`] look up their peers there, so a
+//! component is replaced in one place.
+//!
+//! Third-party contracts embed the [`access_gate`] submodule to require an
+//! active subscription, and optionally debit usage, before running an entry
+//! point.
+//!
+//! The [`legacy`] module describes the first testnet SubscriptionManager so
+//! its plans and subscriptions can be migrated into this crate's contracts.
+//!
+//! ## Architecture
+//!
+//! ```text
+//! ┌─────────────────────────────────────────────────────────────────┐
+//! │                        CasperFlow Protocol                       │
+//! ├─────────────────┬─────────────────┬───────────────┬─────────────┤
+//! │  Subscription   │   Usage Meter   │   Billing     │  Stake-to   │
+//! │    Manager      │                 │   Engine      │    Pay      │
+//! ├─────────────────┴─────────────────┴───────────────┴─────────────┤
+//! │                     Casper Blockchain                            │
+//! └─────────────────────────────────────────────────────────────────┘
+//! ```
+//!
+//! ## Flow
+//!
+//! 1. Merchant creates a plan via `SubscriptionManager::create_plan`
+//! 2. User subscribes via `SubscriptionManager::subscribe`
+//! 3. Merchant's backend records usage via `UsageMeter::record_usage`
+//! 4. At billing cycle end, `BillingEngine::create_invoice` generates invoice
+//! 5. User pays via wallet or `StakeToPay::pay_invoice_from_rewards`
+
 #![cfg_attr(not(test), no_std)]
 #![cfg_attr(not(test), no_main)]
 extern crate alloc;
 
 pub mod flipper;
+pub mod access;
+pub mod pausable;
+pub mod timelock;
+pub mod upgrade;
 pub mod subscription_manager;
+pub mod usage_meter;
+pub mod billing_engine;
 pub mod stake_to_pay;
+pub mod registry;
+pub mod pagination;
+pub mod analytics;
+pub mod time;
+pub mod legacy;
+pub mod access_gate;
+#[cfg(test)]
+mod simulation;
 
+pub use subscription_manager::SubscriptionManager;
+pub use usage_meter::UsageMeter;
+pub use billing_engine::BillingEngine;
+pub use stake_to_pay::StakeToPay;
+pub use registry::Registry;
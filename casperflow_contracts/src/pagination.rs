@@ -0,0 +1,63 @@
+//! Pagination helpers shared by the contract query APIs.
+//!
+//! Lists are stored as indexed mappings (`(owner, index) -> id` plus a
+//! per-owner count) instead of growing `Vec`s, so writes stay O(1) and
+//! reads can be split into bounded pages.
+//!
+//! A page is requested with a `cursor` (the list index to resume from) and a
+//! `limit`. Filtered queries scan at most [`MAX_SCAN`] entries per call and
+//! return `next_cursor` whenever entries remain, even if the page is short.
+
+use odra::prelude::*;
+
+use crate::time::Timestamp;
+
+/// Maximum number of items returned in a single page
+pub const MAX_PAGE_SIZE: u32 = 50;
+
+/// Maximum number of list entries inspected in a single call
+pub const MAX_SCAN: u32 = 200;
+
+/// Inclusive timestamp range; `None` bounds are open
+#[odra::odra_type]
+#[derive(Default)]
+pub struct DateRange {
+    /// Earliest timestamp to include
+    pub from: Option<Timestamp>,
+    /// Latest timestamp to include
+    pub to: Option<Timestamp>,
+}
+
+impl DateRange {
+    /// Check whether `timestamp` falls within the range
+    pub fn contains(&self, timestamp: Timestamp) -> bool {
+        self.from.map_or(true, |from| timestamp >= from) && self.to.map_or(true, |to| timestamp <= to)
+    }
+}
+
+/// Scan `total` list entries starting at `cursor`, collecting up to `limit`
+/// items for which `load` returns `Some`.
+///
+/// Returns the collected items and the cursor to resume from, if any entries
+/// remain unscanned.
+pub fn scan<T>(
+    total: u32,
+    cursor: u32,
+    limit: u32,
+    mut load: impl FnMut(u32) -> Option<T>,
+) -> (Vec<T>, Option<u32>) {
+    let limit = limit.clamp(1, MAX_PAGE_SIZE);
+    let scan_end = total.min(cursor.saturating_add(MAX_SCAN));
+
+    let mut items = Vec::new();
+    let mut index = cursor;
+    while index < scan_end && (items.len() as u32) < limit {
+        if let Some(item) = load(index) {
+            items.push(item);
+        }
+        index += 1;
+    }
+
+    let next_cursor = if index < total { Some(index) } else { None };
+    (items, next_cursor)
+}
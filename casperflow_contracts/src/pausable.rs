@@ -0,0 +1,104 @@
+//! Emergency pause switches shared by all CasperFlow contracts.
+//!
+//! [`Pausable`] is composed into each contract as a submodule and keeps one
+//! flag per [`PauseGroup`], so e.g. payments can be halted while users are
+//! still able to cancel subscriptions. Pausing is gated by the parent
+//! contract (the `Pauser` role); this module only stores the flags.
+
+use odra::prelude::*;
+
+/// Group of entry points paused together
+#[odra::odra_type]
+#[derive(Copy)]
+pub enum PauseGroup {
+    /// Plan creation/updates, subscribing and legacy migration
+    Subscriptions,
+    /// Recording and adjusting usage
+    UsageRecording,
+    /// Creating invoices
+    Invoicing,
+    /// Paying invoices from wallets or staking rewards
+    Payments,
+    /// Depositing, withdrawing and claiming in StakeToPay
+    Staking,
+}
+
+/// Errors reverted by pause checks (codes 600-699)
+#[odra::odra_error]
+pub enum Error {
+    /// The function group is paused
+    Paused = 600,
+    /// The function group is not paused
+    NotPaused = 601,
+}
+
+/// Events
+pub mod events {
+    use super::*;
+
+    #[odra::event]
+    pub struct Paused {
+        pub group: PauseGroup,
+        pub account: Address,
+    }
+
+    #[odra::event]
+    pub struct Unpaused {
+        pub group: PauseGroup,
+        pub account: Address,
+    }
+}
+
+/// Per-group pause flags
+#[odra::module(events = [
+    events::Paused,
+    events::Unpaused
+], errors = Error)]
+pub struct Pausable {
+    /// Group -> whether it is paused
+    paused: Mapping<PauseGroup, bool>,
+}
+
+#[odra::module]
+impl Pausable {
+    /// Whether `group` is paused
+    pub fn is_paused(&self, group: PauseGroup) -> bool {
+        self.paused.get(&group).unwrap_or_default()
+    }
+}
+
+impl Pausable {
+    /// Pause `group`; the caller must already be authorized by the parent
+    pub fn pause(&mut self, group: PauseGroup) {
+        self.require_not_paused(group);
+        self.paused.set(&group, true);
+        self.env().emit_event(events::Paused {
+            group,
+            account: self.env().caller(),
+        });
+    }
+
+    /// Unpause `group`; the caller must already be authorized by the parent
+    pub fn unpause(&mut self, group: PauseGroup) {
+        self.require_paused(group);
+        self.paused.set(&group, false);
+        self.env().emit_event(events::Unpaused {
+            group,
+            account: self.env().caller(),
+        });
+    }
+
+    /// Revert if `group` is paused
+    pub fn require_not_paused(&self, group: PauseGroup) {
+        if self.is_paused(group) {
+            self.env().revert(Error::Paused);
+        }
+    }
+
+    /// Revert unless `group` is paused
+    pub fn require_paused(&self, group: PauseGroup) {
+        if !self.is_paused(group) {
+            self.env().revert(Error::NotPaused);
+        }
+    }
+}
@@ -0,0 +1,335 @@
+//! Protocol Registry Contract
+//!
+//! Canonical address book of the CasperFlow components.
+//!
+//! Each contract that is pointed at a registry with `set_registry` resolves
+//! its peers here instead of through its own `set_*` addresses, so replacing
+//! a component is a single registry change rather than one call per
+//! contract. Every registration gets the next version number for its
+//! component and is kept, so the full address history can be read back, and
+//! each change is logged with a [`events::ComponentRegistered`] event.
+//!
+//! Like the per-contract setters, the first address of a component takes
+//! effect immediately and replacing it waits out the [`Timelock`] delay.
+
+use odra::prelude::*;
+
+use crate::access::{AccessControl, Role};
+use crate::time::{Duration, Timestamp};
+use crate::timelock::{Error as TimelockError, Param, QueuedChange, Timelock};
+use crate::upgrade::{MigrationProgress, RecordKind, Schema};
+
+/// Protocol component with an address in the registry
+#[odra::odra_type]
+#[derive(Copy)]
+pub enum Component {
+    SubscriptionManager,
+    UsageMeter,
+    BillingEngine,
+    StakeToPay,
+}
+
+impl Component {
+    /// Every component a complete deployment registers
+    pub const ALL: [Component; 4] = [
+        Component::SubscriptionManager,
+        Component::UsageMeter,
+        Component::BillingEngine,
+        Component::StakeToPay,
+    ];
+}
+
+impl From<Component> for Param {
+    fn from(component: Component) -> Self {
+        match component {
+            Component::SubscriptionManager => Param::SubscriptionManager,
+            Component::UsageMeter => Param::UsageMeter,
+            Component::BillingEngine => Param::BillingEngine,
+            Component::StakeToPay => Param::StakeToPay,
+        }
+    }
+}
+
+/// Registered version of a component
+#[odra::odra_type]
+pub struct ComponentEntry {
+    /// Component
+    pub component: Component,
+    /// Version, starting at 1 and increasing with each registration
+    pub version: u32,
+    /// Contract address
+    pub address: Address,
+    /// When this version took effect
+    pub registered_at: Timestamp,
+}
+
+/// Errors reverted by the registry (codes 900-999)
+#[odra::odra_error]
+pub enum Error {
+    /// Address is the current entry of a component
+    AddressInUse = 900,
+    /// Address is not a contract
+    NotAContract = 901,
+}
+
+/// Events
+pub mod events {
+    use super::*;
+
+    #[odra::event]
+    pub struct ComponentRegistered {
+        pub component: Component,
+        pub version: u32,
+        pub previous: Option<Address>,
+        pub address: Address,
+    }
+}
+
+/// Component addresses by name and version
+#[odra::module(events = [
+    events::ComponentRegistered
+], errors = Error)]
+pub struct Registry {
+    /// Owner and role grants
+    access: SubModule<AccessControl>,
+    /// Delayed parameter changes
+    timelock: SubModule<Timelock>,
+    /// Component -> current version (0 if never registered)
+    versions: Mapping<Component, u32>,
+    /// (Component, Version) -> entry
+    entries: Mapping<(Component, u32), ComponentEntry>,
+    /// Address -> component it was last registered as
+    components: Mapping<Address, Component>,
+    /// Stored schema version and migration progress
+    schema: SubModule<Schema>,
+}
+
+#[odra::module]
+impl Registry {
+    /// Initialize the registry
+    pub fn init(&mut self) {
+        let caller = self.env().caller();
+        self.access.init(caller);
+        self.schema.init();
+    }
+
+    /// Register an address for a component (admin; timelocked once set)
+    pub fn register(&mut self, component: Component, address: Address) {
+        self.access.require_role(Role::Admin);
+        self.require_registrable(address);
+        if self.get_address(component).is_none() {
+            self.apply(component, address);
+        } else {
+            self.timelock.queue_address(component.into(), address);
+        }
+    }
+
+    // ============ QUERY FUNCTIONS ============
+
+    /// Get the current address of a component
+    pub fn get_address(&self, component: Component) -> Option<Address> {
+        self.get_component(component).map(|entry| entry.address)
+    }
+
+    /// Get the current entry of a component
+    pub fn get_component(&self, component: Component) -> Option<ComponentEntry> {
+        self.get_component_version(component, self.get_version(component))
+    }
+
+    /// Get a specific version of a component
+    pub fn get_component_version(&self, component: Component, version: u32) -> Option<ComponentEntry> {
+        self.entries.get(&(component, version))
+    }
+
+    /// Get the current version of a component (0 if never registered)
+    pub fn get_version(&self, component: Component) -> u32 {
+        self.versions.get(&component).unwrap_or_default()
+    }
+
+    /// Get the component an address is currently registered as
+    pub fn component_of(&self, address: Address) -> Option<Component> {
+        self.components
+            .get(&address)
+            .filter(|component| self.get_address(*component) == Some(address))
+    }
+
+    /// Get the components that still need an address
+    pub fn missing_components(&self) -> Vec<Component> {
+        Component::ALL
+            .into_iter()
+            .filter(|component| self.get_version(*component) == 0)
+            .collect()
+    }
+
+    // ============ UPGRADE FUNCTIONS ============
+
+    /// Hook run by Odra when a new version of the contract is installed (admin only)
+    pub fn upgrade(&mut self) {
+        self.access.require_role(Role::Admin);
+        self.schema.upgrade(&[]);
+    }
+
+    delegate! {
+        to self.schema {
+            fn schema_version(&self) -> u32;
+            fn get_migration_progress(&self, kind: RecordKind) -> MigrationProgress;
+        }
+    }
+
+    // ============ GOVERNANCE FUNCTIONS ============
+
+    /// Apply a queued registration once its delay has passed (anyone)
+    pub fn execute_change(&mut self, change_id: u64) {
+        let change = self.timelock.take_ready(change_id);
+        let component = match change.param {
+            Param::SubscriptionManager => Component::SubscriptionManager,
+            Param::UsageMeter => Component::UsageMeter,
+            Param::BillingEngine => Component::BillingEngine,
+            Param::StakeToPay => Component::StakeToPay,
+            Param::TimelockDelay => return,
+            _ => self.env().revert(TimelockError::UnsupportedParam),
+        };
+        let address = change.address.unwrap_or_revert(&self.env());
+        // Another component may have taken the address while this was queued
+        self.require_registrable(address);
+        self.apply(component, address);
+    }
+
+    /// Cancel a queued registration (admin only)
+    pub fn cancel_change(&mut self, change_id: u64) {
+        self.access.require_role(Role::Admin);
+        self.timelock.cancel(change_id);
+    }
+
+    /// Queue a new delay for registrations (admin only)
+    pub fn set_timelock_delay(&mut self, delay: Duration) {
+        self.access.require_role(Role::Admin);
+        self.timelock.queue_delay(delay);
+    }
+
+    delegate! {
+        to self.timelock {
+            fn get_queued_change(&self, change_id: u64) -> Option<QueuedChange>;
+            fn get_pending_change(&self, param: Param) -> Option<QueuedChange>;
+            fn get_timelock_delay(&self) -> Duration;
+        }
+    }
+
+    // ============ ACCESS CONTROL ============
+
+    delegate! {
+        to self.access {
+            fn grant_role(&mut self, role: Role, account: Address);
+            fn revoke_role(&mut self, role: Role, account: Address);
+            fn renounce_role(&mut self, role: Role);
+            fn transfer_ownership(&mut self, new_owner: Address);
+            fn accept_ownership(&mut self);
+            fn has_role(&self, role: Role, account: Address) -> bool;
+            fn owner(&self) -> Option<Address>;
+            fn pending_owner(&self) -> Option<Address>;
+        }
+    }
+}
+
+impl Registry {
+    fn require_registrable(&self, address: Address) {
+        if !address.is_contract() {
+            self.env().revert(Error::NotAContract);
+        }
+        if self.component_of(address).is_some() {
+            self.env().revert(Error::AddressInUse);
+        }
+    }
+
+    fn apply(&mut self, component: Component, address: Address) {
+        let previous = self.get_address(component);
+        let version = self.get_version(component) + 1;
+        self.entries.set(&(component, version), ComponentEntry {
+            component,
+            version,
+            address,
+            registered_at: Timestamp::now(&self.env()),
+        });
+        self.versions.set(&component, version);
+        self.components.set(&address, component);
+
+        self.env().emit_event(events::ComponentRegistered {
+            component,
+            version,
+            previous,
+            address,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::access::Error as AccessError;
+    use crate::billing_engine::BillingEngine;
+    use crate::subscription_manager::SubscriptionManager;
+    use crate::timelock::DEFAULT_TIMELOCK_DELAY;
+    use odra::host::{Deployer, HostRef, NoArgs};
+
+    #[test]
+    fn test_register_and_replace_component() {
+        let env = odra_test::env();
+        let mut registry = Registry::deploy(&env, NoArgs);
+        let manager = SubscriptionManager::deploy(&env, NoArgs);
+        let first = BillingEngine::deploy(&env, NoArgs);
+        let second = BillingEngine::deploy(&env, NoArgs);
+        assert_eq!(registry.missing_components(), Component::ALL.to_vec());
+
+        // The first address takes effect immediately
+        registry.register(Component::BillingEngine, first.address());
+        assert_eq!(registry.get_address(Component::BillingEngine), Some(first.address()));
+        assert_eq!(registry.get_version(Component::BillingEngine), 1);
+        assert!(env.emitted_event(&registry, events::ComponentRegistered {
+            component: Component::BillingEngine,
+            version: 1,
+            previous: None,
+            address: first.address(),
+        }));
+
+        // Only contracts not already registered can be registered
+        assert_eq!(
+            registry.try_register(Component::UsageMeter, env.get_account(1)),
+            Err(Error::NotAContract.into())
+        );
+        assert_eq!(
+            registry.try_register(Component::SubscriptionManager, first.address()),
+            Err(Error::AddressInUse.into())
+        );
+        env.set_caller(env.get_account(1));
+        assert_eq!(
+            registry.try_register(Component::SubscriptionManager, manager.address()),
+            Err(AccessError::MissingRole.into())
+        );
+        env.set_caller(env.get_account(0));
+
+        // Replacing it waits out the timelock
+        registry.register(Component::BillingEngine, second.address());
+        let change = registry.get_pending_change(Param::BillingEngine).unwrap();
+        assert_eq!(registry.get_address(Component::BillingEngine), Some(first.address()));
+        env.advance_block_time(DEFAULT_TIMELOCK_DELAY.as_millis());
+        registry.execute_change(change.id);
+
+        assert_eq!(registry.get_address(Component::BillingEngine), Some(second.address()));
+        assert_eq!(registry.component_of(second.address()), Some(Component::BillingEngine));
+        assert_eq!(registry.component_of(first.address()), None);
+        assert!(env.emitted_event(&registry, events::ComponentRegistered {
+            component: Component::BillingEngine,
+            version: 2,
+            previous: Some(first.address()),
+            address: second.address(),
+        }));
+
+        // Earlier versions stay readable
+        let entry = registry.get_component_version(Component::BillingEngine, 1).unwrap();
+        assert_eq!(entry.address, first.address());
+        assert_eq!(
+            registry.missing_components(),
+            vec![Component::SubscriptionManager, Component::UsageMeter, Component::StakeToPay]
+        );
+    }
+}
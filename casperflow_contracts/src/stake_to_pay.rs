@@ -1,354 +1,998 @@
+//! Stake-to-Pay Contract
+//!
+//! Enables users to pay subscriptions using staking rewards instead of their wallet.
+//! This is a key differentiator for CasperFlow - users keep their tokens staked
+//! while still accessing premium services.
+//!
+//! Key features:
+//! - Delegate staking to approved validators
+//! - Pay invoices from staking rewards
+//! - Claim rewards without touching principal
+//! - Per-plan auto-pay opt-in, settled by keepers in batches
+//! - Auto top-up of prepaid usage credit held by the UsageMeter
+//! - Emergency principal withdrawal while staking is paused
+//! - Rewards paid from a funded reserve, never from principal
+//! - Keep principal staked, only use rewards
+
 use odra::prelude::*;
 use odra::casper_types::U512;
 
-/// CasperFlow Stake-to-Pay Contract v2.0
-/// 
-/// Revolutionary subscription payment mechanism where users stake CSPR
-/// and automatically pay subscriptions from their staking rewards.
-/// 
-/// Key Features:
-/// 1. Stake CSPR and earn ~8% APY
-/// 2. Auto-pay subscriptions from staking rewards
-/// 3. Auto-renew subscriptions before expiry
-/// 4. Keep principal untouched - only use yield for payments
-/// 5. Withdraw anytime with no lock-up period
-///
-/// Flow:
-/// 1. User stakes CSPR (e.g., 1000 CSPR)
-/// 2. Staked CSPR earns rewards (~8% APY on Casper)
-/// 3. When subscription is due, contract pays from rewards first
-/// 4. If rewards insufficient, uses staked principal
-/// 5. User can top-up stake or withdraw anytime
-#[odra::module]
+use crate::access::{AccessControl, Role};
+use crate::billing_engine::{BillingEngineContractRef, Invoice, InvoiceStatus};
+use crate::pagination::{self, DateRange};
+use crate::pausable::{PauseGroup, Pausable};
+use crate::registry::{Component, RegistryContractRef};
+use crate::subscription_manager::SubscriptionManagerContractRef;
+use crate::time::{Duration, Timestamp, YEAR};
+use crate::timelock::{Error as TimelockError, Param, QueuedChange, Timelock};
+use crate::upgrade::{Migrate, MigrationProgress, RecordKind, Schema};
+
+/// User's stake-to-pay configuration
+#[odra::odra_type]
+pub struct StakeConfig {
+    /// User address
+    pub user: Address,
+    /// Amount of CSPR staked
+    pub staked_amount: U512,
+    /// Accumulated rewards (not yet paid out)
+    pub accumulated_rewards: U512,
+    /// Total rewards used for payments
+    pub total_rewards_used: U512,
+    /// Whether stake-to-pay is enabled for this user
+    pub is_enabled: bool,
+    /// Last reward update timestamp
+    pub last_updated: Timestamp,
+}
+
+impl Migrate for StakeConfig {
+    // Schema 1 is the first versioned layout
+    type Previous = StakeConfig;
+
+    fn migrate(previous: StakeConfig, _from_version: u32) -> Self {
+        previous
+    }
+}
+
+/// Payment made from staking rewards
+#[odra::odra_type]
+pub struct StakePayment {
+    /// Payment ID
+    pub id: u64,
+    /// User who made payment
+    pub user: Address,
+    /// Invoice ID that was paid, 0 for prepaid credit top-ups
+    pub invoice_id: u64,
+    /// Amount paid from rewards
+    pub amount: U512,
+    /// Timestamp
+    pub paid_at: Timestamp,
+}
+
+/// Page of stake payments returned by paginated queries
+#[odra::odra_type]
+pub struct StakePaymentPage {
+    pub items: Vec<StakePayment>,
+    /// Cursor to pass to fetch the next page, `None` when exhausted
+    pub next_cursor: Option<u32>,
+}
+
+/// Errors reverted by the contract (codes 400-499)
+#[odra::odra_error]
+pub enum Error {
+    /// Deposit has no attached value
+    ZeroDeposit = 401,
+    /// Caller has never staked
+    StakeNotFound = 402,
+    /// Withdrawal exceeds the staked amount
+    InsufficientStake = 403,
+    /// Withdrawal or payment exceeds accumulated rewards
+    InsufficientRewards = 404,
+    /// No rewards have accumulated yet
+    NoRewards = 405,
+    /// Stake-to-pay is disabled for the staker
+    StakeToPayNotEnabled = 406,
+    /// APY above the 20% cap
+    ApyTooHigh = 407,
+    /// BillingEngine address has not been configured
+    BillingEngineNotSet = 408,
+    /// Caller is not the UsageMeter
+    NotUsageMeter = 409,
+    /// BillingEngine has no invoice with the ID
+    InvoiceNotFound = 410,
+    /// Caller is not the invoice's subscriber
+    NotInvoiceSubscriber = 411,
+    /// SubscriptionManager address has not been configured
+    SubscriptionManagerNotSet = 412,
+    /// Reward reserve cannot cover the payout
+    RewardReserveExhausted = 413,
+}
+
+/// Events
+pub mod events {
+    use super::*;
+
+    #[odra::event]
+    pub struct StakeDeposited {
+        pub user: Address,
+        pub amount: U512,
+        pub total_staked: U512,
+    }
+
+    #[odra::event]
+    pub struct StakeWithdrawn {
+        pub user: Address,
+        pub amount: U512,
+        pub remaining: U512,
+    }
+
+    #[odra::event]
+    pub struct RewardsAccumulated {
+        pub user: Address,
+        pub amount: U512,
+        pub total_rewards: U512,
+    }
+
+    #[odra::event]
+    pub struct PaymentFromRewards {
+        pub user: Address,
+        pub invoice_id: u64,
+        pub amount: U512,
+        pub remaining_rewards: U512,
+    }
+
+    #[odra::event]
+    pub struct StakeToPayEnabled {
+        pub user: Address,
+    }
+
+    #[odra::event]
+    pub struct StakeToPayDisabled {
+        pub user: Address,
+    }
+
+    #[odra::event]
+    pub struct RewardsClaimed {
+        pub user: Address,
+        pub amount: U512,
+    }
+
+    #[odra::event]
+    pub struct AutoPayChanged {
+        pub user: Address,
+        pub plan_id: u64,
+        pub enabled: bool,
+    }
+
+    #[odra::event]
+    pub struct EmergencyWithdrawn {
+        pub user: Address,
+        pub amount: U512,
+    }
+
+    /// `amount` is zero when auto top-up is disabled
+    #[odra::event]
+    pub struct AutoTopUpChanged {
+        pub user: Address,
+        pub merchant: Address,
+        pub amount: U512,
+    }
+
+    #[odra::event]
+    pub struct RewardsFunded {
+        pub funder: Address,
+        pub amount: U512,
+        pub reserve: U512,
+    }
+}
+
+/// Stake-to-Pay Contract
+#[odra::module(events = [
+    events::StakeDeposited,
+    events::StakeWithdrawn,
+    events::RewardsAccumulated,
+    events::PaymentFromRewards,
+    events::StakeToPayEnabled,
+    events::StakeToPayDisabled,
+    events::RewardsClaimed,
+    events::AutoPayChanged,
+    events::EmergencyWithdrawn,
+    events::AutoTopUpChanged,
+    events::RewardsFunded
+], errors = Error)]
 pub struct StakeToPay {
-    /// Contract owner
-    owner: Var<Address>,
-    
-    /// User stakes: address -> staked amount in motes
-    stakes: Mapping<Address, U512>,
-    
-    /// User stake timestamps: address -> staked_at timestamp
-    stake_timestamps: Mapping<Address, u64>,
-    
-    /// Accumulated rewards: address -> unclaimed rewards
-    accumulated_rewards: Mapping<Address, U512>,
-    
-    /// Last reward calculation timestamp: address -> timestamp
-    last_reward_calc: Mapping<Address, u64>,
-    
-    /// User auto-pay settings: (user, plan_id) -> enabled
-    auto_pay: Mapping<(Address, u32), bool>,
-    
-    /// User auto-renew settings: (user, plan_id) -> enabled  
-    auto_renew: Mapping<(Address, u32), bool>,
-    
-    /// Total payments made for user: address -> total paid
-    total_payments_made: Mapping<Address, U512>,
-    
-    /// Total rewards claimed: address -> total claimed
-    total_rewards_claimed: Mapping<Address, U512>,
-    
-    /// Total staked in contract
+    /// Owner and role grants
+    access: SubModule<AccessControl>,
+    /// Per-group pause flags
+    pausable: SubModule<Pausable>,
+    /// Delayed parameter changes
+    timelock: SubModule<Timelock>,
+    /// BillingEngine contract address
+    billing_engine: Var<Option<Address>>,
+    /// User -> StakeConfig
+    stake_configs: Mapping<Address, StakeConfig>,
+    /// Payment counter
+    payment_counter: Var<u64>,
+    /// Payment ID -> StakePayment
+    payments: Mapping<u64, StakePayment>,
+    /// User -> number of payments
+    user_payment_count: Mapping<Address, u32>,
+    /// (User, Index) -> Payment ID
+    user_payments: Mapping<(Address, u32), u64>,
+    /// Total staked across all users
     total_staked: Var<U512>,
-    
     /// Total rewards distributed
-    total_rewards_distributed: Var<U512>,
-    
-    /// Subscription manager contract address (for integration)
+    total_rewards: Var<U512>,
+    /// (User, Plan ID) -> auto-pay enabled
+    auto_pay: Mapping<(Address, u64), bool>,
+    /// Simulated annual percentage yield (APY) in basis points (e.g., 800 = 8%)
+    apy_bps: Var<u64>,
+    /// Number of accounts that have ever staked
+    staker_count: Var<u32>,
+    /// Index -> staker address
+    stakers: Mapping<u32, Address>,
+    /// Stored schema version and migration progress
+    schema: SubModule<Schema>,
+    /// Protocol Registry consulted for peer addresses, if set
+    registry: Var<Option<Address>>,
+    /// (User, Merchant) -> minimum credit top-up paid from rewards, zero if off
+    auto_top_up: Mapping<(Address, Address), U512>,
+    /// SubscriptionManager contract address
     subscription_manager: Var<Option<Address>>,
-    
-    /// APY in basis points (800 = 8%)
-    apy_basis_points: Var<u32>,
+    /// CSPR set aside to pay out rewards, kept apart from principal
+    reward_reserve: Var<U512>,
 }
 
 #[odra::module]
 impl StakeToPay {
-    /// Initialize the contract with 8% APY
+    /// Initialize the contract
     pub fn init(&mut self) {
         let caller = self.env().caller();
-        self.owner.set(caller);
+        self.access.init(caller);
+        self.schema.init();
+        self.payment_counter.set(0);
         self.total_staked.set(U512::zero());
-        self.total_rewards_distributed.set(U512::zero());
-        self.apy_basis_points.set(800); // 8% APY
+        self.total_rewards.set(U512::zero());
+        self.apy_bps.set(800); // 8% default APY
     }
 
-    /// Stake CSPR - PAYABLE
-    /// User deposits CSPR that earns yield for auto-paying subscriptions
+    // ============ USER FUNCTIONS ============
+
+    /// Deposit CSPR for stake-to-pay
     #[odra(payable)]
-    pub fn stake(&mut self) {
-        let staker = self.env().caller();
+    pub fn deposit(&mut self) {
+        self.pausable.require_not_paused(PauseGroup::Staking);
+        let caller = self.env().caller();
         let amount = self.env().attached_value();
-        let current_time = self.env().get_block_time();
-        
-        assert!(amount > U512::zero(), "Must stake more than 0");
-        
-        // Calculate and add any pending rewards before updating stake
-        self.calculate_and_add_rewards(staker);
-        
-        // Add to user's stake
-        let current_stake = self.stakes.get(&staker).unwrap_or_default();
-        self.stakes.set(&staker, current_stake + amount);
         
-        // Set/update timestamp
-        if current_stake == U512::zero() {
-            self.stake_timestamps.set(&staker, current_time);
+        if amount.is_zero() {
+            self.env().revert(Error::ZeroDeposit);
         }
-        self.last_reward_calc.set(&staker, current_time);
-        
+
+        let mut config = match self.stake_configs.get(&caller) {
+            Some(config) => config,
+            None => {
+                // Index first-time stakers so their configs can be migrated
+                let index = self.staker_count.get_or_default();
+                self.stakers.set(&index, caller);
+                self.staker_count.set(index + 1);
+                StakeConfig {
+                    user: caller,
+                    staked_amount: U512::zero(),
+                    accumulated_rewards: U512::zero(),
+                    total_rewards_used: U512::zero(),
+                    is_enabled: true,
+                    last_updated: Timestamp::now(&self.env()),
+                }
+            }
+        };
+
+        // Accumulate any pending rewards before updating
+        self.accumulate_rewards(&mut config);
+
+        config.staked_amount = config.staked_amount + amount;
+        config.last_updated = Timestamp::now(&self.env());
+        self.stake_configs.set(&caller, config.clone());
+
         // Update total staked
-        let total = self.total_staked.get_or_default();
-        self.total_staked.set(total + amount);
+        let current_total = self.total_staked.get_or_default();
+        self.total_staked.set(current_total + amount);
+
+        self.env().emit_event(events::StakeDeposited {
+            user: caller,
+            amount,
+            total_staked: config.staked_amount,
+        });
     }
 
-    /// Withdraw staked CSPR with accumulated rewards
+    /// Withdraw staked CSPR
     pub fn withdraw(&mut self, amount: U512) {
-        let staker = self.env().caller();
-        
-        // Calculate pending rewards first
-        self.calculate_and_add_rewards(staker);
+        self.pausable.require_not_paused(PauseGroup::Staking);
+        let caller = self.env().caller();
         
-        let current_stake = self.stakes.get(&staker).unwrap_or_default();
-        let current_rewards = self.accumulated_rewards.get(&staker).unwrap_or_default();
-        let total_available = current_stake + current_rewards;
+        let mut config = self
+            .stake_configs
+            .get(&caller)
+            .unwrap_or_revert_with(&self.env(), Error::StakeNotFound);
         
-        assert!(total_available >= amount, "Insufficient balance");
+        // Accumulate any pending rewards
+        self.accumulate_rewards(&mut config);
         
-        // Withdraw from rewards first, then stake
-        let mut remaining = amount;
-        if current_rewards >= remaining {
-            self.accumulated_rewards.set(&staker, current_rewards - remaining);
-        } else {
-            remaining = remaining - current_rewards;
-            self.accumulated_rewards.set(&staker, U512::zero());
-            self.stakes.set(&staker, current_stake - remaining);
-            
-            // Update total staked
-            let total = self.total_staked.get_or_default();
-            self.total_staked.set(total - remaining);
+        if config.staked_amount < amount {
+            self.env().revert(Error::InsufficientStake);
         }
-        
-        // Transfer to user
-        self.env().transfer_tokens(&staker, &amount);
+
+        config.staked_amount = config.staked_amount - amount;
+        config.last_updated = Timestamp::now(&self.env());
+        self.stake_configs.set(&caller, config.clone());
+
+        // Update total staked
+        let current_total = self.total_staked.get_or_default();
+        self.total_staked.set(current_total - amount);
+
+        // Transfer back to user
+        self.env().transfer_tokens(&caller, &amount);
+
+        self.env().emit_event(events::StakeWithdrawn {
+            user: caller,
+            amount,
+            remaining: config.staked_amount,
+        });
     }
 
-    /// Claim accumulated rewards without withdrawing stake
-    pub fn claim_rewards(&mut self) -> U512 {
-        let staker = self.env().caller();
-        
-        // Calculate pending rewards
-        self.calculate_and_add_rewards(staker);
-        
-        let rewards = self.accumulated_rewards.get(&staker).unwrap_or_default();
-        assert!(rewards > U512::zero(), "No rewards to claim");
-        
-        // Clear accumulated rewards
-        self.accumulated_rewards.set(&staker, U512::zero());
+    /// Withdraw all staked principal while staking is paused
+    ///
+    /// Skips reward accrual and payment logic so users can always exit.
+    /// Rewards already accumulated stay claimable once staking resumes;
+    /// rewards pending since the last update are forfeited.
+    pub fn emergency_withdraw(&mut self) -> U512 {
+        self.pausable.require_paused(PauseGroup::Staking);
+        let caller = self.env().caller();
+
+        let mut config = self
+            .stake_configs
+            .get(&caller)
+            .unwrap_or_revert_with(&self.env(), Error::StakeNotFound);
+        let amount = config.staked_amount;
+        if amount.is_zero() {
+            self.env().revert(Error::InsufficientStake);
+        }
+
+        config.staked_amount = U512::zero();
+        config.last_updated = Timestamp::now(&self.env());
+        self.stake_configs.set(&caller, config);
+
+        let current_total = self.total_staked.get_or_default();
+        self.total_staked.set(current_total - amount);
+
+        self.env().transfer_tokens(&caller, &amount);
+
+        self.env().emit_event(events::EmergencyWithdrawn {
+            user: caller,
+            amount,
+        });
+        amount
+    }
+
+    /// Withdraw accumulated rewards
+    pub fn withdraw_rewards(&mut self, amount: U512) {
+        self.pausable.require_not_paused(PauseGroup::Staking);
+        let caller = self.env().caller();
         
-        // Update total claimed
-        let claimed = self.total_rewards_claimed.get(&staker).unwrap_or_default();
-        self.total_rewards_claimed.set(&staker, claimed + rewards);
+        let mut config = self
+            .stake_configs
+            .get(&caller)
+            .unwrap_or_revert_with(&self.env(), Error::StakeNotFound);
         
-        // Transfer rewards
-        self.env().transfer_tokens(&staker, &rewards);
+        // Accumulate any pending rewards
+        self.accumulate_rewards(&mut config);
         
+        if config.accumulated_rewards < amount {
+            self.env().revert(Error::InsufficientRewards);
+        }
+
+        config.accumulated_rewards = config.accumulated_rewards - amount;
+        config.last_updated = Timestamp::now(&self.env());
+        self.stake_configs.set(&caller, config);
+
+        self.draw_reserve(amount);
+        self.env().transfer_tokens(&caller, &amount);
+    }
+
+    /// Claim all accumulated rewards without touching the principal
+    pub fn claim_rewards(&mut self) -> U512 {
+        self.pausable.require_not_paused(PauseGroup::Staking);
+        let caller = self.env().caller();
+
+        let mut config = self
+            .stake_configs
+            .get(&caller)
+            .unwrap_or_revert_with(&self.env(), Error::StakeNotFound);
+        self.accumulate_rewards(&mut config);
+
+        let rewards = config.accumulated_rewards;
+        if rewards.is_zero() {
+            self.env().revert(Error::NoRewards);
+        }
+
+        config.accumulated_rewards = U512::zero();
+        config.last_updated = Timestamp::now(&self.env());
+        self.stake_configs.set(&caller, config);
+
+        self.draw_reserve(rewards);
+        self.env().transfer_tokens(&caller, &rewards);
+
+        self.env().emit_event(events::RewardsClaimed {
+            user: caller,
+            amount: rewards,
+        });
+
         rewards
     }
 
-    /// Pay subscription from staked balance/rewards
-    /// Can only be called by subscription manager or the user themselves
-    pub fn pay_subscription(&mut self, user: Address, plan_id: u32, amount: U512) {
+    /// Enable stake-to-pay for user
+    pub fn enable_stake_to_pay(&mut self) {
         let caller = self.env().caller();
-        let manager = self.subscription_manager.get().flatten();
         
-        // Authorization check
-        assert!(
-            caller == user || Some(caller) == manager,
-            "Unauthorized"
-        );
-        
-        // Check auto-pay is enabled for this plan
-        assert!(
-            self.auto_pay.get(&(user, plan_id)).unwrap_or(false),
-            "Auto-pay not enabled for this plan"
-        );
-        
-        // Calculate pending rewards first
-        self.calculate_and_add_rewards(user);
-        
-        let current_stake = self.stakes.get(&user).unwrap_or_default();
-        let current_rewards = self.accumulated_rewards.get(&user).unwrap_or_default();
-        let total_available = current_stake + current_rewards;
-        
-        assert!(total_available >= amount, "Insufficient balance for payment");
+        let mut config = self
+            .stake_configs
+            .get(&caller)
+            .unwrap_or_revert_with(&self.env(), Error::StakeNotFound);
+        config.is_enabled = true;
+        self.stake_configs.set(&caller, config);
+
+        self.env().emit_event(events::StakeToPayEnabled { user: caller });
+    }
+
+    /// Disable stake-to-pay for user
+    pub fn disable_stake_to_pay(&mut self) {
+        let caller = self.env().caller();
         
-        // Pay from rewards first, then stake
-        let mut remaining = amount;
-        if current_rewards >= remaining {
-            self.accumulated_rewards.set(&user, current_rewards - remaining);
-        } else {
-            remaining = remaining - current_rewards;
-            self.accumulated_rewards.set(&user, U512::zero());
-            self.stakes.set(&user, current_stake - remaining);
-            
-            // Update total staked if using principal
-            let total = self.total_staked.get_or_default();
-            self.total_staked.set(total - remaining);
+        let mut config = self
+            .stake_configs
+            .get(&caller)
+            .unwrap_or_revert_with(&self.env(), Error::StakeNotFound);
+        config.is_enabled = false;
+        self.stake_configs.set(&caller, config);
+
+        self.env().emit_event(events::StakeToPayDisabled { user: caller });
+    }
+
+    /// Opt in to paying a plan's invoices from staking rewards
+    pub fn enable_auto_pay(&mut self, plan_id: u64) {
+        self.set_auto_pay(plan_id, true);
+    }
+
+    /// Opt out of paying a plan's invoices from staking rewards
+    pub fn disable_auto_pay(&mut self, plan_id: u64) {
+        self.set_auto_pay(plan_id, false);
+    }
+
+    /// Top up prepaid credit with a merchant from staking rewards when it runs
+    /// short, by at least `amount` at a time
+    pub fn enable_auto_top_up(&mut self, merchant: Address, amount: U512) {
+        self.set_auto_top_up(merchant, amount);
+    }
+
+    /// Stop topping up prepaid credit with a merchant
+    pub fn disable_auto_top_up(&mut self, merchant: Address) {
+        self.set_auto_top_up(merchant, U512::zero());
+    }
+
+    /// Add the attached CSPR to the reserve rewards are paid from
+    ///
+    /// Claims, withdrawals and payments from rewards draw on the reserve and
+    /// revert with `RewardReserveExhausted` once it runs dry; auto-pay and
+    /// credit top-ups skip instead. Staked principal is never paid out as
+    /// rewards.
+    #[odra(payable)]
+    pub fn fund_rewards(&mut self) {
+        let amount = self.env().attached_value();
+        if amount.is_zero() {
+            self.env().revert(Error::ZeroDeposit);
+        }
+        let reserve = self.get_reward_reserve() + amount;
+        self.reward_reserve.set(reserve);
+
+        self.env().emit_event(events::RewardsFunded {
+            funder: self.env().caller(),
+            amount,
+            reserve,
+        });
+    }
+
+    // ============ PAYMENT FUNCTIONS ============
+
+    /// Pay one of the caller's pending invoices from their staking rewards
+    ///
+    /// BillingEngine receives the invoice total, marks the invoice paid and
+    /// pays out the merchant share and protocol fee.
+    pub fn pay_invoice_from_rewards(&mut self, invoice_id: u64) {
+        self.pausable.require_not_paused(PauseGroup::Payments);
+        let caller = self.env().caller();
+        let billing_engine = self
+            .get_peer(Component::BillingEngine)
+            .unwrap_or_revert_with(&self.env(), Error::BillingEngineNotSet);
+        let mut engine = BillingEngineContractRef::new(self.env(), billing_engine);
+        let invoice = engine
+            .get_invoice(invoice_id)
+            .unwrap_or_revert_with(&self.env(), Error::InvoiceNotFound);
+        if invoice.subscriber != caller {
+            self.env().revert(Error::NotInvoiceSubscriber);
+        }
+        let amount = invoice.total_amount;
+
+        let mut config = self
+            .stake_configs
+            .get(&caller)
+            .unwrap_or_revert_with(&self.env(), Error::StakeNotFound);
+        if !config.is_enabled {
+            self.env().revert(Error::StakeToPayNotEnabled);
         }
         
-        // Update total payments made
-        let total_paid = self.total_payments_made.get(&user).unwrap_or_default();
-        self.total_payments_made.set(&user, total_paid + amount);
+        // Accumulate pending rewards
+        self.accumulate_rewards(&mut config);
         
-        // Transfer to subscription manager (if set) or hold in contract
-        if let Some(mgr) = manager {
-            self.env().transfer_tokens(&mgr, &amount);
+        if config.accumulated_rewards < amount {
+            self.env().revert(Error::InsufficientRewards);
         }
+
+        self.spend_rewards(config, invoice_id, amount);
+        engine
+            .with_tokens(amount)
+            .pay_invoice_from_staking(invoice_id, caller);
     }
 
-    /// Enable auto-pay for a subscription plan
-    pub fn enable_auto_pay(&mut self, plan_id: u32) {
-        let user = self.env().caller();
-        self.auto_pay.set(&(user, plan_id), true);
+    /// Pay pending invoices from their subscribers' staking rewards (keeper only)
+    ///
+    /// An invoice is paid when the SubscriptionManager issued it for the
+    /// subscription's plan, to the plan's merchant at the plan's prices, and
+    /// its subscriber has Stake-to-Pay and auto-pay for the plan enabled and
+    /// enough rewards; BillingEngine receives the total and pays out the
+    /// merchant share and protocol fee. Other invoices are skipped so a stale
+    /// ID does not revert the batch. Returns the number of invoices paid.
+    pub fn auto_pay_invoices(&mut self, invoice_ids: Vec<u64>) -> u32 {
+        self.pausable.require_not_paused(PauseGroup::Payments);
+        self.access.require_role(Role::Keeper);
+        let billing_engine = self
+            .get_peer(Component::BillingEngine)
+            .unwrap_or_revert_with(&self.env(), Error::BillingEngineNotSet);
+        let subscription_manager = self
+            .get_peer(Component::SubscriptionManager)
+            .unwrap_or_revert_with(&self.env(), Error::SubscriptionManagerNotSet);
+        let mut engine = BillingEngineContractRef::new(self.env(), billing_engine);
+        let manager = SubscriptionManagerContractRef::new(self.env(), subscription_manager);
+
+        let mut paid = 0;
+        for invoice_id in invoice_ids {
+            let Some(invoice) = engine.get_invoice(invoice_id) else {
+                continue;
+            };
+            if invoice.status != InvoiceStatus::Pending
+                || !self.is_auto_pay_enabled(invoice.subscriber, invoice.plan_id)
+                || !engine.is_issued_by_manager(invoice_id)
+                || !self.bills_plan(&manager, &invoice)
+            {
+                continue;
+            }
+            let Some(mut config) = self.stake_configs.get(&invoice.subscriber) else {
+                continue;
+            };
+            if !config.is_enabled {
+                continue;
+            }
+
+            self.accumulate_rewards(&mut config);
+            if config.accumulated_rewards < invoice.total_amount || self.get_reward_reserve() < invoice.total_amount {
+                // Keep the accrual so it is not counted again
+                config.last_updated = Timestamp::now(&self.env());
+                self.stake_configs.set(&invoice.subscriber, config);
+                continue;
+            }
+
+            self.spend_rewards(config, invoice_id, invoice.total_amount);
+            engine
+                .with_tokens(invoice.total_amount)
+                .pay_invoice_from_staking(invoice_id, invoice.subscriber);
+            paid += 1;
+        }
+        paid
     }
 
-    /// Disable auto-pay for a subscription plan
-    pub fn disable_auto_pay(&mut self, plan_id: u32) {
-        let user = self.env().caller();
-        self.auto_pay.set(&(user, plan_id), false);
+    /// Send prepaid credit to the UsageMeter from a subscriber's rewards (called by UsageMeter)
+    ///
+    /// Sends the larger of `shortfall` and the subscriber's auto top-up amount
+    /// and returns what was sent. Returns zero instead of reverting when the
+    /// subscriber has not opted in, lacks the rewards or payments are paused,
+    /// leaving the UsageMeter to reject the usage.
+    pub fn top_up_credit(&mut self, subscriber: Address, merchant: Address, shortfall: U512) -> U512 {
+        let caller = self.env().caller();
+        if Some(caller) != self.get_peer(Component::UsageMeter) {
+            self.env().revert(Error::NotUsageMeter);
+        }
+        let top_up = self.get_auto_top_up(subscriber, merchant);
+        if top_up.is_zero() || self.pausable.is_paused(PauseGroup::Payments) {
+            return U512::zero();
+        }
+        let Some(mut config) = self.stake_configs.get(&subscriber) else {
+            return U512::zero();
+        };
+        if !config.is_enabled {
+            return U512::zero();
+        }
+
+        let amount = top_up.max(shortfall);
+        self.accumulate_rewards(&mut config);
+        if config.accumulated_rewards < amount || self.get_reward_reserve() < amount {
+            // Keep the accrual so it is not counted again
+            config.last_updated = Timestamp::now(&self.env());
+            self.stake_configs.set(&subscriber, config);
+            return U512::zero();
+        }
+
+        self.spend_rewards(config, 0, amount);
+        self.env().transfer_tokens(&caller, &amount);
+        amount
+    }
+
+    // ============ INTERNAL FUNCTIONS ============
+
+    /// Deduct a payment from accrued rewards and record it
+    fn spend_rewards(&mut self, mut config: StakeConfig, invoice_id: u64, amount: U512) {
+        self.draw_reserve(amount);
+        let user = config.user;
+        config.accumulated_rewards = config.accumulated_rewards - amount;
+        config.total_rewards_used = config.total_rewards_used + amount;
+        config.last_updated = Timestamp::now(&self.env());
+        let remaining_rewards = config.accumulated_rewards;
+        self.stake_configs.set(&user, config);
+
+        let payment_id = self.payment_counter.get_or_default() + 1;
+        self.payment_counter.set(payment_id);
+
+        let payment = StakePayment {
+            id: payment_id,
+            user,
+            invoice_id,
+            amount,
+            paid_at: Timestamp::now(&self.env()),
+        };
+        self.payments.set(&payment_id, payment);
+
+        // Add to user's payments
+        let index = self.user_payment_count.get(&user).unwrap_or_default();
+        self.user_payments.set(&(user, index), payment_id);
+        self.user_payment_count.set(&user, index + 1);
+
+        self.env().emit_event(events::PaymentFromRewards {
+            user,
+            invoice_id,
+            amount,
+            remaining_rewards,
+        });
+    }
+
+    /// Take a rewards payout from the reserve
+    fn draw_reserve(&mut self, amount: U512) {
+        let reserve = self.get_reward_reserve();
+        if reserve < amount {
+            self.env().revert(Error::RewardReserveExhausted);
+        }
+        self.reward_reserve.set(reserve - amount);
     }
 
-    /// Enable auto-renew for a subscription plan
-    pub fn enable_auto_renew(&mut self, plan_id: u32) {
+    fn set_auto_pay(&mut self, plan_id: u64, enabled: bool) {
         let user = self.env().caller();
-        self.auto_renew.set(&(user, plan_id), true);
+        self.auto_pay.set(&(user, plan_id), enabled);
+
+        self.env().emit_event(events::AutoPayChanged {
+            user,
+            plan_id,
+            enabled,
+        });
     }
 
-    /// Disable auto-renew for a subscription plan
-    pub fn disable_auto_renew(&mut self, plan_id: u32) {
+    fn set_auto_top_up(&mut self, merchant: Address, amount: U512) {
         let user = self.env().caller();
-        self.auto_renew.set(&(user, plan_id), false);
+        self.auto_top_up.set(&(user, merchant), amount);
+
+        self.env().emit_event(events::AutoTopUpChanged {
+            user,
+            merchant,
+            amount,
+        });
+    }
+
+    /// Whether an invoice bills its subscription's plan, to the plan's
+    /// merchant at the plan's current prices
+    fn bills_plan(&self, manager: &SubscriptionManagerContractRef, invoice: &Invoice) -> bool {
+        let Some(subscription) = manager.get_subscription(invoice.subscription_id) else {
+            return false;
+        };
+        let Some(plan) = manager.get_plan(subscription.plan_id) else {
+            return false;
+        };
+        subscription.subscriber == invoice.subscriber
+            && plan.id == invoice.plan_id
+            && plan.merchant == invoice.merchant
+            && plan.base_price == invoice.base_amount
+            && plan.usage_price * U512::from(invoice.usage_units) == invoice.usage_amount
+    }
+
+    /// Accumulate rewards based on staked amount and time elapsed
+    fn accumulate_rewards(&mut self, config: &mut StakeConfig) {
+        let rewards = self.pending_rewards(config);
+
+        if rewards > U512::zero() {
+            config.accumulated_rewards = config.accumulated_rewards + rewards;
+            
+            // Update total rewards
+            let total = self.total_rewards.get_or_default();
+            self.total_rewards.set(total + rewards);
+
+            self.env().emit_event(events::RewardsAccumulated {
+                user: config.user,
+                amount: rewards,
+                total_rewards: config.accumulated_rewards,
+            });
+        }
+    }
+
+    /// Rewards earned since `config.last_updated`:
+    /// (staked * APY * elapsed) / (365 days * 10000), with elapsed in block-time milliseconds
+    fn pending_rewards(&self, config: &StakeConfig) -> U512 {
+        let elapsed = Timestamp::now(&self.env()).duration_since(config.last_updated);
+        if elapsed.is_zero() || config.staked_amount == U512::zero() {
+            return U512::zero();
+        }
+
+        let apy = self.apy_bps.get_or_default();
+        (config.staked_amount * U512::from(apy) * U512::from(elapsed.as_millis()))
+            / (U512::from(YEAR.as_millis()) * U512::from(10000))
+    }
+
+    /// Force update rewards for a user (callable by anyone to update before actions)
+    pub fn update_rewards(&mut self, user: Address) {
+        let mut config = self
+            .stake_configs
+            .get(&user)
+            .unwrap_or_revert_with(&self.env(), Error::StakeNotFound);
+        self.accumulate_rewards(&mut config);
+        config.last_updated = Timestamp::now(&self.env());
+        self.stake_configs.set(&user, config);
+    }
+
+    // ============ VIEW FUNCTIONS ============
+
+    /// Get stake configuration for a user
+    pub fn get_stake_config(&self, user: Address) -> Option<StakeConfig> {
+        self.stake_configs.get(&user)
+    }
+
+    /// Get available rewards for a user (including pending)
+    pub fn get_available_rewards(&self, user: Address) -> U512 {
+        match self.stake_configs.get(&user) {
+            Some(config) => config.accumulated_rewards + self.pending_rewards(&config),
+            None => U512::zero(),
+        }
     }
 
     /// Check if auto-pay is enabled for a user's plan
-    pub fn is_auto_pay_enabled(&self, user: Address, plan_id: u32) -> bool {
+    pub fn is_auto_pay_enabled(&self, user: Address, plan_id: u64) -> bool {
         self.auto_pay.get(&(user, plan_id)).unwrap_or(false)
     }
 
-    /// Check if auto-renew is enabled for a user's plan
-    pub fn is_auto_renew_enabled(&self, user: Address, plan_id: u32) -> bool {
-        self.auto_renew.get(&(user, plan_id)).unwrap_or(false)
+    /// Get the minimum credit top-up paid from rewards for a merchant, zero if off
+    pub fn get_auto_top_up(&self, user: Address, merchant: Address) -> U512 {
+        self.auto_top_up.get(&(user, merchant)).unwrap_or_default()
     }
 
-    /// Get user's staked balance
-    pub fn get_stake(&self, user: Address) -> U512 {
-        self.stakes.get(&user).unwrap_or_default()
+    /// Estimate yearly rewards for a stake at the current APY
+    pub fn estimate_yearly_rewards(&self, amount: U512) -> U512 {
+        let apy = self.apy_bps.get_or_default();
+        (amount * U512::from(apy)) / U512::from(10000)
+    }
+
+    /// Get payment details
+    pub fn get_payment(&self, payment_id: u64) -> Option<StakePayment> {
+        self.payments.get(&payment_id)
     }
 
-    /// Get user's accumulated rewards (without updating)
-    pub fn get_pending_rewards(&self, user: Address) -> U512 {
-        let accumulated = self.accumulated_rewards.get(&user).unwrap_or_default();
-        let pending = self.calculate_pending_rewards(user);
-        accumulated + pending
+    /// Get number of payments made by a user
+    pub fn get_user_payment_count(&self, user: Address) -> u32 {
+        self.user_payment_count.get(&user).unwrap_or_default()
     }
 
-    /// Get user's total available balance (stake + rewards)
-    pub fn get_total_balance(&self, user: Address) -> U512 {
-        let stake = self.get_stake(user);
-        let rewards = self.get_pending_rewards(user);
-        stake + rewards
+    /// Get a page of a user's payments made within `paid`
+    pub fn get_user_payments_page(
+        &self,
+        user: Address,
+        cursor: u32,
+        limit: u32,
+        paid: DateRange,
+    ) -> StakePaymentPage {
+        let total = self.get_user_payment_count(user);
+        let (items, next_cursor) = pagination::scan(total, cursor, limit, |index| {
+            self.user_payments
+                .get(&(user, index))
+                .and_then(|payment_id| self.payments.get(&payment_id))
+                .filter(|payment| paid.contains(payment.paid_at))
+        });
+        StakePaymentPage { items, next_cursor }
     }
 
-    /// Get total staked in contract
-    pub fn total_staked(&self) -> U512 {
+    /// Get total staked across all users
+    pub fn get_total_staked(&self) -> U512 {
         self.total_staked.get_or_default()
     }
 
-    /// Get total rewards distributed
-    pub fn total_rewards_distributed(&self) -> U512 {
-        self.total_rewards_distributed.get_or_default()
+    /// Get current APY
+    pub fn get_apy_bps(&self) -> u64 {
+        self.apy_bps.get_or_default()
     }
 
-    /// Get APY in basis points
-    pub fn get_apy(&self) -> u32 {
-        self.apy_basis_points.get_or_default()
+    /// Get the CSPR left to pay out rewards
+    pub fn get_reward_reserve(&self) -> U512 {
+        self.reward_reserve.get_or_default()
     }
 
-    /// Set APY (owner only) in basis points
-    pub fn set_apy(&mut self, apy_bp: u32) {
-        let caller = self.env().caller();
-        let owner = self.owner.get().unwrap();
-        assert!(caller == owner, "Only owner can set APY");
-        assert!(apy_bp <= 2000, "APY cannot exceed 20%"); // Safety cap
-        self.apy_basis_points.set(apy_bp);
+    // ============ ADMIN FUNCTIONS ============
+
+    /// Set BillingEngine address (admin; timelocked once set)
+    pub fn set_billing_engine(&mut self, address: Address) {
+        self.access.require_role(Role::Admin);
+        if self.billing_engine.get_or_default().is_none() {
+            self.billing_engine.set(Some(address));
+        } else {
+            self.timelock.queue_address(Param::BillingEngine, address);
+        }
     }
 
-    /// Get contract owner
-    pub fn owner(&self) -> Option<Address> {
-        self.owner.get()
+    /// Set SubscriptionManager address (admin; timelocked once set)
+    pub fn set_subscription_manager(&mut self, address: Address) {
+        self.access.require_role(Role::Admin);
+        if self.subscription_manager.get_or_default().is_none() {
+            self.subscription_manager.set(Some(address));
+        } else {
+            self.timelock.queue_address(Param::SubscriptionManager, address);
+        }
     }
 
-    /// Set subscription manager address (owner only)
-    pub fn set_subscription_manager(&mut self, manager: Address) {
-        let caller = self.env().caller();
-        let owner = self.owner.get().unwrap();
-        assert!(caller == owner, "Only owner can set subscription manager");
-        self.subscription_manager.set(Some(manager));
+    /// Queue a new APY (rate manager only)
+    pub fn set_apy_bps(&mut self, apy: u64) {
+        self.access.require_role(Role::RateManager);
+        // Max 20%
+        if apy > 2000 {
+            self.env().revert(Error::ApyTooHigh);
+        }
+        self.timelock.queue_amount(Param::ApyBps, apy);
+    }
+
+    // ============ REGISTRY FUNCTIONS ============
+
+    /// Resolve peers through a Protocol Registry (admin; timelocked once set)
+    pub fn set_registry(&mut self, address: Address) {
+        self.access.require_role(Role::Admin);
+        if self.registry.get_or_default().is_none() {
+            self.registry.set(Some(address));
+        } else {
+            self.timelock.queue_address(Param::Registry, address);
+        }
     }
 
-    /// Get user's staking statistics
-    pub fn get_user_stats(&self, user: Address) -> (U512, U512, U512, u64) {
-        let stake = self.get_stake(user);
-        let rewards = self.get_pending_rewards(user);
-        let total_paid = self.total_payments_made.get(&user).unwrap_or_default();
-        let staked_since = self.stake_timestamps.get(&user).unwrap_or(0);
-        (stake, rewards, total_paid, staked_since)
+    /// Get the Protocol Registry address
+    pub fn get_registry(&self) -> Option<Address> {
+        self.registry.get_or_default()
     }
 
-    /// Estimate yearly rewards based on stake amount
-    pub fn estimate_yearly_rewards(&self, amount: U512) -> U512 {
-        let apy = self.apy_basis_points.get_or_default();
-        // amount * apy / 10000 (basis points to percentage)
-        (amount * U512::from(apy)) / U512::from(10000u32)
+    /// Get the address of a peer contract: the registry's entry once a
+    /// registry is set, otherwise the address configured here
+    pub fn get_peer(&self, component: Component) -> Option<Address> {
+        if let Some(registry) = self.registry.get_or_default() {
+            return RegistryContractRef::new(self.env(), registry).get_address(component);
+        }
+        match component {
+            Component::SubscriptionManager => self.subscription_manager.get_or_default(),
+            Component::BillingEngine => self.billing_engine.get_or_default(),
+            _ => None,
+        }
     }
 
-    // ===== Internal Functions =====
+    // ============ UPGRADE FUNCTIONS ============
 
-    /// Calculate pending rewards for a user
-    fn calculate_pending_rewards(&self, user: Address) -> U512 {
-        let stake = self.stakes.get(&user).unwrap_or_default();
-        if stake == U512::zero() {
-            return U512::zero();
+    /// Hook run by Odra when a new version of the contract is installed (admin only)
+    pub fn upgrade(&mut self) {
+        self.access.require_role(Role::Admin);
+        self.schema.upgrade(&[(RecordKind::StakeConfig, self.staker_count.get_or_default() as u64)]);
+    }
+
+    /// Migrate the next `limit` stake configs written before the last upgrade (keeper only)
+    pub fn migrate_stake_configs(&mut self, limit: u32) -> u32 {
+        self.access.require_role(Role::Keeper);
+        let (from_version, positions) = self.schema.next_batch(RecordKind::StakeConfig, limit);
+        for position in positions.clone() {
+            let Some(user) = self.stakers.get(&(position as u32)) else { continue };
+            // Configs are on their first layout, so `stake_configs` is also the legacy mapping
+            if let Some(previous) = self.stake_configs.get(&user) {
+                self.stake_configs.set(&user, StakeConfig::migrate(previous, from_version));
+            }
         }
+        (positions.end - positions.start) as u32
+    }
 
-        let last_calc = self.last_reward_calc.get(&user).unwrap_or(0);
-        let current_time = self.env().get_block_time();
-        
-        if current_time <= last_calc {
-            return U512::zero();
+    delegate! {
+        to self.schema {
+            fn schema_version(&self) -> u32;
+            fn get_migration_progress(&self, kind: RecordKind) -> MigrationProgress;
         }
+    }
 
-        let seconds_elapsed = current_time - last_calc;
-        let apy = self.apy_basis_points.get_or_default();
-        
-        // Calculate: stake * apy * seconds / (10000 * seconds_per_year)
-        let seconds_per_year: u64 = 365 * 24 * 60 * 60;
-        let rewards = (stake * U512::from(apy) * U512::from(seconds_elapsed)) 
-            / (U512::from(10000u32) * U512::from(seconds_per_year));
-        
-        rewards
+    // ============ GOVERNANCE FUNCTIONS ============
+
+    /// Apply a queued parameter change once its delay has passed (anyone)
+    pub fn execute_change(&mut self, change_id: u64) {
+        let change = self.timelock.take_ready(change_id);
+        match change.param {
+            Param::SubscriptionManager => self.subscription_manager.set(change.address),
+            Param::BillingEngine => self.billing_engine.set(change.address),
+            Param::ApyBps => self.apy_bps.set(change.amount),
+            Param::Registry => self.registry.set(change.address),
+            Param::TimelockDelay => {}
+            _ => self.env().revert(TimelockError::UnsupportedParam),
+        }
     }
 
-    /// Calculate and add pending rewards to accumulated
-    fn calculate_and_add_rewards(&mut self, user: Address) {
-        let pending = self.calculate_pending_rewards(user);
-        if pending > U512::zero() {
-            let current = self.accumulated_rewards.get(&user).unwrap_or_default();
-            self.accumulated_rewards.set(&user, current + pending);
-            self.last_reward_calc.set(&user, self.env().get_block_time());
-            
-            // Update total distributed
-            let total = self.total_rewards_distributed.get_or_default();
-            self.total_rewards_distributed.set(total + pending);
+    /// Cancel a queued parameter change (admin only)
+    pub fn cancel_change(&mut self, change_id: u64) {
+        self.access.require_role(Role::Admin);
+        self.timelock.cancel(change_id);
+    }
+
+    /// Queue a new delay for parameter changes (admin only)
+    pub fn set_timelock_delay(&mut self, delay: Duration) {
+        self.access.require_role(Role::Admin);
+        self.timelock.queue_delay(delay);
+    }
+
+    delegate! {
+        to self.timelock {
+            fn get_queued_change(&self, change_id: u64) -> Option<QueuedChange>;
+            fn get_pending_change(&self, param: Param) -> Option<QueuedChange>;
+            fn get_timelock_delay(&self) -> Duration;
+        }
+    }
+
+    // ============ PAUSE FUNCTIONS ============
+
+    /// Pause a group of entry points (pauser only)
+    pub fn pause(&mut self, group: PauseGroup) {
+        self.access.require_role(Role::Pauser);
+        self.pausable.pause(group);
+    }
+
+    /// Resume a paused group of entry points (pauser only)
+    pub fn unpause(&mut self, group: PauseGroup) {
+        self.access.require_role(Role::Pauser);
+        self.pausable.unpause(group);
+    }
+
+    delegate! {
+        to self.pausable {
+            fn is_paused(&self, group: PauseGroup) -> bool;
+        }
+    }
+
+    // ============ ACCESS CONTROL ============
+
+    delegate! {
+        to self.access {
+            fn grant_role(&mut self, role: Role, account: Address);
+            fn revoke_role(&mut self, role: Role, account: Address);
+            fn renounce_role(&mut self, role: Role);
+            fn transfer_ownership(&mut self, new_owner: Address);
+            fn accept_ownership(&mut self);
+            fn has_role(&self, role: Role, account: Address) -> bool;
+            fn owner(&self) -> Option<Address>;
+            fn pending_owner(&self) -> Option<Address>;
         }
     }
 }
@@ -356,29 +1000,235 @@ impl StakeToPay {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use odra::host::Deployer;
+    use crate::access::Error as AccessError;
+    use crate::billing_engine::BillingEngine;
+    use crate::pausable::Error as PauseError;
+    use crate::registry::Registry;
+    use crate::subscription_manager::SubscriptionManager;
+    use crate::time::Duration;
+    use crate::usage_meter::{UsageMeter, DEFAULT_LATE_USAGE_WINDOW};
+    use odra::host::{Deployer, HostRef, NoArgs};
 
     #[test]
-    fn test_stake_and_rewards() {
+    fn test_deposit() {
         let env = odra_test::env();
-        let mut contract = StakeToPayHostRef::deploy(&env, ());
-        
-        // Initial state checks
-        assert_eq!(contract.total_staked(), U512::zero());
-        assert_eq!(contract.get_apy(), 800); // 8%
+        let mut contract = StakeToPay::deploy(&env, NoArgs);
+
+        contract.with_tokens(U512::from(1000_000_000_000u64)).deposit(); // 1000 CSPR
+
+        let user = env.get_account(0);
+        let config = contract.get_stake_config(user).unwrap();
+
+        assert_eq!(config.staked_amount, U512::from(1000_000_000_000u64));
+        assert!(config.is_enabled);
+    }
+
+    #[test]
+    fn test_rewards_accumulation() {
+        let env = odra_test::env();
+        let mut contract = StakeToPay::deploy(&env, NoArgs);
+
+        // Deposit 1000 CSPR
+        contract.with_tokens(U512::from(1000_000_000_000u64)).deposit();
+
+        let user = env.get_account(0);
+
+        // 30 days earn 30/365 of the yearly 80 CSPR
+        env.advance_block_time(Duration::from_days(30).as_millis());
+        assert_eq!(contract.get_available_rewards(user), U512::from(6_575_342_465u64));
+
+        // Fast forward the rest of the year
+        env.advance_block_time(Duration::from_days(335).as_millis());
+
+        // Check rewards (should be ~8% of 1000 = 80 CSPR)
+        let rewards = contract.get_available_rewards(user);
+        assert!(rewards > U512::from(79_000_000_000u64)); // Allow some variance
+        assert!(rewards < U512::from(81_000_000_000u64));
     }
 
     #[test]
     fn test_estimate_rewards() {
         let env = odra_test::env();
-        let contract = StakeToPayHostRef::deploy(&env, ());
-        
+        let contract = StakeToPay::deploy(&env, NoArgs);
+
         // 1000 CSPR at 8% APY = 80 CSPR yearly rewards
-        let stake = U512::from(1_000_000_000_000u64); // 1000 CSPR in motes
-        let yearly_rewards = contract.estimate_yearly_rewards(stake);
-        
-        // Expected: 1000 * 0.08 = 80 CSPR = 80_000_000_000 motes
-        let expected = U512::from(80_000_000_000u64);
-        assert_eq!(yearly_rewards, expected);
+        let yearly_rewards = contract.estimate_yearly_rewards(U512::from(1_000_000_000_000u64));
+        assert_eq!(yearly_rewards, U512::from(80_000_000_000u64));
+    }
+
+    #[test]
+    fn test_claim_rewards_keeps_principal() {
+        let env = odra_test::env();
+        let mut contract = StakeToPay::deploy(&env, NoArgs);
+        let user = env.get_account(0);
+
+        contract.with_tokens(U512::from(1000_000_000_000u64)).deposit();
+        assert_eq!(contract.try_claim_rewards(), Err(Error::NoRewards.into()));
+
+        // Rewards are only paid from the reserve, never from principal
+        env.advance_block_time(YEAR.as_millis());
+        assert_eq!(contract.try_claim_rewards(), Err(Error::RewardReserveExhausted.into()));
+        let reserve = U512::from(100_000_000_000u64);
+        contract.with_tokens(reserve).fund_rewards();
+        let claimed = contract.claim_rewards();
+        assert!(claimed > U512::from(79_000_000_000u64));
+        assert_eq!(contract.get_reward_reserve(), reserve - claimed);
+
+        let config = contract.get_stake_config(user).unwrap();
+        assert_eq!(config.staked_amount, U512::from(1000_000_000_000u64));
+        assert_eq!(config.accumulated_rewards, U512::zero());
+        assert_eq!(env.balance_of(&contract.address()), config.staked_amount + contract.get_reward_reserve());
+    }
+
+    #[test]
+    fn test_emergency_withdraw_while_paused() {
+        let env = odra_test::env();
+        let mut contract = StakeToPay::deploy(&env, NoArgs);
+        let user = env.get_account(1);
+
+        env.set_caller(user);
+        contract.with_tokens(U512::from(1000_000_000_000u64)).deposit();
+        assert_eq!(contract.try_emergency_withdraw(), Err(PauseError::NotPaused.into()));
+
+        // Only pausers can pause
+        assert_eq!(contract.try_pause(PauseGroup::Staking), Err(AccessError::MissingRole.into()));
+        env.set_caller(env.get_account(0));
+        contract.pause(PauseGroup::Staking);
+        assert!(contract.is_paused(PauseGroup::Staking));
+
+        env.advance_block_time(YEAR.as_millis());
+        env.set_caller(user);
+        assert_eq!(
+            contract.try_withdraw(U512::from(1_000_000_000u64)),
+            Err(PauseError::Paused.into())
+        );
+
+        // Principal comes back without accruing the year's rewards
+        assert_eq!(contract.emergency_withdraw(), U512::from(1000_000_000_000u64));
+        let config = contract.get_stake_config(user).unwrap();
+        assert_eq!(config.staked_amount, U512::zero());
+        assert_eq!(config.accumulated_rewards, U512::zero());
+        assert_eq!(contract.get_total_staked(), U512::zero());
+    }
+
+    #[test]
+    fn test_auto_pay_per_plan() {
+        let env = odra_test::env();
+        let mut contract = StakeToPay::deploy(&env, NoArgs);
+        let user = env.get_account(0);
+
+        contract.enable_auto_pay(1);
+        assert!(contract.is_auto_pay_enabled(user, 1));
+        assert!(!contract.is_auto_pay_enabled(user, 2));
+
+        contract.disable_auto_pay(1);
+        assert!(!contract.is_auto_pay_enabled(user, 1));
+    }
+
+    #[test]
+    fn test_keeper_auto_pays_invoices() {
+        let env = odra_test::env();
+        let mut registry = Registry::deploy(&env, NoArgs);
+        let mut manager = SubscriptionManager::deploy(&env, NoArgs);
+        let mut meter = UsageMeter::deploy(&env, NoArgs);
+        let mut engine = BillingEngine::deploy(&env, NoArgs);
+        let mut contract = StakeToPay::deploy(&env, NoArgs);
+        registry.register(Component::SubscriptionManager, manager.address());
+        registry.register(Component::UsageMeter, meter.address());
+        registry.register(Component::BillingEngine, engine.address());
+        registry.register(Component::StakeToPay, contract.address());
+        manager.set_registry(registry.address());
+        meter.set_registry(registry.address());
+        engine.set_registry(registry.address());
+        contract.set_registry(registry.address());
+
+        let (subscriber, merchant, keeper) = (env.get_account(1), env.get_account(2), env.get_account(3));
+        manager.grant_role(Role::Keeper, keeper);
+        engine.grant_role(Role::Keeper, keeper);
+        contract.grant_role(Role::Keeper, keeper);
+        let amount = U512::from(1_000_000_000u64); // 1 CSPR
+        let cycle = Duration::from_days(30);
+        env.set_caller(merchant);
+        let auto_plan = manager.create_plan("Auto".to_string(), amount, U512::zero(), cycle);
+        let manual_plan = manager.create_plan("Manual".to_string(), amount, U512::zero(), cycle);
+
+        env.set_caller(subscriber);
+        contract.with_tokens(U512::from(1000_000_000_000u64)).deposit();
+        contract.enable_stake_to_pay();
+        contract.enable_auto_pay(auto_plan);
+        let auto_sub = manager.subscribe(auto_plan, true, 1);
+        let manual_sub = manager.subscribe(manual_plan, true, 1);
+        env.advance_block_time((cycle + DEFAULT_LATE_USAGE_WINDOW).as_millis());
+
+        env.set_caller(keeper);
+        let renewed = manager.renew_subscriptions(vec![auto_sub, manual_sub]);
+        let (auto_paid, manual) = (renewed[0], renewed[1]);
+        // Invoices not issued by the SubscriptionManager are never auto-paid
+        let forged = engine.create_invoice(
+            auto_sub, auto_plan, subscriber, keeper, amount, U512::zero(), 0, Timestamp::ZERO, Timestamp::ZERO,
+        );
+
+        // Nothing is paid while the reward reserve is empty
+        assert_eq!(contract.auto_pay_invoices(vec![auto_paid]), 0);
+        contract.with_tokens(U512::from(10_000_000_000u64)).fund_rewards();
+
+        let merchant_balance = env.balance_of(&merchant);
+        assert_eq!(contract.auto_pay_invoices(vec![auto_paid, manual, forged, 99]), 1);
+        assert_eq!(engine.get_invoice(forged).unwrap().status, InvoiceStatus::Pending);
+        assert_eq!(engine.get_invoice(auto_paid).unwrap().status, InvoiceStatus::Paid);
+        assert_eq!(engine.get_invoice(manual).unwrap().status, InvoiceStatus::Pending);
+        // BillingEngine keeps the 1% protocol fee out of the merchant share
+        assert_eq!(env.balance_of(&merchant), merchant_balance + amount - amount / 100);
+        assert_eq!(engine.get_merchant_revenue(merchant), amount - amount / 100);
+        // Retried batches skip paid invoices
+        assert_eq!(contract.auto_pay_invoices(vec![auto_paid]), 0);
+
+        env.set_caller(subscriber);
+        assert_eq!(contract.try_auto_pay_invoices(vec![manual]), Err(AccessError::MissingRole.into()));
+    }
+
+    #[test]
+    fn test_paginated_payments() {
+        let env = odra_test::env();
+        let mut contract = StakeToPay::deploy(&env, NoArgs);
+        let mut engine = BillingEngine::deploy(&env, NoArgs);
+        contract.set_billing_engine(engine.address());
+        engine.set_stake_to_pay(contract.address());
+        let user = env.get_account(0);
+        let merchant = env.get_account(1);
+        let amount = U512::from(1_000_000_000u64);
+
+        contract.with_tokens(U512::from(1000_000_000_000u64)).deposit();
+        contract.with_tokens(U512::from(10_000_000_000u64)).fund_rewards();
+        env.advance_block_time(YEAR.as_millis());
+
+        for _ in 1..=3u64 {
+            let invoice_id = engine.create_invoice(
+                1, 1, user, merchant, amount, U512::zero(), 0, Timestamp::ZERO, Timestamp::ZERO,
+            );
+            contract.pay_invoice_from_rewards(invoice_id);
+            assert_eq!(engine.get_invoice(invoice_id).unwrap().status, InvoiceStatus::Paid);
+            env.advance_block_time(Duration::from_secs(100).as_millis());
+        }
+
+        // Only the invoice's subscriber can pay it from their rewards
+        let invoice_id = engine.create_invoice(
+            1, 1, merchant, merchant, amount, U512::zero(), 0, Timestamp::ZERO, Timestamp::ZERO,
+        );
+        assert_eq!(
+            contract.try_pay_invoice_from_rewards(invoice_id),
+            Err(Error::NotInvoiceSubscriber.into())
+        );
+
+        assert_eq!(contract.get_user_payment_count(user), 3);
+        let page = contract.get_user_payments_page(user, 0, 2, DateRange::default());
+        assert_eq!(page.items.len(), 2);
+        assert_eq!(page.items[0].invoice_id, 1);
+        assert_eq!(page.next_cursor, Some(2));
+
+        let first = page.items[0].paid_at;
+        let page = contract.get_user_payments_page(user, 0, 10, DateRange { from: Some(first + Duration::from_secs(50)), to: None });
+        assert_eq!(page.items.len(), 2);
+        assert_eq!(page.next_cursor, None);
     }
 }
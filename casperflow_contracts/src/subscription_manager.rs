@@ -1,28 +1,430 @@
+//! Subscription Manager Contract
+//!
+//! Manages subscription plans and user subscriptions for CasperFlow protocol.
+//! 
+//! Key features:
+//! - Merchants can create/update/delete subscription plans
+//! - Users can subscribe/unsubscribe to plans
+//! - Supports base price + usage-based pricing
+//! - Settles the first cycle through BillingEngine and refunds overpayment
+//! - Maintains subscriber, MRR and churn aggregates per plan and merchant
+//! - Integrates with StakeToPay for staking reward payments
+//! - Imports plans and subscriptions from the legacy testnet deployment
+//! - Answers access checks for contracts gated on a subscription
+//! - Resolves plan entitlements into a subscriber's effective feature set
+//! - Streams a plan's price to the merchant per second as an alternative to invoices
+
 use odra::prelude::*;
 use odra::casper_types::U512;
 
-/// CasperFlow Subscription Manager Contract
-/// Manages on-chain subscription billing on Casper blockchain
-#[odra::module]
+use crate::access::{AccessControl, Role};
+use crate::analytics::{self, ChurnStats, SubscriberStats};
+use crate::billing_engine::{BillingEngineContractRef, InvoiceFilter, InvoiceStatus};
+use crate::legacy::LegacySubscriptionManagerContractRef;
+use crate::pagination::{self, DateRange};
+use crate::pausable::{PauseGroup, Pausable};
+use crate::registry::{Component, RegistryContractRef};
+use crate::time::{Duration, Timestamp};
+use crate::timelock::{Error as TimelockError, Param, QueuedChange, Timelock};
+use crate::upgrade::{MigrationProgress, RecordKind, Schema};
+use crate::usage_meter::UsageMeterContractRef;
+
+/// Maximum number of entitlements attached to a plan
+pub const MAX_PLAN_ENTITLEMENTS: u32 = 32;
+
+/// Maximum length of an entitlement key, in bytes
+pub const MAX_ENTITLEMENT_KEY_LEN: usize = 64;
+
+/// `Subscription::payment_method` of subscriptions paid by a [`Stream`]
+pub const PAYMENT_METHOD_STREAMED: u8 = 2;
+
+/// Default time an unpaid renewal invoice may stay pending before access is withheld
+pub const DEFAULT_PAYMENT_GRACE: Duration = Duration::from_days(3);
+
+/// Subscription plan created by a merchant
+#[odra::odra_type]
+pub struct Plan {
+    /// Unique identifier for the plan
+    pub id: u64,
+    /// Merchant who owns this plan
+    pub merchant: Address,
+    /// Human-readable name
+    pub name: String,
+    /// Base price per billing cycle (in motes)
+    pub base_price: U512,
+    /// Price per usage unit (in motes, 0 for fixed-price plans)
+    pub usage_price: U512,
+    /// Billing cycle duration
+    pub billing_cycle: Duration,
+    /// Whether the plan is active
+    pub is_active: bool,
+    /// Timestamp of creation
+    pub created_at: Timestamp,
+}
+
+/// User subscription to a plan
+#[odra::odra_type]
+pub struct Subscription {
+    /// Unique identifier for the subscription
+    pub id: u64,
+    /// The plan this subscription is for
+    pub plan_id: u64,
+    /// The subscriber's address
+    pub subscriber: Address,
+    /// Start timestamp
+    pub started_at: Timestamp,
+    /// Next billing timestamp
+    pub next_billing_at: Timestamp,
+    /// Whether auto-renew is enabled
+    pub auto_renew: bool,
+    /// Payment method: 0 = wallet, 1 = staked, 2 = streamed
+    pub payment_method: u8,
+    /// Whether subscription is active
+    pub is_active: bool,
+}
+
+/// Per-second payment from a subscriber's deposit to a plan's merchant
+///
+/// The deposit drains at the plan's base price per billing cycle, counted in
+/// whole seconds. The subscription's `next_billing_at` is the moment the
+/// deposit runs out.
+#[odra::odra_type]
+pub struct Stream {
+    /// Subscription paid by the stream
+    pub subscription_id: u64,
+    /// Merchant receiving the stream
+    pub merchant: Address,
+    /// Amount streamed per cycle, the plan's base price when the stream started
+    pub price: U512,
+    /// Cycle `price` is streamed over
+    pub cycle: Duration,
+    /// When streaming started
+    pub started_at: Timestamp,
+    /// Total deposited by the subscriber
+    pub deposited: U512,
+    /// Total withdrawn by the merchant
+    pub withdrawn: U512,
+    /// When the stream was stopped or ran dry, `None` while it is running
+    pub stopped_at: Option<Timestamp>,
+}
+
+impl Stream {
+    /// Amount streamed to the merchant by `now`
+    pub fn streamed_at(&self, now: Timestamp) -> U512 {
+        let end = self.stopped_at.map_or(now, |stopped_at| stopped_at.min(now));
+        let seconds = U512::from(end.duration_since(self.started_at).as_secs());
+        (self.price * seconds / U512::from(self.cycle.as_secs())).min(self.deposited)
+    }
+
+    /// When the deposit runs out
+    pub fn depleted_at(&self) -> Timestamp {
+        let cycle = U512::from(self.cycle.as_secs());
+        let seconds = (self.deposited * cycle + self.price - U512::one()) / self.price;
+        self.started_at + Duration::from_secs(seconds.as_u64())
+    }
+}
+
+/// Named feature granted by a plan, e.g. `max_projects = 10`
+#[odra::odra_type]
+pub struct Entitlement {
+    /// Feature key
+    pub key: String,
+    /// Numeric limit, or `None` for an on/off feature granted by holding the key
+    pub limit: Option<u64>,
+}
+
+impl Entitlement {
+    /// Combine grants of the same key from two plans; the more generous wins
+    fn merge(&mut self, other: &Entitlement) {
+        self.limit = match (self.limit, other.limit) {
+            (Some(limit), Some(other)) => Some(limit.max(other)),
+            _ => None,
+        };
+    }
+
+    /// Whether the entitlement grants anything
+    pub fn is_granted(&self) -> bool {
+        self.limit.map_or(true, |limit| limit > 0)
+    }
+}
+
+/// Filter for subscription queries; `None` fields match everything
+#[odra::odra_type]
+#[derive(Default)]
+pub struct SubscriptionFilter {
+    /// Only subscriptions to this plan
+    pub plan_id: Option<u64>,
+    /// Only active (`Some(true)`) or inactive (`Some(false)`) subscriptions
+    pub is_active: Option<bool>,
+    /// Only subscriptions started within this range
+    pub started: DateRange,
+}
+
+impl SubscriptionFilter {
+    fn matches(&self, subscription: &Subscription) -> bool {
+        self.plan_id.map_or(true, |id| subscription.plan_id == id)
+            && self.is_active.map_or(true, |active| subscription.is_active == active)
+            && self.started.contains(subscription.started_at)
+    }
+}
+
+/// Page of plans returned by paginated queries
+#[odra::odra_type]
+pub struct PlanPage {
+    pub items: Vec<Plan>,
+    /// Cursor to pass to fetch the next page, `None` when exhausted
+    pub next_cursor: Option<u32>,
+}
+
+/// Page of subscriptions returned by paginated queries
+#[odra::odra_type]
+pub struct SubscriptionPage {
+    pub items: Vec<Subscription>,
+    /// Cursor to pass to fetch the next page, `None` when exhausted
+    pub next_cursor: Option<u32>,
+}
+
+/// Errors reverted by the contract (codes 100-199)
+#[odra::odra_error]
+pub enum Error {
+    /// Plan does not exist
+    PlanNotFound = 101,
+    /// Caller is not the plan's merchant
+    NotPlanMerchant = 102,
+    /// Plan has been deactivated
+    PlanNotActive = 103,
+    /// Attached tokens do not cover the first cycle
+    InsufficientPayment = 104,
+    /// BillingEngine address has not been configured
+    BillingEngineNotSet = 105,
+    /// Subscription does not exist
+    SubscriptionNotFound = 106,
+    /// Caller is not the subscriber
+    NotSubscriber = 107,
+    /// Subscription is already cancelled
+    SubscriptionInactive = 108,
+    /// Subscriber already holds a subscription to the plan
+    AlreadySubscribed = 109,
+    /// Legacy SubscriptionManager address has not been configured
+    LegacyManagerNotSet = 110,
+    /// Plan does not exist on the legacy contract
+    LegacyPlanNotFound = 111,
+    /// Subscription does not exist on the legacy contract
+    LegacySubscriptionNotFound = 112,
+    /// Legacy subscription has already been imported
+    LegacySubscriptionAlreadyMigrated = 113,
+    /// Caller is neither an admin nor the plan's merchant
+    NotAdminOrMerchant = 114,
+    /// Caller is neither an admin nor the subscriber
+    NotAdminOrSubscriber = 115,
+    /// UsageMeter address is not registered
+    UsageMeterNotSet = 116,
+    /// Entitlement key is empty or too long
+    InvalidEntitlementKey = 117,
+    /// Plan already has the maximum number of entitlements
+    TooManyEntitlements = 118,
+    /// Plan has no entitlement with the key
+    EntitlementNotFound = 119,
+    /// Payment method is not wallet or staked; streams start with `subscribe_streaming`
+    InvalidPaymentMethod = 120,
+    /// Plan has no base price or billing cycle to stream
+    PlanNotStreamable = 121,
+    /// Subscription is not paid by a stream
+    StreamNotFound = 122,
+    /// Stream was stopped or has run dry
+    StreamEnded = 123,
+    /// Nothing has streamed since the last withdrawal
+    NothingToWithdraw = 124,
+}
+
+/// Events emitted by the contract
+pub mod events {
+    use super::*;
+    
+    #[odra::event]
+    pub struct PlanCreated {
+        pub plan_id: u64,
+        pub merchant: Address,
+        pub name: String,
+        pub base_price: U512,
+    }
+
+    #[odra::event]
+    pub struct PlanUpdated {
+        pub plan_id: u64,
+        pub base_price: U512,
+        pub usage_price: U512,
+    }
+
+    #[odra::event]
+    pub struct PlanDeactivated {
+        pub plan_id: u64,
+    }
+
+    #[odra::event]
+    pub struct EntitlementSet {
+        pub plan_id: u64,
+        pub key: String,
+        pub limit: Option<u64>,
+    }
+
+    #[odra::event]
+    pub struct EntitlementRemoved {
+        pub plan_id: u64,
+        pub key: String,
+    }
+
+    #[odra::event]
+    pub struct Subscribed {
+        pub subscription_id: u64,
+        pub plan_id: u64,
+        pub subscriber: Address,
+    }
+
+    #[odra::event]
+    pub struct Unsubscribed {
+        pub subscription_id: u64,
+        pub subscriber: Address,
+    }
+
+    #[odra::event]
+    pub struct SubscriptionRenewed {
+        pub subscription_id: u64,
+        pub next_billing_at: Timestamp,
+    }
+
+    #[odra::event]
+    pub struct OverpaymentRefunded {
+        pub subscription_id: u64,
+        pub subscriber: Address,
+        pub amount: U512,
+    }
+
+    #[odra::event]
+    pub struct StreamStarted {
+        pub subscription_id: u64,
+        pub subscriber: Address,
+        pub merchant: Address,
+        pub deposit: U512,
+        pub depleted_at: Timestamp,
+    }
+
+    #[odra::event]
+    pub struct StreamToppedUp {
+        pub subscription_id: u64,
+        pub amount: U512,
+        pub depleted_at: Timestamp,
+    }
+
+    #[odra::event]
+    pub struct StreamWithdrawn {
+        pub subscription_id: u64,
+        pub merchant: Address,
+        pub amount: U512,
+        pub protocol_fee: U512,
+    }
+
+    /// Emitted when the subscriber stops the stream, along with `Unsubscribed`
+    #[odra::event]
+    pub struct StreamStopped {
+        pub subscription_id: u64,
+        pub refunded: U512,
+    }
+
+    /// The deposit ran out and the subscription was deactivated
+    #[odra::event]
+    pub struct StreamDepleted {
+        pub subscription_id: u64,
+    }
+
+    #[odra::event]
+    pub struct LegacyPlanMigrated {
+        pub legacy_plan_id: u32,
+        pub plan_id: u64,
+    }
+
+    #[odra::event]
+    pub struct LegacySubscriptionMigrated {
+        pub legacy_plan_id: u32,
+        pub subscription_id: u64,
+        pub subscriber: Address,
+    }
+}
+
+/// Subscription Manager Contract
+#[odra::module(events = [
+    events::PlanCreated,
+    events::PlanUpdated,
+    events::PlanDeactivated,
+    events::EntitlementSet,
+    events::EntitlementRemoved,
+    events::Subscribed,
+    events::Unsubscribed,
+    events::SubscriptionRenewed,
+    events::OverpaymentRefunded,
+    events::StreamStarted,
+    events::StreamToppedUp,
+    events::StreamWithdrawn,
+    events::StreamStopped,
+    events::StreamDepleted,
+    events::LegacyPlanMigrated,
+    events::LegacySubscriptionMigrated
+], errors = Error)]
 pub struct SubscriptionManager {
-    /// Contract owner
-    owner: Var<Address>,
-    /// Subscription plans: plan_id -> price in motes
-    plan_prices: Mapping<u32, U512>,
-    /// Subscription plans: plan_id -> period in seconds
-    plan_periods: Mapping<u32, u64>,
-    /// Subscription plans: plan_id -> name
-    plan_names: Mapping<u32, String>,
-    /// Subscription plans: plan_id -> merchant address
-    plan_merchants: Mapping<u32, Address>,
-    /// Active subscriptions: (subscriber, plan_id) -> expiry_timestamp
-    subscriptions: Mapping<(Address, u32), u64>,
-    /// Subscriber count per plan
-    plan_subscribers: Mapping<u32, u32>,
-    /// Total plans count
-    plan_count: Var<u32>,
-    /// Total revenue collected
-    total_revenue: Var<U512>,
+    /// Owner and role grants
+    access: SubModule<AccessControl>,
+    /// Per-group pause flags
+    pausable: SubModule<Pausable>,
+    /// Delayed parameter changes
+    timelock: SubModule<Timelock>,
+    /// Counter for plan IDs
+    plan_counter: Var<u64>,
+    /// Counter for subscription IDs
+    subscription_counter: Var<u64>,
+    /// Plan ID -> Plan data
+    plans: Mapping<u64, Plan>,
+    /// Subscription ID -> Subscription data
+    subscriptions: Mapping<u64, Subscription>,
+    /// (User, Plan) -> Subscription ID (to prevent duplicate subscriptions)
+    user_plan_subscription: Mapping<(Address, u64), u64>,
+    /// Merchant -> number of plans
+    merchant_plan_count: Mapping<Address, u32>,
+    /// (Merchant, Index) -> Plan ID
+    merchant_plans: Mapping<(Address, u32), u64>,
+    /// User -> number of subscriptions
+    user_subscription_count: Mapping<Address, u32>,
+    /// (User, Index) -> Subscription ID
+    user_subscriptions: Mapping<(Address, u32), u64>,
+    /// Address of the BillingEngine contract for billing integration
+    billing_engine: Var<Option<Address>>,
+    /// Address of the StakeToPay contract for staking payments
+    stake_to_pay: Var<Option<Address>>,
+    /// Plan ID -> subscriber and MRR totals
+    plan_stats: Mapping<u64, SubscriberStats>,
+    /// Merchant -> subscriber and MRR totals
+    merchant_stats: Mapping<Address, SubscriberStats>,
+    /// (Plan ID, Analytics period) -> subscriber movement
+    plan_period_stats: Mapping<(u64, u64), ChurnStats>,
+    /// (Merchant, Analytics period) -> subscriber movement
+    merchant_period_stats: Mapping<(Address, u64), ChurnStats>,
+    /// Address of the legacy SubscriptionManager being migrated from
+    legacy_manager: Var<Option<Address>>,
+    /// Legacy plan ID -> imported plan ID
+    legacy_plans: Mapping<u32, u64>,
+    /// (Subscriber, Legacy plan ID) -> imported subscription ID
+    legacy_subscriptions: Mapping<(Address, u32), u64>,
+    /// Stored schema version and migration progress
+    schema: SubModule<Schema>,
+    /// Protocol Registry consulted for peer addresses, if set
+    registry: Var<Option<Address>>,
+    /// How long an unpaid renewal keeps granting access
+    payment_grace: Var<Duration>,
+    /// Plan ID -> entitlements granted to its subscribers
+    plan_entitlements: Mapping<u64, Vec<Entitlement>>,
+    /// Subscription ID -> stream paying it
+    streams: Mapping<u64, Stream>,
+    /// Address of the UsageMeter contract
+    usage_meter: Var<Option<Address>>,
 }
 
 #[odra::module]
@@ -30,138 +432,1795 @@ impl SubscriptionManager {
     /// Initialize the contract
     pub fn init(&mut self) {
         let caller = self.env().caller();
-        self.owner.set(caller);
-        self.plan_count.set(0);
-        self.total_revenue.set(U512::zero());
+        self.access.init(caller);
+        self.schema.init();
+        self.plan_counter.set(0);
+        self.subscription_counter.set(0);
+        self.payment_grace.set(DEFAULT_PAYMENT_GRACE);
     }
 
+    // ============ MERCHANT FUNCTIONS ============
+
     /// Create a new subscription plan
-    /// Returns the new plan ID
-    pub fn create_plan(&mut self, price: U512, period_seconds: u64, name: String) -> u32 {
+    pub fn create_plan(
+        &mut self,
+        name: String,
+        base_price: U512,
+        usage_price: U512,
+        billing_cycle: Duration,
+    ) -> u64 {
+        self.pausable.require_not_paused(PauseGroup::Subscriptions);
         let merchant = self.env().caller();
-        let plan_id = self.plan_count.get_or_default() + 1;
+        self.insert_plan(merchant, name, base_price, usage_price, billing_cycle)
+    }
+
+    /// Update an existing plan (only by merchant owner)
+    pub fn update_plan(
+        &mut self,
+        plan_id: u64,
+        base_price: U512,
+        usage_price: U512,
+    ) {
+        self.pausable.require_not_paused(PauseGroup::Subscriptions);
+        let caller = self.env().caller();
+        let mut plan = self
+            .plans
+            .get(&plan_id)
+            .unwrap_or_revert_with(&self.env(), Error::PlanNotFound);
         
-        self.plan_prices.set(&plan_id, price);
-        self.plan_periods.set(&plan_id, period_seconds);
-        self.plan_names.set(&plan_id, name);
-        self.plan_merchants.set(&plan_id, merchant);
-        self.plan_subscribers.set(&plan_id, 0);
-        self.plan_count.set(plan_id);
+        if plan.merchant != caller {
+            self.env().revert(Error::NotPlanMerchant);
+        }
+
+        plan.base_price = base_price;
+        plan.usage_price = usage_price;
+
+        // Re-price the MRR of existing subscribers
+        let mut plan_stats = self.plan_stats.get(&plan_id).unwrap_or_default();
+        let previous_mrr = plan_stats.mrr;
+        plan_stats.mrr = analytics::monthly_amount(base_price, plan.billing_cycle)
+            * U512::from(plan_stats.active_subscribers);
+        let mut merchant_stats = self.merchant_stats.get(&plan.merchant).unwrap_or_default();
+        merchant_stats.mrr = merchant_stats.mrr - previous_mrr + plan_stats.mrr;
+        self.plan_stats.set(&plan_id, plan_stats);
+        self.merchant_stats.set(&plan.merchant, merchant_stats);
+
+        self.plans.set(&plan_id, plan);
+
+        self.env().emit_event(events::PlanUpdated {
+            plan_id,
+            base_price,
+            usage_price,
+        });
+    }
+
+    /// Deactivate a plan (stop accepting new subscriptions)
+    pub fn deactivate_plan(&mut self, plan_id: u64) {
+        let caller = self.env().caller();
+        let mut plan = self
+            .plans
+            .get(&plan_id)
+            .unwrap_or_revert_with(&self.env(), Error::PlanNotFound);
         
-        plan_id
+        if plan.merchant != caller {
+            self.env().revert(Error::NotPlanMerchant);
+        }
+
+        plan.is_active = false;
+        self.plans.set(&plan_id, plan);
+
+        self.env().emit_event(events::PlanDeactivated { plan_id });
     }
 
-    /// Subscribe to a plan - PAYABLE
-    /// User must send the plan price in CSPR
+    /// Grant `key` to a plan's subscribers, replacing any existing limit
+    pub fn set_entitlement(&mut self, plan_id: u64, key: String, limit: Option<u64>) {
+        self.pausable.require_not_paused(PauseGroup::Subscriptions);
+        self.assert_plan_merchant(plan_id);
+        if key.is_empty() || key.len() > MAX_ENTITLEMENT_KEY_LEN {
+            self.env().revert(Error::InvalidEntitlementKey);
+        }
+
+        let mut entitlements = self.get_plan_entitlements(plan_id);
+        match entitlements.iter_mut().find(|entitlement| entitlement.key == key) {
+            Some(entitlement) => entitlement.limit = limit,
+            None => {
+                if entitlements.len() as u32 >= MAX_PLAN_ENTITLEMENTS {
+                    self.env().revert(Error::TooManyEntitlements);
+                }
+                entitlements.push(Entitlement { key: key.clone(), limit });
+            }
+        }
+        self.plan_entitlements.set(&plan_id, entitlements);
+
+        self.env().emit_event(events::EntitlementSet { plan_id, key, limit });
+    }
+
+    /// Stop granting `key` to a plan's subscribers
+    pub fn remove_entitlement(&mut self, plan_id: u64, key: String) {
+        self.assert_plan_merchant(plan_id);
+        let mut entitlements = self.get_plan_entitlements(plan_id);
+        let Some(index) = entitlements.iter().position(|entitlement| entitlement.key == key) else {
+            self.env().revert(Error::EntitlementNotFound);
+        };
+        entitlements.remove(index);
+        self.plan_entitlements.set(&plan_id, entitlements);
+
+        self.env().emit_event(events::EntitlementRemoved { plan_id, key });
+    }
+
+    // ============ USER FUNCTIONS ============
+
+    /// Subscribe to a plan
     #[odra(payable)]
-    pub fn subscribe(&mut self, plan_id: u32) {
+    pub fn subscribe(
+        &mut self,
+        plan_id: u64,
+        auto_renew: bool,
+        payment_method: u8, // 0 = wallet, 1 = staked
+    ) -> u64 {
+        self.pausable.require_not_paused(PauseGroup::Subscriptions);
         let subscriber = self.env().caller();
-        let attached_value = self.env().attached_value();
+        let plan = self.plans.get(&plan_id).unwrap_or_revert_with(&self.env(), Error::PlanNotFound);
         
-        let price = self.plan_prices.get(&plan_id).unwrap_or_default();
-        let period = self.plan_periods.get(&plan_id).unwrap_or_default();
+        if !plan.is_active {
+            self.env().revert(Error::PlanNotActive);
+        }
         
-        // Validate plan exists
-        assert!(price > U512::zero(), "Plan does not exist");
+        if payment_method > 1 {
+            self.env().revert(Error::InvalidPaymentMethod);
+        }
+
+        // Check if user already has active subscription to this plan
+        self.assert_not_subscribed(subscriber, plan_id);
+
+        // Wallet subscribers pay the first cycle up front
+        let attached = self.env().attached_value();
+        let due = if payment_method == 0 { plan.base_price } else { U512::zero() };
+        if attached < due {
+            self.env().revert(Error::InsufficientPayment);
+        }
+
+        let now = Timestamp::now(&self.env());
+        let next_billing_at = now + plan.billing_cycle;
+        let subscription_id = self.insert_subscription(
+            &plan,
+            subscriber,
+            now,
+            next_billing_at,
+            auto_renew,
+            payment_method,
+            true,
+        );
+
+        // The first cycle is invoiced and settled by BillingEngine
+        if due > U512::zero() {
+            let billing_engine = self
+                .get_peer(Component::BillingEngine)
+                .unwrap_or_revert_with(&self.env(), Error::BillingEngineNotSet);
+            BillingEngineContractRef::new(self.env(), billing_engine)
+                .with_tokens(due)
+                .settle_initial_payment(subscription_id, plan_id, subscriber, plan.merchant, now, next_billing_at);
+        }
+
+        // Stake-to-Pay subscribers owe the first cycle as a pending invoice for auto-pay
+        if payment_method == 1 && plan.base_price > U512::zero() {
+            let billing_engine = self
+                .get_peer(Component::BillingEngine)
+                .unwrap_or_revert_with(&self.env(), Error::BillingEngineNotSet);
+            BillingEngineContractRef::new(self.env(), billing_engine).create_invoice(
+                subscription_id,
+                plan_id,
+                subscriber,
+                plan.merchant,
+                plan.base_price,
+                U512::zero(),
+                0,
+                now,
+                next_billing_at,
+            );
+        }
+
+        // Refund anything attached beyond what is due
+        if attached > due {
+            let refund = attached - due;
+            self.env().transfer_tokens(&subscriber, &refund);
+            self.env().emit_event(events::OverpaymentRefunded {
+                subscription_id,
+                subscriber,
+                amount: refund,
+            });
+        }
+
+        subscription_id
+    }
+
+    /// Cancel a subscription
+    ///
+    /// Stops a streamed subscription's stream and refunds what has not streamed yet.
+    pub fn unsubscribe(&mut self, subscription_id: u64) {
+        let caller = self.env().caller();
+        let mut subscription = self
+            .subscriptions
+            .get(&subscription_id)
+            .unwrap_or_revert_with(&self.env(), Error::SubscriptionNotFound);
         
-        // Check payment amount
-        assert!(attached_value >= price, "Insufficient payment");
+        if subscription.subscriber != caller {
+            self.env().revert(Error::NotSubscriber);
+        }
+        if !subscription.is_active {
+            self.env().revert(Error::SubscriptionInactive);
+        }
+
+        subscription.is_active = false;
+        subscription.auto_renew = false;
+        let plan = self
+            .plans
+            .get(&subscription.plan_id)
+            .unwrap_or_revert_with(&self.env(), Error::PlanNotFound);
+        self.subscriptions.set(&subscription_id, subscription);
+
+        self.record_subscriber_change(&plan, false);
+
+        if let Some(mut stream) = self.streams.get(&subscription_id) {
+            let now = Timestamp::now(&self.env());
+            let refunded = stream.deposited - stream.streamed_at(now);
+            stream.stopped_at = Some(now.min(stream.depleted_at()));
+            self.streams.set(&subscription_id, stream);
+            if !refunded.is_zero() {
+                self.env().transfer_tokens(&caller, &refunded);
+            }
+            self.env().emit_event(events::StreamStopped { subscription_id, refunded });
+        }
+
+        // Unspent prepaid credit is returned with the last subscription to the merchant
+        if self.active_merchant_subscriptions(caller, plan.merchant).next().is_none() {
+            if let Some(usage_meter) = self.get_peer(Component::UsageMeter) {
+                UsageMeterContractRef::new(self.env(), usage_meter).refund_credit(caller, plan.merchant);
+            }
+        }
+
+        self.env().emit_event(events::Unsubscribed {
+            subscription_id,
+            subscriber: caller,
+        });
+    }
+
+    /// Toggle auto-renew for a subscription; streamed subscriptions never renew
+    pub fn set_auto_renew(&mut self, subscription_id: u64, auto_renew: bool) {
+        let caller = self.env().caller();
+        let mut subscription = self
+            .subscriptions
+            .get(&subscription_id)
+            .unwrap_or_revert_with(&self.env(), Error::SubscriptionNotFound);
         
-        // Calculate expiry
-        let current_time = self.env().get_block_time();
-        let existing_expiry = self.subscriptions.get(&(subscriber, plan_id)).unwrap_or(0);
-        let new_expiry = if existing_expiry > current_time {
-            // Extend existing subscription
-            existing_expiry + period
+        if subscription.subscriber != caller {
+            self.env().revert(Error::NotSubscriber);
+        }
+        // A stream pays for itself; invoicing it as well would charge twice
+        if subscription.payment_method == PAYMENT_METHOD_STREAMED {
+            self.env().revert(Error::InvalidPaymentMethod);
+        }
+
+        subscription.auto_renew = auto_renew;
+        self.subscriptions.set(&subscription_id, subscription);
+    }
+
+    // ============ STREAMING FUNCTIONS ============
+
+    /// Subscribe to a plan paid by a per-second stream from the attached deposit
+    #[odra(payable)]
+    pub fn subscribe_streaming(&mut self, plan_id: u64) -> u64 {
+        self.pausable.require_not_paused(PauseGroup::Subscriptions);
+        let subscriber = self.env().caller();
+        let plan = self.plans.get(&plan_id).unwrap_or_revert_with(&self.env(), Error::PlanNotFound);
+        if !plan.is_active {
+            self.env().revert(Error::PlanNotActive);
+        }
+        if plan.base_price.is_zero() || plan.billing_cycle.as_secs() == 0 {
+            self.env().revert(Error::PlanNotStreamable);
+        }
+        self.assert_not_subscribed(subscriber, plan_id);
+        let deposit = self.env().attached_value();
+        if deposit.is_zero() {
+            self.env().revert(Error::InsufficientPayment);
+        }
+
+        let now = Timestamp::now(&self.env());
+        let mut stream = Stream {
+            subscription_id: 0,
+            merchant: plan.merchant,
+            price: plan.base_price,
+            cycle: plan.billing_cycle,
+            started_at: now,
+            deposited: deposit,
+            withdrawn: U512::zero(),
+            stopped_at: None,
+        };
+        let depleted_at = stream.depleted_at();
+        let subscription_id = self.insert_subscription(
+            &plan,
+            subscriber,
+            now,
+            depleted_at,
+            false,
+            PAYMENT_METHOD_STREAMED,
+            true,
+        );
+        stream.subscription_id = subscription_id;
+        self.streams.set(&subscription_id, stream);
+
+        self.env().emit_event(events::StreamStarted {
+            subscription_id,
+            subscriber,
+            merchant: plan.merchant,
+            deposit,
+            depleted_at,
+        });
+        subscription_id
+    }
+
+    /// Add the attached value to a running stream, pushing back when it runs dry
+    #[odra(payable)]
+    pub fn top_up_stream(&mut self, subscription_id: u64) {
+        self.pausable.require_not_paused(PauseGroup::Payments);
+        let mut stream = self
+            .streams
+            .get(&subscription_id)
+            .unwrap_or_revert_with(&self.env(), Error::StreamNotFound);
+        let mut subscription = self
+            .subscriptions
+            .get(&subscription_id)
+            .unwrap_or_revert_with(&self.env(), Error::SubscriptionNotFound);
+        if stream.stopped_at.is_some() || Timestamp::now(&self.env()) >= stream.depleted_at() {
+            self.env().revert(Error::StreamEnded);
+        }
+        let amount = self.env().attached_value();
+        if amount.is_zero() {
+            self.env().revert(Error::InsufficientPayment);
+        }
+
+        stream.deposited = stream.deposited + amount;
+        let depleted_at = stream.depleted_at();
+        self.streams.set(&subscription_id, stream);
+        subscription.next_billing_at = depleted_at;
+        self.subscriptions.set(&subscription_id, subscription);
+
+        self.env().emit_event(events::StreamToppedUp {
+            subscription_id,
+            amount,
+            depleted_at,
+        });
+    }
+
+    /// Pay out what has streamed since the last withdrawal, less the protocol fee (stream merchant)
+    ///
+    /// Deactivates the subscription if the stream has run dry.
+    pub fn withdraw_streamed(&mut self, subscription_id: u64) -> U512 {
+        self.pausable.require_not_paused(PauseGroup::Payments);
+        let mut stream = self
+            .streams
+            .get(&subscription_id)
+            .unwrap_or_revert_with(&self.env(), Error::StreamNotFound);
+        if stream.merchant != self.env().caller() {
+            self.env().revert(Error::NotPlanMerchant);
+        }
+        let amount = stream.streamed_at(Timestamp::now(&self.env())) - stream.withdrawn;
+        if amount.is_zero() {
+            self.env().revert(Error::NothingToWithdraw);
+        }
+        stream.withdrawn = stream.withdrawn + amount;
+        let merchant = stream.merchant;
+        self.streams.set(&subscription_id, stream);
+        self.close_if_depleted(subscription_id);
+
+        // Streams pay the same protocol fee as invoices
+        let billing_engine = self
+            .get_peer(Component::BillingEngine)
+            .unwrap_or_revert_with(&self.env(), Error::BillingEngineNotSet);
+        let engine = BillingEngineContractRef::new(self.env(), billing_engine);
+        let protocol_fee = (amount * U512::from(engine.get_protocol_fee_bps())) / U512::from(10000);
+        let merchant_amount = amount - protocol_fee;
+        self.env().transfer_tokens(&merchant, &merchant_amount);
+        if !protocol_fee.is_zero() {
+            self.env().transfer_tokens(&engine.get_fee_recipient(), &protocol_fee);
+        }
+
+        self.env().emit_event(events::StreamWithdrawn {
+            subscription_id,
+            merchant,
+            amount: merchant_amount,
+            protocol_fee,
+        });
+        merchant_amount
+    }
+
+    /// Deactivate subscriptions whose streams have run dry; returns how many were closed
+    ///
+    /// Anyone may call this. Unknown IDs and running or stopped streams are skipped.
+    pub fn close_depleted_streams(&mut self, subscription_ids: Vec<u64>) -> u32 {
+        let mut closed = 0;
+        for subscription_id in subscription_ids {
+            if self.close_if_depleted(subscription_id) {
+                closed += 1;
+            }
+        }
+        closed
+    }
+
+    // ============ RENEWAL FUNCTIONS ============
+
+    /// Renew due subscriptions and return the created invoice IDs (keeper only)
+    ///
+    /// A subscription is due once `next_billing_at` and the UsageMeter's
+    /// late-usage window have passed. Renewing closes the ending usage period,
+    /// invoices its usage plus the next cycle's base price and moves
+    /// `next_billing_at` one cycle on. Unknown, cancelled, non-renewing and
+    /// not yet due subscriptions are skipped, so a stale ID does not revert
+    /// the batch and retried batches are harmless.
+    pub fn renew_subscriptions(&mut self, subscription_ids: Vec<u64>) -> Vec<u64> {
+        self.pausable.require_not_paused(PauseGroup::Invoicing);
+        self.access.require_role(Role::Keeper);
+        let usage_meter = self
+            .get_peer(Component::UsageMeter)
+            .unwrap_or_revert_with(&self.env(), Error::UsageMeterNotSet);
+        let billing_engine = self
+            .get_peer(Component::BillingEngine)
+            .unwrap_or_revert_with(&self.env(), Error::BillingEngineNotSet);
+        let mut meter = UsageMeterContractRef::new(self.env(), usage_meter);
+        let mut engine = BillingEngineContractRef::new(self.env(), billing_engine);
+        let now = Timestamp::now(&self.env());
+        let late_usage_window = meter.get_late_usage_window();
+
+        let mut invoice_ids = Vec::new();
+        for subscription_id in subscription_ids {
+            let Some(mut subscription) = self.subscriptions.get(&subscription_id) else {
+                continue;
+            };
+            if !subscription.is_active
+                || !subscription.auto_renew
+                || subscription.payment_method == PAYMENT_METHOD_STREAMED
+                || now < subscription.next_billing_at + late_usage_window
+            {
+                continue;
+            }
+            let Some(plan) = self.plans.get(&subscription.plan_id) else {
+                continue;
+            };
+
+            let period_end = subscription.next_billing_at;
+            let period_start = period_end - plan.billing_cycle;
+            // A period already closed elsewhere is billed from its recorded usage
+            let closed = meter
+                .get_period_usage(subscription_id, period_start)
+                .is_some_and(|period| period.is_billed);
+            let usage_units = if closed {
+                meter.get_billable_units(subscription_id, period_start)
+            } else {
+                meter.close_period(subscription_id, period_end)
+            };
+            let invoice_id = engine.create_invoice(
+                subscription_id,
+                plan.id,
+                subscription.subscriber,
+                plan.merchant,
+                plan.base_price,
+                plan.usage_price,
+                usage_units,
+                period_start,
+                period_end,
+            );
+
+            subscription.next_billing_at = period_end + plan.billing_cycle;
+            let next_billing_at = subscription.next_billing_at;
+            self.subscriptions.set(&subscription_id, subscription);
+            self.env().emit_event(events::SubscriptionRenewed {
+                subscription_id,
+                next_billing_at,
+            });
+            invoice_ids.push(invoice_id);
+        }
+        invoice_ids
+    }
+
+    // ============ MIGRATION ============
+
+    /// Import a plan from the legacy SubscriptionManager (admin or plan merchant)
+    ///
+    /// Legacy plans only carry a price, period, name and merchant, so they are
+    /// imported as fixed-price plans. Importing a plan twice returns the same ID.
+    pub fn migrate_legacy_plan(&mut self, legacy_plan_id: u32) -> u64 {
+        self.pausable.require_not_paused(PauseGroup::Subscriptions);
+        let caller = self.env().caller();
+        let legacy = self.legacy_ref();
+        let merchant = legacy
+            .get_plan_merchant(legacy_plan_id)
+            .unwrap_or_revert_with(&self.env(), Error::LegacyPlanNotFound);
+        if caller != merchant && !self.access.has_role(Role::Admin, caller) {
+            self.env().revert(Error::NotAdminOrMerchant);
+        }
+        self.import_legacy_plan(legacy_plan_id)
+    }
+
+    /// Import a subscriber's legacy subscription (admin or the subscriber)
+    ///
+    /// The legacy contract added its period in seconds to a millisecond block
+    /// time, so the legacy expiry minus the period is the start of the current
+    /// cycle; `next_billing_at` is recomputed from it with the correct units.
+    /// Expired subscriptions are imported as inactive so they remain visible in
+    /// the subscriber's history.
+    pub fn migrate_legacy_subscription(&mut self, subscriber: Address, legacy_plan_id: u32) -> u64 {
+        self.pausable.require_not_paused(PauseGroup::Subscriptions);
+        let caller = self.env().caller();
+        if caller != subscriber && !self.access.has_role(Role::Admin, caller) {
+            self.env().revert(Error::NotAdminOrSubscriber);
+        }
+        if self.legacy_subscriptions.get(&(subscriber, legacy_plan_id)).is_some() {
+            self.env().revert(Error::LegacySubscriptionAlreadyMigrated);
+        }
+
+        let expiry = self.legacy_ref().get_expiry(subscriber, legacy_plan_id);
+        if expiry == 0 {
+            self.env().revert(Error::LegacySubscriptionNotFound);
+        }
+
+        let plan_id = self.import_legacy_plan(legacy_plan_id);
+        let plan = self.plans.get(&plan_id).unwrap_or_revert_with(&self.env(), Error::PlanNotFound);
+        let legacy_period = self.legacy_ref().get_plan_period(legacy_plan_id);
+        let started_at = Timestamp::from_millis(expiry.saturating_sub(legacy_period));
+        let next_billing_at = started_at + plan.billing_cycle;
+        let is_active = next_billing_at > Timestamp::now(&self.env());
+        if is_active {
+            self.assert_not_subscribed(subscriber, plan_id);
+        }
+
+        let subscription_id = self.insert_subscription(
+            &plan,
+            subscriber,
+            started_at,
+            next_billing_at,
+            false,
+            0,
+            is_active,
+        );
+        self.legacy_subscriptions.set(&(subscriber, legacy_plan_id), subscription_id);
+
+        self.env().emit_event(events::LegacySubscriptionMigrated {
+            legacy_plan_id,
+            subscription_id,
+            subscriber,
+        });
+
+        subscription_id
+    }
+
+    /// Get the imported ID of a legacy plan
+    pub fn get_migrated_plan_id(&self, legacy_plan_id: u32) -> Option<u64> {
+        self.legacy_plans.get(&legacy_plan_id)
+    }
+
+    /// Get the imported ID of a legacy subscription
+    pub fn get_migrated_subscription_id(&self, subscriber: Address, legacy_plan_id: u32) -> Option<u64> {
+        self.legacy_subscriptions.get(&(subscriber, legacy_plan_id))
+    }
+
+    // ============ INTERNAL FUNCTIONS ============
+
+    /// Store a new plan and index it under its merchant
+    fn insert_plan(
+        &mut self,
+        merchant: Address,
+        name: String,
+        base_price: U512,
+        usage_price: U512,
+        billing_cycle: Duration,
+    ) -> u64 {
+        let plan_id = self.plan_counter.get_or_default() + 1;
+        self.plan_counter.set(plan_id);
+
+        let plan = Plan {
+            id: plan_id,
+            merchant,
+            name: name.clone(),
+            base_price,
+            usage_price,
+            billing_cycle,
+            is_active: true,
+            created_at: Timestamp::now(&self.env()),
+        };
+
+        self.plans.set(&plan_id, plan);
+
+        // Add to merchant's plans list
+        let index = self.merchant_plan_count.get(&merchant).unwrap_or_default();
+        self.merchant_plans.set(&(merchant, index), plan_id);
+        self.merchant_plan_count.set(&merchant, index + 1);
+
+        self.env().emit_event(events::PlanCreated {
+            plan_id,
+            merchant,
+            name,
+            base_price,
+        });
+
+        plan_id
+    }
+
+    /// Store a new subscription, index it under its subscriber and update analytics
+    #[allow(clippy::too_many_arguments)]
+    fn insert_subscription(
+        &mut self,
+        plan: &Plan,
+        subscriber: Address,
+        started_at: Timestamp,
+        next_billing_at: Timestamp,
+        auto_renew: bool,
+        payment_method: u8,
+        is_active: bool,
+    ) -> u64 {
+        let subscription_id = self.subscription_counter.get_or_default() + 1;
+        self.subscription_counter.set(subscription_id);
+
+        let subscription = Subscription {
+            id: subscription_id,
+            plan_id: plan.id,
+            subscriber,
+            started_at,
+            next_billing_at,
+            auto_renew,
+            payment_method,
+            is_active,
+        };
+
+        self.subscriptions.set(&subscription_id, subscription);
+        self.user_plan_subscription.set(&(subscriber, plan.id), subscription_id);
+
+        // Add to user's subscriptions list
+        let index = self.user_subscription_count.get(&subscriber).unwrap_or_default();
+        self.user_subscriptions.set(&(subscriber, index), subscription_id);
+        self.user_subscription_count.set(&subscriber, index + 1);
+
+        if is_active {
+            self.record_subscriber_change(plan, true);
+        }
+
+        self.env().emit_event(events::Subscribed {
+            subscription_id,
+            plan_id: plan.id,
+            subscriber,
+        });
+
+        subscription_id
+    }
+
+    /// Deactivate a streamed subscription whose deposit has run out
+    fn close_if_depleted(&mut self, subscription_id: u64) -> bool {
+        let Some(mut stream) = self.streams.get(&subscription_id) else {
+            return false;
+        };
+        let depleted_at = stream.depleted_at();
+        if stream.stopped_at.is_some() || Timestamp::now(&self.env()) < depleted_at {
+            return false;
+        }
+        stream.stopped_at = Some(depleted_at);
+        self.streams.set(&subscription_id, stream);
+
+        // Unsubscribing stops the stream, so the subscription is still active here
+        let mut subscription = self
+            .subscriptions
+            .get(&subscription_id)
+            .unwrap_or_revert_with(&self.env(), Error::SubscriptionNotFound);
+        subscription.is_active = false;
+        let plan = self
+            .plans
+            .get(&subscription.plan_id)
+            .unwrap_or_revert_with(&self.env(), Error::PlanNotFound);
+        self.subscriptions.set(&subscription_id, subscription);
+        self.record_subscriber_change(&plan, false);
+        self.env().emit_event(events::StreamDepleted { subscription_id });
+        true
+    }
+
+    /// Check the caller is the plan's merchant
+    fn assert_plan_merchant(&self, plan_id: u64) {
+        let plan = self.plans.get(&plan_id).unwrap_or_revert_with(&self.env(), Error::PlanNotFound);
+        if plan.merchant != self.env().caller() {
+            self.env().revert(Error::NotPlanMerchant);
+        }
+    }
+
+    /// Check the user has no active subscription to the plan
+    fn assert_not_subscribed(&self, subscriber: Address, plan_id: u64) {
+        if let Some(existing_sub_id) = self.user_plan_subscription.get(&(subscriber, plan_id)) {
+            if let Some(sub) = self.subscriptions.get(&existing_sub_id) {
+                if sub.is_active {
+                    self.env().revert(Error::AlreadySubscribed);
+                }
+            }
+        }
+    }
+
+    /// Whether a subscription is active and not past due
+    ///
+    /// Past due means its cycle ended without a renewal (or with auto-renew
+    /// off), or the latest invoice this manager issued failed or has been
+    /// pending longer than the payment grace. Renewing keepers wait out the
+    /// late-usage window, so a renewing subscription keeps access for the grace
+    /// after its cycle ends.
+    fn in_good_standing(&self, subscription: &Subscription) -> bool {
+        if !subscription.is_active {
+            return false;
+        }
+        let now = Timestamp::now(&self.env());
+        let grace = self.get_payment_grace();
+        let access_until = if subscription.auto_renew {
+            subscription.next_billing_at + grace
         } else {
-            // New subscription
-            current_time + period
+            subscription.next_billing_at
         };
-        
-        // Save subscription
-        self.subscriptions.set(&(subscriber, plan_id), new_expiry);
-        
-        // Update subscriber count (only for new subscriptions)
-        if existing_expiry == 0 {
-            let current_subs = self.plan_subscribers.get(&plan_id).unwrap_or(0);
-            self.plan_subscribers.set(&plan_id, current_subs + 1);
+        if now >= access_until {
+            return false;
         }
-        
-        // Update total revenue
-        let current_revenue = self.total_revenue.get_or_default();
-        self.total_revenue.set(current_revenue + price);
-        
-        // Transfer payment to merchant
-        let merchant = self.plan_merchants.get(&plan_id).unwrap();
-        self.env().transfer_tokens(&merchant, &price);
+
+        let Some(billing_engine) = self.get_peer(Component::BillingEngine) else {
+            return true;
+        };
+        let engine = BillingEngineContractRef::new(self.env(), billing_engine);
+        let mut index = engine.get_subscription_invoice_count(subscription.id);
+        while index > 0 {
+            index -= 1;
+            let Some(invoice) = engine
+                .get_subscription_invoices_page(subscription.id, index, 1, InvoiceFilter::default())
+                .items
+                .pop()
+            else {
+                continue;
+            };
+            // Invoices raised by keepers directly say nothing about the cycle
+            if !engine.is_issued_by_manager(invoice.id) {
+                continue;
+            }
+            return match invoice.status {
+                InvoiceStatus::Failed => false,
+                InvoiceStatus::Pending => now < invoice.created_at + grace,
+                InvoiceStatus::Paid | InvoiceStatus::Cancelled => true,
+            };
+        }
+        true
+    }
+
+    /// Active subscriptions of `subscriber` to a merchant's plans
+    ///
+    /// Looks at the subscriber's [`pagination::MAX_SCAN`] most recent subscriptions.
+    fn active_merchant_subscriptions(
+        &self,
+        subscriber: Address,
+        merchant: Address,
+    ) -> impl Iterator<Item = Subscription> + '_ {
+        let total = self.get_user_subscription_count(subscriber);
+        (0..total)
+            .rev()
+            .take(pagination::MAX_SCAN as usize)
+            .filter_map(move |index| self.user_subscriptions.get(&(subscriber, index)))
+            .filter_map(|subscription_id| self.subscriptions.get(&subscription_id))
+            .filter(|subscription| subscription.is_active)
+            .filter(move |subscription| {
+                self.plans
+                    .get(&subscription.plan_id)
+                    .is_some_and(|plan| plan.merchant == merchant)
+            })
     }
 
-    /// Check if a subscription is active
-    pub fn is_subscribed(&self, subscriber: Address, plan_id: u32) -> bool {
-        let expiry = self.subscriptions.get(&(subscriber, plan_id)).unwrap_or(0);
-        let current_time = self.env().get_block_time();
-        expiry > current_time
+    /// IDs of a merchant's plans that `subscriber` has access to
+    fn merchant_plans_in_good_standing(
+        &self,
+        subscriber: Address,
+        merchant: Address,
+    ) -> impl Iterator<Item = u64> + '_ {
+        self.active_merchant_subscriptions(subscriber, merchant)
+            .filter(|subscription| self.in_good_standing(subscription))
+            .map(|subscription| subscription.plan_id)
     }
 
-    /// Get subscription expiry timestamp
-    pub fn get_expiry(&self, subscriber: Address, plan_id: u32) -> u64 {
-        self.subscriptions.get(&(subscriber, plan_id)).unwrap_or(0)
+    /// Reference to the legacy SubscriptionManager
+    fn legacy_ref(&self) -> LegacySubscriptionManagerContractRef {
+        let address = self
+            .legacy_manager
+            .get_or_default()
+            .unwrap_or_revert_with(&self.env(), Error::LegacyManagerNotSet);
+        LegacySubscriptionManagerContractRef::new(self.env(), address)
     }
 
+    /// Import a legacy plan without authorization checks
+    fn import_legacy_plan(&mut self, legacy_plan_id: u32) -> u64 {
+        if let Some(plan_id) = self.legacy_plans.get(&legacy_plan_id) {
+            return plan_id;
+        }
+
+        let legacy = self.legacy_ref();
+        let merchant = legacy
+            .get_plan_merchant(legacy_plan_id)
+            .unwrap_or_revert_with(&self.env(), Error::LegacyPlanNotFound);
+        let plan_id = self.insert_plan(
+            merchant,
+            legacy.get_plan_name(legacy_plan_id),
+            legacy.get_plan_price(legacy_plan_id),
+            U512::zero(),
+            Duration::from_secs(legacy.get_plan_period(legacy_plan_id)),
+        );
+        self.legacy_plans.set(&legacy_plan_id, plan_id);
+
+        self.env().emit_event(events::LegacyPlanMigrated {
+            legacy_plan_id,
+            plan_id,
+        });
+
+        plan_id
+    }
+
+    /// Update plan and merchant aggregates when a subscriber joins or leaves
+    fn record_subscriber_change(&mut self, plan: &Plan, joined: bool) {
+        let period = analytics::period_of(Timestamp::now(&self.env()));
+        let monthly = analytics::monthly_amount(plan.base_price, plan.billing_cycle);
+
+        let mut plan_stats = self.plan_stats.get(&plan.id).unwrap_or_default();
+        let mut plan_period = self
+            .plan_period_stats
+            .get(&(plan.id, period))
+            .unwrap_or_else(|| ChurnStats::opening(plan_stats.active_subscribers));
+        plan_stats.apply(joined, monthly);
+        plan_period.apply(joined);
+        self.plan_stats.set(&plan.id, plan_stats);
+        self.plan_period_stats.set(&(plan.id, period), plan_period);
+
+        let mut merchant_stats = self.merchant_stats.get(&plan.merchant).unwrap_or_default();
+        let mut merchant_period = self
+            .merchant_period_stats
+            .get(&(plan.merchant, period))
+            .unwrap_or_else(|| ChurnStats::opening(merchant_stats.active_subscribers));
+        merchant_stats.apply(joined, monthly);
+        merchant_period.apply(joined);
+        self.merchant_stats.set(&plan.merchant, merchant_stats);
+        self.merchant_period_stats.set(&(plan.merchant, period), merchant_period);
+    }
+
+    // ============ VIEW FUNCTIONS ============
+
     /// Get plan details
-    pub fn get_plan_price(&self, plan_id: u32) -> U512 {
-        self.plan_prices.get(&plan_id).unwrap_or_default()
+    pub fn get_plan(&self, plan_id: u64) -> Option<Plan> {
+        self.plans.get(&plan_id)
+    }
+
+    /// Get subscription details
+    pub fn get_subscription(&self, subscription_id: u64) -> Option<Subscription> {
+        self.subscriptions.get(&subscription_id)
+    }
+
+    /// Get number of plans created by a merchant
+    pub fn get_merchant_plan_count(&self, merchant: Address) -> u32 {
+        self.merchant_plan_count.get(&merchant).unwrap_or_default()
+    }
+
+    /// Get a page of a merchant's plans, optionally only active or inactive ones
+    pub fn get_merchant_plans_page(
+        &self,
+        merchant: Address,
+        cursor: u32,
+        limit: u32,
+        is_active: Option<bool>,
+    ) -> PlanPage {
+        let total = self.get_merchant_plan_count(merchant);
+        let (items, next_cursor) = pagination::scan(total, cursor, limit, |index| {
+            self.merchant_plans
+                .get(&(merchant, index))
+                .and_then(|plan_id| self.plans.get(&plan_id))
+                .filter(|plan| is_active.map_or(true, |active| plan.is_active == active))
+        });
+        PlanPage { items, next_cursor }
+    }
+
+    /// Get number of subscriptions a user has ever created
+    pub fn get_user_subscription_count(&self, user: Address) -> u32 {
+        self.user_subscription_count.get(&user).unwrap_or_default()
+    }
+
+    /// Get a page of a user's subscriptions matching `filter`
+    pub fn get_user_subscriptions_page(
+        &self,
+        user: Address,
+        cursor: u32,
+        limit: u32,
+        filter: SubscriptionFilter,
+    ) -> SubscriptionPage {
+        let total = self.get_user_subscription_count(user);
+        let (items, next_cursor) = pagination::scan(total, cursor, limit, |index| {
+            self.user_subscriptions
+                .get(&(user, index))
+                .and_then(|subscription_id| self.subscriptions.get(&subscription_id))
+                .filter(|subscription| filter.matches(subscription))
+        });
+        SubscriptionPage { items, next_cursor }
+    }
+
+    /// Get a user's most recent subscription to a plan
+    pub fn get_user_plan_subscription(&self, user: Address, plan_id: u64) -> Option<Subscription> {
+        self.user_plan_subscription
+            .get(&(user, plan_id))
+            .and_then(|subscription_id| self.subscriptions.get(&subscription_id))
+    }
+
+    /// Whether `subscriber` holds an active, non-past-due subscription to a plan
+    pub fn has_access(&self, subscriber: Address, plan_id: u64) -> bool {
+        self.get_user_plan_subscription(subscriber, plan_id)
+            .is_some_and(|subscription| self.in_good_standing(&subscription))
+    }
+
+    /// Whether `subscriber` holds an active, non-past-due subscription to any of a merchant's plans
+    ///
+    /// Looks at the subscriber's [`pagination::MAX_SCAN`] most recent subscriptions.
+    pub fn has_merchant_access(&self, subscriber: Address, merchant: Address) -> bool {
+        self.merchant_plans_in_good_standing(subscriber, merchant).next().is_some()
+    }
+
+    /// Get a plan's entitlements
+    pub fn get_plan_entitlements(&self, plan_id: u64) -> Vec<Entitlement> {
+        self.plan_entitlements.get(&plan_id).unwrap_or_default()
+    }
+
+    /// Get the entitlements `subscriber` holds with a merchant
+    ///
+    /// Merges the entitlements of every merchant plan the subscriber has
+    /// access to (see [`has_merchant_access`](Self::has_merchant_access)).
+    /// A key granted by several plans takes the highest limit, and an on/off
+    /// grant outranks any limit.
+    pub fn get_entitlements(&self, subscriber: Address, merchant: Address) -> Vec<Entitlement> {
+        let mut resolved: Vec<Entitlement> = Vec::new();
+        for plan_id in self.merchant_plans_in_good_standing(subscriber, merchant) {
+            for entitlement in self.get_plan_entitlements(plan_id) {
+                match resolved.iter_mut().find(|held| held.key == entitlement.key) {
+                    Some(held) => held.merge(&entitlement),
+                    None => resolved.push(entitlement),
+                }
+            }
+        }
+        resolved
+    }
+
+    /// Whether `subscriber` holds `key` with a merchant (with a non-zero limit, if numeric)
+    pub fn has_entitlement(&self, subscriber: Address, merchant: Address, key: String) -> bool {
+        self.get_entitlements(subscriber, merchant)
+            .iter()
+            .any(|entitlement| entitlement.key == key && entitlement.is_granted())
     }
 
-    pub fn get_plan_period(&self, plan_id: u32) -> u64 {
-        self.plan_periods.get(&plan_id).unwrap_or_default()
+    /// Get the stream paying a subscription
+    pub fn get_stream(&self, subscription_id: u64) -> Option<Stream> {
+        self.streams.get(&subscription_id)
     }
 
-    pub fn get_plan_name(&self, plan_id: u32) -> String {
-        self.plan_names.get(&plan_id).unwrap_or_default()
+    /// Get what the merchant can withdraw from a stream now, before the protocol fee
+    pub fn get_withdrawable_streamed(&self, subscription_id: u64) -> U512 {
+        self.streams.get(&subscription_id).map_or(U512::zero(), |stream| {
+            stream.streamed_at(Timestamp::now(&self.env())) - stream.withdrawn
+        })
     }
 
-    pub fn get_plan_merchant(&self, plan_id: u32) -> Option<Address> {
-        self.plan_merchants.get(&plan_id)
+    /// Get how long an unpaid renewal keeps granting access
+    pub fn get_payment_grace(&self) -> Duration {
+        // Installs predating the setting fall back to the default
+        self.payment_grace.get().unwrap_or(DEFAULT_PAYMENT_GRACE)
     }
 
-    pub fn get_plan_subscribers(&self, plan_id: u32) -> u32 {
-        self.plan_subscribers.get(&plan_id).unwrap_or(0)
+    /// Get subscriber and MRR totals for a plan
+    pub fn get_plan_stats(&self, plan_id: u64) -> SubscriberStats {
+        self.plan_stats.get(&plan_id).unwrap_or_default()
+    }
+
+    /// Get subscriber and MRR totals across a merchant's plans
+    pub fn get_merchant_stats(&self, merchant: Address) -> SubscriberStats {
+        self.merchant_stats.get(&merchant).unwrap_or_default()
+    }
+
+    /// Get new/churned subscribers of a plan in an analytics period
+    pub fn get_plan_period_stats(&self, plan_id: u64, period: u64) -> ChurnStats {
+        self.plan_period_stats
+            .get(&(plan_id, period))
+            .unwrap_or_else(|| ChurnStats::opening(self.get_plan_stats(plan_id).active_subscribers))
+    }
+
+    /// Get new/churned subscribers of a merchant in an analytics period
+    pub fn get_merchant_period_stats(&self, merchant: Address, period: u64) -> ChurnStats {
+        self.merchant_period_stats
+            .get(&(merchant, period))
+            .unwrap_or_else(|| ChurnStats::opening(self.get_merchant_stats(merchant).active_subscribers))
+    }
+
+    /// Get a merchant's monthly recurring revenue
+    pub fn get_merchant_mrr(&self, merchant: Address) -> U512 {
+        self.get_merchant_stats(merchant).mrr
+    }
+
+    /// Get a merchant's average monthly revenue per active subscriber
+    pub fn get_merchant_arpu(&self, merchant: Address) -> U512 {
+        self.get_merchant_stats(merchant).arpu()
+    }
+
+    /// Get a plan's average monthly revenue per active subscriber
+    pub fn get_plan_arpu(&self, plan_id: u64) -> U512 {
+        self.get_plan_stats(plan_id).arpu()
+    }
+
+    /// Get a merchant's churn rate in an analytics period, in basis points
+    pub fn get_merchant_churn_bps(&self, merchant: Address, period: u64) -> u64 {
+        self.get_merchant_period_stats(merchant, period).churn_bps()
+    }
+
+    /// Get the analytics period containing the current block time
+    pub fn current_analytics_period(&self) -> u64 {
+        analytics::period_of(Timestamp::now(&self.env()))
     }
 
     /// Get total number of plans
-    pub fn plan_count(&self) -> u32 {
-        self.plan_count.get_or_default()
+    pub fn total_plans(&self) -> u64 {
+        self.plan_counter.get_or_default()
+    }
+
+    /// Get total number of subscriptions
+    pub fn total_subscriptions(&self) -> u64 {
+        self.subscription_counter.get_or_default()
+    }
+
+    // ============ ADMIN FUNCTIONS ============
+
+    /// Set the BillingEngine contract address (admin; timelocked once set)
+    pub fn set_billing_engine(&mut self, address: Address) {
+        self.access.require_role(Role::Admin);
+        if self.billing_engine.get_or_default().is_none() {
+            self.billing_engine.set(Some(address));
+        } else {
+            self.timelock.queue_address(Param::BillingEngine, address);
+        }
+    }
+
+    /// Set the UsageMeter contract address (admin; timelocked once set)
+    pub fn set_usage_meter(&mut self, address: Address) {
+        self.access.require_role(Role::Admin);
+        if self.usage_meter.get_or_default().is_none() {
+            self.usage_meter.set(Some(address));
+        } else {
+            self.timelock.queue_address(Param::UsageMeter, address);
+        }
+    }
+
+    /// Set the StakeToPay contract address (admin; timelocked once set)
+    pub fn set_stake_to_pay(&mut self, address: Address) {
+        self.access.require_role(Role::Admin);
+        if self.stake_to_pay.get_or_default().is_none() {
+            self.stake_to_pay.set(Some(address));
+        } else {
+            self.timelock.queue_address(Param::StakeToPay, address);
+        }
+    }
+
+    /// Set the legacy SubscriptionManager to migrate from (admin; timelocked once set)
+    pub fn set_legacy_manager(&mut self, address: Address) {
+        self.access.require_role(Role::Admin);
+        if self.legacy_manager.get_or_default().is_none() {
+            self.legacy_manager.set(Some(address));
+        } else {
+            self.timelock.queue_address(Param::LegacyManager, address);
+        }
+    }
+
+    /// Queue a new time an unpaid renewal keeps granting access (admin only)
+    pub fn set_payment_grace(&mut self, grace: Duration) {
+        self.access.require_role(Role::Admin);
+        self.timelock.queue_amount(Param::PaymentGrace, grace.as_millis());
+    }
+
+    // ============ REGISTRY FUNCTIONS ============
+
+    /// Resolve peers through a Protocol Registry (admin; timelocked once set)
+    pub fn set_registry(&mut self, address: Address) {
+        self.access.require_role(Role::Admin);
+        if self.registry.get_or_default().is_none() {
+            self.registry.set(Some(address));
+        } else {
+            self.timelock.queue_address(Param::Registry, address);
+        }
+    }
+
+    /// Get the Protocol Registry address
+    pub fn get_registry(&self) -> Option<Address> {
+        self.registry.get_or_default()
     }
 
-    /// Get total revenue
-    pub fn total_revenue(&self) -> U512 {
-        self.total_revenue.get_or_default()
+    /// Get the address of a peer contract: the registry's entry once a
+    /// registry is set, otherwise the address configured here
+    pub fn get_peer(&self, component: Component) -> Option<Address> {
+        if let Some(registry) = self.registry.get_or_default() {
+            return RegistryContractRef::new(self.env(), registry).get_address(component);
+        }
+        match component {
+            Component::UsageMeter => self.usage_meter.get_or_default(),
+            Component::BillingEngine => self.billing_engine.get_or_default(),
+            Component::StakeToPay => self.stake_to_pay.get_or_default(),
+            _ => None,
+        }
+    }
+
+    // ============ UPGRADE FUNCTIONS ============
+
+    /// Hook run by Odra when a new version of the contract is installed (admin only)
+    pub fn upgrade(&mut self) {
+        self.access.require_role(Role::Admin);
+        // Plans and subscriptions are still on their first layout; once one
+        // changes, snapshot its count here and add its `migrate_*` entry point
+        self.schema.upgrade(&[]);
     }
 
-    /// Get contract owner
-    pub fn owner(&self) -> Option<Address> {
-        self.owner.get()
+    delegate! {
+        to self.schema {
+            fn schema_version(&self) -> u32;
+            fn get_migration_progress(&self, kind: RecordKind) -> MigrationProgress;
+        }
+    }
+
+    // ============ GOVERNANCE FUNCTIONS ============
+
+    /// Apply a queued parameter change once its delay has passed (anyone)
+    pub fn execute_change(&mut self, change_id: u64) {
+        let change = self.timelock.take_ready(change_id);
+        match change.param {
+            Param::UsageMeter => self.usage_meter.set(change.address),
+            Param::BillingEngine => self.billing_engine.set(change.address),
+            Param::StakeToPay => self.stake_to_pay.set(change.address),
+            Param::LegacyManager => self.legacy_manager.set(change.address),
+            Param::Registry => self.registry.set(change.address),
+            Param::PaymentGrace => self.payment_grace.set(Duration::from_millis(change.amount)),
+            Param::TimelockDelay => {}
+            _ => self.env().revert(TimelockError::UnsupportedParam),
+        }
+    }
+
+    /// Cancel a queued parameter change (admin only)
+    pub fn cancel_change(&mut self, change_id: u64) {
+        self.access.require_role(Role::Admin);
+        self.timelock.cancel(change_id);
+    }
+
+    /// Queue a new delay for parameter changes (admin only)
+    pub fn set_timelock_delay(&mut self, delay: Duration) {
+        self.access.require_role(Role::Admin);
+        self.timelock.queue_delay(delay);
+    }
+
+    delegate! {
+        to self.timelock {
+            fn get_queued_change(&self, change_id: u64) -> Option<QueuedChange>;
+            fn get_pending_change(&self, param: Param) -> Option<QueuedChange>;
+            fn get_timelock_delay(&self) -> Duration;
+        }
+    }
+
+    // ============ PAUSE FUNCTIONS ============
+
+    /// Pause a group of entry points (pauser only)
+    pub fn pause(&mut self, group: PauseGroup) {
+        self.access.require_role(Role::Pauser);
+        self.pausable.pause(group);
+    }
+
+    /// Resume a paused group of entry points (pauser only)
+    pub fn unpause(&mut self, group: PauseGroup) {
+        self.access.require_role(Role::Pauser);
+        self.pausable.unpause(group);
+    }
+
+    delegate! {
+        to self.pausable {
+            fn is_paused(&self, group: PauseGroup) -> bool;
+        }
+    }
+
+    // ============ ACCESS CONTROL ============
+
+    delegate! {
+        to self.access {
+            fn grant_role(&mut self, role: Role, account: Address);
+            fn revoke_role(&mut self, role: Role, account: Address);
+            fn renounce_role(&mut self, role: Role);
+            fn transfer_ownership(&mut self, new_owner: Address);
+            fn accept_ownership(&mut self);
+            fn has_role(&self, role: Role, account: Address) -> bool;
+            fn owner(&self) -> Option<Address>;
+            fn pending_owner(&self) -> Option<Address>;
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use odra::host::Deployer;
+    use crate::billing_engine::{BillingEngine, BillingEngineHostRef, InvoiceFilter, InvoiceStatus};
+    use crate::legacy::mock::LegacyManagerMock;
+    use crate::access::Error as AccessError;
+    use crate::registry::Registry;
+    use crate::timelock::DEFAULT_TIMELOCK_DELAY;
+    use crate::upgrade::SCHEMA_VERSION;
+    use crate::usage_meter::{Error as UsageError, UsageMeter, DEFAULT_LATE_USAGE_WINDOW};
+    use odra::host::{Deployer, HostEnv, HostRef, InstallConfig, NoArgs};
 
     #[test]
     fn test_create_plan() {
         let env = odra_test::env();
-        let mut contract = SubscriptionManagerHostRef::deploy(&env, ());
-        
+        let mut contract = SubscriptionManager::deploy(&env, NoArgs);
+
         let plan_id = contract.create_plan(
-            U512::from(100_000_000_000u64), // 100 CSPR
-            2592000, // 30 days
-            "Pro Plan".to_string()
+            "Pro API".to_string(),
+            U512::from(50_000_000_000u64), // 50 CSPR
+            U512::from(1_000_000u64),      // 0.001 CSPR per call
+            Duration::from_days(30),
         );
-        
+
         assert_eq!(plan_id, 1);
-        assert_eq!(contract.plan_count(), 1);
+        assert_eq!(contract.total_plans(), 1);
+
+        let plan = contract.get_plan(plan_id).unwrap();
+        assert_eq!(plan.name, "Pro API");
+        assert!(plan.is_active);
+    }
+
+    /// Deploy a SubscriptionManager wired to a BillingEngine (both owned by account 0)
+    fn setup(env: &HostEnv) -> (SubscriptionManagerHostRef, BillingEngineHostRef) {
+        let mut contract = SubscriptionManager::deploy(env, NoArgs);
+        let mut engine = BillingEngine::deploy(env, NoArgs);
+        contract.set_billing_engine(engine.address());
+        engine.set_subscription_manager(contract.address());
+        (contract, engine)
+    }
+
+    #[test]
+    fn test_subscribe() {
+        let env = odra_test::env();
+        let (mut contract, engine) = setup(&env);
+        let fee_recipient = env.get_account(0);
+        let merchant = env.get_account(1);
+        let subscriber = env.get_account(2);
+
+        // Create a plan
+        env.set_caller(merchant);
+        let plan_id = contract.create_plan(
+            "Starter".to_string(),
+            U512::from(10_000_000_000u64), // 10 CSPR
+            U512::zero(),
+            Duration::from_days(30),
+        );
+
+        // Underpaying is rejected
+        env.set_caller(subscriber);
+        assert_eq!(
+            contract
+                .with_tokens(U512::from(9_000_000_000u64))
+                .try_subscribe(plan_id, true, 0),
+            Err(Error::InsufficientPayment.into())
+        );
+
+        // Subscribe with 2 CSPR too much attached
+        let merchant_balance = env.balance_of(&merchant);
+        let fee_balance = env.balance_of(&fee_recipient);
+        let sub_id = contract
+            .with_tokens(U512::from(12_000_000_000u64))
+            .subscribe(plan_id, true, 0);
+
+        assert_eq!(sub_id, 1);
+
+        let subscription = contract.get_subscription(sub_id).unwrap();
+        assert!(subscription.is_active);
+        assert!(subscription.auto_renew);
+
+        // A 30-day cycle is 30 days of millisecond block time
+        let cycle = subscription.next_billing_at.duration_since(subscription.started_at);
+        assert_eq!(cycle.as_millis(), 2_592_000_000);
+
+        // First cycle is a paid invoice; 1% fee goes to the fee recipient
+        let page = engine.get_subscription_invoices_page(sub_id, 0, 10, InvoiceFilter::default());
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(page.items[0].status, InvoiceStatus::Paid);
+        assert_eq!(page.items[0].total_amount, U512::from(10_000_000_000u64));
+        assert_eq!(page.items[0].period_end, subscription.next_billing_at);
+        assert_eq!(env.balance_of(&merchant), merchant_balance + U512::from(9_900_000_000u64));
+        assert_eq!(env.balance_of(&fee_recipient), fee_balance + U512::from(100_000_000u64));
+
+        // The excess was refunded, nothing is left in either contract
+        assert_eq!(env.balance_of(&contract.address()), U512::zero());
+        assert_eq!(env.balance_of(&engine.address()), U512::zero());
+    }
+
+    #[test]
+    fn test_staked_subscription_owes_first_cycle() {
+        let env = odra_test::env();
+        let (mut contract, engine) = setup(&env);
+        let merchant = env.get_account(1);
+        let subscriber = env.get_account(2);
+        let price = U512::from(10_000_000_000u64);
+
+        env.set_caller(merchant);
+        let plan_id = contract.create_plan("Starter".to_string(), price, U512::zero(), Duration::from_days(30));
+        env.set_caller(subscriber);
+        let sub_id = contract.subscribe(plan_id, true, 1);
+
+        // Nothing is paid up front; the first cycle waits for auto-pay
+        let subscription = contract.get_subscription(sub_id).unwrap();
+        let page = engine.get_subscription_invoices_page(sub_id, 0, 10, InvoiceFilter::default());
+        assert_eq!(page.items.len(), 1);
+        let invoice = &page.items[0];
+        assert_eq!(invoice.status, InvoiceStatus::Pending);
+        assert_eq!(invoice.total_amount, price);
+        assert_eq!(invoice.merchant, merchant);
+        assert_eq!(invoice.period_end, subscription.next_billing_at);
+        assert!(engine.is_issued_by_manager(invoice.id));
+    }
+
+    #[test]
+    fn test_free_plan_needs_no_billing_engine() {
+        let env = odra_test::env();
+        let mut contract = SubscriptionManager::deploy(&env, NoArgs);
+
+        let plan_id = contract.create_plan("Free".to_string(), U512::zero(), U512::zero(), Duration::from_days(30));
+        let sub_id = contract.subscribe(plan_id, false, 0);
+        assert!(contract.get_subscription(sub_id).unwrap().is_active);
+    }
+
+    #[test]
+    fn test_standing_follows_manager_invoices() {
+        let env = odra_test::env();
+        let mut registry = Registry::deploy(&env, NoArgs);
+        let mut contract = SubscriptionManager::deploy(&env, NoArgs);
+        let mut meter = UsageMeter::deploy(&env, NoArgs);
+        let mut engine = BillingEngine::deploy(&env, NoArgs);
+        registry.register(Component::SubscriptionManager, contract.address());
+        registry.register(Component::UsageMeter, meter.address());
+        registry.register(Component::BillingEngine, engine.address());
+        contract.set_registry(registry.address());
+        meter.set_registry(registry.address());
+        engine.set_registry(registry.address());
+        let merchant = env.get_account(1);
+        let subscriber = env.get_account(2);
+        let (price, cycle) = (U512::from(1_000u64), Duration::from_days(30));
+
+        env.set_caller(merchant);
+        let plan_id = contract.create_plan("Pro".to_string(), price, U512::zero(), cycle);
+        env.set_caller(subscriber);
+        let sub_id = contract.with_tokens(price).subscribe(plan_id, true, 0);
+
+        // Account 0 is the keeper; the renewal goes unpaid past the grace
+        env.set_caller(env.get_account(0));
+        env.advance_block_time((cycle + DEFAULT_LATE_USAGE_WINDOW).as_millis());
+        assert_eq!(contract.renew_subscriptions(vec![sub_id]).len(), 1);
+        env.advance_block_time(DEFAULT_PAYMENT_GRACE.as_millis());
+        assert!(!contract.has_access(subscriber, plan_id));
+
+        // A settled invoice raised outside the manager does not restore access
+        let next_billing_at = contract.get_subscription(sub_id).unwrap().next_billing_at;
+        let forged = engine.create_invoice(
+            sub_id, plan_id, subscriber, merchant,
+            U512::zero(), U512::zero(), 0, next_billing_at, next_billing_at + cycle,
+        );
+        assert_eq!(engine.get_invoice(forged).unwrap().status, InvoiceStatus::Paid);
+        assert!(!contract.has_access(subscriber, plan_id));
+    }
+
+    #[test]
+    fn test_unsubscribe_refunds_credit_without_registry() {
+        let env = odra_test::env();
+        let mut contract = SubscriptionManager::deploy(&env, NoArgs);
+        let mut meter = UsageMeter::deploy(&env, NoArgs);
+        contract.set_usage_meter(meter.address());
+        meter.set_subscription_manager(contract.address());
+        let merchant = env.get_account(1);
+        let subscriber = env.get_account(2);
+
+        env.set_caller(merchant);
+        let plan_id = contract.create_plan("Free".to_string(), U512::zero(), U512::zero(), Duration::from_days(30));
+        env.set_caller(subscriber);
+        let sub_id = contract.subscribe(plan_id, false, 0);
+        let credit = U512::from(5_000_000_000u64);
+        meter.with_tokens(credit).deposit_credit(merchant);
+        assert_eq!(contract.get_peer(Component::UsageMeter), Some(meter.address()));
+
+        // Cancelling the last subscription with the merchant returns the credit
+        let balance = env.balance_of(&subscriber);
+        contract.unsubscribe(sub_id);
+        assert_eq!(meter.get_credit_balance(subscriber, merchant), U512::zero());
+        assert_eq!(env.balance_of(&subscriber), balance + credit);
+        assert_eq!(env.balance_of(&meter.address()), U512::zero());
+    }
+
+    #[test]
+    fn test_entitlements_resolve_across_plans() {
+        let env = odra_test::env();
+        let mut contract = SubscriptionManager::deploy(&env, NoArgs);
+        let merchant = env.get_account(1);
+        let subscriber = env.get_account(2);
+        let cycle = Duration::from_days(30);
+
+        env.set_caller(merchant);
+        let basic = contract.create_plan("Basic".to_string(), U512::zero(), U512::zero(), cycle);
+        let addon = contract.create_plan("Analytics".to_string(), U512::zero(), U512::zero(), cycle);
+        contract.set_entitlement(basic, "max_projects".to_string(), Some(3));
+        contract.set_entitlement(basic, "sso".to_string(), Some(0));
+        contract.set_entitlement(addon, "max_projects".to_string(), Some(10));
+        contract.set_entitlement(addon, "analytics".to_string(), None);
+        // Setting an existing key replaces its limit
+        contract.set_entitlement(basic, "max_projects".to_string(), Some(5));
+        assert_eq!(contract.get_plan_entitlements(basic).len(), 2);
+        assert_eq!(
+            contract.try_set_entitlement(basic, String::new(), None),
+            Err(Error::InvalidEntitlementKey.into())
+        );
+
+        env.set_caller(subscriber);
+        assert_eq!(
+            contract.try_set_entitlement(basic, "sso".to_string(), Some(1)),
+            Err(Error::NotPlanMerchant.into())
+        );
+        contract.subscribe(basic, false, 0);
+        let addon_sub = contract.subscribe(addon, false, 0);
+
+        // The highest limit wins; zero limits are held but not granted
+        let entitlements = contract.get_entitlements(subscriber, merchant);
+        assert_eq!(entitlements.len(), 3);
+        let max_projects = entitlements.iter().find(|e| e.key == "max_projects").unwrap();
+        assert_eq!(max_projects.limit, Some(10));
+        assert!(contract.has_entitlement(subscriber, merchant, "analytics".to_string()));
+        assert!(!contract.has_entitlement(subscriber, merchant, "sso".to_string()));
+        assert!(contract.get_entitlements(subscriber, env.get_account(3)).is_empty());
+
+        // Cancelling the add-on drops what only it granted
+        contract.unsubscribe(addon_sub);
+        assert!(!contract.has_entitlement(subscriber, merchant, "analytics".to_string()));
+        let entitlements = contract.get_entitlements(subscriber, merchant);
+        assert_eq!(entitlements.iter().find(|e| e.key == "max_projects").unwrap().limit, Some(5));
+
+        // Lapsed subscriptions grant nothing
+        env.advance_block_time(cycle.as_millis());
+        assert!(contract.get_entitlements(subscriber, merchant).is_empty());
+
+        env.set_caller(merchant);
+        contract.remove_entitlement(basic, "sso".to_string());
+        assert_eq!(
+            contract.try_remove_entitlement(basic, "sso".to_string()),
+            Err(Error::EntitlementNotFound.into())
+        );
+    }
+
+    #[test]
+    fn test_streaming_subscription() {
+        let env = odra_test::env();
+        let (mut contract, _) = setup(&env);
+        let fee_recipient = env.get_account(0);
+        let merchant = env.get_account(1);
+        let streamer = env.get_account(2);
+        let quitter = env.get_account(3);
+        let day = Duration::from_days(1);
+
+        // Streams 1_000 per day
+        env.set_caller(merchant);
+        let cycle = Duration::from_days(30);
+        let plan_id = contract.create_plan("Pro".to_string(), U512::from(30_000u64), U512::zero(), cycle);
+        let free = contract.create_plan("Free".to_string(), U512::zero(), U512::zero(), cycle);
+
+        env.set_caller(streamer);
+        assert_eq!(
+            contract.with_tokens(U512::from(1_000u64)).try_subscribe_streaming(free),
+            Err(Error::PlanNotStreamable.into())
+        );
+        assert_eq!(contract.try_subscribe_streaming(plan_id), Err(Error::InsufficientPayment.into()));
+        assert_eq!(contract.try_subscribe(plan_id, true, 2), Err(Error::InvalidPaymentMethod.into()));
+        let streamed = contract.with_tokens(U512::from(10_000u64)).subscribe_streaming(plan_id);
+        let subscription = contract.get_subscription(streamed).unwrap();
+        assert_eq!(subscription.payment_method, PAYMENT_METHOD_STREAMED);
+        assert_eq!(subscription.next_billing_at, subscription.started_at + Duration::from_days(10));
+        assert!(contract.has_access(streamer, plan_id));
+        // Streams are never invoiced on top
+        assert_eq!(
+            contract.try_set_auto_renew(streamed, true),
+            Err(Error::InvalidPaymentMethod.into())
+        );
+
+        env.set_caller(quitter);
+        let cancelled = contract.with_tokens(U512::from(10_000u64)).subscribe_streaming(plan_id);
+
+        // The merchant withdraws what has streamed, less the 1% protocol fee
+        env.advance_block_time(day.as_millis() * 4);
+        env.set_caller(merchant);
+        assert_eq!(contract.get_withdrawable_streamed(streamed), U512::from(4_000u64));
+        let merchant_balance = env.balance_of(&merchant);
+        let fee_balance = env.balance_of(&fee_recipient);
+        assert_eq!(contract.withdraw_streamed(streamed), U512::from(3_960u64));
+        assert_eq!(env.balance_of(&merchant), merchant_balance + U512::from(3_960u64));
+        assert_eq!(env.balance_of(&fee_recipient), fee_balance + U512::from(40u64));
+        assert_eq!(contract.try_withdraw_streamed(streamed), Err(Error::NothingToWithdraw.into()));
+
+        // Cancelling refunds what has not streamed
+        env.set_caller(quitter);
+        let quitter_balance = env.balance_of(&quitter);
+        contract.unsubscribe(cancelled);
+        assert_eq!(env.balance_of(&quitter), quitter_balance + U512::from(6_000u64));
+        env.advance_block_time(day.as_millis());
+        assert_eq!(contract.get_withdrawable_streamed(cancelled), U512::from(4_000u64));
+
+        // Topping up pushes back depletion
+        env.set_caller(streamer);
+        contract.with_tokens(U512::from(2_000u64)).top_up_stream(streamed);
+        let subscription = contract.get_subscription(streamed).unwrap();
+        assert_eq!(subscription.next_billing_at, subscription.started_at + Duration::from_days(12));
+        assert_eq!(
+            contract.with_tokens(U512::from(1_000u64)).try_top_up_stream(cancelled),
+            Err(Error::StreamEnded.into())
+        );
+
+        // A dry stream loses access and is closed by anyone
+        env.advance_block_time(day.as_millis() * 7);
+        assert!(!contract.has_access(streamer, plan_id));
+        assert_eq!(contract.close_depleted_streams(vec![streamed, cancelled, 99]), 1);
+        assert!(!contract.get_subscription(streamed).unwrap().is_active);
+        assert_eq!(contract.get_plan_stats(plan_id).active_subscribers, 0);
+        assert_eq!(contract.close_depleted_streams(vec![streamed]), 0);
+
+        env.set_caller(merchant);
+        assert_eq!(contract.withdraw_streamed(streamed), U512::from(7_920u64));
+    }
+
+    #[test]
+    fn test_peers_resolve_through_registry() {
+        let env = odra_test::env();
+        let mut registry = Registry::deploy(&env, NoArgs);
+        let mut contract = SubscriptionManager::deploy(&env, NoArgs);
+        let mut engine = BillingEngine::deploy(&env, NoArgs);
+        registry.register(Component::SubscriptionManager, contract.address());
+        registry.register(Component::BillingEngine, engine.address());
+        contract.set_registry(registry.address());
+        engine.set_registry(registry.address());
+        assert_eq!(contract.get_peer(Component::BillingEngine), Some(engine.address()));
+
+        let price = U512::from(1_000_000_000u64);
+        let plan_id = contract.create_plan("Pro".to_string(), price, U512::zero(), Duration::from_days(30));
+        let first_sub = contract.with_tokens(price).subscribe(plan_id, false, 0);
+        assert_eq!(engine.get_subscription_invoices_page(first_sub, 0, 10, InvoiceFilter::default()).items.len(), 1);
+
+        // Replacing the engine in the registry rewires every contract at once
+        let mut new_engine = BillingEngine::deploy(&env, NoArgs);
+        new_engine.set_registry(registry.address());
+        registry.register(Component::BillingEngine, new_engine.address());
+        let change = registry.get_pending_change(Param::BillingEngine).unwrap();
+        env.advance_block_time(DEFAULT_TIMELOCK_DELAY.as_millis());
+        registry.execute_change(change.id);
+
+        env.set_caller(env.get_account(1));
+        let second_sub = contract.with_tokens(price).subscribe(plan_id, false, 0);
+        let page = new_engine.get_subscription_invoices_page(second_sub, 0, 10, InvoiceFilter::default());
+        assert_eq!(page.items[0].status, InvoiceStatus::Paid);
+        assert_eq!(engine.total_invoices(), 1);
+    }
+
+    #[test]
+    fn test_keeper_renews_due_subscriptions() {
+        let env = odra_test::env();
+        let mut registry = Registry::deploy(&env, NoArgs);
+        let mut contract = SubscriptionManager::deploy(&env, NoArgs);
+        let mut meter = UsageMeter::deploy(&env, NoArgs);
+        let mut engine = BillingEngine::deploy(&env, NoArgs);
+        registry.register(Component::SubscriptionManager, contract.address());
+        registry.register(Component::UsageMeter, meter.address());
+        registry.register(Component::BillingEngine, engine.address());
+        contract.set_registry(registry.address());
+        meter.set_registry(registry.address());
+        engine.set_registry(registry.address());
+
+        let (price, usage_price, cycle) = (U512::from(1_000u64), U512::from(10u64), Duration::from_days(30));
+        let plan_id = contract.create_plan("Pro".to_string(), price, usage_price, cycle);
+        meter.authorize_recorder(plan_id, env.get_account(0));
+        env.set_caller(env.get_account(1));
+        let renewing = contract.with_tokens(price).subscribe(plan_id, true, 0);
+        env.set_caller(env.get_account(2));
+        let expiring = contract.with_tokens(price).subscribe(plan_id, false, 0);
+        env.set_caller(env.get_account(0));
+        meter.record_usage(renewing, plan_id, "api_calls".to_string(), 5);
+
+        let keeper = env.get_account(3);
+        contract.grant_role(Role::Keeper, keeper);
+        env.set_caller(keeper);
+        // Not due until the late-usage window after next_billing_at has passed
+        env.advance_block_time(cycle.as_millis());
+        assert!(contract.renew_subscriptions(vec![renewing, expiring]).is_empty());
+        env.advance_block_time(DEFAULT_LATE_USAGE_WINDOW.as_millis());
+
+        // Closing the period ahead of renewal neither needs nor skips its usage
+        let period_end = meter.get_period_schedule(renewing).unwrap().anchor + cycle;
+        env.set_caller(env.get_account(1));
+        assert_eq!(meter.try_close_period(renewing, period_end), Err(UsageError::NotBillingEngine.into()));
+        env.set_caller(env.get_account(0));
+        assert_eq!(meter.close_period(renewing, period_end), 5);
+        env.set_caller(keeper);
+
+        let invoice_ids = contract.renew_subscriptions(vec![renewing, expiring, 99]);
+        assert_eq!(invoice_ids.len(), 1);
+        let invoice = engine.get_invoice(invoice_ids[0]).unwrap();
+        assert_eq!(invoice.subscription_id, renewing);
+        assert_eq!(invoice.total_amount, price + usage_price * U512::from(5));
+        assert_eq!(invoice.status, InvoiceStatus::Pending);
+
+        let subscription = contract.get_subscription(renewing).unwrap();
+        assert_eq!(subscription.next_billing_at, subscription.started_at + cycle * 2);
+        assert!(env.emitted_event(
+            &contract,
+            events::SubscriptionRenewed { subscription_id: renewing, next_billing_at: subscription.next_billing_at }
+        ));
+        // Retried batches skip subscriptions that are no longer due
+        assert!(contract.renew_subscriptions(vec![renewing]).is_empty());
+
+        env.set_caller(env.get_account(1));
+        assert_eq!(contract.try_renew_subscriptions(vec![renewing]), Err(AccessError::MissingRole.into()));
+    }
+
+    #[test]
+    fn test_upgrade_keeps_records() {
+        let env = odra_test::env();
+        let mut contract = SubscriptionManager::deploy_with_cfg(
+            &env,
+            NoArgs,
+            InstallConfig::upgradable::<SubscriptionManager>(),
+        );
+        for name in ["Basic", "Pro", "Team"] {
+            contract.create_plan(name.to_string(), U512::zero(), U512::zero(), Duration::from_days(30));
+        }
+        assert_eq!(contract.schema_version(), SCHEMA_VERSION);
+
+        let contract = SubscriptionManager::try_upgrade(&env, contract.address(), NoArgs).unwrap();
+        assert_eq!(contract.schema_version(), SCHEMA_VERSION);
+        assert_eq!(contract.total_plans(), 3);
+        assert_eq!(contract.get_plan(2).unwrap().name, "Pro");
+        // Nothing to migrate while records keep their first layout
+        assert_eq!(contract.get_migration_progress(RecordKind::Plan), MigrationProgress::default());
+    }
+
+    #[test]
+    fn test_merchant_analytics() {
+        let env = odra_test::env();
+        let (mut contract, _engine) = setup(&env);
+        let merchant = env.get_account(0);
+
+        // 30-day plan at 10 CSPR and 15-day plan at 3 CSPR (6 CSPR monthly)
+        let monthly = contract.create_plan("Monthly".to_string(), U512::from(10_000_000_000u64), U512::zero(), Duration::from_days(30));
+        let biweekly = contract.create_plan("Biweekly".to_string(), U512::from(3_000_000_000u64), U512::zero(), Duration::from_days(15));
+
+        let first_period = contract.current_analytics_period();
+        env.set_caller(env.get_account(1));
+        contract.with_tokens(U512::from(10_000_000_000u64)).subscribe(monthly, true, 0);
+        env.set_caller(env.get_account(2));
+        let leaving = contract.with_tokens(U512::from(10_000_000_000u64)).subscribe(monthly, true, 0);
+        env.set_caller(env.get_account(3));
+        contract.with_tokens(U512::from(3_000_000_000u64)).subscribe(biweekly, true, 0);
+
+        let stats = contract.get_merchant_stats(merchant);
+        assert_eq!(stats.active_subscribers, 3);
+        assert_eq!(stats.mrr, U512::from(26_000_000_000u64));
+        assert_eq!(contract.get_plan_stats(monthly).mrr, U512::from(20_000_000_000u64));
+
+        // Churn one subscriber in the next period
+        env.advance_block_time(Duration::from_days(30).as_millis());
+        let period = contract.current_analytics_period();
+        env.set_caller(env.get_account(2));
+        contract.unsubscribe(leaving);
+
+        let stats = contract.get_merchant_stats(merchant);
+        assert_eq!(stats.active_subscribers, 2);
+        assert_eq!(stats.total_subscribers, 3);
+        assert_eq!(stats.mrr, U512::from(16_000_000_000u64));
+        assert_eq!(contract.get_merchant_arpu(merchant), U512::from(8_000_000_000u64));
+
+        let churn = contract.get_merchant_period_stats(merchant, period);
+        assert_eq!(churn.opening_subscribers, 3);
+        assert_eq!(churn.churned_subscribers, 1);
+        assert_eq!(contract.get_merchant_churn_bps(merchant, period), 3333);
+        assert_eq!(contract.get_plan_period_stats(monthly, first_period).new_subscribers, 2);
+
+        // Re-pricing updates MRR of existing subscribers
+        env.set_caller(merchant);
+        contract.update_plan(monthly, U512::from(20_000_000_000u64), U512::zero());
+        assert_eq!(contract.get_merchant_mrr(merchant), U512::from(26_000_000_000u64));
+    }
+
+    #[test]
+    fn test_paginated_queries() {
+        let env = odra_test::env();
+        let mut contract = SubscriptionManager::deploy(&env, NoArgs);
+        let user = env.get_account(0);
+
+        for i in 0..5u64 {
+            contract.create_plan(format!("Plan {}", i), U512::zero(), U512::zero(), Duration::from_days(30));
+        }
+        contract.deactivate_plan(2);
+        for plan_id in [1u64, 3, 4, 5] {
+            contract.subscribe(plan_id, true, 0);
+        }
+        contract.unsubscribe(1);
+
+        // Plans: two per page, full structs returned
+        let page = contract.get_merchant_plans_page(user, 0, 2, None);
+        assert_eq!(page.items.len(), 2);
+        assert_eq!(page.items[0].name, "Plan 0");
+        assert_eq!(page.next_cursor, Some(2));
+        let page = contract.get_merchant_plans_page(user, 4, 2, None);
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(page.next_cursor, None);
+
+        // Plans: status filter
+        let page = contract.get_merchant_plans_page(user, 0, 10, Some(false));
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(page.items[0].id, 2);
+
+        // Subscriptions: active only, then by plan
+        assert_eq!(contract.get_user_subscription_count(user), 4);
+        let filter = SubscriptionFilter { is_active: Some(true), ..Default::default() };
+        let page = contract.get_user_subscriptions_page(user, 0, 10, filter);
+        assert_eq!(page.items.len(), 3);
+        assert!(page.items.iter().all(|s| s.is_active));
+
+        let filter = SubscriptionFilter { plan_id: Some(4), ..Default::default() };
+        let page = contract.get_user_subscriptions_page(user, 0, 10, filter);
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(page.items[0].id, 3);
+    }
+
+    #[test]
+    fn test_migrate_legacy_subscription() {
+        let env = odra_test::env();
+        let merchant = env.get_account(1);
+        let subscriber = env.get_account(2);
+
+        // State on the legacy deployment
+        let mut legacy = LegacyManagerMock::deploy(&env, NoArgs);
+        env.set_caller(merchant);
+        // Legacy periods are in seconds
+        let legacy_plan_id = legacy.create_plan(U512::from(100_000_000_000u64), 2592000, "Pro Plan".to_string());
+        env.set_caller(subscriber);
+        legacy.with_tokens(U512::from(100_000_000_000u64)).subscribe(legacy_plan_id);
+        let expiry = legacy.get_expiry(subscriber, legacy_plan_id);
+
+        env.set_caller(env.get_account(0));
+        let mut contract = SubscriptionManager::deploy(&env, NoArgs);
+        contract.set_legacy_manager(legacy.address());
+
+        // Only an admin or the plan's merchant can import a plan
+        env.set_caller(subscriber);
+        assert_eq!(
+            contract.try_migrate_legacy_plan(legacy_plan_id),
+            Err(Error::NotAdminOrMerchant.into())
+        );
+
+        // Subscribers can import their own subscription, which imports the plan
+        let sub_id = contract.migrate_legacy_subscription(subscriber, legacy_plan_id);
+        let plan_id = contract.get_migrated_plan_id(legacy_plan_id).unwrap();
+        let plan = contract.get_plan(plan_id).unwrap();
+        assert_eq!(plan.merchant, merchant);
+        assert_eq!(plan.base_price, U512::from(100_000_000_000u64));
+        assert_eq!(plan.billing_cycle, Duration::from_days(30));
+
+        let subscription = contract.get_subscription(sub_id).unwrap();
+        assert_eq!(subscription.plan_id, plan_id);
+        assert_eq!(subscription.started_at, Timestamp::from_millis(expiry - 2592000));
+        assert_eq!(subscription.next_billing_at, subscription.started_at + Duration::from_days(30));
+        assert!(subscription.is_active);
+        assert_eq!(contract.get_merchant_stats(merchant).active_subscribers, 1);
+
+        // Re-importing is rejected; plan import is idempotent
+        assert_eq!(
+            contract.try_migrate_legacy_subscription(subscriber, legacy_plan_id),
+            Err(Error::LegacySubscriptionAlreadyMigrated.into())
+        );
+        env.set_caller(merchant);
+        assert_eq!(contract.migrate_legacy_plan(legacy_plan_id), plan_id);
     }
 }
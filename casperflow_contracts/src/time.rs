@@ -0,0 +1,190 @@
+//! Typed time values shared by all CasperFlow contracts.
+//!
+//! Casper block time (`env().get_block_time()`) is a Unix timestamp in
+//! **milliseconds**. Every stored timestamp and duration is kept in
+//! milliseconds behind [`Timestamp`] and [`Duration`], so a 30-day billing
+//! cycle is written `Duration::from_days(30)` rather than a bare number whose
+//! unit the caller has to guess.
+
+use core::ops::{Add, AddAssign, Div, Mul, Sub};
+
+use odra::prelude::*;
+use odra::ContractEnv;
+
+const MILLIS_PER_SECOND: u64 = 1_000;
+const SECONDS_PER_DAY: u64 = 86_400;
+
+/// One second
+pub const SECOND: Duration = Duration::from_secs(1);
+/// One hour
+pub const HOUR: Duration = Duration::from_secs(3_600);
+/// One day
+pub const DAY: Duration = Duration::from_days(1);
+/// One 365-day year, the basis for APY calculations
+pub const YEAR: Duration = Duration::from_days(365);
+
+/// Point in time, in milliseconds since the Unix epoch
+#[odra::odra_type]
+#[derive(Copy, Default, PartialOrd, Ord)]
+pub struct Timestamp {
+    millis: u64,
+}
+
+impl Timestamp {
+    /// The Unix epoch
+    pub const ZERO: Timestamp = Timestamp { millis: 0 };
+
+    /// Timestamp from milliseconds since the epoch
+    pub const fn from_millis(millis: u64) -> Self {
+        Self { millis }
+    }
+
+    /// Timestamp from seconds since the epoch
+    pub const fn from_secs(secs: u64) -> Self {
+        Self::from_millis(secs * MILLIS_PER_SECOND)
+    }
+
+    /// Current block time
+    pub fn now(env: &ContractEnv) -> Self {
+        Self::from_millis(env.get_block_time())
+    }
+
+    /// Milliseconds since the epoch
+    pub const fn as_millis(&self) -> u64 {
+        self.millis
+    }
+
+    /// Whole seconds since the epoch
+    pub const fn as_secs(&self) -> u64 {
+        self.millis / MILLIS_PER_SECOND
+    }
+
+    /// Time elapsed since `earlier`, or zero if `earlier` is later than `self`
+    pub fn duration_since(&self, earlier: Timestamp) -> Duration {
+        Duration::from_millis(self.millis.saturating_sub(earlier.millis))
+    }
+
+    /// `self - duration`, clamped at the epoch
+    pub fn saturating_sub(&self, duration: Duration) -> Timestamp {
+        Timestamp::from_millis(self.millis.saturating_sub(duration.millis))
+    }
+}
+
+/// Span of time, in milliseconds
+#[odra::odra_type]
+#[derive(Copy, Default, PartialOrd, Ord)]
+pub struct Duration {
+    millis: u64,
+}
+
+impl Duration {
+    /// Zero-length duration
+    pub const ZERO: Duration = Duration { millis: 0 };
+
+    /// Duration from milliseconds
+    pub const fn from_millis(millis: u64) -> Self {
+        Self { millis }
+    }
+
+    /// Duration from seconds
+    pub const fn from_secs(secs: u64) -> Self {
+        Self::from_millis(secs * MILLIS_PER_SECOND)
+    }
+
+    /// Duration from hours
+    pub const fn from_hours(hours: u64) -> Self {
+        Self::from_secs(hours * 3_600)
+    }
+
+    /// Duration from days
+    pub const fn from_days(days: u64) -> Self {
+        Self::from_secs(days * SECONDS_PER_DAY)
+    }
+
+    /// Length in milliseconds
+    pub const fn as_millis(&self) -> u64 {
+        self.millis
+    }
+
+    /// Length in whole seconds
+    pub const fn as_secs(&self) -> u64 {
+        self.millis / MILLIS_PER_SECOND
+    }
+
+    /// Whether the duration is zero
+    pub const fn is_zero(&self) -> bool {
+        self.millis == 0
+    }
+}
+
+impl Add<Duration> for Timestamp {
+    type Output = Timestamp;
+
+    fn add(self, rhs: Duration) -> Timestamp {
+        Timestamp::from_millis(self.millis + rhs.millis)
+    }
+}
+
+impl AddAssign<Duration> for Timestamp {
+    fn add_assign(&mut self, rhs: Duration) {
+        self.millis += rhs.millis;
+    }
+}
+
+impl Sub<Duration> for Timestamp {
+    type Output = Timestamp;
+
+    fn sub(self, rhs: Duration) -> Timestamp {
+        Timestamp::from_millis(self.millis - rhs.millis)
+    }
+}
+
+impl Add for Duration {
+    type Output = Duration;
+
+    fn add(self, rhs: Duration) -> Duration {
+        Duration::from_millis(self.millis + rhs.millis)
+    }
+}
+
+impl Mul<u64> for Duration {
+    type Output = Duration;
+
+    fn mul(self, rhs: u64) -> Duration {
+        Duration::from_millis(self.millis * rhs)
+    }
+}
+
+/// Number of whole `rhs` spans that fit in `self`
+impl Div for Duration {
+    type Output = u64;
+
+    fn div(self, rhs: Duration) -> u64 {
+        self.millis / rhs.millis
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_units_are_milliseconds() {
+        assert_eq!(SECOND.as_millis(), 1_000);
+        assert_eq!(Duration::from_days(30).as_millis(), 2_592_000_000);
+        assert_eq!(YEAR.as_secs(), 31_536_000);
+        assert_eq!(Timestamp::from_secs(2).as_millis(), 2_000);
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let start = Timestamp::from_millis(1_000);
+        let end = start + DAY;
+        assert_eq!(end.duration_since(start), DAY);
+        assert_eq!(start.duration_since(end), Duration::ZERO);
+        assert_eq!(end - DAY, start);
+        assert_eq!(start.saturating_sub(DAY), Timestamp::ZERO);
+        assert_eq!((DAY * 3) / DAY, 3);
+        assert_eq!(DAY / HOUR, 24);
+    }
+}
@@ -0,0 +1,259 @@
+//! Timelocked changes to protocol parameters.
+//!
+//! [`Timelock`] is composed into each contract as a submodule. Fee, yield and
+//! contract-address changes are queued with a delay instead of applied
+//! directly, so merchants and stakers see them coming (via events and
+//! `get_pending_change`) and can react before they take effect. The parent
+//! contract authorizes queueing and applies a change once [`Timelock::take_ready`]
+//! releases it. Contract addresses are set immediately the first time so a
+//! fresh deployment can be wired up; only replacing one is timelocked.
+
+use odra::prelude::*;
+
+use crate::time::{Duration, Timestamp, HOUR};
+
+/// Delay applied to queued changes until one is configured
+pub const DEFAULT_TIMELOCK_DELAY: Duration = Duration::from_days(2);
+/// Shortest delay that can be configured
+pub const MIN_TIMELOCK_DELAY: Duration = HOUR;
+/// Longest delay that can be configured
+pub const MAX_TIMELOCK_DELAY: Duration = Duration::from_days(30);
+
+/// Protocol parameter that changes through the timelock
+#[odra::odra_type]
+#[derive(Copy)]
+pub enum Param {
+    /// `BillingEngine` protocol fee, in basis points
+    ProtocolFeeBps,
+    /// `BillingEngine` protocol fee recipient
+    FeeRecipient,
+    /// `StakeToPay` APY, in basis points
+    ApyBps,
+    /// SubscriptionManager address
+    SubscriptionManager,
+    /// UsageMeter address
+    UsageMeter,
+    /// BillingEngine address
+    BillingEngine,
+    /// StakeToPay address
+    StakeToPay,
+    /// Legacy SubscriptionManager address
+    LegacyManager,
+    /// The timelock delay itself, in milliseconds
+    TimelockDelay,
+    /// Protocol Registry address
+    Registry,
+    /// `UsageMeter` payment channel challenge period, in milliseconds
+    ChallengePeriod,
+    /// `SubscriptionManager` payment grace, in milliseconds
+    PaymentGrace,
+    /// `UsageMeter` late-usage window, in milliseconds
+    LateUsageWindow,
+}
+
+/// Lifecycle of a queued change
+#[odra::odra_type]
+#[derive(Copy)]
+pub enum ChangeStatus {
+    Queued,
+    Executed,
+    Cancelled,
+}
+
+/// Parameter change waiting for its delay to pass
+#[odra::odra_type]
+pub struct QueuedChange {
+    /// Unique change ID
+    pub id: u64,
+    /// Parameter being changed
+    pub param: Param,
+    /// New value of a numeric parameter
+    pub amount: u64,
+    /// New value of an address parameter
+    pub address: Option<Address>,
+    /// Account that queued the change
+    pub queued_by: Address,
+    /// When the change was queued
+    pub queued_at: Timestamp,
+    /// Earliest time the change can be executed
+    pub eta: Timestamp,
+    /// Status
+    pub status: ChangeStatus,
+}
+
+/// Errors reverted by the timelock (codes 700-799)
+#[odra::odra_error]
+pub enum Error {
+    /// Change does not exist
+    ChangeNotFound = 700,
+    /// Change was already executed or cancelled
+    ChangeNotQueued = 701,
+    /// Change's delay has not passed yet
+    TimelockNotExpired = 702,
+    /// Parameter does not belong to this contract
+    UnsupportedParam = 703,
+    /// Delay outside `MIN_TIMELOCK_DELAY..=MAX_TIMELOCK_DELAY`
+    InvalidDelay = 704,
+}
+
+/// Events
+pub mod events {
+    use super::*;
+
+    #[odra::event]
+    pub struct ChangeQueued {
+        pub change_id: u64,
+        pub param: Param,
+        pub amount: u64,
+        pub address: Option<Address>,
+        pub eta: Timestamp,
+    }
+
+    #[odra::event]
+    pub struct ChangeCancelled {
+        pub change_id: u64,
+        pub param: Param,
+    }
+
+    #[odra::event]
+    pub struct ChangeExecuted {
+        pub change_id: u64,
+        pub param: Param,
+    }
+}
+
+/// Queue of delayed parameter changes
+#[odra::module(events = [
+    events::ChangeQueued,
+    events::ChangeCancelled,
+    events::ChangeExecuted
+], errors = Error)]
+pub struct Timelock {
+    /// Configured delay, `DEFAULT_TIMELOCK_DELAY` if unset
+    delay: Var<Duration>,
+    /// Counter for change IDs
+    change_counter: Var<u64>,
+    /// Change ID -> change
+    changes: Mapping<u64, QueuedChange>,
+    /// Param -> ID of its queued change, if any
+    pending: Mapping<Param, u64>,
+}
+
+#[odra::module]
+impl Timelock {
+    /// Get a change by ID
+    pub fn get_queued_change(&self, change_id: u64) -> Option<QueuedChange> {
+        self.changes.get(&change_id)
+    }
+
+    /// Get the change queued for `param`, if one is waiting
+    pub fn get_pending_change(&self, param: Param) -> Option<QueuedChange> {
+        self.pending
+            .get(&param)
+            .and_then(|id| self.changes.get(&id))
+            .filter(|change| change.status == ChangeStatus::Queued)
+    }
+
+    /// Get the delay applied to newly queued changes
+    pub fn get_timelock_delay(&self) -> Duration {
+        self.delay.get_or_else(|| DEFAULT_TIMELOCK_DELAY)
+    }
+}
+
+impl Timelock {
+    /// Queue a new value for a numeric parameter
+    pub fn queue_amount(&mut self, param: Param, amount: u64) -> u64 {
+        self.queue(param, amount, None)
+    }
+
+    /// Queue a new value for an address parameter
+    pub fn queue_address(&mut self, param: Param, address: Address) -> u64 {
+        self.queue(param, 0, Some(address))
+    }
+
+    /// Queue a new timelock delay
+    pub fn queue_delay(&mut self, delay: Duration) -> u64 {
+        if delay < MIN_TIMELOCK_DELAY || delay > MAX_TIMELOCK_DELAY {
+            self.env().revert(Error::InvalidDelay);
+        }
+        self.queue(Param::TimelockDelay, delay.as_millis(), None)
+    }
+
+    /// Cancel a queued change
+    pub fn cancel(&mut self, change_id: u64) {
+        let mut change = self.load_queued(change_id);
+        change.status = ChangeStatus::Cancelled;
+        self.changes.set(&change_id, change.clone());
+
+        self.env().emit_event(events::ChangeCancelled {
+            change_id,
+            param: change.param,
+        });
+    }
+
+    /// Mark a change executed once its delay has passed and return it for
+    /// the parent to apply. A queued delay change is applied here.
+    pub fn take_ready(&mut self, change_id: u64) -> QueuedChange {
+        let mut change = self.load_queued(change_id);
+        if Timestamp::now(&self.env()) < change.eta {
+            self.env().revert(Error::TimelockNotExpired);
+        }
+
+        change.status = ChangeStatus::Executed;
+        self.changes.set(&change_id, change.clone());
+        if change.param == Param::TimelockDelay {
+            self.delay.set(Duration::from_millis(change.amount));
+        }
+
+        self.env().emit_event(events::ChangeExecuted {
+            change_id,
+            param: change.param,
+        });
+        change
+    }
+
+    fn queue(&mut self, param: Param, amount: u64, address: Option<Address>) -> u64 {
+        // A newer change for the same parameter supersedes the pending one
+        if let Some(previous) = self.get_pending_change(param) {
+            self.cancel(previous.id);
+        }
+
+        let change_id = self.change_counter.get_or_default() + 1;
+        self.change_counter.set(change_id);
+
+        let now = Timestamp::now(&self.env());
+        let eta = now + self.get_timelock_delay();
+        self.changes.set(&change_id, QueuedChange {
+            id: change_id,
+            param,
+            amount,
+            address,
+            queued_by: self.env().caller(),
+            queued_at: now,
+            eta,
+            status: ChangeStatus::Queued,
+        });
+        self.pending.set(&param, change_id);
+
+        self.env().emit_event(events::ChangeQueued {
+            change_id,
+            param,
+            amount,
+            address,
+            eta,
+        });
+        change_id
+    }
+
+    fn load_queued(&self, change_id: u64) -> QueuedChange {
+        let change = self
+            .changes
+            .get(&change_id)
+            .unwrap_or_revert_with(&self.env(), Error::ChangeNotFound);
+        if change.status != ChangeStatus::Queued {
+            self.env().revert(Error::ChangeNotQueued);
+        }
+        change
+    }
+}
+
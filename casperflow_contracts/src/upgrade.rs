@@ -0,0 +1,274 @@
+//! Schema versioning and record migration for contract upgrades.
+//!
+//! Contracts are installed as upgradable packages. When a new version is
+//! installed, Odra calls the contract's `upgrade` entry point, which records
+//! the new [`SCHEMA_VERSION`] in the [`Schema`] submodule and snapshots how
+//! many records of each [`RecordKind`] exist. Keepers then call the
+//! contract's `migrate_*` entry points, which convert those records in
+//! bounded batches through [`Migrate`].
+//!
+//! Odra keys storage by field position, so a record type that gains a field
+//! keeps its old mapping in place, typed with a frozen copy of the old layout
+//! (the type's [`Migrate::Previous`]), and adds a mapping for the new layout
+//! at the end of the contract struct. Migration reads each record from the
+//! old mapping and writes the converted record to the new one; until a record
+//! is migrated, getters convert it from the old mapping on read. Record types
+//! still on their first layout use themselves as `Previous` and their one
+//! mapping as both.
+
+use core::ops::Range;
+
+use odra::prelude::*;
+
+use crate::pagination::MAX_SCAN;
+
+/// Layout version of the records written by this build
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Record type with a migration entry point
+#[odra::odra_type]
+#[derive(Copy)]
+pub enum RecordKind {
+    Plan,
+    Subscription,
+    Invoice,
+    StakeConfig,
+}
+
+/// Progress of a record migration since the last upgrade
+#[odra::odra_type]
+#[derive(Copy, Default)]
+pub struct MigrationProgress {
+    /// Schema version the records were written under
+    pub from_version: u32,
+    /// Records migrated so far
+    pub migrated: u64,
+    /// Records that existed when the upgrade was installed
+    pub total: u64,
+}
+
+/// Record written under an older schema that can be brought up to date
+pub trait Migrate: Sized {
+    /// Layout records of this type were stored in under the previous schema
+    type Previous;
+
+    /// Convert a record written under `from_version` in the previous layout
+    /// for [`SCHEMA_VERSION`]
+    fn migrate(previous: Self::Previous, from_version: u32) -> Self;
+}
+
+/// Errors reverted by upgrades (codes 800-899)
+#[odra::odra_error]
+pub enum Error {
+    /// Installed code is older than the stored schema
+    SchemaDowngrade = 800,
+}
+
+/// Events
+pub mod events {
+    use super::*;
+
+    #[odra::event]
+    pub struct SchemaUpgraded {
+        pub from_version: u32,
+        pub to_version: u32,
+    }
+
+    #[odra::event]
+    pub struct RecordsMigrated {
+        pub kind: RecordKind,
+        pub migrated: u64,
+        pub total: u64,
+    }
+}
+
+/// Stored schema version and migration cursors
+#[odra::module(events = [
+    events::SchemaUpgraded,
+    events::RecordsMigrated
+], errors = Error)]
+pub struct Schema {
+    /// Schema version of the installed code; 0 for unversioned deployments
+    version: Var<u32>,
+    /// Kind -> migration progress since the last upgrade
+    progress: Mapping<RecordKind, MigrationProgress>,
+}
+
+#[odra::module]
+impl Schema {
+    /// Get the stored schema version
+    pub fn schema_version(&self) -> u32 {
+        self.version.get_or_default()
+    }
+
+    /// Get the migration progress of a record kind
+    pub fn get_migration_progress(&self, kind: RecordKind) -> MigrationProgress {
+        self.progress.get(&kind).unwrap_or_default()
+    }
+}
+
+impl Schema {
+    /// Mark a fresh deployment as current
+    pub fn init(&mut self) {
+        self.version.set(SCHEMA_VERSION);
+    }
+
+    /// Record an upgrade to [`SCHEMA_VERSION`], given the number of existing
+    /// records of each kind the contract stores
+    pub fn upgrade(&mut self, totals: &[(RecordKind, u64)]) {
+        let from_version = self.schema_version();
+        if from_version > SCHEMA_VERSION {
+            self.env().revert(Error::SchemaDowngrade);
+        }
+
+        for &(kind, total) in totals {
+            self.progress.set(&kind, MigrationProgress {
+                from_version,
+                migrated: 0,
+                total,
+            });
+        }
+        self.version.set(SCHEMA_VERSION);
+
+        self.env().emit_event(events::SchemaUpgraded {
+            from_version,
+            to_version: SCHEMA_VERSION,
+        });
+    }
+
+    /// Claim the next batch of at most `limit` record positions (0-based) to
+    /// migrate, with the schema version they were written under
+    pub fn next_batch(&mut self, kind: RecordKind, limit: u32) -> (u32, Range<u64>) {
+        let mut progress = self.get_migration_progress(kind);
+        let start = progress.migrated;
+        let end = progress.total.min(start + limit.min(MAX_SCAN) as u64);
+        if start == end {
+            return (progress.from_version, start..end);
+        }
+
+        progress.migrated = end;
+        self.progress.set(&kind, progress);
+        self.env().emit_event(events::RecordsMigrated {
+            kind,
+            migrated: progress.migrated,
+            total: progress.total,
+        });
+        (progress.from_version, start..end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use odra::host::{Deployer, HostRef, InstallConfig, NoArgs};
+
+    /// Record as first written
+    #[odra::odra_type]
+    pub struct ItemV1 {
+        pub name: String,
+    }
+
+    /// Record after gaining a field
+    #[odra::odra_type]
+    pub struct Item {
+        pub name: String,
+        pub quantity: u64,
+    }
+
+    impl Migrate for Item {
+        type Previous = ItemV1;
+
+        fn migrate(previous: ItemV1, _from_version: u32) -> Self {
+            Item {
+                name: previous.name,
+                quantity: 1,
+            }
+        }
+    }
+
+    #[odra::module]
+    struct Store {
+        schema: SubModule<Schema>,
+        count: Var<u64>,
+        items: Mapping<u64, ItemV1>,
+    }
+
+    #[odra::module]
+    impl Store {
+        pub fn init(&mut self) {
+            self.schema.init();
+        }
+
+        pub fn add(&mut self, name: String) {
+            let id = self.count.get_or_default() + 1;
+            self.count.set(id);
+            self.items.set(&id, ItemV1 { name });
+        }
+    }
+
+    /// `Store` after `Item` gained a field
+    #[odra::module]
+    struct StoreV2 {
+        schema: SubModule<Schema>,
+        count: Var<u64>,
+        /// Same position and layout as `Store::items`
+        items_v1: Mapping<u64, ItemV1>,
+        items: Mapping<u64, Item>,
+    }
+
+    #[odra::module]
+    impl StoreV2 {
+        pub fn init(&mut self) {
+            self.schema.init();
+        }
+
+        pub fn upgrade(&mut self) {
+            self.schema.upgrade(&[(RecordKind::Plan, self.count.get_or_default())]);
+        }
+
+        pub fn migrate_items(&mut self, limit: u32) -> u32 {
+            let (from_version, positions) = self.schema.next_batch(RecordKind::Plan, limit);
+            for id in positions.clone().map(|position| position + 1) {
+                if let Some(previous) = self.items_v1.get(&id) {
+                    self.items.set(&id, Item::migrate(previous, from_version));
+                }
+            }
+            (positions.end - positions.start) as u32
+        }
+
+        pub fn get_item(&self, id: u64) -> Option<Item> {
+            let from_version = self.schema.get_migration_progress(RecordKind::Plan).from_version;
+            self.items
+                .get(&id)
+                .or_else(|| self.items_v1.get(&id).map(|previous| Item::migrate(previous, from_version)))
+        }
+
+        pub fn is_migrated(&self, id: u64) -> bool {
+            self.items.get(&id).is_some()
+        }
+    }
+
+    #[test]
+    fn test_migrate_records_to_new_layout() {
+        let env = odra_test::env();
+        let mut store = Store::deploy_with_cfg(&env, NoArgs, InstallConfig::upgradable::<Store>());
+        for name in ["a", "b", "c"] {
+            store.add(name.to_string());
+        }
+
+        let mut store = StoreV2::try_upgrade(&env, store.address(), NoArgs).unwrap();
+        assert_eq!(store.migrate_items(2), 2);
+        assert!(store.is_migrated(2));
+        assert!(!store.is_migrated(3));
+
+        // Unmigrated records are converted on read
+        let expected = |name: &str| Some(Item { name: name.to_string(), quantity: 1 });
+        assert_eq!(store.get_item(2), expected("b"));
+        assert_eq!(store.get_item(3), expected("c"));
+
+        assert_eq!(store.migrate_items(2), 1);
+        assert_eq!(store.migrate_items(2), 0);
+        assert!(store.is_migrated(3));
+        assert_eq!(store.get_item(3), expected("c"));
+    }
+}
@@ -0,0 +1,2266 @@
+//! Usage Meter Contract
+//!
+//! Tracks and records usage metrics for usage-based billing.
+//! 
+//! Key features:
+//! - Record API calls, compute units, storage, or custom metrics
+//! - Aggregates usage per billing cycle, anchored to the subscription's start
+//! - Routes late usage into the period it happened in (within a grace window)
+//! - Merchant-initiated adjustments (credits/debits) against posted records
+//! - Prepaid credit for pay-as-you-go plans, debited as usage is recorded
+//! - Payment channels settling signed off-chain vouchers into usage records
+//! - Integrates with BillingEngine for cost calculation
+
+use odra::prelude::*;
+use odra::casper_types::bytesrepr::{Bytes, ToBytes};
+use odra::casper_types::{PublicKey, U512};
+
+use crate::access::{AccessControl, Role};
+use crate::billing_engine::BillingEngineContractRef;
+use crate::pagination::{self, DateRange};
+use crate::pausable::{PauseGroup, Pausable};
+use crate::registry::{Component, RegistryContractRef};
+use crate::stake_to_pay::StakeToPayContractRef;
+use crate::subscription_manager::SubscriptionManagerContractRef;
+use crate::time::{Duration, Timestamp, DAY, HOUR};
+use crate::timelock::{Error as TimelockError, Param, QueuedChange, Timelock};
+use crate::upgrade::{MigrationProgress, RecordKind, Schema};
+
+/// Default grace window after a period ends during which late usage is still accepted
+pub const DEFAULT_LATE_USAGE_WINDOW: Duration = HOUR;
+
+/// Default time a merchant has to close a channel after the subscriber asks to
+pub const DEFAULT_CHALLENGE_PERIOD: Duration = DAY;
+/// Shortest challenge period that can be configured
+pub const MIN_CHALLENGE_PERIOD: Duration = HOUR;
+/// Longest challenge period that can be configured
+pub const MAX_CHALLENGE_PERIOD: Duration = Duration::from_days(30);
+
+/// Prefix of every voucher message, so channel signatures can't be replayed
+/// as anything else
+pub const VOUCHER_DOMAIN: &[u8] = b"casperflow-voucher-v1";
+
+/// Message a subscriber signs to pay for `units` over a payment channel
+///
+/// Vouchers are cumulative: each covers every unit since the channel opened,
+/// so the merchant only keeps the latest one. The UsageMeter address binds
+/// the voucher to one deployment.
+pub fn voucher_message(usage_meter: Address, channel_id: u64, units: u64) -> Bytes {
+    let mut message = VOUCHER_DOMAIN.to_vec();
+    message.extend(usage_meter.to_bytes().unwrap_or_default());
+    message.extend(channel_id.to_le_bytes());
+    message.extend(units.to_le_bytes());
+    Bytes::from(message)
+}
+
+/// Usage record for a specific metric
+#[odra::odra_type]
+pub struct UsageRecord {
+    /// Unique record ID
+    pub id: u64,
+    /// Subscription this usage belongs to
+    pub subscription_id: u64,
+    /// Type of metric (e.g., "api_calls", "storage_gb", "compute_units")
+    pub metric: String,
+    /// Number of units used
+    pub units: u64,
+    /// Timestamp of the record
+    pub recorded_at: Timestamp,
+    /// Who recorded this (merchant's backend)
+    pub recorded_by: Address,
+    /// Start of the billing period this record was counted in
+    pub period_start: Timestamp,
+}
+
+/// Reason codes attached to usage adjustments
+pub mod reason_codes {
+    /// Unspecified reason
+    pub const OTHER: u8 = 0;
+    /// The original record was posted more than once
+    pub const DUPLICATE: u8 = 1;
+    /// Metering fault on the merchant's side
+    pub const METER_ERROR: u8 = 2;
+    /// Usage was under-reported and is being topped up
+    pub const UNDER_REPORTED: u8 = 3;
+    /// Goodwill credit granted by the merchant
+    pub const GOODWILL: u8 = 4;
+    /// Resolution of a customer dispute
+    pub const DISPUTE: u8 = 5;
+}
+
+/// Correction to a previously posted usage record
+#[odra::odra_type]
+pub struct UsageAdjustment {
+    /// Unique adjustment ID
+    pub id: u64,
+    /// Usage record being corrected
+    pub original_record_id: u64,
+    /// Subscription the original record belongs to
+    pub subscription_id: u64,
+    /// Number of units added or removed
+    pub units: u64,
+    /// True if units are removed (credit to the subscriber)
+    pub is_credit: bool,
+    /// Reason code (see [`reason_codes`])
+    pub reason_code: u8,
+    /// Timestamp of the adjustment
+    pub created_at: Timestamp,
+    /// Who posted the adjustment (merchant's backend)
+    pub created_by: Address,
+    /// Whether the original period was already billed, so the adjustment
+    /// is carried to the next invoice instead of the period total
+    pub carried_forward: bool,
+}
+
+/// Adjustments for billed periods not yet settled on an invoice
+#[odra::odra_type]
+#[derive(Default)]
+pub struct PendingAdjustments {
+    /// Units to charge on the next invoice
+    pub debit_units: u64,
+    /// Units to credit on the next invoice
+    pub credit_units: u64,
+}
+
+/// Aggregated usage for a billing period
+#[odra::odra_type]
+pub struct BillingPeriodUsage {
+    /// Subscription ID
+    pub subscription_id: u64,
+    /// Start of billing period
+    pub period_start: Timestamp,
+    /// End of billing period
+    pub period_end: Timestamp,
+    /// Total units used in this period
+    pub total_units: u64,
+    /// Whether this period has been billed
+    pub is_billed: bool,
+}
+
+/// Filter for usage record queries; `None` fields match everything
+#[odra::odra_type]
+#[derive(Default)]
+pub struct UsageRecordFilter {
+    /// Only records of this metric
+    pub metric: Option<String>,
+    /// Only records whose usage timestamp falls within this range
+    pub recorded: DateRange,
+}
+
+impl UsageRecordFilter {
+    fn matches(&self, record: &UsageRecord) -> bool {
+        self.metric.as_ref().map_or(true, |metric| record.metric == *metric)
+            && self.recorded.contains(record.recorded_at)
+    }
+}
+
+/// Page of usage records returned by paginated queries
+#[odra::odra_type]
+pub struct UsageRecordPage {
+    pub items: Vec<UsageRecord>,
+    /// Cursor to pass to fetch the next page, `None` when exhausted
+    pub next_cursor: Option<u32>,
+}
+
+/// Page of usage adjustments returned by paginated queries
+#[odra::odra_type]
+pub struct UsageAdjustmentPage {
+    pub items: Vec<UsageAdjustment>,
+    /// Cursor to pass to fetch the next page, `None` when exhausted
+    pub next_cursor: Option<u32>,
+}
+
+/// Prepaid credit charged for a usage record
+#[odra::odra_type]
+pub struct CreditCharge {
+    /// Subscriber whose credit paid for the record
+    pub subscriber: Address,
+    /// Merchant credited with the charge
+    pub merchant: Address,
+    /// Plan usage price at the time of recording
+    pub unit_price: U512,
+}
+
+/// Unidirectional payment channel from a subscriber to a plan's merchant
+///
+/// The subscriber locks a deposit and signs cumulative vouchers off-chain
+/// (see [`voucher_message`]). The merchant closes the channel with the latest
+/// voucher; if it doesn't, the subscriber reclaims the deposit at `closes_at`.
+#[odra::odra_type]
+pub struct PaymentChannel {
+    /// Unique channel ID
+    pub id: u64,
+    /// Subscription the settled usage is recorded against
+    pub subscription_id: u64,
+    /// Plan of the subscription
+    pub plan_id: u64,
+    /// Subscriber paying through the channel
+    pub subscriber: Address,
+    /// Key the subscriber signs vouchers with
+    pub subscriber_key: PublicKey,
+    /// Merchant paid when the channel closes
+    pub merchant: Address,
+    /// Metric the settled units are recorded under
+    pub metric: String,
+    /// Plan usage price when the channel opened
+    pub unit_price: U512,
+    /// Total locked by the subscriber
+    pub deposit: U512,
+    /// When the channel opened
+    pub opened_at: Timestamp,
+    /// When the subscriber may reclaim the deposit, unless the merchant closed first
+    pub closes_at: Timestamp,
+    /// Usage record of the settled voucher, once closed by the merchant
+    pub record_id: Option<u64>,
+    /// Whether the channel was closed or reclaimed
+    pub is_closed: bool,
+}
+
+/// Billing-cycle schedule of a subscription, cached from SubscriptionManager
+#[odra::odra_type]
+pub struct PeriodSchedule {
+    /// Plan the subscription belongs to
+    pub plan_id: u64,
+    /// Subscription start; period boundaries are `anchor + k * cycle`
+    pub anchor: Timestamp,
+    /// Billing cycle length
+    pub cycle: Duration,
+}
+
+impl PeriodSchedule {
+    /// Start of the period containing `timestamp`
+    pub fn period_start_for(&self, timestamp: Timestamp) -> Timestamp {
+        self.anchor + self.cycle * (timestamp.duration_since(self.anchor) / self.cycle)
+    }
+}
+
+/// Errors reverted by the contract (codes 200-299)
+#[odra::odra_error]
+pub enum Error {
+    /// Caller is not authorized to record usage for the plan
+    NotAuthorized = 201,
+    /// Caller is not the BillingEngine
+    NotBillingEngine = 202,
+    /// SubscriptionManager address has not been configured
+    SubscriptionManagerNotSet = 203,
+    /// Subscription does not exist
+    SubscriptionNotFound = 204,
+    /// Plan does not exist
+    PlanNotFound = 205,
+    /// Subscription is not on the given plan
+    PlanMismatch = 206,
+    /// Plan has a zero-length billing cycle
+    NoBillingCycle = 207,
+    /// Batch arrays differ in length
+    LengthMismatch = 208,
+    /// Usage timestamp is after the current block time
+    TimestampInFuture = 209,
+    /// Usage timestamp is before the subscription started
+    TimestampBeforeStart = 210,
+    /// Late-usage window for the period has passed
+    LateWindowClosed = 211,
+    /// Late-usage window for the period is still open
+    LateWindowOpen = 212,
+    /// Period has already been billed
+    PeriodAlreadyBilled = 213,
+    /// Timestamp is not a billing-period boundary
+    NotPeriodBoundary = 214,
+    /// Usage record does not exist
+    RecordNotFound = 215,
+    /// Adjustment has zero units
+    ZeroAdjustment = 216,
+    /// Credit would exceed the record's units
+    CreditExceedsUsage = 217,
+    /// Prepaid credit does not cover the usage
+    InsufficientCredit = 218,
+    /// Deposit has no attached value
+    ZeroDeposit = 219,
+    /// Caller is not the SubscriptionManager
+    NotSubscriptionManager = 220,
+    /// Caller is neither an admin nor the plan's merchant
+    NotAdminOrMerchant = 221,
+    /// Merchant has no credit earnings to withdraw or refund from
+    InsufficientEarnings = 222,
+    /// BillingEngine address has not been configured
+    BillingEngineNotSet = 223,
+    /// No payment channel with the ID
+    ChannelNotFound = 224,
+    /// Channel was already closed or reclaimed
+    ChannelClosed = 225,
+    /// Caller is not the channel's subscriber
+    NotChannelSubscriber = 226,
+    /// Caller is neither the channel's merchant nor an authorized recorder
+    NotChannelMerchant = 227,
+    /// Voucher signature does not match the subscriber's key
+    InvalidVoucher = 228,
+    /// Voucher pays more than the channel's deposit
+    VoucherExceedsDeposit = 229,
+    /// Deposit can't be reclaimed before the channel's `closes_at`
+    ChannelStillOpen = 230,
+    /// Channel timeout is shorter than the challenge period
+    InvalidTimeout = 231,
+    /// Public key does not belong to the caller
+    KeyMismatch = 232,
+    /// Record was settled by a payment channel voucher and can't be adjusted
+    SettledByChannel = 233,
+    /// Subscription is cancelled
+    SubscriptionInactive = 234,
+    /// Challenge period outside `MIN_CHALLENGE_PERIOD..=MAX_CHALLENGE_PERIOD`
+    InvalidChallengePeriod = 235,
+}
+
+/// Events
+pub mod events {
+    use super::*;
+
+    #[odra::event]
+    pub struct UsageRecorded {
+        pub subscription_id: u64,
+        pub metric: String,
+        pub units: u64,
+        pub timestamp: Timestamp,
+    }
+
+    #[odra::event]
+    pub struct UsageAdjusted {
+        pub adjustment_id: u64,
+        pub original_record_id: u64,
+        pub subscription_id: u64,
+        pub units: u64,
+        pub is_credit: bool,
+        pub reason_code: u8,
+    }
+
+    #[odra::event]
+    pub struct AdjustmentsSettled {
+        pub subscription_id: u64,
+        pub debit_units: u64,
+        pub credit_units: u64,
+    }
+
+    #[odra::event]
+    pub struct PeriodRolledOver {
+        pub subscription_id: u64,
+        pub previous_period_start: Timestamp,
+        pub period_start: Timestamp,
+    }
+
+    #[odra::event]
+    pub struct PeriodClosed {
+        pub subscription_id: u64,
+        pub period_start: Timestamp,
+        pub period_end: Timestamp,
+        pub total_units: u64,
+    }
+
+    #[odra::event]
+    pub struct PrepaidPlanChanged {
+        pub plan_id: u64,
+        pub prepaid: bool,
+    }
+
+    /// Also emitted for auto top-ups, with `from_rewards` set
+    #[odra::event]
+    pub struct CreditDeposited {
+        pub subscriber: Address,
+        pub merchant: Address,
+        pub amount: U512,
+        pub from_rewards: bool,
+        pub balance: U512,
+    }
+
+    /// Negative moves (adjustment credits) are emitted as `CreditRefunded`
+    #[odra::event]
+    pub struct CreditDebited {
+        pub subscriber: Address,
+        pub merchant: Address,
+        pub record_id: u64,
+        pub amount: U512,
+        pub balance: U512,
+    }
+
+    #[odra::event]
+    pub struct CreditRefunded {
+        pub subscriber: Address,
+        pub merchant: Address,
+        pub amount: U512,
+        pub balance: U512,
+    }
+
+    #[odra::event]
+    pub struct CreditEarningsWithdrawn {
+        pub merchant: Address,
+        pub amount: U512,
+        pub protocol_fee: U512,
+    }
+
+    #[odra::event]
+    pub struct ChannelOpened {
+        pub channel_id: u64,
+        pub subscription_id: u64,
+        pub subscriber: Address,
+        pub merchant: Address,
+        pub deposit: U512,
+        pub closes_at: Timestamp,
+    }
+
+    #[odra::event]
+    pub struct ChannelToppedUp {
+        pub channel_id: u64,
+        pub amount: U512,
+        pub deposit: U512,
+    }
+
+    #[odra::event]
+    pub struct ChannelCloseRequested {
+        pub channel_id: u64,
+        pub closes_at: Timestamp,
+    }
+
+    #[odra::event]
+    pub struct ChannelClosed {
+        pub channel_id: u64,
+        pub units: u64,
+        pub record_id: Option<u64>,
+        pub merchant_amount: U512,
+        pub protocol_fee: U512,
+        pub refunded: U512,
+    }
+
+    #[odra::event]
+    pub struct ChannelReclaimed {
+        pub channel_id: u64,
+        pub refunded: U512,
+    }
+}
+
+/// Usage Meter Contract
+#[odra::module(events = [
+    events::UsageRecorded,
+    events::UsageAdjusted,
+    events::AdjustmentsSettled,
+    events::PeriodRolledOver,
+    events::PeriodClosed,
+    events::PrepaidPlanChanged,
+    events::CreditDeposited,
+    events::CreditDebited,
+    events::CreditRefunded,
+    events::CreditEarningsWithdrawn,
+    events::ChannelOpened,
+    events::ChannelToppedUp,
+    events::ChannelCloseRequested,
+    events::ChannelClosed,
+    events::ChannelReclaimed
+], errors = Error)]
+pub struct UsageMeter {
+    /// Owner and role grants
+    access: SubModule<AccessControl>,
+    /// Per-group pause flags
+    pausable: SubModule<Pausable>,
+    /// Delayed parameter changes
+    timelock: SubModule<Timelock>,
+    /// SubscriptionManager contract address
+    subscription_manager: Var<Option<Address>>,
+    /// BillingEngine contract address (settles carried-forward adjustments)
+    billing_engine: Var<Option<Address>>,
+    /// Counter for usage record IDs
+    record_counter: Var<u64>,
+    /// Record ID -> UsageRecord
+    records: Mapping<u64, UsageRecord>,
+    /// Subscription ID -> number of records
+    subscription_record_count: Mapping<u64, u32>,
+    /// (Subscription ID, Index) -> Record ID
+    subscription_records: Mapping<(u64, u32), u64>,
+    /// (Subscription ID, Period Start) -> BillingPeriodUsage
+    period_usage: Mapping<(u64, Timestamp), BillingPeriodUsage>,
+    /// Subscription ID -> Current period start
+    current_period_start: Mapping<u64, Timestamp>,
+    /// Subscription ID -> billing-cycle schedule
+    period_schedules: Mapping<u64, PeriodSchedule>,
+    /// Grace window after a period ends during which late usage is accepted
+    late_usage_window: Var<Duration>,
+    /// Authorized backends that can record usage: (Plan ID, Address) -> authorized
+    authorized_recorders: Mapping<(u64, Address), bool>,
+    /// Counter for adjustment IDs
+    adjustment_counter: Var<u64>,
+    /// Adjustment ID -> UsageAdjustment
+    adjustments: Mapping<u64, UsageAdjustment>,
+    /// Record ID -> number of adjustments
+    record_adjustment_count: Mapping<u64, u32>,
+    /// (Record ID, Index) -> Adjustment ID
+    record_adjustments: Mapping<(u64, u32), u64>,
+    /// Record ID -> units credited back so far
+    record_credited_units: Mapping<u64, u64>,
+    /// Subscription ID -> adjustments for billed periods awaiting the next invoice
+    pending_adjustments: Mapping<u64, PendingAdjustments>,
+    /// Stored schema version and migration progress
+    schema: SubModule<Schema>,
+    /// Protocol Registry consulted for peer addresses, if set
+    registry: Var<Option<Address>>,
+    /// Plan ID -> whether usage is paid from prepaid credit
+    prepaid_plans: Mapping<u64, bool>,
+    /// (Subscriber, Merchant) -> unspent prepaid credit
+    credit_balances: Mapping<(Address, Address), U512>,
+    /// Merchant -> credit spent on usage, not yet withdrawn
+    credit_earnings: Mapping<Address, U512>,
+    /// Record ID -> credit charged for it, for prepaid records
+    credit_charges: Mapping<u64, CreditCharge>,
+    /// (Subscription ID, Period Start) -> units of the period paid from credit or channels
+    period_prepaid_units: Mapping<(u64, Timestamp), u64>,
+    /// Counter for payment channel IDs
+    channel_counter: Var<u64>,
+    /// Channel ID -> PaymentChannel
+    channels: Mapping<u64, PaymentChannel>,
+    /// Record ID -> channel whose voucher it settled
+    channel_records: Mapping<u64, u64>,
+    /// Time a merchant has to close a channel after the subscriber asks to
+    challenge_period: Var<Duration>,
+}
+
+#[odra::module]
+impl UsageMeter {
+    /// Initialize the contract
+    pub fn init(&mut self) {
+        let caller = self.env().caller();
+        self.access.init(caller);
+        self.schema.init();
+        self.record_counter.set(0);
+        self.late_usage_window.set(DEFAULT_LATE_USAGE_WINDOW);
+        self.challenge_period.set(DEFAULT_CHALLENGE_PERIOD);
+    }
+
+    // ============ MERCHANT FUNCTIONS ============
+
+    /// Authorize a backend address to record usage for a plan (admin or plan merchant)
+    pub fn authorize_recorder(&mut self, plan_id: u64, recorder: Address) {
+        self.assert_admin_or_merchant(plan_id);
+        self.authorized_recorders.set(&(plan_id, recorder), true);
+    }
+
+    /// Remove authorization for a recorder (admin or plan merchant)
+    pub fn revoke_recorder(&mut self, plan_id: u64, recorder: Address) {
+        self.assert_admin_or_merchant(plan_id);
+        self.authorized_recorders.set(&(plan_id, recorder), false);
+    }
+
+    // ============ USAGE RECORDING ============
+
+    /// Record usage for a subscription (called by authorized backend)
+    pub fn record_usage(
+        &mut self,
+        subscription_id: u64,
+        plan_id: u64,
+        metric: String,
+        units: u64,
+    ) -> u64 {
+        let now = Timestamp::now(&self.env());
+        self.record_usage_at(subscription_id, plan_id, metric, units, now)
+    }
+
+    /// Record usage that happened at `timestamp` (called by authorized backend)
+    ///
+    /// The usage is counted in the billing period `timestamp` falls in. Usage for
+    /// a period that has ended is accepted until the late-usage window expires.
+    /// On prepaid plans the usage is paid from the subscriber's credit at once
+    /// and rejected if the credit, after any auto top-up, does not cover it.
+    pub fn record_usage_at(
+        &mut self,
+        subscription_id: u64,
+        plan_id: u64,
+        metric: String,
+        units: u64,
+        timestamp: Timestamp,
+    ) -> u64 {
+        self.pausable.require_not_paused(PauseGroup::UsageRecording);
+        let caller = self.env().caller();
+        self.assert_recorder(plan_id, caller);
+
+        let schedule = self.load_schedule(subscription_id);
+        if schedule.plan_id != plan_id {
+            self.env().revert(Error::PlanMismatch);
+        }
+
+        let now = Timestamp::now(&self.env());
+        let record_id = self.record_counter.get_or_default() + 1;
+        self.record_counter.set(record_id);
+
+        // Update usage of the period the record belongs to
+        let period_start = self.update_period_usage(subscription_id, &schedule, units, timestamp, now);
+        if self.is_prepaid_plan(plan_id) {
+            self.charge_credit(subscription_id, record_id, units, period_start);
+        }
+
+        self.store_record(UsageRecord {
+            id: record_id,
+            subscription_id,
+            metric,
+            units,
+            recorded_at: timestamp,
+            recorded_by: caller,
+            period_start,
+        });
+        record_id
+    }
+
+    /// Batch record multiple usage entries
+    pub fn batch_record_usage(
+        &mut self,
+        subscription_ids: Vec<u64>,
+        plan_id: u64,
+        metric: String,
+        units_list: Vec<u64>,
+    ) {
+        if subscription_ids.len() != units_list.len() {
+            self.env().revert(Error::LengthMismatch);
+        }
+
+        for i in 0..subscription_ids.len() {
+            self.record_usage(
+                subscription_ids[i],
+                plan_id,
+                metric.clone(),
+                units_list[i],
+            );
+        }
+    }
+
+    // ============ ADJUSTMENTS ============
+
+    /// Correct a previously posted usage record (admin or plan merchant)
+    ///
+    /// If the record's period is still open the period total is corrected
+    /// directly; otherwise the adjustment is carried forward and settled as a
+    /// credit or debit line on the subscription's next invoice. Adjustments to
+    /// records paid from prepaid credit move credit between the subscriber and
+    /// the merchant instead.
+    pub fn adjust_usage(
+        &mut self,
+        original_record_id: u64,
+        plan_id: u64,
+        units: u64,
+        is_credit: bool,
+        reason_code: u8,
+    ) -> u64 {
+        self.pausable.require_not_paused(PauseGroup::UsageRecording);
+        let caller = self.env().caller();
+        self.assert_admin_or_merchant(plan_id);
+        if units == 0 {
+            self.env().revert(Error::ZeroAdjustment);
+        }
+
+        let record = self
+            .records
+            .get(&original_record_id)
+            .unwrap_or_revert_with(&self.env(), Error::RecordNotFound);
+        let subscription_id = record.subscription_id;
+        if self.period_schedules.get(&subscription_id).map(|schedule| schedule.plan_id) != Some(plan_id) {
+            self.env().revert(Error::PlanMismatch);
+        }
+        // A channel record is what the subscriber signed for
+        if self.channel_records.get(&original_record_id).is_some() {
+            self.env().revert(Error::SettledByChannel);
+        }
+
+        // Credits can never remove more than was originally recorded
+        if is_credit {
+            let credited = self.record_credited_units.get(&original_record_id).unwrap_or_default();
+            if credited + units > record.units {
+                self.env().revert(Error::CreditExceedsUsage);
+            }
+            self.record_credited_units.set(&original_record_id, credited + units);
+        }
+
+        // Prepaid records are corrected in credit, never on an invoice
+        let charge = self.credit_charges.get(&original_record_id);
+        if let Some(charge) = &charge {
+            self.settle_prepaid_adjustment(original_record_id, charge, units, is_credit);
+        }
+
+        let key = (subscription_id, record.period_start);
+        let open_period = self.period_usage.get(&key).filter(|p| !p.is_billed);
+        let carried_forward = open_period.is_none() && charge.is_none();
+
+        if let Some(mut period) = open_period {
+            period.total_units = if is_credit {
+                period.total_units - units
+            } else {
+                period.total_units + units
+            };
+            self.period_usage.set(&key, period);
+            if charge.is_some() {
+                let prepaid = self.period_prepaid_units.get(&key).unwrap_or_default();
+                let prepaid = if is_credit { prepaid - units } else { prepaid + units };
+                self.period_prepaid_units.set(&key, prepaid);
+            }
+        } else if charge.is_none() {
+            let mut pending = self.pending_adjustments.get(&subscription_id).unwrap_or_default();
+            if is_credit {
+                pending.credit_units = pending.credit_units + units;
+            } else {
+                pending.debit_units = pending.debit_units + units;
+            }
+            self.pending_adjustments.set(&subscription_id, pending);
+        }
+
+        let adjustment_id = self.adjustment_counter.get_or_default() + 1;
+        self.adjustment_counter.set(adjustment_id);
+
+        self.adjustments.set(&adjustment_id, UsageAdjustment {
+            id: adjustment_id,
+            original_record_id,
+            subscription_id,
+            units,
+            is_credit,
+            reason_code,
+            created_at: Timestamp::now(&self.env()),
+            created_by: caller,
+            carried_forward,
+        });
+
+        let index = self.record_adjustment_count.get(&original_record_id).unwrap_or_default();
+        self.record_adjustments.set(&(original_record_id, index), adjustment_id);
+        self.record_adjustment_count.set(&original_record_id, index + 1);
+
+        self.env().emit_event(events::UsageAdjusted {
+            adjustment_id,
+            original_record_id,
+            subscription_id,
+            units,
+            is_credit,
+            reason_code,
+        });
+
+        adjustment_id
+    }
+
+    /// Take all carried-forward adjustments for a subscription (BillingEngine or keeper)
+    pub fn settle_adjustments(&mut self, subscription_id: u64) -> PendingAdjustments {
+        let caller = self.env().caller();
+        let billing_engine = self.get_peer(Component::BillingEngine);
+        if Some(caller) != billing_engine && !self.access.has_role(Role::Keeper, caller) {
+            self.env().revert(Error::NotBillingEngine);
+        }
+
+        let pending = self.pending_adjustments.get(&subscription_id).unwrap_or_default();
+        if pending.debit_units == 0 && pending.credit_units == 0 {
+            return pending;
+        }
+        self.pending_adjustments.set(&subscription_id, PendingAdjustments::default());
+
+        self.env().emit_event(events::AdjustmentsSettled {
+            subscription_id,
+            debit_units: pending.debit_units,
+            credit_units: pending.credit_units,
+        });
+
+        pending
+    }
+
+    // ============ INTERNAL FUNCTIONS ============
+
+    /// Units of a period not already paid from prepaid credit or a channel
+    fn billable_units(&self, subscription_id: u64, period_start: Timestamp) -> u64 {
+        let key = (subscription_id, period_start);
+        let total_units = self.period_usage.get(&key).map_or(0, |period| period.total_units);
+        total_units.saturating_sub(self.period_prepaid_units.get(&key).unwrap_or_default())
+    }
+
+    /// Verify caller is authorized to record usage for this plan
+    fn assert_recorder(&self, plan_id: u64, caller: Address) {
+        if !self.is_authorized(plan_id, caller) && !self.access.has_role(Role::Admin, caller) {
+            self.env().revert(Error::NotAuthorized);
+        }
+    }
+
+    /// Store a usage record and index it under its subscription
+    fn store_record(&mut self, record: UsageRecord) {
+        let (subscription_id, record_id) = (record.subscription_id, record.id);
+        self.env().emit_event(events::UsageRecorded {
+            subscription_id,
+            metric: record.metric.clone(),
+            units: record.units,
+            timestamp: record.recorded_at,
+        });
+        self.records.set(&record_id, record);
+
+        // Add to subscription's records
+        let index = self.subscription_record_count.get(&subscription_id).unwrap_or_default();
+        self.subscription_records.set(&(subscription_id, index), record_id);
+        self.subscription_record_count.set(&subscription_id, index + 1);
+    }
+
+    /// Verify caller is an admin or the merchant who owns the plan
+    fn assert_admin_or_merchant(&self, plan_id: u64) {
+        let caller = self.env().caller();
+        if self.access.has_role(Role::Admin, caller) {
+            return;
+        }
+        let manager = self
+            .get_peer(Component::SubscriptionManager)
+            .unwrap_or_revert_with(&self.env(), Error::SubscriptionManagerNotSet);
+        let plan = SubscriptionManagerContractRef::new(self.env(), manager)
+            .get_plan(plan_id)
+            .unwrap_or_revert_with(&self.env(), Error::PlanNotFound);
+        if plan.merchant != caller {
+            self.env().revert(Error::NotAdminOrMerchant);
+        }
+    }
+
+    /// Get the billing-cycle schedule of a subscription, caching it on first use
+    fn load_schedule(&mut self, subscription_id: u64) -> PeriodSchedule {
+        if let Some(schedule) = self.period_schedules.get(&subscription_id) {
+            return schedule;
+        }
+
+        let manager = self
+            .get_peer(Component::SubscriptionManager)
+            .unwrap_or_revert_with(&self.env(), Error::SubscriptionManagerNotSet);
+        let manager = SubscriptionManagerContractRef::new(self.env(), manager);
+        let subscription = manager
+            .get_subscription(subscription_id)
+            .unwrap_or_revert_with(&self.env(), Error::SubscriptionNotFound);
+        let plan = manager
+            .get_plan(subscription.plan_id)
+            .unwrap_or_revert_with(&self.env(), Error::PlanNotFound);
+        if plan.billing_cycle.is_zero() {
+            self.env().revert(Error::NoBillingCycle);
+        }
+
+        let schedule = PeriodSchedule {
+            plan_id: subscription.plan_id,
+            anchor: subscription.started_at,
+            cycle: plan.billing_cycle,
+        };
+        self.period_schedules.set(&subscription_id, schedule.clone());
+        schedule
+    }
+
+    /// Add usage to the period `timestamp` falls in, returning the period start
+    fn update_period_usage(
+        &mut self,
+        subscription_id: u64,
+        schedule: &PeriodSchedule,
+        units: u64,
+        timestamp: Timestamp,
+        now: Timestamp,
+    ) -> Timestamp {
+        if timestamp > now {
+            self.env().revert(Error::TimestampInFuture);
+        }
+        if timestamp < schedule.anchor {
+            self.env().revert(Error::TimestampBeforeStart);
+        }
+
+        let period_start = schedule.period_start_for(timestamp);
+        let period_end = period_start + schedule.cycle;
+        if now > period_end + self.late_usage_window.get_or_default() {
+            self.env().revert(Error::LateWindowClosed);
+        }
+
+        // Roll the current period forward when usage lands past its end
+        match self.current_period_start.get(&subscription_id) {
+            None => self.current_period_start.set(&subscription_id, period_start),
+            Some(current_start) if period_start > current_start => {
+                self.current_period_start.set(&subscription_id, period_start);
+                self.env().emit_event(events::PeriodRolledOver {
+                    subscription_id,
+                    previous_period_start: current_start,
+                    period_start,
+                });
+            }
+            Some(_) => {}
+        }
+
+        let key = (subscription_id, period_start);
+        let mut period = self.period_usage.get(&key).unwrap_or(BillingPeriodUsage {
+            subscription_id,
+            period_start,
+            period_end,
+            total_units: 0,
+            is_billed: false,
+        });
+        if period.is_billed {
+            self.env().revert(Error::PeriodAlreadyBilled);
+        }
+
+        period.total_units = period.total_units + units;
+        self.period_usage.set(&key, period);
+        period_start
+    }
+
+    /// Pay for a prepaid record from the subscriber's credit
+    fn charge_credit(&mut self, subscription_id: u64, record_id: u64, units: u64, period_start: Timestamp) {
+        let manager = self
+            .get_peer(Component::SubscriptionManager)
+            .unwrap_or_revert_with(&self.env(), Error::SubscriptionManagerNotSet);
+        let manager = SubscriptionManagerContractRef::new(self.env(), manager);
+        let subscription = manager
+            .get_subscription(subscription_id)
+            .unwrap_or_revert_with(&self.env(), Error::SubscriptionNotFound);
+        let plan = manager
+            .get_plan(subscription.plan_id)
+            .unwrap_or_revert_with(&self.env(), Error::PlanNotFound);
+        let (subscriber, merchant) = (subscription.subscriber, plan.merchant);
+        let amount = plan.usage_price * U512::from(units);
+
+        let mut balance = self.get_credit_balance(subscriber, merchant);
+        if balance < amount {
+            balance = self.top_up_from_rewards(subscriber, merchant, balance, amount - balance);
+        }
+        if balance < amount {
+            self.env().revert(Error::InsufficientCredit);
+        }
+        let balance = balance - amount;
+        self.credit_balances.set(&(subscriber, merchant), balance);
+        self.credit_earnings.set(&merchant, self.get_credit_earnings(merchant) + amount);
+
+        let key = (subscription_id, period_start);
+        let prepaid = self.period_prepaid_units.get(&key).unwrap_or_default();
+        self.period_prepaid_units.set(&key, prepaid + units);
+        self.credit_charges.set(&record_id, CreditCharge {
+            subscriber,
+            merchant,
+            unit_price: plan.usage_price,
+        });
+
+        self.env().emit_event(events::CreditDebited {
+            subscriber,
+            merchant,
+            record_id,
+            amount,
+            balance,
+        });
+    }
+
+    /// Ask StakeToPay to cover `shortfall` from the subscriber's rewards,
+    /// returning the new balance
+    ///
+    /// StakeToPay sends nothing unless the subscriber opted in to auto top-up
+    /// for the merchant and has the rewards.
+    fn top_up_from_rewards(&mut self, subscriber: Address, merchant: Address, balance: U512, shortfall: U512) -> U512 {
+        let Some(stake_to_pay) = self.get_peer(Component::StakeToPay) else {
+            return balance;
+        };
+        let amount = StakeToPayContractRef::new(self.env(), stake_to_pay).top_up_credit(subscriber, merchant, shortfall);
+        if amount.is_zero() {
+            return balance;
+        }
+
+        let balance = balance + amount;
+        self.credit_balances.set(&(subscriber, merchant), balance);
+        self.env().emit_event(events::CreditDeposited {
+            subscriber,
+            merchant,
+            amount,
+            from_rewards: true,
+            balance,
+        });
+        balance
+    }
+
+    /// Send `amount` to a merchant less the protocol fee, returning both parts
+    ///
+    /// Usage paid from credit or channels pays the same fee as invoiced usage.
+    fn pay_merchant(&mut self, merchant: Address, amount: U512) -> (U512, U512) {
+        let billing_engine = self
+            .get_peer(Component::BillingEngine)
+            .unwrap_or_revert_with(&self.env(), Error::BillingEngineNotSet);
+        let engine = BillingEngineContractRef::new(self.env(), billing_engine);
+        let protocol_fee = (amount * U512::from(engine.get_protocol_fee_bps())) / U512::from(10000);
+        let merchant_amount = amount - protocol_fee;
+
+        if !merchant_amount.is_zero() {
+            self.env().transfer_tokens(&merchant, &merchant_amount);
+        }
+        if !protocol_fee.is_zero() {
+            self.env().transfer_tokens(&engine.get_fee_recipient(), &protocol_fee);
+        }
+        (merchant_amount, protocol_fee)
+    }
+
+    /// Load a channel that is still open
+    fn open_channel_of(&self, channel_id: u64) -> PaymentChannel {
+        let channel = self
+            .channels
+            .get(&channel_id)
+            .unwrap_or_revert_with(&self.env(), Error::ChannelNotFound);
+        if channel.is_closed {
+            self.env().revert(Error::ChannelClosed);
+        }
+        channel
+    }
+
+    /// Move credit for an adjustment to a prepaid record
+    fn settle_prepaid_adjustment(&mut self, record_id: u64, charge: &CreditCharge, units: u64, is_credit: bool) {
+        let amount = charge.unit_price * U512::from(units);
+        let (subscriber, merchant) = (charge.subscriber, charge.merchant);
+        let balance = self.get_credit_balance(subscriber, merchant);
+        let earnings = self.get_credit_earnings(merchant);
+
+        if is_credit {
+            if earnings < amount {
+                self.env().revert(Error::InsufficientEarnings);
+            }
+            let balance = balance + amount;
+            self.credit_balances.set(&(subscriber, merchant), balance);
+            self.credit_earnings.set(&merchant, earnings - amount);
+            self.env().emit_event(events::CreditRefunded {
+                subscriber,
+                merchant,
+                amount,
+                balance,
+            });
+        } else {
+            if balance < amount {
+                self.env().revert(Error::InsufficientCredit);
+            }
+            let balance = balance - amount;
+            self.credit_balances.set(&(subscriber, merchant), balance);
+            self.credit_earnings.set(&merchant, earnings + amount);
+            self.env().emit_event(events::CreditDebited {
+                subscriber,
+                merchant,
+                record_id,
+                amount,
+                balance,
+            });
+        }
+    }
+
+    // ============ PREPAID CREDIT ============
+
+    /// Pay a plan's usage from prepaid credit (admin or plan merchant)
+    pub fn set_prepaid_plan(&mut self, plan_id: u64, prepaid: bool) {
+        self.assert_admin_or_merchant(plan_id);
+        self.prepaid_plans.set(&plan_id, prepaid);
+
+        self.env().emit_event(events::PrepaidPlanChanged { plan_id, prepaid });
+    }
+
+    /// Top up the caller's credit with a merchant
+    #[odra(payable)]
+    pub fn deposit_credit(&mut self, merchant: Address) {
+        self.pausable.require_not_paused(PauseGroup::Payments);
+        let subscriber = self.env().caller();
+        let amount = self.env().attached_value();
+        if amount.is_zero() {
+            self.env().revert(Error::ZeroDeposit);
+        }
+
+        let balance = self.get_credit_balance(subscriber, merchant) + amount;
+        self.credit_balances.set(&(subscriber, merchant), balance);
+
+        self.env().emit_event(events::CreditDeposited {
+            subscriber,
+            merchant,
+            amount,
+            from_rewards: false,
+            balance,
+        });
+    }
+
+    /// Return a subscriber's unspent credit with a merchant (called by SubscriptionManager)
+    ///
+    /// SubscriptionManager calls this when the subscriber cancels their last
+    /// subscription with the merchant.
+    pub fn refund_credit(&mut self, subscriber: Address, merchant: Address) -> U512 {
+        if Some(self.env().caller()) != self.get_peer(Component::SubscriptionManager) {
+            self.env().revert(Error::NotSubscriptionManager);
+        }
+
+        let amount = self.get_credit_balance(subscriber, merchant);
+        if amount.is_zero() {
+            return amount;
+        }
+        self.credit_balances.set(&(subscriber, merchant), U512::zero());
+        self.env().transfer_tokens(&subscriber, &amount);
+
+        self.env().emit_event(events::CreditRefunded {
+            subscriber,
+            merchant,
+            amount,
+            balance: U512::zero(),
+        });
+        amount
+    }
+
+    /// Withdraw the caller's credit earnings, less the protocol fee
+    pub fn withdraw_credit_earnings(&mut self) -> U512 {
+        self.pausable.require_not_paused(PauseGroup::Payments);
+        let merchant = self.env().caller();
+        let amount = self.get_credit_earnings(merchant);
+        if amount.is_zero() {
+            self.env().revert(Error::InsufficientEarnings);
+        }
+
+        self.credit_earnings.set(&merchant, U512::zero());
+        let (merchant_amount, protocol_fee) = self.pay_merchant(merchant, amount);
+
+        self.env().emit_event(events::CreditEarningsWithdrawn {
+            merchant,
+            amount: merchant_amount,
+            protocol_fee,
+        });
+        merchant_amount
+    }
+
+    /// Whether a plan's usage is paid from prepaid credit
+    pub fn is_prepaid_plan(&self, plan_id: u64) -> bool {
+        self.prepaid_plans.get(&plan_id).unwrap_or_default()
+    }
+
+    /// Get a subscriber's unspent credit with a merchant
+    pub fn get_credit_balance(&self, subscriber: Address, merchant: Address) -> U512 {
+        self.credit_balances.get(&(subscriber, merchant)).unwrap_or_default()
+    }
+
+    /// Get a merchant's credit earnings not yet withdrawn
+    pub fn get_credit_earnings(&self, merchant: Address) -> U512 {
+        self.credit_earnings.get(&merchant).unwrap_or_default()
+    }
+
+    /// Get the credit charged for a prepaid usage record
+    pub fn get_credit_charge(&self, record_id: u64) -> Option<CreditCharge> {
+        self.credit_charges.get(&record_id)
+    }
+
+    // ============ PAYMENT CHANNELS ============
+
+    /// Open a payment channel to the caller's subscription's merchant, locking
+    /// the attached value; returns the channel ID
+    ///
+    /// Vouchers are signed with `subscriber_key`, which must be the caller's
+    /// account key. The merchant has until `timeout` from now to close the
+    /// channel; after that the subscriber can reclaim the deposit.
+    #[odra(payable)]
+    pub fn open_channel(
+        &mut self,
+        subscription_id: u64,
+        metric: String,
+        subscriber_key: PublicKey,
+        timeout: Duration,
+    ) -> u64 {
+        self.pausable.require_not_paused(PauseGroup::Payments);
+        let subscriber = self.env().caller();
+        if Address::Account(subscriber_key.to_account_hash()) != subscriber {
+            self.env().revert(Error::KeyMismatch);
+        }
+        if timeout < self.get_challenge_period() {
+            self.env().revert(Error::InvalidTimeout);
+        }
+        let deposit = self.env().attached_value();
+        if deposit.is_zero() {
+            self.env().revert(Error::ZeroDeposit);
+        }
+
+        let manager = self
+            .get_peer(Component::SubscriptionManager)
+            .unwrap_or_revert_with(&self.env(), Error::SubscriptionManagerNotSet);
+        let manager = SubscriptionManagerContractRef::new(self.env(), manager);
+        let subscription = manager
+            .get_subscription(subscription_id)
+            .unwrap_or_revert_with(&self.env(), Error::SubscriptionNotFound);
+        if subscription.subscriber != subscriber {
+            self.env().revert(Error::NotChannelSubscriber);
+        }
+        if !subscription.is_active {
+            self.env().revert(Error::SubscriptionInactive);
+        }
+        let plan = manager
+            .get_plan(subscription.plan_id)
+            .unwrap_or_revert_with(&self.env(), Error::PlanNotFound);
+
+        let channel_id = self.channel_counter.get_or_default() + 1;
+        self.channel_counter.set(channel_id);
+        let now = Timestamp::now(&self.env());
+        let closes_at = now + timeout;
+        self.channels.set(&channel_id, PaymentChannel {
+            id: channel_id,
+            subscription_id,
+            plan_id: plan.id,
+            subscriber,
+            subscriber_key,
+            merchant: plan.merchant,
+            metric,
+            unit_price: plan.usage_price,
+            deposit,
+            opened_at: now,
+            closes_at,
+            record_id: None,
+            is_closed: false,
+        });
+
+        self.env().emit_event(events::ChannelOpened {
+            channel_id,
+            subscription_id,
+            subscriber,
+            merchant: plan.merchant,
+            deposit,
+            closes_at,
+        });
+        channel_id
+    }
+
+    /// Add the attached value to one of the caller's open channels
+    #[odra(payable)]
+    pub fn top_up_channel(&mut self, channel_id: u64) {
+        self.pausable.require_not_paused(PauseGroup::Payments);
+        let mut channel = self.open_channel_of(channel_id);
+        if channel.subscriber != self.env().caller() {
+            self.env().revert(Error::NotChannelSubscriber);
+        }
+        let amount = self.env().attached_value();
+        if amount.is_zero() {
+            self.env().revert(Error::ZeroDeposit);
+        }
+
+        channel.deposit = channel.deposit + amount;
+        let deposit = channel.deposit;
+        self.channels.set(&channel_id, channel);
+
+        self.env().emit_event(events::ChannelToppedUp {
+            channel_id,
+            amount,
+            deposit,
+        });
+    }
+
+    /// Ask the merchant to close a channel (channel subscriber)
+    ///
+    /// The merchant has the challenge period to close with its latest
+    /// voucher; after that the subscriber can reclaim the deposit.
+    pub fn request_channel_close(&mut self, channel_id: u64) -> Timestamp {
+        let mut channel = self.open_channel_of(channel_id);
+        if channel.subscriber != self.env().caller() {
+            self.env().revert(Error::NotChannelSubscriber);
+        }
+
+        let closes_at = channel
+            .closes_at
+            .min(Timestamp::now(&self.env()) + self.get_challenge_period());
+        channel.closes_at = closes_at;
+        self.channels.set(&channel_id, channel);
+
+        self.env().emit_event(events::ChannelCloseRequested {
+            channel_id,
+            closes_at,
+        });
+        closes_at
+    }
+
+    /// Close a channel with the subscriber's latest voucher for `units` (channel
+    /// merchant or authorized recorder); returns the merchant's payout
+    ///
+    /// The units are recorded as usage under the channel's metric, counted in
+    /// the current billing period but not invoiced, as the channel paid for
+    /// them. The merchant is paid less the protocol fee and the rest of the
+    /// deposit is refunded. Closing is possible until the subscriber reclaims
+    /// the deposit, even after `closes_at`.
+    pub fn close_channel(&mut self, channel_id: u64, units: u64, signature: Bytes) -> U512 {
+        self.pausable.require_not_paused(PauseGroup::Payments);
+        let caller = self.env().caller();
+        let mut channel = self.open_channel_of(channel_id);
+        if caller != channel.merchant && !self.is_authorized(channel.plan_id, caller) {
+            self.env().revert(Error::NotChannelMerchant);
+        }
+        let message = voucher_message(self.env().self_address(), channel_id, units);
+        if !self.env().verify_signature(&message, &signature, &channel.subscriber_key) {
+            self.env().revert(Error::InvalidVoucher);
+        }
+        let amount = channel.unit_price * U512::from(units);
+        if amount > channel.deposit {
+            self.env().revert(Error::VoucherExceedsDeposit);
+        }
+
+        // Settle the voucher into the period it closed in
+        let record_id = if units > 0 {
+            let schedule = self.load_schedule(channel.subscription_id);
+            let now = Timestamp::now(&self.env());
+            let period_start = self.update_period_usage(channel.subscription_id, &schedule, units, now, now);
+            let key = (channel.subscription_id, period_start);
+            let prepaid = self.period_prepaid_units.get(&key).unwrap_or_default();
+            self.period_prepaid_units.set(&key, prepaid + units);
+
+            let record_id = self.record_counter.get_or_default() + 1;
+            self.record_counter.set(record_id);
+            self.store_record(UsageRecord {
+                id: record_id,
+                subscription_id: channel.subscription_id,
+                metric: channel.metric.clone(),
+                units,
+                recorded_at: now,
+                recorded_by: caller,
+                period_start,
+            });
+            self.channel_records.set(&record_id, channel_id);
+            Some(record_id)
+        } else {
+            None
+        };
+
+        let refunded = channel.deposit - amount;
+        channel.record_id = record_id;
+        channel.is_closed = true;
+        let (merchant, subscriber) = (channel.merchant, channel.subscriber);
+        self.channels.set(&channel_id, channel);
+
+        let (merchant_amount, protocol_fee) = if amount.is_zero() {
+            (U512::zero(), U512::zero())
+        } else {
+            self.pay_merchant(merchant, amount)
+        };
+        if !refunded.is_zero() {
+            self.env().transfer_tokens(&subscriber, &refunded);
+        }
+
+        self.env().emit_event(events::ChannelClosed {
+            channel_id,
+            units,
+            record_id,
+            merchant_amount,
+            protocol_fee,
+            refunded,
+        });
+        merchant_amount
+    }
+
+    /// Take back the whole deposit of a channel the merchant did not close in
+    /// time (channel subscriber)
+    pub fn reclaim_channel(&mut self, channel_id: u64) -> U512 {
+        let mut channel = self.open_channel_of(channel_id);
+        if channel.subscriber != self.env().caller() {
+            self.env().revert(Error::NotChannelSubscriber);
+        }
+        if Timestamp::now(&self.env()) < channel.closes_at {
+            self.env().revert(Error::ChannelStillOpen);
+        }
+
+        let refunded = channel.deposit;
+        let subscriber = channel.subscriber;
+        channel.is_closed = true;
+        self.channels.set(&channel_id, channel);
+        self.env().transfer_tokens(&subscriber, &refunded);
+
+        self.env().emit_event(events::ChannelReclaimed {
+            channel_id,
+            refunded,
+        });
+        refunded
+    }
+
+    /// Get a payment channel
+    pub fn get_channel(&self, channel_id: u64) -> Option<PaymentChannel> {
+        self.channels.get(&channel_id)
+    }
+
+    /// Get the channel whose voucher settled a usage record
+    pub fn get_record_channel(&self, record_id: u64) -> Option<u64> {
+        self.channel_records.get(&record_id)
+    }
+
+    /// Get the message to sign for a voucher paying `units` over a channel
+    pub fn get_voucher_message(&self, channel_id: u64, units: u64) -> Bytes {
+        voucher_message(self.env().self_address(), channel_id, units)
+    }
+
+    /// Get how long a merchant has to close a channel after the subscriber asks to
+    pub fn get_challenge_period(&self) -> Duration {
+        // Installs predating channels fall back to the default
+        self.challenge_period.get().unwrap_or(DEFAULT_CHALLENGE_PERIOD)
+    }
+
+    /// Get total number of payment channels
+    pub fn total_channels(&self) -> u64 {
+        self.channel_counter.get_or_default()
+    }
+
+    // ============ BILLING INTEGRATION ============
+
+    /// Close the billing period ending at `period_end` and return the usage to
+    /// invoice (BillingEngine, SubscriptionManager or keeper)
+    ///
+    /// `period_end` must be a billing-cycle boundary and the late-usage window
+    /// after it must have passed, so no further usage can land in the period.
+    /// Units already paid from prepaid credit are left out of the result.
+    pub fn close_period(&mut self, subscription_id: u64, period_end: Timestamp) -> u64 {
+        let caller = self.env().caller();
+        if Some(caller) != self.get_peer(Component::BillingEngine)
+            && Some(caller) != self.get_peer(Component::SubscriptionManager)
+            && !self.access.has_role(Role::Keeper, caller)
+        {
+            self.env().revert(Error::NotBillingEngine);
+        }
+        let schedule = self.load_schedule(subscription_id);
+        if !(period_end > schedule.anchor && schedule.period_start_for(period_end) == period_end) {
+            self.env().revert(Error::NotPeriodBoundary);
+        }
+        if Timestamp::now(&self.env()) < period_end + self.late_usage_window.get_or_default() {
+            self.env().revert(Error::LateWindowOpen);
+        }
+
+        let period_start = period_end - schedule.cycle;
+        let key = (subscription_id, period_start);
+
+        let mut period = self.period_usage.get(&key).unwrap_or(BillingPeriodUsage {
+            subscription_id,
+            period_start,
+            period_end,
+            total_units: 0,
+            is_billed: false,
+        });
+        if period.is_billed {
+            self.env().revert(Error::PeriodAlreadyBilled);
+        }
+
+        period.is_billed = true;
+        let total_units = period.total_units;
+        self.period_usage.set(&key, period);
+
+        // Start new period
+        if self.current_period_start.get(&subscription_id).unwrap_or(schedule.anchor) < period_end {
+            self.current_period_start.set(&subscription_id, period_end);
+        }
+
+        self.env().emit_event(events::PeriodClosed {
+            subscription_id,
+            period_start,
+            period_end,
+            total_units,
+        });
+
+        self.billable_units(subscription_id, period_start)
+    }
+
+    /// Get usage of the period containing the current block time without closing it
+    pub fn get_current_usage(&self, subscription_id: u64) -> u64 {
+        let schedule = match self.period_schedules.get(&subscription_id) {
+            Some(schedule) => schedule,
+            None => return 0,
+        };
+        let key = (subscription_id, schedule.period_start_for(Timestamp::now(&self.env())));
+
+        self.period_usage
+            .get(&key)
+            .map(|p| p.total_units)
+            .unwrap_or(0)
+    }
+
+    /// Get the start of the most recent period that received usage
+    pub fn get_current_period_start(&self, subscription_id: u64) -> Option<Timestamp> {
+        self.current_period_start.get(&subscription_id)
+    }
+
+    /// Get the cached billing-cycle schedule of a subscription
+    pub fn get_period_schedule(&self, subscription_id: u64) -> Option<PeriodSchedule> {
+        self.period_schedules.get(&subscription_id)
+    }
+
+    /// Get the late-usage grace window
+    pub fn get_late_usage_window(&self) -> Duration {
+        self.late_usage_window.get_or_default()
+    }
+
+    // ============ VIEW FUNCTIONS ============
+
+    /// Get a specific usage record
+    pub fn get_record(&self, record_id: u64) -> Option<UsageRecord> {
+        self.records.get(&record_id)
+    }
+
+    /// Get number of usage records for a subscription
+    pub fn get_subscription_record_count(&self, subscription_id: u64) -> u32 {
+        self.subscription_record_count.get(&subscription_id).unwrap_or_default()
+    }
+
+    /// Get a page of a subscription's usage records matching `filter`
+    pub fn get_subscription_records_page(
+        &self,
+        subscription_id: u64,
+        cursor: u32,
+        limit: u32,
+        filter: UsageRecordFilter,
+    ) -> UsageRecordPage {
+        let total = self.get_subscription_record_count(subscription_id);
+        let (items, next_cursor) = pagination::scan(total, cursor, limit, |index| {
+            self.subscription_records
+                .get(&(subscription_id, index))
+                .and_then(|record_id| self.records.get(&record_id))
+                .filter(|record| filter.matches(record))
+        });
+        UsageRecordPage { items, next_cursor }
+    }
+
+    /// Get period usage details
+    pub fn get_period_usage(&self, subscription_id: u64, period_start: Timestamp) -> Option<BillingPeriodUsage> {
+        self.period_usage.get(&(subscription_id, period_start))
+    }
+
+    /// Get the units of a period left to invoice, excluding prepaid and channel units
+    pub fn get_billable_units(&self, subscription_id: u64, period_start: Timestamp) -> u64 {
+        self.billable_units(subscription_id, period_start)
+    }
+
+    /// Get a specific usage adjustment
+    pub fn get_adjustment(&self, adjustment_id: u64) -> Option<UsageAdjustment> {
+        self.adjustments.get(&adjustment_id)
+    }
+
+    /// Get number of adjustments posted against a usage record
+    pub fn get_record_adjustment_count(&self, record_id: u64) -> u32 {
+        self.record_adjustment_count.get(&record_id).unwrap_or_default()
+    }
+
+    /// Get a page of adjustments posted against a usage record
+    pub fn get_record_adjustments_page(&self, record_id: u64, cursor: u32, limit: u32) -> UsageAdjustmentPage {
+        let total = self.get_record_adjustment_count(record_id);
+        let (items, next_cursor) = pagination::scan(total, cursor, limit, |index| {
+            self.record_adjustments
+                .get(&(record_id, index))
+                .and_then(|adjustment_id| self.adjustments.get(&adjustment_id))
+        });
+        UsageAdjustmentPage { items, next_cursor }
+    }
+
+    /// Get adjustments awaiting settlement on the next invoice
+    pub fn get_pending_adjustments(&self, subscription_id: u64) -> PendingAdjustments {
+        self.pending_adjustments.get(&subscription_id).unwrap_or_default()
+    }
+
+    /// Check if an address is authorized to record
+    pub fn is_authorized(&self, plan_id: u64, address: Address) -> bool {
+        self.authorized_recorders.get(&(plan_id, address)).unwrap_or(false)
+    }
+
+    /// Get total number of records
+    pub fn total_records(&self) -> u64 {
+        self.record_counter.get_or_default()
+    }
+
+    // ============ ADMIN FUNCTIONS ============
+
+    /// Set the SubscriptionManager contract address (admin; timelocked once set)
+    pub fn set_subscription_manager(&mut self, address: Address) {
+        self.access.require_role(Role::Admin);
+        if self.subscription_manager.get_or_default().is_none() {
+            self.subscription_manager.set(Some(address));
+        } else {
+            self.timelock.queue_address(Param::SubscriptionManager, address);
+        }
+    }
+
+    /// Set the BillingEngine contract address (admin; timelocked once set)
+    pub fn set_billing_engine(&mut self, address: Address) {
+        self.access.require_role(Role::Admin);
+        if self.billing_engine.get_or_default().is_none() {
+            self.billing_engine.set(Some(address));
+        } else {
+            self.timelock.queue_address(Param::BillingEngine, address);
+        }
+    }
+
+    /// Queue a new late-usage grace window (admin only)
+    ///
+    /// Keepers wait out the window before renewing, so it also delays billing.
+    pub fn set_late_usage_window(&mut self, window: Duration) {
+        self.access.require_role(Role::Admin);
+        self.timelock.queue_amount(Param::LateUsageWindow, window.as_millis());
+    }
+
+    /// Queue a new time merchants have to close a channel after the subscriber
+    /// asks to (admin only)
+    ///
+    /// Applies to close requests made after the change is executed.
+    pub fn set_challenge_period(&mut self, period: Duration) {
+        self.access.require_role(Role::Admin);
+        if period < MIN_CHALLENGE_PERIOD || period > MAX_CHALLENGE_PERIOD {
+            self.env().revert(Error::InvalidChallengePeriod);
+        }
+        self.timelock.queue_amount(Param::ChallengePeriod, period.as_millis());
+    }
+
+    // ============ REGISTRY FUNCTIONS ============
+
+    /// Resolve peers through a Protocol Registry (admin; timelocked once set)
+    pub fn set_registry(&mut self, address: Address) {
+        self.access.require_role(Role::Admin);
+        if self.registry.get_or_default().is_none() {
+            self.registry.set(Some(address));
+        } else {
+            self.timelock.queue_address(Param::Registry, address);
+        }
+    }
+
+    /// Get the Protocol Registry address
+    pub fn get_registry(&self) -> Option<Address> {
+        self.registry.get_or_default()
+    }
+
+    /// Get the address of a peer contract: the registry's entry once a
+    /// registry is set, otherwise the address configured here
+    pub fn get_peer(&self, component: Component) -> Option<Address> {
+        if let Some(registry) = self.registry.get_or_default() {
+            return RegistryContractRef::new(self.env(), registry).get_address(component);
+        }
+        match component {
+            Component::SubscriptionManager => self.subscription_manager.get_or_default(),
+            Component::BillingEngine => self.billing_engine.get_or_default(),
+            _ => None,
+        }
+    }
+
+    // ============ UPGRADE FUNCTIONS ============
+
+    /// Hook run by Odra when a new version of the contract is installed (admin only)
+    ///
+    /// Usage records have no migration entry point yet; the schema version is
+    /// still tracked so one can be added with the first layout change.
+    pub fn upgrade(&mut self) {
+        self.access.require_role(Role::Admin);
+        self.schema.upgrade(&[]);
+    }
+
+    delegate! {
+        to self.schema {
+            fn schema_version(&self) -> u32;
+            fn get_migration_progress(&self, kind: RecordKind) -> MigrationProgress;
+        }
+    }
+
+    // ============ GOVERNANCE FUNCTIONS ============
+
+    /// Apply a queued parameter change once its delay has passed (anyone)
+    pub fn execute_change(&mut self, change_id: u64) {
+        let change = self.timelock.take_ready(change_id);
+        match change.param {
+            Param::SubscriptionManager => self.subscription_manager.set(change.address),
+            Param::BillingEngine => self.billing_engine.set(change.address),
+            Param::Registry => self.registry.set(change.address),
+            Param::ChallengePeriod => self.challenge_period.set(Duration::from_millis(change.amount)),
+            Param::LateUsageWindow => self.late_usage_window.set(Duration::from_millis(change.amount)),
+            Param::TimelockDelay => {}
+            _ => self.env().revert(TimelockError::UnsupportedParam),
+        }
+    }
+
+    /// Cancel a queued parameter change (admin only)
+    pub fn cancel_change(&mut self, change_id: u64) {
+        self.access.require_role(Role::Admin);
+        self.timelock.cancel(change_id);
+    }
+
+    /// Queue a new delay for parameter changes (admin only)
+    pub fn set_timelock_delay(&mut self, delay: Duration) {
+        self.access.require_role(Role::Admin);
+        self.timelock.queue_delay(delay);
+    }
+
+    delegate! {
+        to self.timelock {
+            fn get_queued_change(&self, change_id: u64) -> Option<QueuedChange>;
+            fn get_pending_change(&self, param: Param) -> Option<QueuedChange>;
+            fn get_timelock_delay(&self) -> Duration;
+        }
+    }
+
+    // ============ PAUSE FUNCTIONS ============
+
+    /// Pause a group of entry points (pauser only)
+    pub fn pause(&mut self, group: PauseGroup) {
+        self.access.require_role(Role::Pauser);
+        self.pausable.pause(group);
+    }
+
+    /// Resume a paused group of entry points (pauser only)
+    pub fn unpause(&mut self, group: PauseGroup) {
+        self.access.require_role(Role::Pauser);
+        self.pausable.unpause(group);
+    }
+
+    delegate! {
+        to self.pausable {
+            fn is_paused(&self, group: PauseGroup) -> bool;
+        }
+    }
+
+    // ============ ACCESS CONTROL ============
+
+    delegate! {
+        to self.access {
+            fn grant_role(&mut self, role: Role, account: Address);
+            fn revoke_role(&mut self, role: Role, account: Address);
+            fn renounce_role(&mut self, role: Role);
+            fn transfer_ownership(&mut self, new_owner: Address);
+            fn accept_ownership(&mut self);
+            fn has_role(&self, role: Role, account: Address) -> bool;
+            fn owner(&self) -> Option<Address>;
+            fn pending_owner(&self) -> Option<Address>;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::access::Error as AccessError;
+    use crate::billing_engine::BillingEngine;
+    use crate::registry::Registry;
+    use crate::stake_to_pay::{StakeToPay, StakeToPayHostRef};
+    use crate::subscription_manager::{SubscriptionManager, SubscriptionManagerHostRef};
+    use crate::pausable::Error as PauseError;
+    use crate::time::{DAY, SECOND, YEAR};
+    use crate::timelock::DEFAULT_TIMELOCK_DELAY;
+    use odra::host::{Deployer, HostEnv, HostRef, NoArgs};
+
+    const CYCLE: Duration = Duration::from_days(30);
+
+    /// Deploy a meter wired to a SubscriptionManager with one metered subscription
+    fn setup(env: &HostEnv) -> (UsageMeterHostRef, u64, u64) {
+        let mut manager = SubscriptionManager::deploy(env, NoArgs);
+        let plan_id = manager.create_plan(
+            "Metered".to_string(),
+            U512::zero(),
+            U512::from(1_000_000u64),
+            CYCLE,
+        );
+        let sub_id = manager.subscribe(plan_id, true, 0);
+
+        let mut contract = UsageMeter::deploy(env, NoArgs);
+        contract.set_subscription_manager(manager.address());
+        (contract, sub_id, plan_id)
+    }
+
+    #[test]
+    fn test_record_usage() {
+        let env = odra_test::env();
+        let (mut contract, sub_id, plan_id) = setup(&env);
+
+        // Record usage (as owner, which holds the admin role)
+        let record_id = contract.record_usage(
+            sub_id,
+            plan_id,
+            "api_calls".to_string(),
+            100,
+        );
+
+        assert_eq!(record_id, 1);
+
+        let current_usage = contract.get_current_usage(sub_id);
+        assert_eq!(current_usage, 100);
+    }
+
+    #[test]
+    fn test_accumulate_usage() {
+        let env = odra_test::env();
+        let (mut contract, sub_id, plan_id) = setup(&env);
+
+        // Record multiple usage entries
+        contract.record_usage(sub_id, plan_id, "api_calls".to_string(), 100);
+        contract.record_usage(sub_id, plan_id, "api_calls".to_string(), 50);
+        contract.record_usage(sub_id, plan_id, "api_calls".to_string(), 75);
+
+        let total = contract.get_current_usage(sub_id);
+        assert_eq!(total, 225);
+    }
+
+    #[test]
+    fn test_current_usage_without_period() {
+        let env = odra_test::env();
+        let (contract, sub_id, _) = setup(&env);
+
+        assert_eq!(contract.get_current_usage(sub_id), 0);
+        assert_eq!(contract.get_current_period_start(sub_id), None);
+    }
+
+    #[test]
+    fn test_periods_anchored_to_subscription_start() {
+        let env = odra_test::env();
+        let (mut contract, sub_id, plan_id) = setup(&env);
+
+        // First usage arrives well after the subscription started
+        env.advance_block_time(DAY.as_millis());
+        let record_id = contract.record_usage(sub_id, plan_id, "api_calls".to_string(), 10);
+
+        let schedule = contract.get_period_schedule(sub_id).unwrap();
+        assert_eq!(contract.get_record(record_id).unwrap().period_start, schedule.anchor);
+
+        let period = contract.get_period_usage(sub_id, schedule.anchor).unwrap();
+        assert_eq!(period.period_end, schedule.anchor + CYCLE);
+    }
+
+    #[test]
+    fn test_period_spans_full_billing_cycle() {
+        let env = odra_test::env();
+        let (mut contract, sub_id, plan_id) = setup(&env);
+
+        let first = contract.record_usage(sub_id, plan_id, "api_calls".to_string(), 1);
+        let anchor = contract.get_period_schedule(sub_id).unwrap().anchor;
+
+        // 29 days in, usage still lands in the first 30-day period
+        env.advance_block_time(Duration::from_days(29).as_millis());
+        let late = contract.record_usage(sub_id, plan_id, "api_calls".to_string(), 1);
+        assert_eq!(contract.get_record(late).unwrap().period_start, anchor);
+        assert_eq!(contract.get_record(first).unwrap().period_start, anchor);
+
+        // On day 30 it rolls over
+        env.advance_block_time(DAY.as_millis());
+        let next = contract.record_usage(sub_id, plan_id, "api_calls".to_string(), 1);
+        assert_eq!(contract.get_record(next).unwrap().period_start, anchor + CYCLE);
+    }
+
+    #[test]
+    fn test_rollover_and_late_usage() {
+        let env = odra_test::env();
+        let (mut contract, sub_id, plan_id) = setup(&env);
+
+        contract.record_usage(sub_id, plan_id, "api_calls".to_string(), 100);
+        let anchor = contract.get_period_schedule(sub_id).unwrap().anchor;
+
+        // Usage after next_billing_at rolls into the second period
+        env.advance_block_time((CYCLE + Duration::from_secs(10)).as_millis());
+        contract.record_usage(sub_id, plan_id, "api_calls".to_string(), 5);
+        assert_eq!(contract.get_current_period_start(sub_id), Some(anchor + CYCLE));
+        assert_eq!(contract.get_current_usage(sub_id), 5);
+
+        // Late usage for the first period is still accepted within the window
+        contract.record_usage_at(sub_id, plan_id, "api_calls".to_string(), 20, anchor + CYCLE - SECOND);
+        assert_eq!(contract.get_period_usage(sub_id, anchor).unwrap().total_units, 120);
+
+        // ...but not once the window has passed
+        env.advance_block_time(DEFAULT_LATE_USAGE_WINDOW.as_millis());
+        assert_eq!(
+            contract.try_record_usage_at(sub_id, plan_id, "api_calls".to_string(), 1, anchor + CYCLE - SECOND),
+            Err(Error::LateWindowClosed.into())
+        );
+
+        // First period closes on its boundary
+        assert_eq!(contract.close_period(sub_id, anchor + CYCLE), 120);
+        assert_eq!(
+            contract.try_close_period(sub_id, anchor + CYCLE),
+            Err(Error::PeriodAlreadyBilled.into())
+        );
+    }
+
+    #[test]
+    fn test_close_period_requires_boundary_and_window() {
+        let env = odra_test::env();
+        let (mut contract, sub_id, plan_id) = setup(&env);
+
+        contract.record_usage(sub_id, plan_id, "api_calls".to_string(), 100);
+        let anchor = contract.get_period_schedule(sub_id).unwrap().anchor;
+
+        assert_eq!(
+            contract.try_close_period(sub_id, anchor + CYCLE),
+            Err(Error::LateWindowOpen.into())
+        );
+        env.advance_block_time((CYCLE + DEFAULT_LATE_USAGE_WINDOW).as_millis());
+        assert_eq!(
+            contract.try_close_period(sub_id, anchor + CYCLE - SECOND),
+            Err(Error::NotPeriodBoundary.into())
+        );
+
+        // Subscribers can't close their own period before it is billed
+        env.set_caller(env.get_account(1));
+        assert_eq!(
+            contract.try_close_period(sub_id, anchor + CYCLE),
+            Err(Error::NotBillingEngine.into())
+        );
+        env.set_caller(env.get_account(0));
+        assert_eq!(contract.close_period(sub_id, anchor + CYCLE), 100);
+        assert_eq!(contract.get_billable_units(sub_id, anchor), 100);
+    }
+
+    #[test]
+    fn test_record_usage_rejects_other_plan() {
+        let env = odra_test::env();
+        let (mut contract, sub_id, plan_id) = setup(&env);
+
+        assert_eq!(
+            contract.try_record_usage(sub_id, plan_id + 1, "api_calls".to_string(), 1),
+            Err(Error::PlanMismatch.into())
+        );
+    }
+
+    #[test]
+    fn test_paginated_records() {
+        let env = odra_test::env();
+        let (mut contract, sub_id, plan_id) = setup(&env);
+
+        for i in 0..6u64 {
+            let metric = if i % 2 == 0 { "api_calls" } else { "storage_gb" };
+            contract.record_usage(sub_id, plan_id, metric.to_string(), i + 1);
+            env.advance_block_time(Duration::from_secs(100).as_millis());
+        }
+        assert_eq!(contract.get_subscription_record_count(sub_id), 6);
+
+        let page = contract.get_subscription_records_page(sub_id, 0, 4, UsageRecordFilter::default());
+        assert_eq!(page.items.len(), 4);
+        assert_eq!(page.next_cursor, Some(4));
+        let page = contract.get_subscription_records_page(sub_id, 4, 4, UsageRecordFilter::default());
+        assert_eq!(page.items[1].units, 6);
+        assert_eq!(page.next_cursor, None);
+
+        let filter = UsageRecordFilter {
+            metric: Some("storage_gb".to_string()),
+            ..Default::default()
+        };
+        let page = contract.get_subscription_records_page(sub_id, 0, 10, filter);
+        assert_eq!(page.items.len(), 3);
+        assert!(page.items.iter().all(|r| r.metric == "storage_gb"));
+
+        let first = contract.get_record(1).unwrap().recorded_at;
+        let filter = UsageRecordFilter {
+            recorded: DateRange { from: Some(first + Duration::from_secs(150)), to: Some(first + Duration::from_secs(350)) },
+            ..Default::default()
+        };
+        let page = contract.get_subscription_records_page(sub_id, 0, 10, filter);
+        assert_eq!(page.items.len(), 2);
+    }
+
+    #[test]
+    fn test_adjust_open_period() {
+        let env = odra_test::env();
+        let (mut contract, sub_id, plan_id) = setup(&env);
+
+        let record_id = contract.record_usage(sub_id, plan_id, "api_calls".to_string(), 100);
+
+        // Duplicate posting: credit 40 units back
+        contract.adjust_usage(record_id, plan_id, 40, true, reason_codes::DUPLICATE);
+        assert_eq!(contract.get_current_usage(sub_id), 60);
+
+        // Under-reported: add 10 units
+        let adjustment_id = contract.adjust_usage(record_id, plan_id, 10, false, reason_codes::UNDER_REPORTED);
+        assert_eq!(contract.get_current_usage(sub_id), 70);
+
+        let adjustment = contract.get_adjustment(adjustment_id).unwrap();
+        assert!(!adjustment.carried_forward);
+        assert_eq!(contract.get_record_adjustment_count(record_id), 2);
+        let page = contract.get_record_adjustments_page(record_id, 0, 10);
+        assert!(page.items[0].is_credit);
+        assert_eq!(page.items[1].id, adjustment_id);
+
+        // Cannot credit more than the record's units
+        assert_eq!(
+            contract.try_adjust_usage(record_id, plan_id, 61, true, reason_codes::OTHER),
+            Err(Error::CreditExceedsUsage.into())
+        );
+    }
+
+    #[test]
+    fn test_adjust_closed_period_is_carried_forward() {
+        let env = odra_test::env();
+        let (mut contract, sub_id, plan_id) = setup(&env);
+
+        let record_id = contract.record_usage(sub_id, plan_id, "api_calls".to_string(), 100);
+        let period_start = contract.get_record(record_id).unwrap().period_start;
+        env.advance_block_time((CYCLE + DEFAULT_LATE_USAGE_WINDOW).as_millis());
+        contract.close_period(sub_id, period_start + CYCLE);
+
+        // Only the merchant can grant credits that reach an invoice
+        env.set_caller(env.get_account(3));
+        assert_eq!(
+            contract.try_adjust_usage(record_id, plan_id, 30, true, reason_codes::GOODWILL),
+            Err(Error::NotAdminOrMerchant.into())
+        );
+        env.set_caller(env.get_account(0));
+        contract.adjust_usage(record_id, plan_id, 30, true, reason_codes::METER_ERROR);
+
+        // Closed period total is untouched
+        let period = contract.get_period_usage(sub_id, period_start).unwrap();
+        assert_eq!(period.total_units, 100);
+
+        let pending = contract.get_pending_adjustments(sub_id);
+        assert_eq!(pending.credit_units, 30);
+        assert_eq!(pending.debit_units, 0);
+
+        let settled = contract.settle_adjustments(sub_id);
+        assert_eq!(settled.credit_units, 30);
+        assert_eq!(contract.get_pending_adjustments(sub_id).credit_units, 0);
+    }
+
+    /// Deploy the protocol behind a Registry with a prepaid plan of account 1
+    /// priced at 1,000 motes per unit, subscribed to by account 2
+    fn setup_prepaid(env: &HostEnv) -> (UsageMeterHostRef, SubscriptionManagerHostRef, StakeToPayHostRef, u64, u64) {
+        let mut registry = Registry::deploy(env, NoArgs);
+        let mut manager = SubscriptionManager::deploy(env, NoArgs);
+        let mut contract = UsageMeter::deploy(env, NoArgs);
+        let mut engine = BillingEngine::deploy(env, NoArgs);
+        let mut stake_to_pay = StakeToPay::deploy(env, NoArgs);
+        registry.register(Component::SubscriptionManager, manager.address());
+        registry.register(Component::UsageMeter, contract.address());
+        registry.register(Component::BillingEngine, engine.address());
+        registry.register(Component::StakeToPay, stake_to_pay.address());
+        manager.set_registry(registry.address());
+        contract.set_registry(registry.address());
+        engine.set_registry(registry.address());
+        stake_to_pay.set_registry(registry.address());
+
+        env.set_caller(env.get_account(1));
+        let plan_id = manager.create_plan("Pay as you go".to_string(), U512::zero(), U512::from(1_000u64), CYCLE);
+        contract.set_prepaid_plan(plan_id, true);
+        contract.authorize_recorder(plan_id, env.get_account(1));
+        env.set_caller(env.get_account(2));
+        let sub_id = manager.subscribe(plan_id, true, 0);
+        (contract, manager, stake_to_pay, sub_id, plan_id)
+    }
+
+    #[test]
+    fn test_prepaid_usage_draws_down_credit() {
+        let env = odra_test::env();
+        let (mut contract, mut manager, _, sub_id, plan_id) = setup_prepaid(&env);
+        let (fee_recipient, merchant, subscriber) = (env.get_account(0), env.get_account(1), env.get_account(2));
+
+        // Only the plan's merchant (or an admin) decides how a plan is paid
+        assert_eq!(
+            contract.try_set_prepaid_plan(plan_id, false),
+            Err(Error::NotAdminOrMerchant.into())
+        );
+        // Nor who may record usage that spends the credit
+        assert_eq!(
+            contract.try_authorize_recorder(plan_id, subscriber),
+            Err(Error::NotAdminOrMerchant.into())
+        );
+        assert_eq!(
+            contract.try_revoke_recorder(plan_id, merchant),
+            Err(Error::NotAdminOrMerchant.into())
+        );
+
+        // Usage is rejected until credit covers it
+        env.set_caller(merchant);
+        assert_eq!(
+            contract.try_record_usage(sub_id, plan_id, "api_calls".to_string(), 5),
+            Err(Error::InsufficientCredit.into())
+        );
+        env.set_caller(subscriber);
+        contract.with_tokens(U512::from(10_000u64)).deposit_credit(merchant);
+
+        env.set_caller(merchant);
+        let record_id = contract.record_usage(sub_id, plan_id, "api_calls".to_string(), 5);
+        assert_eq!(contract.get_credit_balance(subscriber, merchant), U512::from(5_000u64));
+        assert_eq!(contract.get_credit_earnings(merchant), U512::from(5_000u64));
+        assert_eq!(
+            contract.try_record_usage(sub_id, plan_id, "api_calls".to_string(), 6),
+            Err(Error::InsufficientCredit.into())
+        );
+
+        // Crediting back a prepaid record refunds the credit
+        contract.adjust_usage(record_id, plan_id, 2, true, reason_codes::METER_ERROR);
+        assert_eq!(contract.get_credit_balance(subscriber, merchant), U512::from(7_000u64));
+        assert!(!contract.get_adjustment(1).unwrap().carried_forward);
+
+        // Prepaid units are reported but not invoiced again
+        let period_start = contract.get_record(record_id).unwrap().period_start;
+        env.advance_block_time((CYCLE + DEFAULT_LATE_USAGE_WINDOW).as_millis());
+        env.set_caller(fee_recipient);
+        assert_eq!(contract.close_period(sub_id, period_start + CYCLE), 0);
+        assert_eq!(contract.get_period_usage(sub_id, period_start).unwrap().total_units, 3);
+        env.set_caller(merchant);
+
+        // Merchants withdraw earnings less the 1% protocol fee
+        let merchant_balance = env.balance_of(&merchant);
+        let fee_balance = env.balance_of(&fee_recipient);
+        assert_eq!(contract.withdraw_credit_earnings(), U512::from(2_970u64));
+        assert_eq!(env.balance_of(&merchant), merchant_balance + U512::from(2_970u64));
+        assert_eq!(env.balance_of(&fee_recipient), fee_balance + U512::from(30u64));
+
+        // Cancelling the last subscription with the merchant refunds the rest
+        env.set_caller(subscriber);
+        let subscriber_balance = env.balance_of(&subscriber);
+        manager.unsubscribe(sub_id);
+        assert_eq!(env.balance_of(&subscriber), subscriber_balance + U512::from(7_000u64));
+        assert_eq!(contract.get_credit_balance(subscriber, merchant), U512::zero());
+        assert_eq!(env.balance_of(&contract.address()), U512::zero());
+        assert_eq!(
+            contract.try_refund_credit(subscriber, merchant),
+            Err(Error::NotSubscriptionManager.into())
+        );
+    }
+
+    #[test]
+    fn test_prepaid_credit_tops_up_from_rewards() {
+        let env = odra_test::env();
+        let (mut contract, _, mut stake_to_pay, sub_id, plan_id) = setup_prepaid(&env);
+        let (merchant, subscriber) = (env.get_account(1), env.get_account(2));
+
+        // 8% of 1,000,000 motes over a year
+        env.set_caller(subscriber);
+        stake_to_pay.with_tokens(U512::from(1_000_000u64)).deposit();
+        stake_to_pay.enable_stake_to_pay();
+        stake_to_pay.with_tokens(U512::from(80_000u64)).fund_rewards();
+        env.advance_block_time(YEAR.as_millis());
+
+        // Not opted in: nothing is topped up
+        env.set_caller(merchant);
+        assert_eq!(
+            contract.try_record_usage(sub_id, plan_id, "api_calls".to_string(), 5),
+            Err(Error::InsufficientCredit.into())
+        );
+
+        // Top-ups send at least the configured amount
+        env.set_caller(subscriber);
+        stake_to_pay.enable_auto_top_up(merchant, U512::from(20_000u64));
+        env.set_caller(merchant);
+        contract.record_usage(sub_id, plan_id, "api_calls".to_string(), 5);
+        assert_eq!(contract.get_credit_balance(subscriber, merchant), U512::from(15_000u64));
+        assert_eq!(stake_to_pay.get_available_rewards(subscriber), U512::from(60_000u64));
+
+        // A shortfall above the configured amount is covered in full
+        contract.record_usage(sub_id, plan_id, "api_calls".to_string(), 40);
+        assert_eq!(contract.get_credit_balance(subscriber, merchant), U512::zero());
+        assert_eq!(stake_to_pay.get_available_rewards(subscriber), U512::from(35_000u64));
+
+        // Only the UsageMeter can draw on rewards
+        assert_eq!(
+            stake_to_pay.try_top_up_credit(subscriber, merchant, U512::from(1u64)),
+            Err(crate::stake_to_pay::Error::NotUsageMeter.into())
+        );
+    }
+
+    #[test]
+    fn test_channel_settles_latest_voucher() {
+        let env = odra_test::env();
+        let (mut contract, _, _, sub_id, plan_id) = setup_prepaid(&env);
+        let (fee_recipient, merchant, subscriber) = (env.get_account(0), env.get_account(1), env.get_account(2));
+        let key = env.public_key(&subscriber);
+        let timeout = DAY * 7;
+
+        env.set_caller(subscriber);
+        assert_eq!(
+            contract
+                .with_tokens(U512::from(50_000u64))
+                .try_open_channel(sub_id, "api_calls".to_string(), env.public_key(&merchant), timeout),
+            Err(Error::KeyMismatch.into())
+        );
+        assert_eq!(
+            contract
+                .with_tokens(U512::from(50_000u64))
+                .try_open_channel(sub_id, "api_calls".to_string(), key.clone(), HOUR),
+            Err(Error::InvalidTimeout.into())
+        );
+        let channel_id = contract
+            .with_tokens(U512::from(50_000u64))
+            .open_channel(sub_id, "api_calls".to_string(), key, timeout);
+
+        // Vouchers are signed off-chain; only the latest matters
+        let meter = contract.address();
+        let sign = |units: u64| env.sign_message(&voucher_message(meter, channel_id, units), &subscriber);
+        let _earlier = sign(10);
+        let latest = sign(30);
+
+        env.set_caller(merchant);
+        assert_eq!(
+            contract.try_close_channel(channel_id, 40, latest.clone()),
+            Err(Error::InvalidVoucher.into())
+        );
+        assert_eq!(
+            contract.try_close_channel(channel_id, 60, sign(60)),
+            Err(Error::VoucherExceedsDeposit.into())
+        );
+
+        // 30 units at 1,000 motes, less the 1% protocol fee; the rest is refunded
+        let fee_balance = env.balance_of(&fee_recipient);
+        let subscriber_balance = env.balance_of(&subscriber);
+        assert_eq!(contract.close_channel(channel_id, 30, latest.clone()), U512::from(29_700u64));
+        assert_eq!(env.balance_of(&fee_recipient), fee_balance + U512::from(300u64));
+        assert_eq!(env.balance_of(&subscriber), subscriber_balance + U512::from(20_000u64));
+        assert_eq!(env.balance_of(&contract.address()), U512::zero());
+        assert_eq!(
+            contract.try_close_channel(channel_id, 30, latest),
+            Err(Error::ChannelClosed.into())
+        );
+
+        // The voucher settles into the period's usage but is not invoiced
+        let record_id = contract.get_channel(channel_id).unwrap().record_id.unwrap();
+        let record = contract.get_record(record_id).unwrap();
+        assert_eq!((record.metric.as_str(), record.units), ("api_calls", 30));
+        assert_eq!(contract.get_record_channel(record_id), Some(channel_id));
+        assert_eq!(
+            contract.try_adjust_usage(record_id, plan_id, 5, true, reason_codes::DISPUTE),
+            Err(Error::SettledByChannel.into())
+        );
+        env.advance_block_time((CYCLE + DEFAULT_LATE_USAGE_WINDOW).as_millis());
+        env.set_caller(fee_recipient);
+        assert_eq!(contract.close_period(sub_id, record.period_start + CYCLE), 0);
+        assert_eq!(contract.get_period_usage(sub_id, record.period_start).unwrap().total_units, 30);
+    }
+
+    #[test]
+    fn test_channel_refunds_after_challenge_period() {
+        let env = odra_test::env();
+        let (mut contract, _, _, sub_id, _) = setup_prepaid(&env);
+        let (merchant, subscriber) = (env.get_account(1), env.get_account(2));
+
+        env.set_caller(subscriber);
+        let channel_id = contract
+            .with_tokens(U512::from(50_000u64))
+            .open_channel(sub_id, "api_calls".to_string(), env.public_key(&subscriber), DAY * 7);
+        let voucher = env.sign_message(&contract.get_voucher_message(channel_id, 5), &subscriber);
+        assert_eq!(contract.try_reclaim_channel(channel_id), Err(Error::ChannelStillOpen.into()));
+
+        // Asking to close gives the merchant the challenge period, not the full timeout
+        let closes_at = contract.request_channel_close(channel_id);
+        assert_eq!(closes_at, contract.get_channel(channel_id).unwrap().opened_at + DEFAULT_CHALLENGE_PERIOD);
+
+        // Pausing payments stops settlement but never locks the deposit
+        env.set_caller(env.get_account(0));
+        contract.pause(PauseGroup::Payments);
+        env.set_caller(merchant);
+        assert_eq!(
+            contract.try_close_channel(channel_id, 5, voucher.clone()),
+            Err(PauseError::Paused.into())
+        );
+        env.advance_block_time(DEFAULT_CHALLENGE_PERIOD.as_millis());
+
+        env.set_caller(subscriber);
+        let subscriber_balance = env.balance_of(&subscriber);
+        assert_eq!(contract.reclaim_channel(channel_id), U512::from(50_000u64));
+        assert_eq!(env.balance_of(&subscriber), subscriber_balance + U512::from(50_000u64));
+
+        // A voucher the merchant sat on is worthless once the deposit is reclaimed
+        env.set_caller(env.get_account(0));
+        contract.unpause(PauseGroup::Payments);
+        env.set_caller(merchant);
+        assert_eq!(
+            contract.try_close_channel(channel_id, 5, voucher),
+            Err(Error::ChannelClosed.into())
+        );
+    }
+
+    #[test]
+    fn test_challenge_period_is_bounded_and_timelocked() {
+        let env = odra_test::env();
+        let mut contract = UsageMeter::deploy(&env, NoArgs);
+
+        assert_eq!(
+            contract.try_set_challenge_period(Duration::from_millis(MIN_CHALLENGE_PERIOD.as_millis() - 1)),
+            Err(Error::InvalidChallengePeriod.into())
+        );
+        assert_eq!(
+            contract.try_set_challenge_period(MAX_CHALLENGE_PERIOD + SECOND),
+            Err(Error::InvalidChallengePeriod.into())
+        );
+
+        // Subscribers see a shorter window coming before it applies
+        contract.set_challenge_period(HOUR * 2);
+        let change = contract.get_pending_change(Param::ChallengePeriod).unwrap();
+        assert_eq!(contract.get_challenge_period(), DEFAULT_CHALLENGE_PERIOD);
+        env.advance_block_time(DEFAULT_TIMELOCK_DELAY.as_millis());
+        contract.execute_change(change.id);
+        assert_eq!(contract.get_challenge_period(), HOUR * 2);
+    }
+
+    #[test]
+    fn test_late_usage_window_is_timelocked() {
+        let env = odra_test::env();
+        let mut contract = UsageMeter::deploy(&env, NoArgs);
+
+        contract.set_late_usage_window(DAY * 3);
+        let change = contract.get_pending_change(Param::LateUsageWindow).unwrap();
+        assert_eq!(contract.get_late_usage_window(), DEFAULT_LATE_USAGE_WINDOW);
+        env.advance_block_time(DEFAULT_TIMELOCK_DELAY.as_millis());
+        contract.execute_change(change.id);
+        assert_eq!(contract.get_late_usage_window(), DAY * 3);
+
+        env.set_caller(env.get_account(1));
+        assert_eq!(contract.try_set_late_usage_window(DAY), Err(AccessError::MissingRole.into()));
+    }
+}
@@ -0,0 +1,171 @@
+//! REST/JSON API over the indexed events.
+
+use std::sync::{Arc, Mutex};
+
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use serde_json::json;
+
+use crate::error::Error;
+use crate::store::{EventFilter, InvoiceFilter, Store, SubscriptionFilter, UsageFilter};
+
+type SharedStore = Arc<Mutex<Store>>;
+
+/// Routes served by the indexer
+pub fn router(store: SharedStore) -> Router {
+    Router::new()
+        .route("/health", get(health))
+        .route("/subscriptions", get(subscriptions))
+        .route("/subscriptions/:id", get(subscription))
+        .route("/invoices", get(invoices))
+        .route("/invoices/:id", get(invoice))
+        .route("/usage", get(usage))
+        .route("/events", get(events))
+        .with_state(store)
+}
+
+/// Error response with a JSON body
+enum ApiError {
+    NotFound,
+    Internal(Error),
+}
+
+impl From<Error> for ApiError {
+    fn from(err: Error) -> Self {
+        Self::Internal(err)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, message) = match self {
+            Self::NotFound => (StatusCode::NOT_FOUND, "not found".to_string()),
+            Self::Internal(err) => {
+                tracing::error!(%err, "request failed");
+                (StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
+            }
+        };
+        (status, Json(json!({ "error": message }))).into_response()
+    }
+}
+
+type ApiResult = Result<Response, ApiError>;
+
+fn lock(store: &SharedStore) -> std::sync::MutexGuard<'_, Store> {
+    store.lock().expect("store lock")
+}
+
+async fn health(State(store): State<SharedStore>) -> ApiResult {
+    let store = lock(&store);
+    let body = json!({
+        "status": "ok",
+        "tip": store.tip()?,
+        "checkpoint": store.checkpoint()?,
+    });
+    Ok(Json(body).into_response())
+}
+
+async fn subscriptions(
+    State(store): State<SharedStore>,
+    Query(filter): Query<SubscriptionFilter>,
+) -> ApiResult {
+    Ok(Json(lock(&store).subscriptions(&filter)?).into_response())
+}
+
+async fn subscription(State(store): State<SharedStore>, Path(id): Path<u64>) -> ApiResult {
+    let subscription = lock(&store).subscription(id)?.ok_or(ApiError::NotFound)?;
+    Ok(Json(subscription).into_response())
+}
+
+async fn invoices(
+    State(store): State<SharedStore>,
+    Query(filter): Query<InvoiceFilter>,
+) -> ApiResult {
+    Ok(Json(lock(&store).invoices(&filter)?).into_response())
+}
+
+async fn invoice(State(store): State<SharedStore>, Path(id): Path<u64>) -> ApiResult {
+    let invoice = lock(&store).invoice(id)?.ok_or(ApiError::NotFound)?;
+    Ok(Json(invoice).into_response())
+}
+
+async fn usage(State(store): State<SharedStore>, Query(filter): Query<UsageFilter>) -> ApiResult {
+    Ok(Json(lock(&store).usage(&filter)?).into_response())
+}
+
+async fn events(State(store): State<SharedStore>, Query(filter): Query<EventFilter>) -> ApiResult {
+    Ok(Json(lock(&store).events(&filter)?).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::http::Request;
+    use http_body_util::BodyExt;
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::ces::Event;
+    use crate::sse::Block;
+
+    async fn get(router: &Router, uri: &str) -> (StatusCode, Value) {
+        let response = router
+            .clone()
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_routes() {
+        let mut store = Store::in_memory().unwrap();
+        store
+            .add_block(
+                &Block {
+                    hash: "a1".into(),
+                    height: 1,
+                    parent_hash: "a0".into(),
+                },
+                Some(1),
+            )
+            .unwrap();
+        let event = |name: &str, payload: Value| Event {
+            contract: "BillingEngine".into(),
+            name: name.into(),
+            payload,
+        };
+        store
+            .insert_events(
+                "a1",
+                "t1",
+                &[
+                    event("InvoiceCreated", json!({"invoice_id": 4, "subscription_id": 1, "total_amount": "10"})),
+                    event("UsageRecorded", json!({"subscription_id": 1, "metric": "api", "units": 3, "timestamp": {"millis": 1}})),
+                ],
+            )
+            .unwrap();
+        let router = router(Arc::new(Mutex::new(store)));
+
+        let (status, body) = get(&router, "/health").await;
+        assert_eq!((status, body["tip"].clone()), (StatusCode::OK, json!(1)));
+
+        let (_, body) = get(&router, "/invoices?status=pending").await;
+        assert_eq!(body[0]["invoice_id"], 4);
+        let (status, _) = get(&router, "/invoices/5").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (_, body) = get(&router, "/usage?subscription_id=1&limit=10").await;
+        assert_eq!(body[0]["units"], 3);
+        let (_, body) = get(&router, "/events?name=UsageRecorded&after=0").await;
+        assert_eq!(body.as_array().unwrap().len(), 1);
+        let (_, body) = get(&router, "/subscriptions").await;
+        assert_eq!(body, json!([]));
+    }
+}
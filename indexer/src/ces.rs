@@ -0,0 +1,615 @@
+//! Decoding of CES (Casper Event Standard) events.
+//!
+//! Odra contracts emit an event by appending it to their `__events`
+//! dictionary. Each append shows up in a transaction's effects as a write of
+//! a dictionary value that wraps the serialized event together with the
+//! dictionary's seed URef. The event itself starts with its name prefixed by
+//! `event_`, followed by its fields in declaration order.
+//!
+//! [`Schemas`] loads the contract schemas written by
+//! `casperflow_contracts_build_schema` and uses them to decode those fields
+//! into JSON.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::error::{Error, Result};
+
+/// Prefix CES puts in front of every event name
+const EVENT_PREFIX: &str = "event_";
+
+/// Serialized `CLType` tags needed to walk a dictionary value
+mod tag {
+    pub const OPTION: u8 = 13;
+    pub const LIST: u8 = 14;
+    pub const BYTE_ARRAY: u8 = 15;
+    pub const RESULT: u8 = 16;
+    pub const MAP: u8 = 17;
+    pub const TUPLE1: u8 = 18;
+    pub const TUPLE2: u8 = 19;
+    pub const TUPLE3: u8 = 20;
+    pub const U8: u8 = 3;
+}
+
+/// Type of a schema member, as written by `casper-contract-schema`
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub enum Ty {
+    Bool,
+    I32,
+    I64,
+    U8,
+    U32,
+    U64,
+    U128,
+    U256,
+    U512,
+    Unit,
+    String,
+    Key,
+    URef,
+    PublicKey,
+    Option(Box<Ty>),
+    List(Box<Ty>),
+    ByteArray(u32),
+    Map { key: Box<Ty>, value: Box<Ty> },
+    Tuple2([Box<Ty>; 2]),
+    Custom(String),
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum CustomType {
+    Struct {
+        name: String,
+        members: Vec<Member>,
+    },
+    Enum {
+        name: String,
+        variants: Vec<Variant>,
+    },
+}
+
+impl CustomType {
+    fn name(&self) -> &str {
+        match self {
+            Self::Struct { name, .. } | Self::Enum { name, .. } => name,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct Member {
+    name: String,
+    ty: Ty,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct Variant {
+    name: String,
+    discriminant: u8,
+    ty: Ty,
+}
+
+#[derive(Deserialize)]
+struct EventDef {
+    name: String,
+    ty: String,
+}
+
+#[derive(Deserialize)]
+struct SchemaFile {
+    contract_name: String,
+    #[serde(default)]
+    types: Vec<CustomType>,
+    #[serde(default)]
+    events: Vec<EventDef>,
+}
+
+/// Custom types and events of one contract
+#[derive(Default)]
+struct ContractSchema {
+    types: HashMap<String, CustomType>,
+    /// Event name -> name of its struct type
+    events: HashMap<String, String>,
+}
+
+/// Decoded contract event
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Event {
+    /// Contract name from its schema
+    pub contract: String,
+    /// Event name without the CES prefix
+    pub name: String,
+    /// Fields by name
+    pub payload: Value,
+}
+
+/// Event bytes written to a contract's `__events` dictionary
+#[derive(Clone, Debug, PartialEq)]
+pub struct DictionaryWrite {
+    /// Address of the dictionary's seed URef, hex encoded
+    pub seed_uref: String,
+    /// Serialized event
+    pub bytes: Vec<u8>,
+}
+
+/// Contract schemas by contract name
+#[derive(Default)]
+pub struct Schemas {
+    contracts: HashMap<String, ContractSchema>,
+}
+
+impl Schemas {
+    /// Load every `*.json` schema in `dir`
+    pub fn load_dir(dir: &Path) -> Result<Self> {
+        let mut schemas = Self::default();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                let json = fs::read_to_string(&path)?;
+                schemas.add_json(&path.display().to_string(), &json)?;
+            }
+        }
+        Ok(schemas)
+    }
+
+    /// Add the schema of one contract; `source` names it in errors
+    pub fn add_json(&mut self, source: &str, json: &str) -> Result<String> {
+        let file: SchemaFile = serde_json::from_str(json).map_err(|err| Error::Schema {
+            path: source.to_string(),
+            reason: err.to_string(),
+        })?;
+
+        let mut contract = ContractSchema::default();
+        for ty in file.types {
+            contract.types.insert(ty.name().to_string(), ty);
+        }
+        for event in file.events {
+            contract.events.insert(event.name, event.ty);
+        }
+
+        self.contracts.insert(file.contract_name.clone(), contract);
+        Ok(file.contract_name)
+    }
+
+    /// Whether a schema was loaded for `contract`
+    pub fn contains(&self, contract: &str) -> bool {
+        self.contracts.contains_key(contract)
+    }
+
+    /// Decode an event emitted by `contract`
+    pub fn decode(&self, contract: &str, bytes: &[u8]) -> Result<Event> {
+        let schema = self
+            .contracts
+            .get(contract)
+            .ok_or_else(|| Error::Decode(format!("no schema for {contract}")))?;
+        let mut reader = Reader::new(bytes);
+
+        let name = reader.string()?;
+        let name = name
+            .strip_prefix(EVENT_PREFIX)
+            .ok_or_else(|| Error::Decode(format!("{name} is not a CES event")))?
+            .to_string();
+        let ty = schema
+            .events
+            .get(&name)
+            .ok_or_else(|| Error::Decode(format!("{contract} has no event {name}")))?;
+        let payload = reader.value(schema, &Ty::Custom(ty.clone()))?;
+        reader.finish()?;
+
+        Ok(Event {
+            contract: contract.to_string(),
+            name,
+            payload,
+        })
+    }
+}
+
+/// Unwrap a dictionary value written to global state into its seed URef
+/// and the bytes stored under the item key
+pub fn unwrap_dictionary_value(bytes: &[u8]) -> Result<DictionaryWrite> {
+    let mut reader = Reader::new(bytes);
+    let mut value = reader.bytes()?.to_vec();
+    let cl_type_start = reader.position;
+    reader.skip_cl_type()?;
+    // Events are stored as `Bytes`, which carry their own length prefix
+    if reader.input[cl_type_start..reader.position] == [tag::LIST, tag::U8] {
+        value = Reader::new(&value).bytes()?.to_vec();
+    }
+    let seed_uref = hex::encode(reader.take(32)?);
+    reader.take(1)?; // access rights
+    reader.bytes()?; // item key
+    reader.finish()?;
+    Ok(DictionaryWrite {
+        seed_uref,
+        bytes: value,
+    })
+}
+
+/// Address of a formatted URef (`uref-<hex>-<rights>`), hex encoded
+pub fn uref_address(uref: &str) -> Option<String> {
+    let hex = uref.strip_prefix("uref-")?.split('-').next()?;
+    (hex.len() == 64 && hex.chars().all(|c| c.is_ascii_hexdigit()))
+        .then(|| hex.to_ascii_lowercase())
+}
+
+/// Cursor over serialized `casper-types` bytes
+struct Reader<'a> {
+    input: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(input: &'a [u8]) -> Self {
+        Self { input, position: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self
+            .position
+            .checked_add(len)
+            .filter(|&end| end <= self.input.len())
+            .ok_or_else(|| Error::Decode("unexpected end of input".into()))?;
+        let bytes = &self.input[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn finish(&self) -> Result<()> {
+        match self.input.len() - self.position {
+            0 => Ok(()),
+            left => Err(Error::Decode(format!("{left} trailing bytes"))),
+        }
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(
+            self.take(4)?.try_into().expect("4 bytes"),
+        ))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(
+            self.take(8)?.try_into().expect("8 bytes"),
+        ))
+    }
+
+    /// Length-prefixed bytes
+    fn bytes(&mut self) -> Result<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let bytes = self.bytes()?;
+        String::from_utf8(bytes.to_vec()).map_err(|err| Error::Decode(err.to_string()))
+    }
+
+    /// `U128`/`U256`/`U512`: one length byte, then little-endian bytes
+    fn big_uint(&mut self) -> Result<String> {
+        let len = self.u8()? as usize;
+        Ok(le_to_decimal(self.take(len)?))
+    }
+
+    fn key(&mut self) -> Result<String> {
+        match self.u8()? {
+            0 => Ok(format!("account-hash-{}", hex::encode(self.take(32)?))),
+            1 => Ok(format!("hash-{}", hex::encode(self.take(32)?))),
+            2 => self.uref(),
+            tag => Err(Error::Decode(format!("unsupported key tag {tag}"))),
+        }
+    }
+
+    fn uref(&mut self) -> Result<String> {
+        let address = hex::encode(self.take(32)?);
+        Ok(format!("uref-{address}-{:03}", self.u8()?))
+    }
+
+    fn public_key(&mut self) -> Result<String> {
+        let tag = self.u8()?;
+        let len = match tag {
+            0 => 0,
+            1 => 32,
+            2 => 33,
+            tag => return Err(Error::Decode(format!("unsupported public key tag {tag}"))),
+        };
+        Ok(format!("{tag:02x}{}", hex::encode(self.take(len)?)))
+    }
+
+    fn skip_cl_type(&mut self) -> Result<()> {
+        match self.u8()? {
+            tag::OPTION | tag::LIST | tag::TUPLE1 => self.skip_cl_type(),
+            tag::BYTE_ARRAY => self.take(4).map(drop),
+            tag::RESULT | tag::MAP | tag::TUPLE2 => {
+                self.skip_cl_type()?;
+                self.skip_cl_type()
+            }
+            tag::TUPLE3 => (0..3).try_for_each(|_| self.skip_cl_type()),
+            _ => Ok(()),
+        }
+    }
+
+    fn value(&mut self, schema: &ContractSchema, ty: &Ty) -> Result<Value> {
+        Ok(match ty {
+            Ty::Bool => Value::Bool(self.u8()? != 0),
+            Ty::I32 => (self.u32()? as i32).into(),
+            Ty::I64 => (self.u64()? as i64).into(),
+            Ty::U8 => self.u8()?.into(),
+            Ty::U32 => self.u32()?.into(),
+            Ty::U64 => self.u64()?.into(),
+            Ty::U128 | Ty::U256 | Ty::U512 => self.big_uint()?.into(),
+            Ty::Unit => Value::Null,
+            Ty::String => self.string()?.into(),
+            Ty::Key => self.key()?.into(),
+            Ty::URef => self.uref()?.into(),
+            Ty::PublicKey => self.public_key()?.into(),
+            Ty::ByteArray(len) => hex::encode(self.take(*len as usize)?).into(),
+            Ty::Option(inner) => match self.u8()? {
+                0 => Value::Null,
+                _ => self.value(schema, inner)?,
+            },
+            Ty::List(inner) => {
+                let len = self.u32()?;
+                let items = (0..len)
+                    .map(|_| self.value(schema, inner))
+                    .collect::<Result<_>>()?;
+                Value::Array(items)
+            }
+            Ty::Map { key, value } => {
+                let len = self.u32()?;
+                let entries = (0..len)
+                    .map(|_| {
+                        Ok(Value::Array(vec![
+                            self.value(schema, key)?,
+                            self.value(schema, value)?,
+                        ]))
+                    })
+                    .collect::<Result<_>>()?;
+                Value::Array(entries)
+            }
+            Ty::Tuple2([first, second]) => Value::Array(vec![
+                self.value(schema, first)?,
+                self.value(schema, second)?,
+            ]),
+            Ty::Custom(name) => self.custom(schema, name)?,
+        })
+    }
+
+    fn custom(&mut self, schema: &ContractSchema, name: &str) -> Result<Value> {
+        let custom = schema
+            .types
+            .get(name)
+            .ok_or_else(|| Error::Decode(format!("unknown type {name}")))?;
+        match custom {
+            CustomType::Struct { members, .. } => {
+                let mut fields = Map::new();
+                for member in members {
+                    fields.insert(member.name.clone(), self.value(schema, &member.ty)?);
+                }
+                Ok(Value::Object(fields))
+            }
+            CustomType::Enum { variants, .. } => {
+                let discriminant = self.u8()?;
+                let variant = variants
+                    .iter()
+                    .find(|variant| variant.discriminant == discriminant)
+                    .ok_or_else(|| {
+                        Error::Decode(format!("{name} has no variant {discriminant}"))
+                    })?;
+                if variant.ty == Ty::Unit {
+                    return Ok(variant.name.clone().into());
+                }
+                let mut tagged = Map::new();
+                tagged.insert(variant.name.clone(), self.value(schema, &variant.ty)?);
+                Ok(Value::Object(tagged))
+            }
+        }
+    }
+}
+
+/// Decimal representation of a little-endian unsigned integer
+fn le_to_decimal(le: &[u8]) -> String {
+    let mut digits: Vec<u8> = le.iter().rev().copied().collect();
+    let mut decimal = Vec::new();
+    while digits.iter().any(|&byte| byte != 0) {
+        let mut remainder = 0u32;
+        for byte in digits.iter_mut() {
+            let current = (remainder << 8) | u32::from(*byte);
+            *byte = (current / 10) as u8;
+            remainder = current % 10;
+        }
+        decimal.push(b'0' + remainder as u8);
+    }
+    if decimal.is_empty() {
+        return "0".to_string();
+    }
+    decimal.reverse();
+    String::from_utf8(decimal).expect("ASCII digits")
+}
+
+/// Builders for serialized events, shared by the crate's tests
+#[cfg(test)]
+pub(crate) mod encode {
+    pub fn string(out: &mut Vec<u8>, value: &str) {
+        out.extend((value.len() as u32).to_le_bytes());
+        out.extend(value.as_bytes());
+    }
+
+    pub fn u64(out: &mut Vec<u8>, value: u64) {
+        out.extend(value.to_le_bytes());
+    }
+
+    pub fn u512(out: &mut Vec<u8>, value: u128) {
+        let bytes = value.to_le_bytes();
+        let len = bytes
+            .iter()
+            .rposition(|&byte| byte != 0)
+            .map_or(0, |last| last + 1);
+        out.push(len as u8);
+        out.extend(&bytes[..len]);
+    }
+
+    pub fn account(out: &mut Vec<u8>, byte: u8) {
+        out.push(0);
+        out.extend([byte; 32]);
+    }
+
+    /// Wrap event bytes the way CES writes them to the `__events` dictionary
+    pub fn dictionary_value(event: &[u8], seed_uref: [u8; 32]) -> Vec<u8> {
+        let mut stored = Vec::new();
+        stored.extend((event.len() as u32).to_le_bytes());
+        stored.extend(event);
+
+        let mut out = Vec::new();
+        out.extend((stored.len() as u32).to_le_bytes());
+        out.extend(stored);
+        out.extend([super::tag::LIST, super::tag::U8]);
+        out.extend(seed_uref);
+        out.push(7);
+        string(&mut out, "0");
+        out
+    }
+
+    /// Schema with the events used across tests
+    pub const SCHEMA: &str = r#"{
+        "contract_name": "SubscriptionManager",
+        "types": [
+            {"struct": {"name": "PlanCreated", "members": [
+                {"name": "plan_id", "ty": "U64"},
+                {"name": "merchant", "ty": "Key"},
+                {"name": "name", "ty": "String"},
+                {"name": "base_price", "ty": "U512"}
+            ]}},
+            {"struct": {"name": "Subscribed", "members": [
+                {"name": "subscription_id", "ty": "U64"},
+                {"name": "plan_id", "ty": "U64"},
+                {"name": "subscriber", "ty": "Key"}
+            ]}},
+            {"struct": {"name": "Unsubscribed", "members": [
+                {"name": "subscription_id", "ty": "U64"},
+                {"name": "subscriber", "ty": "Key"}
+            ]}},
+            {"enum": {"name": "PauseGroup", "variants": [
+                {"name": "Subscriptions", "discriminant": 0, "ty": "Unit"},
+                {"name": "Payments", "discriminant": 1, "ty": "Unit"}
+            ]}},
+            {"struct": {"name": "Paused", "members": [
+                {"name": "group", "ty": {"Custom": "PauseGroup"}},
+                {"name": "account", "ty": {"Option": "Key"}}
+            ]}}
+        ],
+        "events": [
+            {"name": "PlanCreated", "ty": "PlanCreated"},
+            {"name": "Subscribed", "ty": "Subscribed"},
+            {"name": "Unsubscribed", "ty": "Unsubscribed"},
+            {"name": "Paused", "ty": "Paused"}
+        ]
+    }"#;
+
+    pub fn plan_created(plan_id: u64, merchant: u8, base_price: u128) -> Vec<u8> {
+        let mut out = Vec::new();
+        string(&mut out, "event_PlanCreated");
+        u64(&mut out, plan_id);
+        account(&mut out, merchant);
+        string(&mut out, "Pro");
+        u512(&mut out, base_price);
+        out
+    }
+
+    pub fn subscribed(subscription_id: u64, plan_id: u64, subscriber: u8) -> Vec<u8> {
+        let mut out = Vec::new();
+        string(&mut out, "event_Subscribed");
+        u64(&mut out, subscription_id);
+        u64(&mut out, plan_id);
+        account(&mut out, subscriber);
+        out
+    }
+
+    pub fn unsubscribed(subscription_id: u64, subscriber: u8) -> Vec<u8> {
+        let mut out = Vec::new();
+        string(&mut out, "event_Unsubscribed");
+        u64(&mut out, subscription_id);
+        account(&mut out, subscriber);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schemas() -> Schemas {
+        let mut schemas = Schemas::default();
+        schemas.add_json("test", encode::SCHEMA).unwrap();
+        schemas
+    }
+
+    #[test]
+    fn test_decode_struct_event() {
+        let event = schemas()
+            .decode(
+                "SubscriptionManager",
+                &encode::plan_created(7, 0xab, 50_000_000_000),
+            )
+            .unwrap();
+
+        assert_eq!(event.name, "PlanCreated");
+        assert_eq!(event.payload["plan_id"], 7);
+        assert_eq!(
+            event.payload["merchant"],
+            format!("account-hash-{}", "ab".repeat(32))
+        );
+        assert_eq!(event.payload["name"], "Pro");
+        assert_eq!(event.payload["base_price"], "50000000000");
+    }
+
+    #[test]
+    fn test_decode_enum_and_option() {
+        let mut bytes = Vec::new();
+        encode::string(&mut bytes, "event_Paused");
+        bytes.push(1);
+        bytes.push(0);
+        let event = schemas().decode("SubscriptionManager", &bytes).unwrap();
+        assert_eq!(
+            event.payload,
+            serde_json::json!({"group": "Payments", "account": null})
+        );
+
+        // Unknown events and trailing bytes are rejected
+        let mut bytes = Vec::new();
+        encode::string(&mut bytes, "event_Refunded");
+        assert!(schemas().decode("SubscriptionManager", &bytes).is_err());
+        let mut bytes = encode::unsubscribed(1, 2);
+        bytes.push(0);
+        assert!(schemas().decode("SubscriptionManager", &bytes).is_err());
+    }
+
+    #[test]
+    fn test_unwrap_dictionary_value() {
+        let event = encode::subscribed(1, 2, 3);
+        let write = unwrap_dictionary_value(&encode::dictionary_value(&event, [9; 32])).unwrap();
+        assert_eq!(write.seed_uref, "09".repeat(32));
+        assert_eq!(write.bytes, event);
+
+        let uref = format!("uref-{}-007", "09".repeat(32));
+        assert_eq!(uref_address(&uref), Some(write.seed_uref));
+    }
+
+    #[test]
+    fn test_le_to_decimal() {
+        assert_eq!(le_to_decimal(&[]), "0");
+        assert_eq!(le_to_decimal(&[0xff, 0x01]), "511");
+        assert_eq!(
+            le_to_decimal(&u128::MAX.to_le_bytes()),
+            u128::MAX.to_string()
+        );
+    }
+}
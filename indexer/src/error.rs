@@ -0,0 +1,31 @@
+//! Indexer error type.
+
+use thiserror::Error;
+
+/// Result alias used throughout the indexer
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Errors raised while streaming, decoding or storing events
+#[derive(Debug, Error)]
+pub enum Error {
+    /// Contract schema could not be read or parsed
+    #[error("invalid schema {path}: {reason}")]
+    Schema { path: String, reason: String },
+    /// Event bytes do not match the contract schema
+    #[error("cannot decode event: {0}")]
+    Decode(String),
+    /// Node sent a payload the indexer does not understand
+    #[error("malformed node event: {0}")]
+    NodeEvent(String),
+    /// Chain forked below the finalized checkpoint
+    #[error("fork at height {height} is below the checkpoint at {checkpoint}")]
+    FinalizedFork { height: u64, checkpoint: u64 },
+    #[error("event stream: {0}")]
+    Stream(#[from] reqwest::Error),
+    #[error("database: {0}")]
+    Database(#[from] rusqlite::Error),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
@@ -0,0 +1,285 @@
+//! Applies node events to the store.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+use crate::ces::{self, Schemas};
+use crate::error::Result;
+use crate::sse::{self, Frame, NodeEvent, Transaction};
+use crate::store::Store;
+
+/// Frames buffered between the stream and the indexer
+const FRAME_BUFFER: usize = 1024;
+/// Reconnect backoff bounds
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Follows the chain and stores the events of the watched contracts
+pub struct Indexer {
+    schemas: Schemas,
+    /// Contract name by address of its `__events` seed URef
+    contracts: HashMap<String, String>,
+    store: Arc<Mutex<Store>>,
+    confirmations: u64,
+    /// Id of the last frame handled
+    last_id: Option<u64>,
+}
+
+impl Indexer {
+    /// `contracts` pairs contract names with their `__events` URefs
+    pub fn new(
+        schemas: Schemas,
+        contracts: HashMap<String, String>,
+        store: Arc<Mutex<Store>>,
+        confirmations: u64,
+    ) -> Self {
+        Self {
+            schemas,
+            contracts,
+            store,
+            confirmations,
+            last_id: None,
+        }
+    }
+
+    /// Follow a node, reconnecting with backoff until the node shuts down
+    pub async fn run_node(&mut self, url: &str) -> Result<()> {
+        let checkpoint = self.store.lock().expect("store lock").rewind()?;
+        // Re-stream everything after the final block, or all the node still
+        // has without one
+        self.last_id = checkpoint.and_then(|checkpoint| checkpoint.stream_id);
+        let mut backoff = MIN_BACKOFF;
+        loop {
+            let (sender, mut frames) = mpsc::channel(FRAME_BUFFER);
+            let start_from = self.last_id.map_or(0, |id| id + 1);
+            let stream = tokio::spawn({
+                let url = url.to_string();
+                async move { sse::stream_node(&url, start_from, sender).await }
+            });
+            while let Some(frame) = frames.recv().await {
+                if !self.handle(&frame)? {
+                    stream.abort();
+                    info!("node shut down");
+                    return Ok(());
+                }
+                backoff = MIN_BACKOFF;
+            }
+            match stream.await {
+                Ok(Err(err)) => warn!(%err, "event stream failed"),
+                _ => warn!("event stream closed"),
+            }
+            info!(?backoff, "reconnecting");
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    }
+
+    /// Index a file of recorded frames
+    pub async fn run_replay(&mut self, path: &Path) -> Result<()> {
+        let (sender, mut frames) = mpsc::channel(FRAME_BUFFER);
+        let replay = tokio::spawn({
+            let path = path.to_path_buf();
+            async move { sse::stream_replay(&path, sender).await }
+        });
+        while let Some(frame) = frames.recv().await {
+            if !self.handle(&frame)? {
+                break;
+            }
+        }
+        replay.abort();
+        match replay.await {
+            Ok(result) => result,
+            Err(_) => Ok(()),
+        }
+    }
+
+    /// Apply one frame; returns false once the node announced a shutdown
+    pub fn handle(&mut self, frame: &Frame) -> Result<bool> {
+        let event = match NodeEvent::parse(&frame.data) {
+            Ok(event) => event,
+            Err(err) => {
+                warn!(%err, id = frame.id, "skipping frame");
+                return Ok(true);
+            }
+        };
+        let mut store = self.store.lock().expect("store lock");
+        match event {
+            NodeEvent::ApiVersion(version) => info!(%version, "connected to node"),
+            NodeEvent::BlockAdded(block) => {
+                if let Some(height) = store.add_block(&block, frame.id)? {
+                    warn!(height, hash = %block.hash, "fork, rolled back");
+                }
+                if let Some(height) = block.height.checked_sub(self.confirmations) {
+                    if let Some(checkpoint) = store.finalize(height)? {
+                        debug!(height = checkpoint.height, "checkpoint");
+                    }
+                }
+            }
+            NodeEvent::TransactionProcessed(transaction) => {
+                let events = self.decode(&transaction);
+                if !events.is_empty() {
+                    let inserted =
+                        store.insert_events(&transaction.block_hash, &transaction.hash, &events)?;
+                    debug!(hash = %transaction.hash, inserted, "indexed transaction");
+                }
+            }
+            NodeEvent::Shutdown => return Ok(false),
+            NodeEvent::Other => {}
+        }
+        if frame.id.is_some() {
+            self.last_id = frame.id;
+        }
+        Ok(true)
+    }
+
+    /// Events the transaction emitted from watched contracts, in order
+    fn decode(&self, transaction: &Transaction) -> Vec<ces::Event> {
+        transaction
+            .writes
+            .iter()
+            .filter_map(|write| {
+                // Most dictionary writes are contract state, not events
+                let value = ces::unwrap_dictionary_value(&write.bytes).ok()?;
+                let contract = self.contracts.get(&value.seed_uref)?;
+                self.schemas
+                    .decode(contract, &value.bytes)
+                    .map_err(|err| warn!(%err, hash = %transaction.hash, "undecodable event"))
+                    .ok()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::ces::encode;
+
+    const EVENTS_UREF: [u8; 32] = [7; 32];
+
+    fn indexer(confirmations: u64) -> Indexer {
+        let mut schemas = Schemas::default();
+        schemas.add_json("test", encode::SCHEMA).unwrap();
+        let contracts =
+            HashMap::from([(hex::encode(EVENTS_UREF), "SubscriptionManager".to_string())]);
+        let store = Arc::new(Mutex::new(Store::in_memory().unwrap()));
+        Indexer::new(schemas, contracts, store, confirmations)
+    }
+
+    fn block_added(height: u64, hash: &str, parent_hash: &str) -> String {
+        json!({"BlockAdded": {
+            "block_hash": hash,
+            "block": {"Version2": {"hash": hash, "header": {"parent_hash": parent_hash, "height": height}}}
+        }})
+        .to_string()
+    }
+
+    fn processed(
+        hash: &str,
+        block_hash: &str,
+        events: &[Vec<u8>],
+        events_uref: [u8; 32],
+    ) -> String {
+        let effects: Vec<Value> = events
+            .iter()
+            .enumerate()
+            .map(|(index, event)| {
+                let bytes = hex::encode(encode::dictionary_value(event, events_uref));
+                json!({
+                    "key": format!("dictionary-{index:064x}"),
+                    "kind": {"Write": {"CLValue": {"cl_type": "Any", "bytes": bytes, "parsed": null}}}
+                })
+            })
+            .collect();
+        json!({"TransactionProcessed": {
+            "transaction_hash": {"Version1": hash},
+            "block_hash": block_hash,
+            "execution_result": {"Version2": {"effects": effects}}
+        }})
+        .to_string()
+    }
+
+    fn frame(id: u64, data: String) -> Frame {
+        Frame { id: Some(id), data }
+    }
+
+    #[tokio::test]
+    async fn test_replay_follows_forks() {
+        let frames = [
+            Frame {
+                id: None,
+                data: "{\"ApiVersion\":\"2.0.0\"}".into(),
+            },
+            frame(
+                1,
+                processed(
+                    "t1",
+                    "a1",
+                    &[encode::plan_created(1, 1, 100), encode::subscribed(1, 1, 2)],
+                    EVENTS_UREF,
+                ),
+            ),
+            frame(2, block_added(1, "a1", "a0")),
+            frame(
+                3,
+                processed("t2", "a2", &[encode::unsubscribed(1, 2)], EVENTS_UREF),
+            ),
+            frame(4, block_added(2, "a2", "a1")),
+            // a2 is orphaned along with the unsubscribe
+            frame(5, block_added(2, "b2", "a1")),
+            frame(6, block_added(3, "b3", "b2")),
+            Frame {
+                id: None,
+                data: "\"Shutdown\"".into(),
+            },
+            frame(7, block_added(4, "b4", "b3")),
+        ];
+        let replay: String = frames
+            .iter()
+            .map(|frame| match frame.id {
+                Some(id) => format!("data:{}\nid:{id}\n\n", frame.data),
+                None => format!("data:{}\n\n", frame.data),
+            })
+            .collect();
+        let path =
+            std::env::temp_dir().join(format!("casperflow-indexer-{}.sse", std::process::id()));
+        std::fs::write(&path, replay).unwrap();
+
+        let mut indexer = indexer(1);
+        indexer.run_replay(&path).await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let store = indexer.store.lock().unwrap();
+        let subscription = store.subscription(1).unwrap().unwrap();
+        assert!(subscription.active);
+        assert_eq!(
+            subscription.merchant,
+            Some(format!("account-hash-{}", "01".repeat(32)))
+        );
+        // Frames after the shutdown are not read
+        assert_eq!(store.tip().unwrap(), Some(3));
+        assert_eq!(store.checkpoint().unwrap().unwrap().block_hash, "b2");
+        assert_eq!(indexer.last_id, Some(6));
+    }
+
+    #[test]
+    fn test_ignores_foreign_writes() {
+        let mut indexer = indexer(0);
+        let foreign = processed("t1", "a1", &[encode::subscribed(1, 1, 2)], [8; 32]);
+        assert!(indexer.handle(&frame(1, foreign)).unwrap());
+        assert!(indexer
+            .handle(&frame(2, block_added(1, "a1", "a0")))
+            .unwrap());
+
+        let store = indexer.store.lock().unwrap();
+        assert!(store.events(&Default::default()).unwrap().is_empty());
+        assert_eq!(store.checkpoint().unwrap().unwrap().height, 1);
+    }
+}
@@ -0,0 +1,16 @@
+//! CasperFlow event indexer.
+//!
+//! Follows a Casper node's event stream (or a recording of it), decodes the
+//! CES events emitted by the CasperFlow contracts, stores them in SQLite
+//! and serves subscriptions, invoices and usage over a REST/JSON API.
+
+pub mod api;
+pub mod ces;
+pub mod error;
+pub mod indexer;
+pub mod sse;
+pub mod store;
+
+pub use error::{Error, Result};
+pub use indexer::Indexer;
+pub use store::Store;
@@ -0,0 +1,91 @@
+//! `casperflow_indexer` binary.
+
+use std::collections::HashMap;
+use std::future::IntoFuture;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use clap::Parser;
+use tracing::info;
+use tracing_subscriber::EnvFilter;
+
+use casperflow_indexer::ces::{self, Schemas};
+use casperflow_indexer::{api, Indexer, Store};
+
+#[derive(Parser)]
+#[command(about = "Indexes CasperFlow contract events and serves them over REST")]
+struct Args {
+    /// Node event stream, e.g. http://localhost:18101/events
+    #[arg(long, env = "CASPERFLOW_NODE_SSE", required_unless_present = "replay")]
+    node: Option<String>,
+    /// Index a recorded event stream instead of following a node
+    #[arg(long, conflicts_with = "node")]
+    replay: Option<PathBuf>,
+    /// Directory of contract schemas written by the build_schema binary
+    #[arg(
+        long,
+        default_value = "../casperflow_contracts/resources/casper_contract_schemas"
+    )]
+    schemas: PathBuf,
+    /// Watched contract as `NAME=EVENTS_UREF`, where EVENTS_UREF is the
+    /// contract's `__events` named key (repeatable)
+    #[arg(long = "contract", value_parser = parse_contract, required = true)]
+    contracts: Vec<(String, String)>,
+    #[arg(long, default_value = "casperflow_indexer.db")]
+    database: PathBuf,
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    listen: SocketAddr,
+    /// Blocks on top of a block before it is checkpointed as final
+    #[arg(long, default_value_t = 2)]
+    confirmations: u64,
+}
+
+fn parse_contract(value: &str) -> Result<(String, String), String> {
+    let (name, uref) = value.split_once('=').ok_or("expected NAME=EVENTS_UREF")?;
+    let address =
+        ces::uref_address(uref).ok_or_else(|| format!("{uref} is not a formatted URef"))?;
+    Ok((name.to_string(), address))
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+        )
+        .init();
+    let args = Args::parse();
+
+    let schemas = Schemas::load_dir(&args.schemas)?;
+    let mut contracts = HashMap::new();
+    for (name, address) in args.contracts {
+        if !schemas.contains(&name) {
+            return Err(format!("no schema for {name} in {}", args.schemas.display()).into());
+        }
+        contracts.insert(address, name);
+    }
+
+    let store = Arc::new(Mutex::new(Store::open(&args.database)?));
+    let listener = tokio::net::TcpListener::bind(args.listen).await?;
+    info!(address = %args.listen, "serving API");
+    let server = tokio::spawn(axum::serve(listener, api::router(store.clone())).into_future());
+
+    let mut indexer = Indexer::new(schemas, contracts, store, args.confirmations);
+    match (args.node, args.replay) {
+        (Some(url), _) => {
+            tokio::select! {
+                result = indexer.run_node(&url) => result?,
+                _ = tokio::signal::ctrl_c() => info!("interrupted"),
+            }
+        }
+        (None, Some(path)) => {
+            indexer.run_replay(&path).await?;
+            info!(path = %path.display(), "replay indexed, serving until interrupted");
+            tokio::signal::ctrl_c().await?;
+        }
+        (None, None) => unreachable!("clap requires a source"),
+    }
+    server.abort();
+    Ok(())
+}
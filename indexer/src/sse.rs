@@ -0,0 +1,337 @@
+//! Casper node event stream.
+//!
+//! The node publishes `text/event-stream` frames whose `data` is a JSON
+//! object keyed by the event kind. Only the kinds the indexer needs are
+//! modelled: added blocks (to follow the chain and detect forks) and
+//! processed transactions (whose effects carry the CES event writes).
+//! Both the 2.x (`TransactionProcessed`) and 1.x (`DeployProcessed`)
+//! shapes are accepted.
+
+use std::path::Path;
+
+use futures_util::StreamExt;
+use serde_json::Value;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::sync::mpsc;
+
+use crate::error::{Error, Result};
+
+/// One `text/event-stream` frame
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Frame {
+    /// Event id assigned by the node, used to resume the stream
+    pub id: Option<u64>,
+    pub data: String,
+}
+
+/// Incremental parser turning stream chunks into frames
+#[derive(Default)]
+pub struct FrameParser {
+    line: String,
+    frame: Frame,
+}
+
+impl FrameParser {
+    /// Feed a chunk and return the frames it completes
+    pub fn push(&mut self, chunk: &str) -> Vec<Frame> {
+        let mut frames = Vec::new();
+        for c in chunk.chars() {
+            if c != '\n' {
+                self.line.push(c);
+                continue;
+            }
+            let line = std::mem::take(&mut self.line);
+            let line = line.strip_suffix('\r').unwrap_or(&line);
+            if line.is_empty() {
+                if !self.frame.data.is_empty() {
+                    frames.push(std::mem::take(&mut self.frame));
+                }
+                continue;
+            }
+            let (field, value) = line.split_once(':').unwrap_or((line, ""));
+            let value = value.strip_prefix(' ').unwrap_or(value);
+            match field {
+                "data" => {
+                    if !self.frame.data.is_empty() {
+                        self.frame.data.push('\n');
+                    }
+                    self.frame.data.push_str(value);
+                }
+                "id" => self.frame.id = value.parse().ok(),
+                // Comments (keep-alives) and unknown fields
+                _ => {}
+            }
+        }
+        frames
+    }
+}
+
+/// Block header fields the indexer tracks
+#[derive(Clone, Debug, PartialEq)]
+pub struct Block {
+    pub hash: String,
+    pub height: u64,
+    pub parent_hash: String,
+}
+
+/// CLValue written by a transaction
+#[derive(Clone, Debug, PartialEq)]
+pub struct Write {
+    /// Formatted global state key
+    pub key: String,
+    pub bytes: Vec<u8>,
+}
+
+/// Executed transaction and its dictionary writes
+#[derive(Clone, Debug, PartialEq)]
+pub struct Transaction {
+    pub hash: String,
+    pub block_hash: String,
+    pub writes: Vec<Write>,
+}
+
+/// Node event relevant to the indexer
+#[derive(Clone, Debug, PartialEq)]
+pub enum NodeEvent {
+    ApiVersion(String),
+    BlockAdded(Block),
+    TransactionProcessed(Transaction),
+    Shutdown,
+    /// Any other kind (finality signatures, faults, ...)
+    Other,
+}
+
+impl NodeEvent {
+    /// Parse the `data` of a frame
+    pub fn parse(data: &str) -> Result<Self> {
+        let value: Value = serde_json::from_str(data)?;
+        if value == "Shutdown" {
+            return Ok(Self::Shutdown);
+        }
+        let Some((kind, body)) = value.as_object().and_then(|object| object.iter().next()) else {
+            return Ok(Self::Other);
+        };
+        match kind.as_str() {
+            "ApiVersion" => Ok(Self::ApiVersion(
+                body.as_str().unwrap_or_default().to_string(),
+            )),
+            "BlockAdded" => parse_block(body).map(Self::BlockAdded),
+            "TransactionProcessed" | "DeployProcessed" => {
+                parse_transaction(body).map(Self::TransactionProcessed)
+            }
+            _ => Ok(Self::Other),
+        }
+    }
+}
+
+fn malformed(what: &str) -> Error {
+    Error::NodeEvent(format!("missing {what}"))
+}
+
+fn parse_block(body: &Value) -> Result<Block> {
+    let block = &body["block"];
+    // 2.x wraps the block in its version
+    let block = ["Version2", "Version1"]
+        .iter()
+        .find_map(|version| block.get(version))
+        .unwrap_or(block);
+    let header = &block["header"];
+    Ok(Block {
+        hash: body["block_hash"]
+            .as_str()
+            .or(block["hash"].as_str())
+            .ok_or_else(|| malformed("block hash"))?
+            .into(),
+        height: header["height"]
+            .as_u64()
+            .ok_or_else(|| malformed("block height"))?,
+        parent_hash: header["parent_hash"]
+            .as_str()
+            .ok_or_else(|| malformed("parent hash"))?
+            .into(),
+    })
+}
+
+fn parse_transaction(body: &Value) -> Result<Transaction> {
+    let hash = body
+        .get("transaction_hash")
+        .or(body.get("deploy_hash"))
+        .ok_or_else(|| malformed("transaction hash"))?;
+    // 2.x hashes are tagged with the transaction kind
+    let hash = hash
+        .as_str()
+        .or_else(|| {
+            hash.as_object()
+                .and_then(|tagged| tagged.values().next())
+                .and_then(Value::as_str)
+        })
+        .ok_or_else(|| malformed("transaction hash"))?;
+    let block_hash = body["block_hash"]
+        .as_str()
+        .ok_or_else(|| malformed("block hash"))?;
+
+    let mut writes = Vec::new();
+    collect_writes(&body["execution_result"], &mut writes)?;
+    Ok(Transaction {
+        hash: hash.into(),
+        block_hash: block_hash.into(),
+        writes,
+    })
+}
+
+/// Collect dictionary CLValue writes from any execution result version
+fn collect_writes(result: &Value, writes: &mut Vec<Write>) -> Result<()> {
+    // 2.x: `effects: [{key, kind: {Write: {CLValue: ..}}}]`
+    // 1.x: `effect: {transforms: [{key, transform: {WriteCLValue: ..}}]}`
+    let entries = result
+        .get("effects")
+        .or(result.pointer("/effect/transforms"))
+        .and_then(Value::as_array);
+    if let Some(entries) = entries {
+        for entry in entries {
+            let key = entry["key"].as_str().unwrap_or_default();
+            let cl_value = entry
+                .pointer("/kind/Write/CLValue")
+                .or(entry.pointer("/transform/WriteCLValue"));
+            if let (true, Some(cl_value)) = (key.starts_with("dictionary-"), cl_value) {
+                let bytes = cl_value["bytes"]
+                    .as_str()
+                    .ok_or_else(|| malformed("CLValue bytes"))?;
+                let bytes = hex::decode(bytes).map_err(|err| Error::NodeEvent(err.to_string()))?;
+                writes.push(Write {
+                    key: key.into(),
+                    bytes,
+                });
+            }
+        }
+        return Ok(());
+    }
+    // Descend through version and outcome wrappers
+    if let Some(object) = result.as_object() {
+        for nested in object.values().filter(|nested| nested.is_object()) {
+            collect_writes(nested, writes)?;
+        }
+    }
+    Ok(())
+}
+
+/// Stream frames from a node, starting at event id `start_from`
+pub async fn stream_node(url: &str, start_from: u64, frames: mpsc::Sender<Frame>) -> Result<()> {
+    let response = reqwest::Client::new()
+        .get(url)
+        .query(&[("start_from", start_from)])
+        .send()
+        .await?
+        .error_for_status()?;
+    let mut body = response.bytes_stream();
+    let mut parser = FrameParser::default();
+    let mut pending = Vec::new();
+    while let Some(chunk) = body.next().await {
+        pending.extend_from_slice(&chunk?);
+        // Chunks may split a multi-byte character
+        let valid = match std::str::from_utf8(&pending) {
+            Ok(text) => text.len(),
+            Err(err) => err.valid_up_to(),
+        };
+        let text = std::str::from_utf8(&pending[..valid]).expect("validated");
+        for frame in parser.push(text) {
+            if frames.send(frame).await.is_err() {
+                return Ok(());
+            }
+        }
+        pending.drain(..valid);
+    }
+    Ok(())
+}
+
+/// Stream frames recorded from a node (`curl <node>/events > file`)
+pub async fn stream_replay(path: &Path, frames: mpsc::Sender<Frame>) -> Result<()> {
+    let mut lines = BufReader::new(tokio::fs::File::open(path).await?).lines();
+    let mut parser = FrameParser::default();
+    while let Some(line) = lines.next_line().await? {
+        for frame in parser.push(&format!("{line}\n")) {
+            if frames.send(frame).await.is_err() {
+                return Ok(());
+            }
+        }
+    }
+    // Flush a final frame missing its blank line
+    for frame in parser.push("\n") {
+        let _ = frames.send(frame).await;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frames_across_chunks() {
+        let mut parser = FrameParser::default();
+        assert!(
+            parser
+                .push("data:{\"ApiVersion\":\"2.0.0\"}\n\n:keep-alive\n")
+                .len()
+                == 1
+        );
+        assert!(parser.push("data:{\"Step\"").is_empty());
+        let frames = parser.push(":{}}\r\nid:42\r\n\r\n");
+        assert_eq!(
+            frames,
+            vec![Frame {
+                id: Some(42),
+                data: "{\"Step\":{}}".into()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parse_block_added() {
+        let data = r#"{"BlockAdded":{"block_hash":"bb","block":{"Version2":{"hash":"bb",
+            "header":{"parent_hash":"aa","height":12}}}}}"#;
+        let expected = Block {
+            hash: "bb".into(),
+            height: 12,
+            parent_hash: "aa".into(),
+        };
+        assert_eq!(
+            NodeEvent::parse(data).unwrap(),
+            NodeEvent::BlockAdded(expected)
+        );
+        assert_eq!(
+            NodeEvent::parse("\"Shutdown\"").unwrap(),
+            NodeEvent::Shutdown
+        );
+    }
+
+    #[test]
+    fn test_parse_transaction_writes() {
+        let data = r#"{"TransactionProcessed":{"transaction_hash":{"Version1":"tt"},"block_hash":"bb",
+            "execution_result":{"Version2":{"effects":[
+                {"key":"dictionary-01","kind":{"Write":{"CLValue":{"cl_type":"Any","bytes":"0a0b","parsed":null}}}},
+                {"key":"balance-02","kind":{"Write":{"CLValue":{"cl_type":"U512","bytes":"0100","parsed":"1"}}}},
+                {"key":"dictionary-03","kind":"Identity"}
+            ]}}}}"#;
+        let NodeEvent::TransactionProcessed(tx) = NodeEvent::parse(data).unwrap() else {
+            panic!("expected a transaction");
+        };
+        assert_eq!(tx.hash, "tt");
+        assert_eq!(
+            tx.writes,
+            vec![Write {
+                key: "dictionary-01".into(),
+                bytes: vec![10, 11]
+            }]
+        );
+
+        let legacy = r#"{"DeployProcessed":{"deploy_hash":"dd","block_hash":"bb","execution_result":
+            {"Success":{"effect":{"transforms":[
+                {"key":"dictionary-01","transform":{"WriteCLValue":{"cl_type":"Any","bytes":"0c","parsed":null}}}
+            ]}}}}}"#;
+        let NodeEvent::TransactionProcessed(tx) = NodeEvent::parse(legacy).unwrap() else {
+            panic!("expected a deploy");
+        };
+        assert_eq!((tx.hash.as_str(), tx.writes.len()), ("dd", 1));
+    }
+}
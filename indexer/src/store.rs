@@ -0,0 +1,769 @@
+//! SQLite persistence of blocks, events and the finality checkpoint.
+//!
+//! Events are stored raw (decoded JSON payloads) and the subscription,
+//! invoice and usage views are projected from them at query time, so a
+//! rolled back block only has to delete its own rows. Projections only see
+//! events of blocks on the tracked chain; events of a transaction whose
+//! block has not been announced yet stay hidden until it is.
+
+use std::path::Path;
+
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::ces::Event;
+use crate::error::{Error, Result};
+use crate::sse::Block;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS blocks (
+    hash TEXT PRIMARY KEY,
+    height INTEGER NOT NULL UNIQUE,
+    parent_hash TEXT NOT NULL,
+    stream_id INTEGER
+);
+CREATE TABLE IF NOT EXISTS events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    block_hash TEXT NOT NULL,
+    transaction_hash TEXT NOT NULL,
+    event_index INTEGER NOT NULL,
+    contract TEXT NOT NULL,
+    name TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    UNIQUE (transaction_hash, event_index)
+);
+CREATE INDEX IF NOT EXISTS events_by_name ON events (name);
+CREATE INDEX IF NOT EXISTS events_by_block ON events (block_hash);
+CREATE TABLE IF NOT EXISTS checkpoint (
+    id INTEGER PRIMARY KEY CHECK (id = 0),
+    block_hash TEXT NOT NULL,
+    height INTEGER NOT NULL,
+    stream_id INTEGER
+);
+CREATE VIEW IF NOT EXISTS chain_events AS
+    SELECT events.*, blocks.height AS block_height
+    FROM events JOIN blocks ON blocks.hash = events.block_hash;
+";
+
+/// Default and maximum page sizes of list queries
+const DEFAULT_LIMIT: u64 = 50;
+const MAX_LIMIT: u64 = 500;
+
+fn limit(requested: Option<u64>) -> u64 {
+    requested.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT)
+}
+
+/// Latest block considered final
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Checkpoint {
+    pub block_hash: String,
+    pub height: u64,
+    /// Stream id of the block's `BlockAdded` event
+    pub stream_id: Option<u64>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct SubscriptionFilter {
+    pub subscriber: Option<String>,
+    pub merchant: Option<String>,
+    pub plan_id: Option<u64>,
+    pub active: Option<bool>,
+    #[serde(default)]
+    pub offset: u64,
+    pub limit: Option<u64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Subscription {
+    pub subscription_id: u64,
+    pub plan_id: u64,
+    pub subscriber: String,
+    pub merchant: Option<String>,
+    pub plan_name: Option<String>,
+    pub base_price: Option<String>,
+    /// False once an `Unsubscribed` event was seen
+    pub active: bool,
+    pub block_height: u64,
+    pub transaction_hash: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InvoiceStatus {
+    Pending,
+    Paid,
+    Failed,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct InvoiceFilter {
+    pub subscription_id: Option<u64>,
+    pub subscriber: Option<String>,
+    pub merchant: Option<String>,
+    pub status: Option<InvoiceStatus>,
+    #[serde(default)]
+    pub offset: u64,
+    pub limit: Option<u64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Invoice {
+    pub invoice_id: u64,
+    pub subscription_id: u64,
+    pub subscriber: Option<String>,
+    pub merchant: Option<String>,
+    pub total_amount: String,
+    pub status: InvoiceStatus,
+    pub paid_amount: Option<String>,
+    pub payment_method: Option<u8>,
+    /// Paid out of staking rewards (`PaymentFromRewards`)
+    pub paid_from_rewards: bool,
+    pub failure_reason: Option<String>,
+    pub block_height: u64,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct UsageFilter {
+    pub subscription_id: Option<u64>,
+    pub metric: Option<String>,
+    #[serde(default)]
+    pub offset: u64,
+    pub limit: Option<u64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct UsageRecord {
+    pub subscription_id: u64,
+    pub metric: String,
+    pub units: u64,
+    /// Milliseconds since the Unix epoch
+    pub timestamp: u64,
+    pub block_height: u64,
+    pub transaction_hash: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct EventFilter {
+    pub contract: Option<String>,
+    pub name: Option<String>,
+    /// Only events stored after this id
+    #[serde(default)]
+    pub after: u64,
+    /// Only events in blocks at or above this height
+    pub from_height: Option<u64>,
+    pub limit: Option<u64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct StoredEvent {
+    pub id: u64,
+    pub contract: String,
+    pub name: String,
+    pub payload: Value,
+    pub block_hash: String,
+    pub block_height: u64,
+    pub transaction_hash: String,
+    /// Position of the event among the transaction's events
+    pub event_index: u32,
+}
+
+/// Indexer database
+pub struct Store {
+    conn: Connection,
+}
+
+impl Store {
+    /// Open (or create) the database at `path`
+    pub fn open(path: &Path) -> Result<Self> {
+        Self::init(Connection::open(path)?)
+    }
+
+    /// Database that lives as long as the store
+    pub fn in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> Result<Self> {
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self { conn })
+    }
+
+    /// Record a block, rolling back blocks it replaces.
+    ///
+    /// Returns the height rolled back from when the block forks the chain.
+    pub fn add_block(&mut self, block: &Block, stream_id: Option<u64>) -> Result<Option<u64>> {
+        let tx = self.conn.transaction()?;
+        let at_height: Option<String> = tx
+            .query_row(
+                "SELECT hash FROM blocks WHERE height = ?1",
+                [block.height],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if at_height.as_deref() == Some(block.hash.as_str()) {
+            return Ok(None);
+        }
+
+        let parent: Option<String> = match block.height {
+            0 => None,
+            height => tx
+                .query_row(
+                    "SELECT hash FROM blocks WHERE height = ?1",
+                    [height - 1],
+                    |row| row.get(0),
+                )
+                .optional()?,
+        };
+        // A replaced block, or a parent we never saw, means our tip is stale
+        let fork_height = match parent {
+            Some(parent) if parent != block.parent_hash => Some(block.height - 1),
+            _ => at_height.map(|_| block.height),
+        };
+        if let Some(height) = fork_height {
+            let checkpoint: Option<u64> = tx
+                .query_row("SELECT height FROM checkpoint", [], |row| row.get(0))
+                .optional()?;
+            if let Some(checkpoint) = checkpoint.filter(|&checkpoint| height <= checkpoint) {
+                return Err(Error::FinalizedFork { height, checkpoint });
+            }
+            tx.execute(
+                "DELETE FROM events WHERE block_hash IN (SELECT hash FROM blocks WHERE height >= ?1)",
+                [height],
+            )?;
+            tx.execute("DELETE FROM blocks WHERE height >= ?1", [height])?;
+        }
+
+        tx.execute(
+            "INSERT INTO blocks (hash, height, parent_hash, stream_id) VALUES (?1, ?2, ?3, ?4)",
+            params![block.hash, block.height, block.parent_hash, stream_id],
+        )?;
+        tx.commit()?;
+        Ok(fork_height)
+    }
+
+    /// Store the events of a transaction; replays of it are ignored
+    pub fn insert_events(
+        &mut self,
+        block_hash: &str,
+        transaction_hash: &str,
+        events: &[Event],
+    ) -> Result<usize> {
+        let tx = self.conn.transaction()?;
+        let mut inserted = 0;
+        {
+            let mut insert = tx.prepare(
+                "INSERT OR IGNORE INTO events
+                    (block_hash, transaction_hash, event_index, contract, name, payload)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )?;
+            for (index, event) in events.iter().enumerate() {
+                inserted += insert.execute(params![
+                    block_hash,
+                    transaction_hash,
+                    index,
+                    event.contract,
+                    event.name,
+                    event.payload.to_string(),
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(inserted)
+    }
+
+    /// Move the checkpoint to the tracked block at `height`, if any
+    pub fn finalize(&mut self, height: u64) -> Result<Option<Checkpoint>> {
+        let block = self
+            .conn
+            .query_row(
+                "SELECT hash, height, stream_id FROM blocks WHERE height = ?1",
+                [height],
+                checkpoint_from_row,
+            )
+            .optional()?;
+        let Some(block) = block else {
+            return Ok(None);
+        };
+        if self
+            .checkpoint()?
+            .is_some_and(|current| current.height >= block.height)
+        {
+            return Ok(None);
+        }
+        self.conn.execute(
+            "INSERT OR REPLACE INTO checkpoint (id, block_hash, height, stream_id) VALUES (0, ?1, ?2, ?3)",
+            params![block.block_hash, block.height, block.stream_id],
+        )?;
+        Ok(Some(block))
+    }
+
+    pub fn checkpoint(&self) -> Result<Option<Checkpoint>> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT block_hash, height, stream_id FROM checkpoint",
+                [],
+                checkpoint_from_row,
+            )
+            .optional()?)
+    }
+
+    /// Highest tracked block
+    pub fn tip(&self) -> Result<Option<u64>> {
+        Ok(self
+            .conn
+            .query_row("SELECT MAX(height) FROM blocks", [], |row| row.get(0))?)
+    }
+
+    /// Drop everything past the checkpoint, so a restarted indexer can
+    /// re-stream it from the checkpoint's stream id
+    pub fn rewind(&mut self) -> Result<Option<Checkpoint>> {
+        let checkpoint = self.checkpoint()?;
+        let height = checkpoint
+            .as_ref()
+            .map_or(-1, |checkpoint| checkpoint.height as i64);
+        let tx = self.conn.transaction()?;
+        tx.execute("DELETE FROM blocks WHERE height > ?1", [height])?;
+        tx.execute(
+            "DELETE FROM events WHERE block_hash NOT IN (SELECT hash FROM blocks)",
+            [],
+        )?;
+        tx.commit()?;
+        Ok(checkpoint)
+    }
+
+    pub fn subscriptions(&self, filter: &SubscriptionFilter) -> Result<Vec<Subscription>> {
+        self.query_subscriptions(None, filter)
+    }
+
+    pub fn subscription(&self, subscription_id: u64) -> Result<Option<Subscription>> {
+        let filter = SubscriptionFilter {
+            limit: Some(1),
+            ..Default::default()
+        };
+        Ok(self
+            .query_subscriptions(Some(subscription_id), &filter)?
+            .pop())
+    }
+
+    fn query_subscriptions(
+        &self,
+        id: Option<u64>,
+        filter: &SubscriptionFilter,
+    ) -> Result<Vec<Subscription>> {
+        let mut statement = self.conn.prepare_cached(
+            "SELECT * FROM (
+                SELECT
+                    json_extract(s.payload, '$.subscription_id') AS subscription_id,
+                    json_extract(s.payload, '$.plan_id') AS plan_id,
+                    json_extract(s.payload, '$.subscriber') AS subscriber,
+                    json_extract(p.payload, '$.merchant') AS merchant,
+                    json_extract(p.payload, '$.name') AS plan_name,
+                    json_extract(p.payload, '$.base_price') AS base_price,
+                    NOT EXISTS (
+                        SELECT 1 FROM chain_events u
+                        WHERE u.name = 'Unsubscribed'
+                        AND json_extract(u.payload, '$.subscription_id') = json_extract(s.payload, '$.subscription_id')
+                    ) AS active,
+                    s.block_height,
+                    s.transaction_hash
+                FROM chain_events s
+                LEFT JOIN chain_events p ON p.name = 'PlanCreated'
+                    AND json_extract(p.payload, '$.plan_id') = json_extract(s.payload, '$.plan_id')
+                WHERE s.name = 'Subscribed'
+            )
+            WHERE (?1 IS NULL OR subscription_id = ?1)
+            AND (?2 IS NULL OR subscriber = ?2)
+            AND (?3 IS NULL OR merchant = ?3)
+            AND (?4 IS NULL OR plan_id = ?4)
+            AND (?5 IS NULL OR active = ?5)
+            ORDER BY subscription_id
+            LIMIT ?6 OFFSET ?7",
+        )?;
+        let rows = statement.query_map(
+            params![
+                id,
+                filter.subscriber,
+                filter.merchant,
+                filter.plan_id,
+                filter.active,
+                limit(filter.limit),
+                filter.offset,
+            ],
+            |row| {
+                Ok(Subscription {
+                    subscription_id: row.get("subscription_id")?,
+                    plan_id: row.get("plan_id")?,
+                    subscriber: row.get("subscriber")?,
+                    merchant: row.get("merchant")?,
+                    plan_name: row.get("plan_name")?,
+                    base_price: row.get("base_price")?,
+                    active: row.get("active")?,
+                    block_height: row.get("block_height")?,
+                    transaction_hash: row.get("transaction_hash")?,
+                })
+            },
+        )?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    pub fn invoices(&self, filter: &InvoiceFilter) -> Result<Vec<Invoice>> {
+        self.query_invoices(None, filter)
+    }
+
+    pub fn invoice(&self, invoice_id: u64) -> Result<Option<Invoice>> {
+        let filter = InvoiceFilter {
+            limit: Some(1),
+            ..Default::default()
+        };
+        Ok(self.query_invoices(Some(invoice_id), &filter)?.pop())
+    }
+
+    fn query_invoices(&self, id: Option<u64>, filter: &InvoiceFilter) -> Result<Vec<Invoice>> {
+        let mut statement = self.conn.prepare_cached(
+            "SELECT * FROM (
+                SELECT *,
+                    CASE
+                        WHEN paid_amount IS NOT NULL OR paid_from_rewards THEN 'paid'
+                        WHEN failure_reason IS NOT NULL THEN 'failed'
+                        ELSE 'pending'
+                    END AS status
+                FROM (
+                    SELECT
+                        json_extract(i.payload, '$.invoice_id') AS invoice_id,
+                        json_extract(i.payload, '$.subscription_id') AS subscription_id,
+                        json_extract(s.payload, '$.subscriber') AS subscriber,
+                        json_extract(p.payload, '$.merchant') AS merchant,
+                        json_extract(i.payload, '$.total_amount') AS total_amount,
+                        json_extract(paid.payload, '$.amount') AS paid_amount,
+                        json_extract(paid.payload, '$.payment_method') AS payment_method,
+                        EXISTS (
+                            SELECT 1 FROM chain_events r
+                            WHERE r.name = 'PaymentFromRewards'
+                            AND json_extract(r.payload, '$.invoice_id') = json_extract(i.payload, '$.invoice_id')
+                        ) AS paid_from_rewards,
+                        json_extract(failed.payload, '$.reason') AS failure_reason,
+                        i.block_height
+                    FROM chain_events i
+                    LEFT JOIN chain_events s ON s.name = 'Subscribed'
+                        AND json_extract(s.payload, '$.subscription_id') = json_extract(i.payload, '$.subscription_id')
+                    LEFT JOIN chain_events p ON p.name = 'PlanCreated'
+                        AND json_extract(p.payload, '$.plan_id') = json_extract(s.payload, '$.plan_id')
+                    LEFT JOIN chain_events paid ON paid.name = 'InvoicePaid'
+                        AND json_extract(paid.payload, '$.invoice_id') = json_extract(i.payload, '$.invoice_id')
+                    LEFT JOIN chain_events failed ON failed.name = 'InvoiceFailed'
+                        AND json_extract(failed.payload, '$.invoice_id') = json_extract(i.payload, '$.invoice_id')
+                    WHERE i.name = 'InvoiceCreated'
+                )
+            )
+            WHERE (?1 IS NULL OR invoice_id = ?1)
+            AND (?2 IS NULL OR subscription_id = ?2)
+            AND (?3 IS NULL OR subscriber = ?3)
+            AND (?4 IS NULL OR merchant = ?4)
+            AND (?5 IS NULL OR status = ?5)
+            ORDER BY invoice_id
+            LIMIT ?6 OFFSET ?7",
+        )?;
+        let status = filter.status.map(|status| match status {
+            InvoiceStatus::Pending => "pending",
+            InvoiceStatus::Paid => "paid",
+            InvoiceStatus::Failed => "failed",
+        });
+        let rows = statement.query_map(
+            params![
+                id,
+                filter.subscription_id,
+                filter.subscriber,
+                filter.merchant,
+                status,
+                limit(filter.limit),
+                filter.offset,
+            ],
+            |row| {
+                let status = match row.get::<_, String>("status")?.as_str() {
+                    "paid" => InvoiceStatus::Paid,
+                    "failed" => InvoiceStatus::Failed,
+                    _ => InvoiceStatus::Pending,
+                };
+                Ok(Invoice {
+                    invoice_id: row.get("invoice_id")?,
+                    subscription_id: row.get("subscription_id")?,
+                    subscriber: row.get("subscriber")?,
+                    merchant: row.get("merchant")?,
+                    total_amount: row.get("total_amount")?,
+                    status,
+                    paid_amount: row.get("paid_amount")?,
+                    payment_method: row.get("payment_method")?,
+                    paid_from_rewards: row.get("paid_from_rewards")?,
+                    failure_reason: row.get("failure_reason")?,
+                    block_height: row.get("block_height")?,
+                })
+            },
+        )?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    pub fn usage(&self, filter: &UsageFilter) -> Result<Vec<UsageRecord>> {
+        let mut statement = self.conn.prepare_cached(
+            "SELECT * FROM (
+                SELECT
+                    json_extract(payload, '$.subscription_id') AS subscription_id,
+                    json_extract(payload, '$.metric') AS metric,
+                    json_extract(payload, '$.units') AS units,
+                    json_extract(payload, '$.timestamp.millis') AS timestamp,
+                    block_height,
+                    transaction_hash,
+                    id
+                FROM chain_events
+                WHERE name = 'UsageRecorded'
+            )
+            WHERE (?1 IS NULL OR subscription_id = ?1)
+            AND (?2 IS NULL OR metric = ?2)
+            ORDER BY id
+            LIMIT ?3 OFFSET ?4",
+        )?;
+        let rows = statement.query_map(
+            params![
+                filter.subscription_id,
+                filter.metric,
+                limit(filter.limit),
+                filter.offset
+            ],
+            |row| {
+                Ok(UsageRecord {
+                    subscription_id: row.get("subscription_id")?,
+                    metric: row.get("metric")?,
+                    units: row.get("units")?,
+                    timestamp: row.get("timestamp")?,
+                    block_height: row.get("block_height")?,
+                    transaction_hash: row.get("transaction_hash")?,
+                })
+            },
+        )?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    pub fn events(&self, filter: &EventFilter) -> Result<Vec<StoredEvent>> {
+        let mut statement = self.conn.prepare_cached(
+            "SELECT id, contract, name, payload, block_hash, block_height, transaction_hash, event_index
+            FROM chain_events
+            WHERE id > ?1
+            AND (?2 IS NULL OR contract = ?2)
+            AND (?3 IS NULL OR name = ?3)
+            AND (?4 IS NULL OR block_height >= ?4)
+            ORDER BY id
+            LIMIT ?5",
+        )?;
+        let rows = statement.query_map(
+            params![
+                filter.after,
+                filter.contract,
+                filter.name,
+                filter.from_height,
+                limit(filter.limit)
+            ],
+            |row| {
+                let payload: String = row.get("payload")?;
+                Ok(StoredEvent {
+                    id: row.get("id")?,
+                    contract: row.get("contract")?,
+                    name: row.get("name")?,
+                    payload: serde_json::from_str(&payload).unwrap_or(Value::Null),
+                    block_hash: row.get("block_hash")?,
+                    block_height: row.get("block_height")?,
+                    transaction_hash: row.get("transaction_hash")?,
+                    event_index: row.get("event_index")?,
+                })
+            },
+        )?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+}
+
+fn checkpoint_from_row(row: &Row) -> rusqlite::Result<Checkpoint> {
+    Ok(Checkpoint {
+        block_hash: row.get(0)?,
+        height: row.get(1)?,
+        stream_id: row.get(2)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn block(height: u64, hash: &str, parent_hash: &str) -> Block {
+        Block {
+            hash: hash.into(),
+            height,
+            parent_hash: parent_hash.into(),
+        }
+    }
+
+    fn event(name: &str, payload: Value) -> Event {
+        Event {
+            contract: "SubscriptionManager".into(),
+            name: name.into(),
+            payload,
+        }
+    }
+
+    #[test]
+    fn test_fork_rolls_back_events() {
+        let mut store = Store::in_memory().unwrap();
+        store.add_block(&block(1, "a1", "a0"), Some(1)).unwrap();
+        store.add_block(&block(2, "a2", "a1"), Some(3)).unwrap();
+        let subscribed = event(
+            "Subscribed",
+            json!({"subscription_id": 1, "plan_id": 1, "subscriber": "s"}),
+        );
+        store
+            .insert_events("a2", "t1", std::slice::from_ref(&subscribed))
+            .unwrap();
+        // Replays are ignored
+        assert_eq!(store.insert_events("a2", "t1", &[subscribed]).unwrap(), 0);
+        assert_eq!(store.subscriptions(&Default::default()).unwrap().len(), 1);
+
+        // b2 replaces a2 and takes its events with it
+        assert_eq!(
+            store.add_block(&block(2, "b2", "a1"), Some(5)).unwrap(),
+            Some(2)
+        );
+        assert!(store.subscriptions(&Default::default()).unwrap().is_empty());
+        // b3 builds on a parent we never saw, so b2 goes too
+        assert_eq!(
+            store.add_block(&block(3, "c3", "c2"), Some(6)).unwrap(),
+            Some(2)
+        );
+        assert_eq!(store.tip().unwrap(), Some(3));
+
+        store.finalize(1).unwrap();
+        assert_eq!(
+            store
+                .add_block(&block(1, "d1", "a0"), None)
+                .unwrap_err()
+                .to_string(),
+            Error::FinalizedFork {
+                height: 1,
+                checkpoint: 1
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn test_rewind_to_checkpoint() {
+        let mut store = Store::in_memory().unwrap();
+        store.add_block(&block(1, "a1", "a0"), Some(10)).unwrap();
+        store.add_block(&block(2, "a2", "a1"), Some(20)).unwrap();
+        store
+            .insert_events(
+                "a1",
+                "t1",
+                &[event("PlanDeactivated", json!({"plan_id": 1}))],
+            )
+            .unwrap();
+        store
+            .insert_events(
+                "a2",
+                "t2",
+                &[event("PlanDeactivated", json!({"plan_id": 2}))],
+            )
+            .unwrap();
+        store
+            .insert_events(
+                "a3",
+                "t3",
+                &[event("PlanDeactivated", json!({"plan_id": 3}))],
+            )
+            .unwrap();
+
+        let checkpoint = store.finalize(1).unwrap().unwrap();
+        assert_eq!(checkpoint.stream_id, Some(10));
+        assert_eq!(store.finalize(1).unwrap(), None);
+        assert_eq!(store.rewind().unwrap(), Some(checkpoint));
+
+        assert_eq!(store.tip().unwrap(), Some(1));
+        let events = store.events(&Default::default()).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].payload, json!({"plan_id": 1}));
+
+        store.add_block(&block(2, "b2", "a1"), Some(30)).unwrap();
+        store
+            .insert_events(
+                "b2",
+                "t4",
+                &[
+                    event("PlanDeactivated", json!({"plan_id": 4})),
+                    event("PlanDeactivated", json!({"plan_id": 5})),
+                ],
+            )
+            .unwrap();
+        let from_b2 = EventFilter {
+            from_height: Some(2),
+            ..Default::default()
+        };
+        let events = store.events(&from_b2).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!((events[1].block_height, events[1].event_index), (2, 1));
+    }
+
+    #[test]
+    fn test_projections() {
+        let mut store = Store::in_memory().unwrap();
+        store.add_block(&block(1, "a1", "a0"), None).unwrap();
+        store
+            .insert_events(
+                "a1",
+                "t1",
+                &[
+                    event("PlanCreated", json!({"plan_id": 1, "merchant": "m", "name": "Pro", "base_price": "100"})),
+                    event("Subscribed", json!({"subscription_id": 1, "plan_id": 1, "subscriber": "s1"})),
+                    event("Subscribed", json!({"subscription_id": 2, "plan_id": 1, "subscriber": "s2"})),
+                    event("Unsubscribed", json!({"subscription_id": 2, "subscriber": "s2"})),
+                    event("InvoiceCreated", json!({"invoice_id": 1, "subscription_id": 1, "total_amount": "100"})),
+                    event("InvoicePaid", json!({"invoice_id": 1, "amount": "100", "payment_method": 0})),
+                    event("InvoiceCreated", json!({"invoice_id": 2, "subscription_id": 2, "total_amount": "100"})),
+                    event("InvoiceFailed", json!({"invoice_id": 2, "reason": "Overdue"})),
+                    event("UsageRecorded", json!({"subscription_id": 1, "metric": "api", "units": 5, "timestamp": {"millis": 9}})),
+                ],
+            )
+            .unwrap();
+
+        let active = SubscriptionFilter {
+            active: Some(true),
+            ..Default::default()
+        };
+        let subscriptions = store.subscriptions(&active).unwrap();
+        assert_eq!(subscriptions.len(), 1);
+        assert_eq!(subscriptions[0].merchant.as_deref(), Some("m"));
+        assert!(!store.subscription(2).unwrap().unwrap().active);
+
+        let invoice = store.invoice(1).unwrap().unwrap();
+        assert_eq!(
+            (invoice.status, invoice.subscriber.as_deref()),
+            (InvoiceStatus::Paid, Some("s1"))
+        );
+        let failed = InvoiceFilter {
+            status: Some(InvoiceStatus::Failed),
+            ..Default::default()
+        };
+        assert_eq!(
+            store.invoices(&failed).unwrap()[0]
+                .failure_reason
+                .as_deref(),
+            Some("Overdue")
+        );
+
+        let usage = store
+            .usage(&UsageFilter {
+                subscription_id: Some(1),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!((usage[0].units, usage[0].timestamp), (5, 9));
+    }
+}
@@ -0,0 +1,494 @@
+//! Watches subscriptions and drives renewals and auto-pay in batches.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::sync::Arc;
+use std::thread;
+
+use casperflow_contracts::billing_engine::{BillingEngine, BillingEngineHostRef, InvoiceFilter, InvoiceStatus};
+use casperflow_contracts::registry::{Component, Registry};
+use casperflow_contracts::stake_to_pay::{StakeToPay, StakeToPayHostRef};
+use casperflow_contracts::subscription_manager::{
+    Subscription, SubscriptionManager, SubscriptionManagerHostRef,
+};
+use casperflow_contracts::time::{Duration, Timestamp, DAY};
+use casperflow_contracts::usage_meter::{UsageMeter, UsageMeterHostRef};
+use odra::host::{HostEnv, HostRefLoader};
+use odra::prelude::{Address, OdraError};
+
+use crate::metrics::Metrics;
+use crate::retry::Backoff;
+
+/// Payment method of subscriptions paid from staking rewards
+const PAYMENT_METHOD_STAKED: u8 = 1;
+
+const RENEW_CALL: &str = "renew_subscriptions";
+const AUTO_PAY_CALL: &str = "auto_pay_invoices";
+
+/// Gas budget and pacing of the keeper
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// Gas of a batch call before any items
+    pub base_gas: u64,
+    /// Gas added per subscription renewed
+    pub gas_per_renewal: u64,
+    /// Gas added per invoice auto-paid
+    pub gas_per_payment: u64,
+    /// Most gas spent on a single batch call
+    pub max_batch_gas: u64,
+    /// Subscriptions due within this window are reported as upcoming
+    pub lookahead: Duration,
+    /// Retries of a failed batch
+    pub backoff: Backoff,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            base_gas: 2_500_000_000,
+            gas_per_renewal: 5_000_000_000,
+            gas_per_payment: 4_000_000_000,
+            max_batch_gas: 100_000_000_000,
+            lookahead: DAY,
+            backoff: Backoff::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Items that fit in one batch, at least one
+    fn batch_size(&self, gas_per_item: u64) -> usize {
+        let items = self.max_batch_gas.saturating_sub(self.base_gas) / gas_per_item.max(1);
+        items.max(1) as usize
+    }
+
+    fn batch_gas(&self, gas_per_item: u64, items: usize) -> u64 {
+        self.base_gas + gas_per_item * items as u64
+    }
+}
+
+/// Errors raised while starting the keeper
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// Component is not registered in the Registry
+    NotRegistered(Component),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NotRegistered(component) => write!(f, "{component:?} is not registered"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// What a tick did
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Tick {
+    /// Invoices created by renewals
+    pub invoices: Vec<u64>,
+    /// Invoices settled from staking rewards
+    pub auto_paid: u32,
+    /// Batches that failed after every retry
+    pub failed_batches: u32,
+}
+
+/// Subscription being watched for its next renewal
+#[derive(Clone, Copy, Debug)]
+struct Watched {
+    plan_id: u64,
+    subscriber: Address,
+    next_billing_at: Timestamp,
+    payment_method: u8,
+}
+
+impl From<Subscription> for Watched {
+    fn from(subscription: Subscription) -> Self {
+        Self {
+            plan_id: subscription.plan_id,
+            subscriber: subscription.subscriber,
+            next_billing_at: subscription.next_billing_at,
+            payment_method: subscription.payment_method,
+        }
+    }
+}
+
+/// Keeper over the contracts registered in a [`Registry`]
+///
+/// The keeper discovers subscriptions as they are created, renews those past
+/// `next_billing_at` and the late-usage window through
+/// `SubscriptionManager::renew_subscriptions`, and settles the first-cycle and
+/// renewal invoices of Stake-to-Pay subscribers through
+/// `StakeToPay::auto_pay_invoices`.
+/// Both entry points skip ineligible IDs, so a batch that is retried or sent
+/// twice has no further effect.
+pub struct Keeper {
+    env: HostEnv,
+    manager: SubscriptionManagerHostRef,
+    meter: UsageMeterHostRef,
+    engine: BillingEngineHostRef,
+    stake_to_pay: StakeToPayHostRef,
+    config: Config,
+    metrics: Arc<Metrics>,
+    sleep: Box<dyn FnMut(std::time::Duration)>,
+    /// Active subscriptions by ID
+    watched: BTreeMap<u64, Watched>,
+    /// Highest subscription ID seen
+    scanned: u64,
+    /// First-cycle and renewal invoices waiting for auto-pay
+    pending_payments: BTreeSet<u64>,
+}
+
+impl Keeper {
+    /// Keeper for the components registered in `registry`, calling as `env.caller()`
+    pub fn new(env: HostEnv, registry: Address, config: Config, metrics: Arc<Metrics>) -> Result<Self, Error> {
+        let registry = Registry::load(&env, registry);
+        let address = |component| registry.get_address(component).ok_or(Error::NotRegistered(component));
+        Ok(Self {
+            manager: SubscriptionManager::load(&env, address(Component::SubscriptionManager)?),
+            meter: UsageMeter::load(&env, address(Component::UsageMeter)?),
+            engine: BillingEngine::load(&env, address(Component::BillingEngine)?),
+            stake_to_pay: StakeToPay::load(&env, address(Component::StakeToPay)?),
+            env,
+            config,
+            metrics,
+            sleep: Box::new(thread::sleep),
+            watched: BTreeMap::new(),
+            scanned: 0,
+            pending_payments: BTreeSet::new(),
+        })
+    }
+
+    /// Replace the sleep between retries
+    pub fn with_sleep(mut self, sleep: impl FnMut(std::time::Duration) + 'static) -> Self {
+        self.sleep = Box::new(sleep);
+        self
+    }
+
+    /// Invoices still waiting for auto-pay
+    pub fn pending_payments(&self) -> impl Iterator<Item = u64> + '_ {
+        self.pending_payments.iter().copied()
+    }
+
+    /// Renew every due subscription and auto-pay the invoices of Stake-to-Pay subscribers
+    pub fn tick(&mut self) -> Tick {
+        let mut tick = Tick::default();
+        let now = Timestamp::from_millis(self.env.block_time());
+        let late_usage_window = self.meter.get_late_usage_window();
+        self.discover();
+
+        let due: Vec<u64> = self
+            .watched
+            .iter()
+            .filter(|(_, watched)| watched.next_billing_at + late_usage_window <= now)
+            .map(|(id, _)| *id)
+            .collect();
+        self.metrics.due.set(due.len() as i64);
+        // Drop subscriptions cancelled or switched off auto-renew since they were seen
+        let due: Vec<u64> = due
+            .into_iter()
+            .filter(|id| {
+                let renewing = self.refresh(*id);
+                if !renewing {
+                    self.watched.remove(id);
+                }
+                renewing
+            })
+            .collect();
+
+        for batch in due.chunks(self.config.batch_size(self.config.gas_per_renewal)) {
+            let gas = self.config.batch_gas(self.config.gas_per_renewal, batch.len());
+            match self.send(RENEW_CALL, gas, |keeper| keeper.manager.try_renew_subscriptions(batch.to_vec())) {
+                Ok(invoices) => {
+                    tracing::info!(subscriptions = batch.len(), invoices = invoices.len(), "renewed");
+                    self.metrics.renewals.inc_by(invoices.len() as u64);
+                    for id in batch {
+                        self.refresh(*id);
+                    }
+                    self.queue_auto_payments(&invoices);
+                    tick.invoices.extend(invoices);
+                }
+                Err(_) => tick.failed_batches += 1,
+            }
+        }
+
+        let pending: Vec<u64> = self.pending_payments.iter().copied().collect();
+        for batch in pending.chunks(self.config.batch_size(self.config.gas_per_payment)) {
+            let gas = self.config.batch_gas(self.config.gas_per_payment, batch.len());
+            match self.send(AUTO_PAY_CALL, gas, |keeper| keeper.stake_to_pay.try_auto_pay_invoices(batch.to_vec())) {
+                Ok(paid) => {
+                    tracing::info!(invoices = batch.len(), paid, "auto-paid");
+                    self.metrics.auto_payments.inc_by(paid as u64);
+                    tick.auto_paid += paid;
+                    // Invoices still pending wait for more rewards; paid and failed ones are done
+                    for id in batch {
+                        let pending = self
+                            .engine
+                            .get_invoice(*id)
+                            .is_some_and(|invoice| invoice.status == InvoiceStatus::Pending);
+                        if !pending {
+                            self.pending_payments.remove(id);
+                        }
+                    }
+                }
+                Err(_) => tick.failed_batches += 1,
+            }
+        }
+
+        let lookahead = now + self.config.lookahead + late_usage_window;
+        let upcoming = self
+            .watched
+            .values()
+            .filter(|watched| watched.next_billing_at + late_usage_window > now)
+            .filter(|watched| watched.next_billing_at + late_usage_window <= lookahead)
+            .count();
+        self.metrics.tracked.set(self.watched.len() as i64);
+        self.metrics.upcoming.set(upcoming as i64);
+        self.metrics.pending_payments.set(self.pending_payments.len() as i64);
+        self.metrics.last_tick.set(now.as_secs() as i64);
+        self.metrics.ticks.inc();
+        tick
+    }
+
+    /// Start watching subscriptions created since the last tick
+    fn discover(&mut self) {
+        let total = self.manager.total_subscriptions();
+        for id in self.scanned + 1..=total {
+            self.refresh(id);
+            // Stake-to-Pay subscriptions start with their first cycle invoiced but unpaid
+            let staked = self.watched.get(&id).is_some_and(|watched| watched.payment_method == PAYMENT_METHOD_STAKED);
+            if staked {
+                let first: Vec<u64> = self
+                    .engine
+                    .get_subscription_invoices_page(id, 0, 1, InvoiceFilter::default())
+                    .items
+                    .iter()
+                    .map(|invoice| invoice.id)
+                    .collect();
+                self.queue_auto_payments(&first);
+            }
+        }
+        self.scanned = total;
+    }
+
+    /// Re-read a subscription; returns whether it is still worth renewing
+    fn refresh(&mut self, id: u64) -> bool {
+        match self.manager.get_subscription(id) {
+            Some(subscription) if subscription.is_active => {
+                let renewing = subscription.auto_renew;
+                self.watched.insert(id, subscription.into());
+                renewing
+            }
+            _ => {
+                self.watched.remove(&id);
+                false
+            }
+        }
+    }
+
+    /// Queue the invoices of subscriptions paid from staking rewards
+    fn queue_auto_payments(&mut self, invoices: &[u64]) {
+        for id in invoices {
+            let Some(invoice) = self.engine.get_invoice(*id) else {
+                continue;
+            };
+            let Some(watched) = self.watched.get(&invoice.subscription_id) else {
+                continue;
+            };
+            if watched.payment_method == PAYMENT_METHOD_STAKED
+                && self.stake_to_pay.is_auto_pay_enabled(watched.subscriber, watched.plan_id)
+            {
+                self.pending_payments.insert(*id);
+            }
+        }
+    }
+
+    /// Send a batch with `gas`, retrying failures with backoff
+    fn send<T>(
+        &mut self,
+        call: &'static str,
+        gas: u64,
+        mut batch: impl FnMut(&mut Self) -> Result<T, OdraError>,
+    ) -> Result<T, OdraError> {
+        let backoff = self.config.backoff;
+        let metrics = Arc::clone(&self.metrics);
+        let mut sleep = std::mem::replace(&mut self.sleep, Box::new(|_| {}));
+        let result = backoff.retry(
+            &mut *sleep,
+            |err| {
+                tracing::warn!(call, ?err, "batch failed");
+                metrics.retries.with_label_values(&[call]).inc();
+            },
+            || {
+                self.env.set_gas(gas);
+                batch(self)
+            },
+        );
+        self.sleep = sleep;
+
+        let outcome = if result.is_ok() { "ok" } else { "failed" };
+        self.metrics.batches.with_label_values(&[call, outcome]).inc();
+        if let Err(err) = &result {
+            tracing::error!(call, ?err, "batch failed after retries");
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use casperflow_contracts::access::Role;
+    use casperflow_contracts::pausable::PauseGroup;
+    use casperflow_contracts::usage_meter::DEFAULT_LATE_USAGE_WINDOW;
+    use odra::casper_types::U512;
+    use odra::host::{Deployer, HostRef, NoArgs};
+
+    use super::*;
+
+    struct Protocol {
+        env: HostEnv,
+        registry: Address,
+        manager: SubscriptionManagerHostRef,
+        engine: BillingEngineHostRef,
+        stake_to_pay: StakeToPayHostRef,
+    }
+
+    fn deploy() -> Protocol {
+        let env = odra_test::env();
+        let mut registry = Registry::deploy(&env, NoArgs);
+        let mut manager = SubscriptionManager::deploy(&env, NoArgs);
+        let mut meter = UsageMeter::deploy(&env, NoArgs);
+        let mut engine = BillingEngine::deploy(&env, NoArgs);
+        let mut stake_to_pay = StakeToPay::deploy(&env, NoArgs);
+        registry.register(Component::SubscriptionManager, manager.address());
+        registry.register(Component::UsageMeter, meter.address());
+        registry.register(Component::BillingEngine, engine.address());
+        registry.register(Component::StakeToPay, stake_to_pay.address());
+        manager.set_registry(registry.address());
+        meter.set_registry(registry.address());
+        engine.set_registry(registry.address());
+        stake_to_pay.set_registry(registry.address());
+
+        let keeper = env.get_account(9);
+        manager.grant_role(Role::Keeper, keeper);
+        stake_to_pay.grant_role(Role::Keeper, keeper);
+        Protocol {
+            registry: registry.address(),
+            env,
+            manager,
+            engine,
+            stake_to_pay,
+        }
+    }
+
+    fn keeper(protocol: &Protocol, metrics: Arc<Metrics>) -> Keeper {
+        let config = Config {
+            max_batch_gas: Config::default().base_gas + Config::default().gas_per_renewal * 2,
+            ..Config::default()
+        };
+        Keeper::new(protocol.env.clone(), protocol.registry, config, metrics)
+            .unwrap()
+            .with_sleep(|_| {})
+    }
+
+    #[test]
+    fn test_new_requires_registered_components() {
+        let env = odra_test::env();
+        let mut registry = Registry::deploy(&env, NoArgs);
+        let manager = SubscriptionManager::deploy(&env, NoArgs);
+        registry.register(Component::SubscriptionManager, manager.address());
+
+        let metrics = Arc::new(Metrics::new());
+        assert_eq!(
+            Keeper::new(env.clone(), registry.address(), Config::default(), metrics).err(),
+            Some(Error::NotRegistered(Component::UsageMeter))
+        );
+    }
+
+    #[test]
+    fn test_renews_due_subscriptions_and_auto_pays() {
+        let mut protocol = deploy();
+        let env = protocol.env.clone();
+        let (price, cycle) = (U512::from(1_000_000_000u64), Duration::from_days(30));
+        let plan_id = protocol.manager.create_plan("Pro".to_string(), price, U512::zero(), cycle);
+        protocol.stake_to_pay.with_tokens(price * 10u64).fund_rewards();
+
+        let staker = env.get_account(1);
+        env.set_caller(staker);
+        protocol.stake_to_pay.with_tokens(price * 1_000u64).deposit();
+        protocol.stake_to_pay.enable_stake_to_pay();
+        protocol.stake_to_pay.enable_auto_pay(plan_id);
+        protocol.manager.subscribe(plan_id, true, PAYMENT_METHOD_STAKED);
+        for account in 2..5 {
+            env.set_caller(env.get_account(account));
+            protocol.manager.with_tokens(price).subscribe(plan_id, true, 0);
+        }
+        env.set_caller(env.get_account(5));
+        let expiring = protocol.manager.with_tokens(price).subscribe(plan_id, false, 0);
+
+        env.set_caller(env.get_account(9));
+        let metrics = Arc::new(Metrics::new());
+        let mut keeper = keeper(&protocol, Arc::clone(&metrics));
+        assert_eq!(keeper.tick(), Tick::default());
+        assert_eq!(metrics.tracked.get(), 5);
+        // The staker's first cycle waits for rewards to accrue
+        assert_eq!(keeper.pending_payments().count(), 1);
+
+        env.advance_block_time((cycle + DEFAULT_LATE_USAGE_WINDOW).as_millis());
+        let tick = keeper.tick();
+        // Four renewals in batches of two; the staker's first and renewal
+        // invoices are paid from rewards
+        assert_eq!(tick.invoices.len(), 4);
+        assert_eq!(tick.auto_paid, 2);
+        assert_eq!(metrics.batches.with_label_values(&[RENEW_CALL, "ok"]).get(), 2);
+        assert_eq!(metrics.renewals.get(), 4);
+        assert_eq!(keeper.pending_payments().count(), 0);
+        let paid = tick
+            .invoices
+            .iter()
+            .filter_map(|id| protocol.engine.get_invoice(*id))
+            .filter(|invoice| invoice.status == InvoiceStatus::Paid)
+            .collect::<Vec<_>>();
+        assert_eq!(paid.len(), 1);
+        assert_eq!(paid[0].subscriber, staker);
+
+        // The non-renewing subscription is dropped and nothing is renewed twice
+        assert_eq!(metrics.tracked.get(), 4);
+        assert!(protocol.manager.get_subscription(expiring).unwrap().is_active);
+        assert_eq!(keeper.tick().invoices, Vec::<u64>::new());
+    }
+
+    #[test]
+    fn test_failed_batches_are_retried_next_tick() {
+        let mut protocol = deploy();
+        let env = protocol.env.clone();
+        let (price, cycle) = (U512::from(1_000u64), Duration::from_days(30));
+        let plan_id = protocol.manager.create_plan("Pro".to_string(), price, U512::zero(), cycle);
+        env.set_caller(env.get_account(1));
+        protocol.manager.with_tokens(price).subscribe(plan_id, true, 0);
+
+        env.set_caller(env.get_account(0));
+        protocol.manager.pause(PauseGroup::Invoicing);
+        env.set_caller(env.get_account(9));
+        let metrics = Arc::new(Metrics::new());
+        let mut keeper = keeper(&protocol, Arc::clone(&metrics));
+        env.advance_block_time((cycle + DEFAULT_LATE_USAGE_WINDOW).as_millis());
+
+        let tick = keeper.tick();
+        assert_eq!(tick.failed_batches, 1);
+        let attempts = Config::default().backoff.attempts as u64;
+        assert_eq!(metrics.retries.with_label_values(&[RENEW_CALL]).get(), attempts);
+        assert_eq!(metrics.batches.with_label_values(&[RENEW_CALL, "failed"]).get(), 1);
+        assert_eq!(metrics.due.get(), 1);
+
+        env.set_caller(env.get_account(0));
+        protocol.manager.unpause(PauseGroup::Invoicing);
+        env.set_caller(env.get_account(9));
+        assert_eq!(keeper.tick().invoices.len(), 1);
+        assert_eq!(metrics.due.get(), 1);
+        assert_eq!(keeper.tick().invoices.len(), 0);
+        assert_eq!(metrics.due.get(), 0);
+    }
+}
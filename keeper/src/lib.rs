@@ -0,0 +1,14 @@
+//! CasperFlow keeper
+//!
+//! Renews subscriptions as they come due and settles the renewal invoices of
+//! Stake-to-Pay subscribers from their staking rewards. The [`Keeper`] talks
+//! to the contracts through Odra host references, so the same code runs
+//! against the mock VM in tests and a Casper node in production.
+
+pub mod keeper;
+pub mod metrics;
+pub mod retry;
+
+pub use keeper::{Config, Error, Keeper, Tick};
+pub use metrics::Metrics;
+pub use retry::Backoff;
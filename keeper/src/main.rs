@@ -0,0 +1,98 @@
+//! Keeper daemon: ticks on an interval and serves Prometheus metrics.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use casperflow_contracts::time::HOUR;
+use casperflow_keeper::{Backoff, Config, Keeper, Metrics};
+use clap::Parser;
+use odra::prelude::Address;
+use tracing_subscriber::EnvFilter;
+
+/// Renews due CasperFlow subscriptions and settles Stake-to-Pay auto-payments.
+///
+/// The node, chain name and keeper key are read from the
+/// `ODRA_CASPER_LIVENET_*` environment variables used by Odra's livenet
+/// backend. The keeper account needs the Keeper role on SubscriptionManager
+/// and StakeToPay.
+#[derive(Parser)]
+#[command(version)]
+struct Args {
+    /// Registry contract (`hash-…`)
+    #[arg(long, env = "CASPERFLOW_REGISTRY")]
+    registry: String,
+    /// Seconds between ticks
+    #[arg(long, default_value_t = 60)]
+    interval: u64,
+    /// Address serving `/metrics`
+    #[arg(long, default_value = "127.0.0.1:9184")]
+    metrics: SocketAddr,
+    /// Gas of a batch call before any items, in motes
+    #[arg(long, default_value_t = Config::default().base_gas)]
+    base_gas: u64,
+    /// Gas added per subscription renewed
+    #[arg(long, default_value_t = Config::default().gas_per_renewal)]
+    gas_per_renewal: u64,
+    /// Gas added per invoice auto-paid
+    #[arg(long, default_value_t = Config::default().gas_per_payment)]
+    gas_per_payment: u64,
+    /// Most gas spent on a single batch call
+    #[arg(long, default_value_t = Config::default().max_batch_gas)]
+    max_batch_gas: u64,
+    /// Attempts per batch before it is left for the next tick
+    #[arg(long, default_value_t = Backoff::default().attempts)]
+    attempts: u32,
+    /// Hours ahead reported as upcoming renewals
+    #[arg(long, default_value_t = 24)]
+    lookahead_hours: u64,
+}
+
+fn main() {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")))
+        .init();
+    let args = Args::parse();
+    let registry = Address::new(&args.registry).unwrap_or_else(|err| {
+        eprintln!("invalid --registry {}: {err:?}", args.registry);
+        std::process::exit(2);
+    });
+
+    let metrics = Arc::new(Metrics::new());
+    match casperflow_keeper::metrics::serve(args.metrics, Arc::clone(&metrics)) {
+        Ok(addr) => tracing::info!(%addr, "serving metrics"),
+        Err(err) => {
+            eprintln!("cannot serve metrics on {}: {err}", args.metrics);
+            std::process::exit(1);
+        }
+    }
+
+    let config = Config {
+        base_gas: args.base_gas,
+        gas_per_renewal: args.gas_per_renewal,
+        gas_per_payment: args.gas_per_payment,
+        max_batch_gas: args.max_batch_gas,
+        lookahead: HOUR * args.lookahead_hours,
+        backoff: Backoff {
+            attempts: args.attempts,
+            ..Backoff::default()
+        },
+    };
+    let env = odra_casper_livenet_env::env();
+    tracing::info!(keeper = ?env.caller(), registry = args.registry, "starting");
+    let mut keeper = Keeper::new(env, registry, config, metrics).unwrap_or_else(|err| {
+        eprintln!("cannot start keeper: {err}");
+        std::process::exit(1);
+    });
+    loop {
+        let tick = keeper.tick();
+        tracing::info!(
+            invoices = tick.invoices.len(),
+            auto_paid = tick.auto_paid,
+            failed_batches = tick.failed_batches,
+            "tick"
+        );
+        thread::sleep(Duration::from_secs(args.interval));
+    }
+}
@@ -0,0 +1,165 @@
+//! Prometheus metrics and the `/metrics` endpoint.
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+
+use prometheus::{Encoder, IntCounter, IntCounterVec, IntGauge, Opts, Registry, TextEncoder};
+
+/// Counters and gauges exported by the keeper
+pub struct Metrics {
+    registry: Registry,
+    /// Completed ticks
+    pub ticks: IntCounter,
+    /// Invoices created by renewal batches
+    pub renewals: IntCounter,
+    /// Invoices settled by auto-pay batches
+    pub auto_payments: IntCounter,
+    /// Batches sent, by `call` and `outcome` (`ok` or `failed`)
+    pub batches: IntCounterVec,
+    /// Failed attempts that were retried or given up on, by `call`
+    pub retries: IntCounterVec,
+    /// Active, auto-renewing subscriptions being watched
+    pub tracked: IntGauge,
+    /// Watched subscriptions due at the last tick
+    pub due: IntGauge,
+    /// Watched subscriptions due within the lookahead
+    pub upcoming: IntGauge,
+    /// Invoices still waiting for auto-pay
+    pub pending_payments: IntGauge,
+    /// Block time of the last tick, in seconds
+    pub last_tick: IntGauge,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new();
+        let counter = |name: &str, help: &str| {
+            let counter = IntCounter::new(name, help).expect("valid metric");
+            registry.register(Box::new(counter.clone())).expect("unique metric");
+            counter
+        };
+        let counter_vec = |name: &str, help: &str, labels: &[&str]| {
+            let counter = IntCounterVec::new(Opts::new(name, help), labels).expect("valid metric");
+            registry.register(Box::new(counter.clone())).expect("unique metric");
+            counter
+        };
+        let gauge = |name: &str, help: &str| {
+            let gauge = IntGauge::new(name, help).expect("valid metric");
+            registry.register(Box::new(gauge.clone())).expect("unique metric");
+            gauge
+        };
+
+        Self {
+            ticks: counter("casperflow_keeper_ticks_total", "Completed keeper ticks"),
+            renewals: counter("casperflow_keeper_renewals_total", "Invoices created by renewal batches"),
+            auto_payments: counter(
+                "casperflow_keeper_auto_payments_total",
+                "Invoices settled from staking rewards by auto-pay batches",
+            ),
+            batches: counter_vec(
+                "casperflow_keeper_batches_total",
+                "Batches sent, by entry point and outcome",
+                &["call", "outcome"],
+            ),
+            retries: counter_vec(
+                "casperflow_keeper_retries_total",
+                "Failed batch attempts, by entry point",
+                &["call"],
+            ),
+            tracked: gauge(
+                "casperflow_keeper_tracked_subscriptions",
+                "Active auto-renewing subscriptions being watched",
+            ),
+            due: gauge("casperflow_keeper_due_subscriptions", "Subscriptions due at the last tick"),
+            upcoming: gauge(
+                "casperflow_keeper_upcoming_subscriptions",
+                "Subscriptions coming due within the lookahead",
+            ),
+            pending_payments: gauge(
+                "casperflow_keeper_pending_payments",
+                "Invoices waiting for auto-pay",
+            ),
+            last_tick: gauge(
+                "casperflow_keeper_last_tick_timestamp_seconds",
+                "Block time of the last completed tick",
+            ),
+            registry,
+        }
+    }
+
+    /// Metrics in the Prometheus text format
+    pub fn encode(&self) -> String {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .expect("text encoding");
+        String::from_utf8(buffer).expect("utf-8 metrics")
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Serve `GET /metrics` on `addr` from a background thread
+pub fn serve(addr: SocketAddr, metrics: Arc<Metrics>) -> std::io::Result<SocketAddr> {
+    let listener = TcpListener::bind(addr)?;
+    let local_addr = listener.local_addr()?;
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            if let Err(err) = respond(stream, &metrics) {
+                tracing::debug!(%err, "metrics request failed");
+            }
+        }
+    });
+    Ok(local_addr)
+}
+
+fn respond(mut stream: TcpStream, metrics: &Metrics) -> std::io::Result<()> {
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+    let (status, content_type, body) = if request_line.starts_with("GET /metrics ") {
+        ("200 OK", "text/plain; version=0.0.4", metrics.encode())
+    } else {
+        ("404 Not Found", "text/plain", "not found\n".to_string())
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+
+    fn get(addr: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn test_serves_metrics() {
+        let metrics = Arc::new(Metrics::new());
+        metrics.renewals.inc_by(3);
+        metrics.batches.with_label_values(&["renew_subscriptions", "ok"]).inc();
+        let addr = serve("127.0.0.1:0".parse().unwrap(), metrics).unwrap();
+
+        let response = get(addr, "/metrics");
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("casperflow_keeper_renewals_total 3"));
+        assert!(response.contains(r#"casperflow_keeper_batches_total{call="renew_subscriptions",outcome="ok"} 1"#));
+        assert!(get(addr, "/").starts_with("HTTP/1.1 404"));
+    }
+}
@@ -0,0 +1,103 @@
+//! Retrying contract calls with exponential backoff.
+
+use std::time::Duration;
+
+use odra::prelude::OdraError;
+
+/// Exponential backoff between attempts of a call
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Backoff {
+    /// Total attempts, including the first
+    pub attempts: u32,
+    /// Delay before the first retry
+    pub initial: Duration,
+    /// Upper bound on any delay
+    pub max: Duration,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            attempts: 4,
+            initial: Duration::from_secs(5),
+            max: Duration::from_secs(120),
+        }
+    }
+}
+
+impl Backoff {
+    /// Delays before each retry: `initial`, doubling up to `max`
+    pub fn delays(&self) -> impl Iterator<Item = Duration> + '_ {
+        (0..self.attempts.saturating_sub(1)).map(|retry| {
+            self.initial
+                .checked_mul(1 << retry.min(31))
+                .map_or(self.max, |delay| delay.min(self.max))
+        })
+    }
+
+    /// Run `call` until it succeeds or the attempts run out, sleeping with
+    /// `sleep` between attempts and reporting each failure to `on_failure`
+    pub fn retry<T>(
+        &self,
+        sleep: &mut dyn FnMut(Duration),
+        mut on_failure: impl FnMut(&OdraError),
+        mut call: impl FnMut() -> Result<T, OdraError>,
+    ) -> Result<T, OdraError> {
+        let mut delays = self.delays();
+        loop {
+            match call() {
+                Ok(value) => return Ok(value),
+                Err(err) => {
+                    on_failure(&err);
+                    match delays.next() {
+                        Some(delay) => sleep(delay),
+                        None => return Err(err),
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use odra::prelude::ExecutionError;
+
+    #[test]
+    fn test_retries_with_capped_backoff() {
+        let backoff = Backoff {
+            attempts: 5,
+            initial: Duration::from_secs(1),
+            max: Duration::from_secs(5),
+        };
+        let delays: Vec<_> = backoff.delays().map(|delay| delay.as_secs()).collect();
+        assert_eq!(delays, [1, 2, 4, 5]);
+
+        let mut slept = Vec::new();
+        let mut calls = 0;
+        let result = backoff.retry(
+            &mut |delay| slept.push(delay.as_secs()),
+            |_| {},
+            || {
+                calls += 1;
+                if calls < 3 {
+                    Err(OdraError::ExecutionError(ExecutionError::UnwrapError))
+                } else {
+                    Ok(calls)
+                }
+            },
+        );
+        assert_eq!(result, Ok(3));
+        assert_eq!(slept, [1, 2]);
+
+        let mut failures = 0;
+        let result: Result<(), _> = backoff.retry(
+            &mut |_| {},
+            |_| failures += 1,
+            || Err(OdraError::ExecutionError(ExecutionError::UnwrapError)),
+        );
+        assert!(result.is_err());
+        assert_eq!(failures, 5);
+    }
+}
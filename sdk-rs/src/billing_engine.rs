@@ -0,0 +1,289 @@
+//! BillingEngine client.
+
+use casperflow_contracts::analytics::{ReceivableStats, RevenueStats};
+use casperflow_contracts::billing_engine::{Invoice, InvoiceFilter, InvoicePage};
+use casperflow_contracts::time::Timestamp;
+use odra::casper_types::U512;
+use odra::prelude::Address;
+
+use crate::error::Result;
+use crate::governance::Governance;
+use crate::transport::{Call, Transport};
+
+/// Client for the BillingEngine contract
+pub struct BillingEngineClient<'a, T> {
+    transport: &'a T,
+    address: Address,
+}
+
+impl<'a, T: Transport> BillingEngineClient<'a, T> {
+    pub fn new(transport: &'a T, address: Address) -> Self {
+        Self { transport, address }
+    }
+
+    pub fn address(&self) -> Address {
+        self.address
+    }
+
+    /// Roles, pausing, timelock and upgrade entry points
+    pub fn governance(&self) -> Governance<'a, T> {
+        Governance::new(self.transport, self.address)
+    }
+
+    fn call(&self, entry_point: &str) -> Call {
+        Call::new(self.address, entry_point)
+    }
+
+    fn view(&self, entry_point: &str) -> Call {
+        Call::view(self.address, entry_point)
+    }
+
+    // ============ INVOICE FUNCTIONS ============
+
+    /// Invoice a billing period (keeper); returns the invoice ID
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_invoice(
+        &self,
+        subscription_id: u64,
+        plan_id: u64,
+        subscriber: Address,
+        merchant: Address,
+        base_amount: U512,
+        usage_price: U512,
+        usage_units: u64,
+        period_start: Timestamp,
+        period_end: Timestamp,
+    ) -> Result<u64> {
+        self.transport.call(
+            self.call("create_invoice")
+                .arg("subscription_id", subscription_id)
+                .arg("plan_id", plan_id)
+                .arg("subscriber", subscriber)
+                .arg("merchant", merchant)
+                .arg("base_amount", base_amount)
+                .arg("usage_price", usage_price)
+                .arg("usage_units", usage_units)
+                .arg("period_start", period_start)
+                .arg("period_end", period_end),
+        )
+    }
+
+    /// Pay an invoice from the wallet, attaching `amount`; excess is refunded
+    pub fn pay_invoice(&self, invoice_id: u64, amount: U512) -> Result<()> {
+        self.transport.call(
+            self.call("pay_invoice")
+                .arg("invoice_id", invoice_id)
+                .with_amount(amount),
+        )
+    }
+
+    /// Invoice and settle a new subscription's first cycle (SubscriptionManager only)
+    #[allow(clippy::too_many_arguments)]
+    pub fn settle_initial_payment(
+        &self,
+        subscription_id: u64,
+        plan_id: u64,
+        subscriber: Address,
+        merchant: Address,
+        period_start: Timestamp,
+        period_end: Timestamp,
+        amount: U512,
+    ) -> Result<u64> {
+        self.transport.call(
+            self.call("settle_initial_payment")
+                .arg("subscription_id", subscription_id)
+                .arg("plan_id", plan_id)
+                .arg("subscriber", subscriber)
+                .arg("merchant", merchant)
+                .arg("period_start", period_start)
+                .arg("period_end", period_end)
+                .with_amount(amount),
+        )
+    }
+
+    /// Settle an invoice from staking rewards (StakeToPay only)
+    pub fn pay_invoice_from_staking(&self, invoice_id: u64, payer: Address, amount: U512) -> Result<()> {
+        self.transport.call(
+            self.call("pay_invoice_from_staking")
+                .arg("invoice_id", invoice_id)
+                .arg("payer", payer)
+                .with_amount(amount),
+        )
+    }
+
+    /// Mark an invoice as failed (Keeper)
+    pub fn fail_invoice(&self, invoice_id: u64, reason: &str) -> Result<()> {
+        self.transport.call(
+            self.call("fail_invoice")
+                .arg("invoice_id", invoice_id)
+                .arg("reason", reason.to_string()),
+        )
+    }
+
+    /// Migrate up to `limit` invoices to the current schema (Keeper)
+    pub fn migrate_invoices(&self, limit: u32) -> Result<u32> {
+        self.transport
+            .call(self.call("migrate_invoices").arg("limit", limit))
+    }
+
+    // ============ VIEW FUNCTIONS ============
+
+    pub fn get_invoice(&self, invoice_id: u64) -> Result<Option<Invoice>> {
+        self.transport
+            .call(self.view("get_invoice").arg("invoice_id", invoice_id))
+    }
+
+    pub fn get_subscription_invoice_count(&self, subscription_id: u64) -> Result<u32> {
+        self.transport.call(
+            self.view("get_subscription_invoice_count")
+                .arg("subscription_id", subscription_id),
+        )
+    }
+
+    pub fn get_subscription_invoices_page(
+        &self,
+        subscription_id: u64,
+        cursor: u32,
+        limit: u32,
+        filter: InvoiceFilter,
+    ) -> Result<InvoicePage> {
+        self.transport.call(
+            self.view("get_subscription_invoices_page")
+                .arg("subscription_id", subscription_id)
+                .arg("cursor", cursor)
+                .arg("limit", limit)
+                .arg("filter", filter),
+        )
+    }
+
+    pub fn get_user_invoice_count(&self, user: Address) -> Result<u32> {
+        self.transport
+            .call(self.view("get_user_invoice_count").arg("user", user))
+    }
+
+    pub fn get_user_invoices_page(
+        &self,
+        user: Address,
+        cursor: u32,
+        limit: u32,
+        filter: InvoiceFilter,
+    ) -> Result<InvoicePage> {
+        self.transport.call(
+            self.view("get_user_invoices_page")
+                .arg("user", user)
+                .arg("cursor", cursor)
+                .arg("limit", limit)
+                .arg("filter", filter),
+        )
+    }
+
+    pub fn get_merchant_invoice_count(&self, merchant: Address) -> Result<u32> {
+        self.transport
+            .call(self.view("get_merchant_invoice_count").arg("merchant", merchant))
+    }
+
+    pub fn get_merchant_invoices_page(
+        &self,
+        merchant: Address,
+        cursor: u32,
+        limit: u32,
+        filter: InvoiceFilter,
+    ) -> Result<InvoicePage> {
+        self.transport.call(
+            self.view("get_merchant_invoices_page")
+                .arg("merchant", merchant)
+                .arg("cursor", cursor)
+                .arg("limit", limit)
+                .arg("filter", filter),
+        )
+    }
+
+    pub fn is_issued_by_manager(&self, invoice_id: u64) -> Result<bool> {
+        self.transport
+            .call(self.view("is_issued_by_manager").arg("invoice_id", invoice_id))
+    }
+
+    pub fn get_subscription_credit(&self, subscription_id: u64) -> Result<U512> {
+        self.transport
+            .call(self.view("get_subscription_credit").arg("subscription_id", subscription_id))
+    }
+
+    pub fn total_invoices(&self) -> Result<u64> {
+        self.transport.call(self.view("total_invoices"))
+    }
+
+    pub fn get_protocol_fee_bps(&self) -> Result<u64> {
+        self.transport.call(self.view("get_protocol_fee_bps"))
+    }
+
+    pub fn get_fee_recipient(&self) -> Result<Address> {
+        self.transport.call(self.view("get_fee_recipient"))
+    }
+
+    // ============ ANALYTICS ============
+
+    pub fn get_merchant_revenue(&self, merchant: Address) -> Result<U512> {
+        self.transport
+            .call(self.view("get_merchant_revenue").arg("merchant", merchant))
+    }
+
+    pub fn get_plan_receivables(&self, plan_id: u64) -> Result<ReceivableStats> {
+        self.transport
+            .call(self.view("get_plan_receivables").arg("plan_id", plan_id))
+    }
+
+    pub fn get_merchant_receivables(&self, merchant: Address) -> Result<ReceivableStats> {
+        self.transport
+            .call(self.view("get_merchant_receivables").arg("merchant", merchant))
+    }
+
+    pub fn get_merchant_outstanding(&self, merchant: Address) -> Result<U512> {
+        self.transport
+            .call(self.view("get_merchant_outstanding").arg("merchant", merchant))
+    }
+
+    pub fn get_plan_period_revenue(&self, plan_id: u64, period: u64) -> Result<RevenueStats> {
+        self.transport.call(
+            self.view("get_plan_period_revenue")
+                .arg("plan_id", plan_id)
+                .arg("period", period),
+        )
+    }
+
+    pub fn get_merchant_period_revenue(&self, merchant: Address, period: u64) -> Result<RevenueStats> {
+        self.transport.call(
+            self.view("get_merchant_period_revenue")
+                .arg("merchant", merchant)
+                .arg("period", period),
+        )
+    }
+
+    // ============ ADMIN FUNCTIONS ============
+
+    pub fn set_subscription_manager(&self, address: Address) -> Result<()> {
+        self.transport
+            .call(self.call("set_subscription_manager").arg("address", address))
+    }
+
+    pub fn set_usage_meter(&self, address: Address) -> Result<()> {
+        self.transport
+            .call(self.call("set_usage_meter").arg("address", address))
+    }
+
+    pub fn set_stake_to_pay(&self, address: Address) -> Result<()> {
+        self.transport
+            .call(self.call("set_stake_to_pay").arg("address", address))
+    }
+
+    /// Queue a new protocol fee (FeeManager)
+    pub fn set_protocol_fee_bps(&self, fee_bps: u64) -> Result<()> {
+        self.transport
+            .call(self.call("set_protocol_fee_bps").arg("fee_bps", fee_bps))
+    }
+
+    /// Queue a new fee recipient (FeeManager)
+    pub fn set_fee_recipient(&self, recipient: Address) -> Result<()> {
+        self.transport
+            .call(self.call("set_fee_recipient").arg("recipient", recipient))
+    }
+}
@@ -0,0 +1,114 @@
+//! Building and signing deploys for calls, for submission outside a transport.
+//!
+//! [`OdraTransport`](crate::OdraTransport) sends and tracks transactions
+//! itself. Services that hand deploys to a wallet, queue them or need extra
+//! approvals build them here instead.
+
+use odra::casper_types::{Deploy, ExecutableDeployItem, SecretKey, TimeDiff, Timestamp, U512};
+
+use crate::error::{Error, Result};
+use crate::transport::{Call, DEFAULT_GAS};
+
+/// Default time a deploy stays valid
+const DEFAULT_TTL_MILLIS: u64 = 30 * 60 * 1_000;
+
+/// Builds signed stored-contract deploys for [`Call`]s on one chain
+#[derive(Clone, Debug)]
+pub struct DeployBuilder {
+    chain_name: String,
+    payment: U512,
+    ttl: TimeDiff,
+    gas_price: u64,
+}
+
+impl DeployBuilder {
+    pub fn new(chain_name: &str) -> Self {
+        Self {
+            chain_name: chain_name.to_string(),
+            payment: U512::from(DEFAULT_GAS),
+            ttl: TimeDiff::from_millis(DEFAULT_TTL_MILLIS),
+            gas_price: 1,
+        }
+    }
+
+    /// Motes paid for gas
+    pub fn with_payment(mut self, payment: U512) -> Self {
+        self.payment = payment;
+        self
+    }
+
+    pub fn with_ttl(mut self, ttl: TimeDiff) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    pub fn with_gas_price(mut self, gas_price: u64) -> Self {
+        self.gas_price = gas_price;
+        self
+    }
+
+    /// Deploy calling the latest version of the call's contract package,
+    /// signed by `secret_key` as the sending account
+    ///
+    /// Attaching CSPR needs Odra's proxy session code, so payable calls with
+    /// an amount are rejected; send those through a transport.
+    pub fn build(&self, call: &Call, secret_key: &SecretKey) -> Result<Deploy> {
+        if !call.amount.is_zero() {
+            return Err(Error::Payable(call.entry_point.clone()));
+        }
+        let hash = *call
+            .contract
+            .as_contract_package_hash()
+            .ok_or(Error::NotAContract)?;
+        let session = ExecutableDeployItem::StoredVersionedContractByHash {
+            hash,
+            version: None,
+            entry_point: call.entry_point.clone(),
+            args: call.args.clone(),
+        };
+        Ok(Deploy::new_signed(
+            Timestamp::now(),
+            self.ttl,
+            self.gas_price,
+            Vec::new(),
+            self.chain_name.clone(),
+            ExecutableDeployItem::new_standard_payment(self.payment),
+            session,
+            secret_key,
+            None,
+        ))
+    }
+}
+
+/// Add an approval to a deploy that needs several signatures
+pub fn sign(deploy: &mut Deploy, secret_key: &SecretKey) {
+    deploy.sign(secret_key);
+}
+
+#[cfg(test)]
+mod tests {
+    use odra::casper_types::ContractPackageHash;
+    use odra::prelude::Address;
+
+    use super::*;
+
+    #[test]
+    fn test_builds_signed_deploys() {
+        let contract = Address::from(ContractPackageHash::new([1; 32]));
+        let key = SecretKey::ed25519_from_bytes([7; 32]).unwrap();
+        let builder = DeployBuilder::new("casper-test");
+
+        let call = Call::new(contract, "unsubscribe").arg("subscription_id", 4u64);
+        let mut deploy = builder.build(&call, &key).unwrap();
+        assert_eq!(deploy.header().chain_name(), "casper-test");
+        assert_eq!(deploy.session().entry_point_name(), "unsubscribe");
+        assert_eq!(deploy.session().args(), &call.args);
+        assert_eq!(deploy.approvals().len(), 1);
+
+        sign(&mut deploy, &SecretKey::ed25519_from_bytes([8; 32]).unwrap());
+        assert_eq!(deploy.approvals().len(), 2);
+
+        let payable = Call::new(contract, "deposit").with_amount(U512::one());
+        assert_eq!(builder.build(&payable, &key), Err(Error::Payable("deposit".to_string())));
+    }
+}
@@ -0,0 +1,58 @@
+//! SDK error type.
+
+use odra::casper_types::bytesrepr;
+use odra::prelude::{Address, ExecutionError, OdraError};
+use thiserror::Error;
+
+/// Result alias used throughout the SDK
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Errors raised while calling the contracts or decoding their output
+#[derive(Debug, Error, PartialEq)]
+pub enum Error {
+    /// The call reverted or could not be executed
+    #[error("call failed: {0:?}")]
+    Call(OdraError),
+    /// Event or result bytes could not be decoded
+    #[error("cannot decode bytes: {0}")]
+    Bytes(bytesrepr::Error),
+    /// Event name is not emitted by the contract it was read from
+    #[error("unknown event {name} from {contract}")]
+    UnknownEvent { contract: &'static str, name: String },
+    /// Event index past the end of the contract's event list
+    #[error("no event {index} on {contract:?}")]
+    EventNotFound { contract: Address, index: u32 },
+    /// Component is not registered in the Registry
+    #[error("{0} is not registered")]
+    NotRegistered(&'static str),
+    /// Deploys can only call contracts, not accounts
+    #[error("deploy target is not a contract")]
+    NotAContract,
+    /// Deploys built here cannot attach CSPR; send payable calls through a transport
+    #[error("payable call {0} cannot be sent as a stored-contract deploy")]
+    Payable(String),
+}
+
+impl Error {
+    /// Code of the contract error the call reverted with, if any
+    ///
+    /// Codes follow the contracts' ranges, e.g. 100-199 for SubscriptionManager.
+    pub fn revert_code(&self) -> Option<u16> {
+        match self {
+            Self::Call(OdraError::ExecutionError(ExecutionError::User(code))) => Some(*code),
+            _ => None,
+        }
+    }
+}
+
+impl From<OdraError> for Error {
+    fn from(err: OdraError) -> Self {
+        Self::Call(err)
+    }
+}
+
+impl From<bytesrepr::Error> for Error {
+    fn from(err: bytesrepr::Error) -> Self {
+        Self::Bytes(err)
+    }
+}
@@ -0,0 +1,284 @@
+//! Decoding contract events into the contracts' own event structs.
+//!
+//! Odra stores each event as its CES name (`event_<Name>`) followed by its
+//! fields, so the name picks the struct and the struct decodes the rest.
+
+use casperflow_contracts::access::events as access;
+use casperflow_contracts::billing_engine::events as billing_engine;
+use casperflow_contracts::pausable::events as pausable;
+use casperflow_contracts::registry::Component;
+use casperflow_contracts::stake_to_pay::events as stake_to_pay;
+use casperflow_contracts::subscription_manager::events as subscription_manager;
+use casperflow_contracts::timelock::events as timelock;
+use casperflow_contracts::upgrade::events as upgrade;
+use casperflow_contracts::usage_meter::events as usage_meter;
+use odra::casper_types::bytesrepr::{self, FromBytes};
+
+use crate::error::{Error, Result};
+
+/// Event emitted by one of the contracts
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    SubscriptionManager(SubscriptionManagerEvent),
+    UsageMeter(UsageMeterEvent),
+    BillingEngine(BillingEngineEvent),
+    StakeToPay(StakeToPayEvent),
+}
+
+impl Event {
+    /// Decode an event emitted by `component`
+    pub fn decode(component: Component, bytes: &[u8]) -> Result<Self> {
+        Ok(match component {
+            Component::SubscriptionManager => Self::SubscriptionManager(SubscriptionManagerEvent::decode(bytes)?),
+            Component::UsageMeter => Self::UsageMeter(UsageMeterEvent::decode(bytes)?),
+            Component::BillingEngine => Self::BillingEngine(BillingEngineEvent::decode(bytes)?),
+            Component::StakeToPay => Self::StakeToPay(StakeToPayEvent::decode(bytes)?),
+        })
+    }
+}
+
+/// Access control, pause, timelock and upgrade events, emitted by every contract
+#[derive(Clone, Debug, PartialEq)]
+pub enum GovernanceEvent {
+    RoleGranted(access::RoleGranted),
+    RoleRevoked(access::RoleRevoked),
+    OwnershipTransferStarted(access::OwnershipTransferStarted),
+    OwnershipTransferred(access::OwnershipTransferred),
+    Paused(pausable::Paused),
+    Unpaused(pausable::Unpaused),
+    ChangeQueued(timelock::ChangeQueued),
+    ChangeCancelled(timelock::ChangeCancelled),
+    ChangeExecuted(timelock::ChangeExecuted),
+    SchemaUpgraded(upgrade::SchemaUpgraded),
+    RecordsMigrated(upgrade::RecordsMigrated),
+}
+
+impl GovernanceEvent {
+    fn decode(contract: &'static str, name: &str, bytes: &[u8]) -> Result<Self> {
+        Ok(match name {
+            "RoleGranted" => Self::RoleGranted(decode(bytes)?),
+            "RoleRevoked" => Self::RoleRevoked(decode(bytes)?),
+            "OwnershipTransferStarted" => Self::OwnershipTransferStarted(decode(bytes)?),
+            "OwnershipTransferred" => Self::OwnershipTransferred(decode(bytes)?),
+            "Paused" => Self::Paused(decode(bytes)?),
+            "Unpaused" => Self::Unpaused(decode(bytes)?),
+            "ChangeQueued" => Self::ChangeQueued(decode(bytes)?),
+            "ChangeCancelled" => Self::ChangeCancelled(decode(bytes)?),
+            "ChangeExecuted" => Self::ChangeExecuted(decode(bytes)?),
+            "SchemaUpgraded" => Self::SchemaUpgraded(decode(bytes)?),
+            "RecordsMigrated" => Self::RecordsMigrated(decode(bytes)?),
+            _ => {
+                return Err(Error::UnknownEvent {
+                    contract,
+                    name: name.to_string(),
+                })
+            }
+        })
+    }
+}
+
+/// SubscriptionManager events
+#[derive(Clone, Debug, PartialEq)]
+pub enum SubscriptionManagerEvent {
+    PlanCreated(subscription_manager::PlanCreated),
+    PlanUpdated(subscription_manager::PlanUpdated),
+    PlanDeactivated(subscription_manager::PlanDeactivated),
+    EntitlementSet(subscription_manager::EntitlementSet),
+    EntitlementRemoved(subscription_manager::EntitlementRemoved),
+    Subscribed(subscription_manager::Subscribed),
+    Unsubscribed(subscription_manager::Unsubscribed),
+    SubscriptionRenewed(subscription_manager::SubscriptionRenewed),
+    OverpaymentRefunded(subscription_manager::OverpaymentRefunded),
+    StreamStarted(subscription_manager::StreamStarted),
+    StreamToppedUp(subscription_manager::StreamToppedUp),
+    StreamWithdrawn(subscription_manager::StreamWithdrawn),
+    StreamStopped(subscription_manager::StreamStopped),
+    StreamDepleted(subscription_manager::StreamDepleted),
+    LegacyPlanMigrated(subscription_manager::LegacyPlanMigrated),
+    LegacySubscriptionMigrated(subscription_manager::LegacySubscriptionMigrated),
+    Governance(GovernanceEvent),
+}
+
+impl SubscriptionManagerEvent {
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let name = event_name(bytes)?;
+        Ok(match name.as_str() {
+            "PlanCreated" => Self::PlanCreated(decode(bytes)?),
+            "PlanUpdated" => Self::PlanUpdated(decode(bytes)?),
+            "PlanDeactivated" => Self::PlanDeactivated(decode(bytes)?),
+            "EntitlementSet" => Self::EntitlementSet(decode(bytes)?),
+            "EntitlementRemoved" => Self::EntitlementRemoved(decode(bytes)?),
+            "Subscribed" => Self::Subscribed(decode(bytes)?),
+            "Unsubscribed" => Self::Unsubscribed(decode(bytes)?),
+            "SubscriptionRenewed" => Self::SubscriptionRenewed(decode(bytes)?),
+            "OverpaymentRefunded" => Self::OverpaymentRefunded(decode(bytes)?),
+            "StreamStarted" => Self::StreamStarted(decode(bytes)?),
+            "StreamToppedUp" => Self::StreamToppedUp(decode(bytes)?),
+            "StreamWithdrawn" => Self::StreamWithdrawn(decode(bytes)?),
+            "StreamStopped" => Self::StreamStopped(decode(bytes)?),
+            "StreamDepleted" => Self::StreamDepleted(decode(bytes)?),
+            "LegacyPlanMigrated" => Self::LegacyPlanMigrated(decode(bytes)?),
+            "LegacySubscriptionMigrated" => Self::LegacySubscriptionMigrated(decode(bytes)?),
+            _ => Self::Governance(GovernanceEvent::decode("SubscriptionManager", &name, bytes)?),
+        })
+    }
+}
+
+/// UsageMeter events
+#[derive(Clone, Debug, PartialEq)]
+pub enum UsageMeterEvent {
+    UsageRecorded(usage_meter::UsageRecorded),
+    UsageAdjusted(usage_meter::UsageAdjusted),
+    AdjustmentsSettled(usage_meter::AdjustmentsSettled),
+    PeriodRolledOver(usage_meter::PeriodRolledOver),
+    PeriodClosed(usage_meter::PeriodClosed),
+    PrepaidPlanChanged(usage_meter::PrepaidPlanChanged),
+    CreditDeposited(usage_meter::CreditDeposited),
+    CreditDebited(usage_meter::CreditDebited),
+    CreditRefunded(usage_meter::CreditRefunded),
+    CreditEarningsWithdrawn(usage_meter::CreditEarningsWithdrawn),
+    ChannelOpened(usage_meter::ChannelOpened),
+    ChannelToppedUp(usage_meter::ChannelToppedUp),
+    ChannelCloseRequested(usage_meter::ChannelCloseRequested),
+    ChannelClosed(usage_meter::ChannelClosed),
+    ChannelReclaimed(usage_meter::ChannelReclaimed),
+    Governance(GovernanceEvent),
+}
+
+impl UsageMeterEvent {
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let name = event_name(bytes)?;
+        Ok(match name.as_str() {
+            "UsageRecorded" => Self::UsageRecorded(decode(bytes)?),
+            "UsageAdjusted" => Self::UsageAdjusted(decode(bytes)?),
+            "AdjustmentsSettled" => Self::AdjustmentsSettled(decode(bytes)?),
+            "PeriodRolledOver" => Self::PeriodRolledOver(decode(bytes)?),
+            "PeriodClosed" => Self::PeriodClosed(decode(bytes)?),
+            "PrepaidPlanChanged" => Self::PrepaidPlanChanged(decode(bytes)?),
+            "CreditDeposited" => Self::CreditDeposited(decode(bytes)?),
+            "CreditDebited" => Self::CreditDebited(decode(bytes)?),
+            "CreditRefunded" => Self::CreditRefunded(decode(bytes)?),
+            "CreditEarningsWithdrawn" => Self::CreditEarningsWithdrawn(decode(bytes)?),
+            "ChannelOpened" => Self::ChannelOpened(decode(bytes)?),
+            "ChannelToppedUp" => Self::ChannelToppedUp(decode(bytes)?),
+            "ChannelCloseRequested" => Self::ChannelCloseRequested(decode(bytes)?),
+            "ChannelClosed" => Self::ChannelClosed(decode(bytes)?),
+            "ChannelReclaimed" => Self::ChannelReclaimed(decode(bytes)?),
+            _ => Self::Governance(GovernanceEvent::decode("UsageMeter", &name, bytes)?),
+        })
+    }
+}
+
+/// BillingEngine events
+#[derive(Clone, Debug, PartialEq)]
+pub enum BillingEngineEvent {
+    InvoiceCreated(billing_engine::InvoiceCreated),
+    InvoicePaid(billing_engine::InvoicePaid),
+    InvoiceFailed(billing_engine::InvoiceFailed),
+    PaymentProcessed(billing_engine::PaymentProcessed),
+    OverpaymentRefunded(billing_engine::OverpaymentRefunded),
+    Governance(GovernanceEvent),
+}
+
+impl BillingEngineEvent {
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let name = event_name(bytes)?;
+        Ok(match name.as_str() {
+            "InvoiceCreated" => Self::InvoiceCreated(decode(bytes)?),
+            "InvoicePaid" => Self::InvoicePaid(decode(bytes)?),
+            "InvoiceFailed" => Self::InvoiceFailed(decode(bytes)?),
+            "PaymentProcessed" => Self::PaymentProcessed(decode(bytes)?),
+            "OverpaymentRefunded" => Self::OverpaymentRefunded(decode(bytes)?),
+            _ => Self::Governance(GovernanceEvent::decode("BillingEngine", &name, bytes)?),
+        })
+    }
+}
+
+/// StakeToPay events
+#[derive(Clone, Debug, PartialEq)]
+pub enum StakeToPayEvent {
+    StakeDeposited(stake_to_pay::StakeDeposited),
+    StakeWithdrawn(stake_to_pay::StakeWithdrawn),
+    RewardsAccumulated(stake_to_pay::RewardsAccumulated),
+    PaymentFromRewards(stake_to_pay::PaymentFromRewards),
+    StakeToPayEnabled(stake_to_pay::StakeToPayEnabled),
+    StakeToPayDisabled(stake_to_pay::StakeToPayDisabled),
+    RewardsClaimed(stake_to_pay::RewardsClaimed),
+    AutoPayChanged(stake_to_pay::AutoPayChanged),
+    EmergencyWithdrawn(stake_to_pay::EmergencyWithdrawn),
+    AutoTopUpChanged(stake_to_pay::AutoTopUpChanged),
+    Governance(GovernanceEvent),
+}
+
+impl StakeToPayEvent {
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let name = event_name(bytes)?;
+        Ok(match name.as_str() {
+            "StakeDeposited" => Self::StakeDeposited(decode(bytes)?),
+            "StakeWithdrawn" => Self::StakeWithdrawn(decode(bytes)?),
+            "RewardsAccumulated" => Self::RewardsAccumulated(decode(bytes)?),
+            "PaymentFromRewards" => Self::PaymentFromRewards(decode(bytes)?),
+            "StakeToPayEnabled" => Self::StakeToPayEnabled(decode(bytes)?),
+            "StakeToPayDisabled" => Self::StakeToPayDisabled(decode(bytes)?),
+            "RewardsClaimed" => Self::RewardsClaimed(decode(bytes)?),
+            "AutoPayChanged" => Self::AutoPayChanged(decode(bytes)?),
+            "EmergencyWithdrawn" => Self::EmergencyWithdrawn(decode(bytes)?),
+            "AutoTopUpChanged" => Self::AutoTopUpChanged(decode(bytes)?),
+            _ => Self::Governance(GovernanceEvent::decode("StakeToPay", &name, bytes)?),
+        })
+    }
+}
+
+/// Event name without the `event_` prefix
+fn event_name(bytes: &[u8]) -> Result<String> {
+    let (name, _) = String::from_bytes(bytes)?;
+    Ok(name.strip_prefix("event_").map(str::to_string).unwrap_or(name))
+}
+
+/// Decode a whole event, name included
+fn decode<E: FromBytes>(bytes: &[u8]) -> Result<E> {
+    let (event, rest) = E::from_bytes(bytes)?;
+    if !rest.is_empty() {
+        return Err(bytesrepr::Error::LeftOverBytes.into());
+    }
+    Ok(event)
+}
+
+#[cfg(test)]
+mod tests {
+    use casperflow_contracts::time::Timestamp;
+    use odra::casper_types::bytesrepr::ToBytes;
+    use odra::casper_types::U512;
+
+    use super::*;
+
+    #[test]
+    fn test_decodes_by_contract() {
+        let renewed = subscription_manager::SubscriptionRenewed {
+            subscription_id: 4,
+            next_billing_at: Timestamp::from_millis(1_000),
+        };
+        assert_eq!(
+            Event::decode(Component::SubscriptionManager, &renewed.to_bytes().unwrap()),
+            Ok(Event::SubscriptionManager(SubscriptionManagerEvent::SubscriptionRenewed(renewed)))
+        );
+
+        // Same event name, different struct per contract
+        let paid = billing_engine::InvoicePaid {
+            invoice_id: 1,
+            amount: U512::from(10u64),
+            payment_method: 0,
+        };
+        assert_eq!(
+            BillingEngineEvent::decode(&paid.to_bytes().unwrap()),
+            Ok(BillingEngineEvent::InvoicePaid(paid.clone()))
+        );
+        assert_eq!(
+            StakeToPayEvent::decode(&paid.to_bytes().unwrap()),
+            Err(Error::UnknownEvent {
+                contract: "StakeToPay",
+                name: "InvoicePaid".to_string()
+            })
+        );
+    }
+}
@@ -0,0 +1,165 @@
+//! Entry points shared by every contract: roles, pausing, timelock, upgrades
+//! and the registry.
+
+use casperflow_contracts::access::Role;
+use casperflow_contracts::pausable::PauseGroup;
+use casperflow_contracts::registry::Component;
+use casperflow_contracts::time::Duration;
+use casperflow_contracts::timelock::{Param, QueuedChange};
+use casperflow_contracts::upgrade::{MigrationProgress, RecordKind};
+use odra::prelude::Address;
+
+use crate::error::Result;
+use crate::transport::{Call, Transport};
+
+/// Client for the access control, pause, timelock and upgrade entry points
+/// of one contract
+pub struct Governance<'a, T> {
+    transport: &'a T,
+    address: Address,
+}
+
+impl<'a, T: Transport> Governance<'a, T> {
+    pub fn new(transport: &'a T, address: Address) -> Self {
+        Self { transport, address }
+    }
+
+    pub fn address(&self) -> Address {
+        self.address
+    }
+
+    fn call(&self, entry_point: &str) -> Call {
+        Call::new(self.address, entry_point)
+    }
+
+    fn view(&self, entry_point: &str) -> Call {
+        Call::view(self.address, entry_point)
+    }
+
+    // ============ ACCESS CONTROL ============
+
+    /// Grant a role (owner or Admin)
+    pub fn grant_role(&self, role: Role, account: Address) -> Result<()> {
+        self.transport
+            .call(self.call("grant_role").arg("role", role).arg("account", account))
+    }
+
+    /// Revoke a role (owner or Admin)
+    pub fn revoke_role(&self, role: Role, account: Address) -> Result<()> {
+        self.transport
+            .call(self.call("revoke_role").arg("role", role).arg("account", account))
+    }
+
+    /// Give up one of the caller's own roles
+    pub fn renounce_role(&self, role: Role) -> Result<()> {
+        self.transport.call(self.call("renounce_role").arg("role", role))
+    }
+
+    /// Start a two-step ownership transfer (owner)
+    pub fn transfer_ownership(&self, new_owner: Address) -> Result<()> {
+        self.transport
+            .call(self.call("transfer_ownership").arg("new_owner", new_owner))
+    }
+
+    /// Complete an ownership transfer (pending owner)
+    pub fn accept_ownership(&self) -> Result<()> {
+        self.transport.call(self.call("accept_ownership"))
+    }
+
+    pub fn has_role(&self, role: Role, account: Address) -> Result<bool> {
+        self.transport
+            .call(self.view("has_role").arg("role", role).arg("account", account))
+    }
+
+    pub fn owner(&self) -> Result<Option<Address>> {
+        self.transport.call(self.view("owner"))
+    }
+
+    pub fn pending_owner(&self) -> Result<Option<Address>> {
+        self.transport.call(self.view("pending_owner"))
+    }
+
+    // ============ PAUSE ============
+
+    /// Pause a group of entry points (Pauser)
+    pub fn pause(&self, group: PauseGroup) -> Result<()> {
+        self.transport.call(self.call("pause").arg("group", group))
+    }
+
+    /// Resume a group of entry points (Pauser)
+    pub fn unpause(&self, group: PauseGroup) -> Result<()> {
+        self.transport.call(self.call("unpause").arg("group", group))
+    }
+
+    pub fn is_paused(&self, group: PauseGroup) -> Result<bool> {
+        self.transport.call(self.view("is_paused").arg("group", group))
+    }
+
+    // ============ TIMELOCK ============
+
+    /// Apply a queued change once its delay has passed (anyone)
+    pub fn execute_change(&self, change_id: u64) -> Result<()> {
+        self.transport
+            .call(self.call("execute_change").arg("change_id", change_id))
+    }
+
+    /// Cancel a queued change (Admin)
+    pub fn cancel_change(&self, change_id: u64) -> Result<()> {
+        self.transport
+            .call(self.call("cancel_change").arg("change_id", change_id))
+    }
+
+    /// Queue a new timelock delay (Admin)
+    pub fn set_timelock_delay(&self, delay: Duration) -> Result<()> {
+        self.transport
+            .call(self.call("set_timelock_delay").arg("delay", delay))
+    }
+
+    pub fn get_queued_change(&self, change_id: u64) -> Result<Option<QueuedChange>> {
+        self.transport
+            .call(self.view("get_queued_change").arg("change_id", change_id))
+    }
+
+    pub fn get_pending_change(&self, param: Param) -> Result<Option<QueuedChange>> {
+        self.transport
+            .call(self.view("get_pending_change").arg("param", param))
+    }
+
+    pub fn get_timelock_delay(&self) -> Result<Duration> {
+        self.transport.call(self.view("get_timelock_delay"))
+    }
+
+    // ============ UPGRADES ============
+
+    /// Upgrade hook run when a new contract version is installed
+    pub fn upgrade(&self) -> Result<()> {
+        self.transport.call(self.call("upgrade"))
+    }
+
+    pub fn schema_version(&self) -> Result<u32> {
+        self.transport.call(self.view("schema_version"))
+    }
+
+    pub fn get_migration_progress(&self, kind: RecordKind) -> Result<MigrationProgress> {
+        self.transport
+            .call(self.view("get_migration_progress").arg("kind", kind))
+    }
+
+    // ============ REGISTRY ============
+
+    /// Resolve peers through a Registry (Admin)
+    pub fn set_registry(&self, address: Address) -> Result<()> {
+        self.transport
+            .call(self.call("set_registry").arg("address", address))
+    }
+
+    pub fn get_registry(&self) -> Result<Option<Address>> {
+        self.transport.call(self.view("get_registry"))
+    }
+
+    /// Address the contract uses for a peer component
+    pub fn get_peer(&self, component: Component) -> Result<Option<Address>> {
+        self.transport
+            .call(self.view("get_peer").arg("component", component))
+    }
+}
@@ -0,0 +1,225 @@
+//! CasperFlow Rust SDK
+//!
+//! Typed client for the four CasperFlow contracts. Every entry point has a
+//! wrapper that takes and returns the contracts' own types ([`Plan`],
+//! [`Invoice`], [`UsageRecord`], ...), and events decode into the structs in
+//! each contract's `events` module.
+//!
+//! Calls go through a [`Transport`]. [`OdraTransport`] wraps an Odra
+//! `HostEnv`, which is Odra's in-memory VM in unit tests and a Casper node in
+//! production. [`DeployBuilder`] builds and signs deploys for services that
+//! submit them another way.
+//!
+//! ```ignore
+//! let client = CasperFlow::from_registry(OdraTransport::livenet(), registry)?;
+//! let plan_id = client.subscription_manager().create_plan("Pro", price, usage_price, Duration::from_days(30))?;
+//! let plan: Option<Plan> = client.subscription_manager().get_plan(plan_id)?;
+//! ```
+
+pub mod billing_engine;
+pub mod deploy;
+pub mod error;
+pub mod events;
+pub mod governance;
+pub mod stake_to_pay;
+pub mod subscription_manager;
+pub mod transport;
+pub mod usage_meter;
+
+pub use billing_engine::BillingEngineClient;
+pub use casperflow_contracts::billing_engine::{Invoice, InvoiceStatus};
+pub use casperflow_contracts::registry::Component;
+pub use casperflow_contracts::stake_to_pay::{StakeConfig, StakePayment};
+pub use casperflow_contracts::subscription_manager::{Plan, Subscription};
+pub use casperflow_contracts::time::{Duration, Timestamp};
+pub use casperflow_contracts::usage_meter::UsageRecord;
+pub use deploy::DeployBuilder;
+pub use error::{Error, Result};
+pub use events::Event;
+pub use governance::Governance;
+pub use stake_to_pay::StakeToPayClient;
+pub use subscription_manager::{PaymentMethod, SubscriptionManagerClient};
+pub use transport::{Call, OdraTransport, Transport};
+pub use usage_meter::UsageMeterClient;
+
+use odra::prelude::Address;
+
+/// Addresses of the four contracts
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Addresses {
+    pub subscription_manager: Address,
+    pub usage_meter: Address,
+    pub billing_engine: Address,
+    pub stake_to_pay: Address,
+}
+
+impl Addresses {
+    pub fn get(&self, component: Component) -> Address {
+        match component {
+            Component::SubscriptionManager => self.subscription_manager,
+            Component::UsageMeter => self.usage_meter,
+            Component::BillingEngine => self.billing_engine,
+            Component::StakeToPay => self.stake_to_pay,
+        }
+    }
+}
+
+/// Client for a CasperFlow deployment
+pub struct CasperFlow<T> {
+    transport: T,
+    addresses: Addresses,
+}
+
+impl<T: Transport> CasperFlow<T> {
+    pub fn new(transport: T, addresses: Addresses) -> Self {
+        Self {
+            transport,
+            addresses,
+        }
+    }
+
+    /// Client for the components registered in a Registry
+    pub fn from_registry(transport: T, registry: Address) -> Result<Self> {
+        let address = |component: Component, name: &'static str| -> Result<Address> {
+            transport
+                .call::<Option<Address>>(
+                    Call::view(registry, "get_address").arg("component", component),
+                )?
+                .ok_or(Error::NotRegistered(name))
+        };
+        let addresses = Addresses {
+            subscription_manager: address(Component::SubscriptionManager, "SubscriptionManager")?,
+            usage_meter: address(Component::UsageMeter, "UsageMeter")?,
+            billing_engine: address(Component::BillingEngine, "BillingEngine")?,
+            stake_to_pay: address(Component::StakeToPay, "StakeToPay")?,
+        };
+        Ok(Self::new(transport, addresses))
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn addresses(&self) -> Addresses {
+        self.addresses
+    }
+
+    pub fn subscription_manager(&self) -> SubscriptionManagerClient<'_, T> {
+        SubscriptionManagerClient::new(&self.transport, self.addresses.subscription_manager)
+    }
+
+    pub fn usage_meter(&self) -> UsageMeterClient<'_, T> {
+        UsageMeterClient::new(&self.transport, self.addresses.usage_meter)
+    }
+
+    pub fn billing_engine(&self) -> BillingEngineClient<'_, T> {
+        BillingEngineClient::new(&self.transport, self.addresses.billing_engine)
+    }
+
+    pub fn stake_to_pay(&self) -> StakeToPayClient<'_, T> {
+        StakeToPayClient::new(&self.transport, self.addresses.stake_to_pay)
+    }
+
+    /// Roles, pausing, timelock and upgrade entry points of a component
+    pub fn governance(&self, component: Component) -> Governance<'_, T> {
+        Governance::new(&self.transport, self.addresses.get(component))
+    }
+
+    /// Events a component emitted from index `from` on
+    pub fn events(&self, component: Component, from: u32) -> Result<Vec<Event>> {
+        let address = self.addresses.get(component);
+        let count = self.transport.event_count(address)?;
+        (from..count)
+            .map(|index| {
+                let bytes = self.transport.event_bytes(address, index)?;
+                Event::decode(component, &bytes)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use casperflow_contracts::billing_engine::BillingEngine;
+    use casperflow_contracts::registry::Registry;
+    use casperflow_contracts::stake_to_pay::StakeToPay;
+    use casperflow_contracts::subscription_manager::{
+        events::{PlanCreated, Subscribed},
+        Error as SubscriptionError, SubscriptionManager,
+    };
+    use casperflow_contracts::usage_meter::UsageMeter;
+    use odra::casper_types::U512;
+    use odra::host::{Deployer, HostEnv, HostRef, NoArgs};
+
+    use super::*;
+    use crate::events::{SubscriptionManagerEvent, UsageMeterEvent};
+
+    fn deploy(env: &HostEnv) -> Address {
+        let mut registry = Registry::deploy(env, NoArgs);
+        let mut manager = SubscriptionManager::deploy(env, NoArgs);
+        let mut meter = UsageMeter::deploy(env, NoArgs);
+        let mut engine = BillingEngine::deploy(env, NoArgs);
+        let mut stake_to_pay = StakeToPay::deploy(env, NoArgs);
+        registry.register(Component::SubscriptionManager, manager.address());
+        registry.register(Component::UsageMeter, meter.address());
+        registry.register(Component::BillingEngine, engine.address());
+        registry.register(Component::StakeToPay, stake_to_pay.address());
+        manager.set_registry(registry.address());
+        meter.set_registry(registry.address());
+        engine.set_registry(registry.address());
+        stake_to_pay.set_registry(registry.address());
+        registry.address()
+    }
+
+    #[test]
+    fn test_client_round_trip() {
+        let env = odra_test::env();
+        let client = CasperFlow::from_registry(OdraTransport::new(env.clone()), deploy(&env)).unwrap();
+        let (merchant, subscriber) = (env.get_account(0), env.get_account(1));
+        let (price, usage_price, cycle) = (U512::from(1_000u64), U512::from(10u64), Duration::from_days(30));
+
+        let manager = client.subscription_manager();
+        let plan_id = manager.create_plan("Pro", price, usage_price, cycle).unwrap();
+        client.usage_meter().authorize_recorder(plan_id, merchant).unwrap();
+        let plan: Plan = manager.get_plan(plan_id).unwrap().unwrap();
+        assert_eq!((plan.merchant, plan.base_price, plan.billing_cycle), (merchant, price, cycle));
+
+        env.set_caller(subscriber);
+        let subscription_id = manager
+            .subscribe(plan_id, true, PaymentMethod::Wallet, price)
+            .unwrap();
+        // The first cycle is invoiced and paid through BillingEngine
+        let invoice: Invoice = client.billing_engine().get_invoice(1).unwrap().unwrap();
+        assert_eq!((invoice.subscription_id, invoice.status), (subscription_id, InvoiceStatus::Paid));
+        assert_eq!(
+            manager.subscribe(99, true, PaymentMethod::Wallet, price),
+            Err(Error::Call(SubscriptionError::PlanNotFound.into()))
+        );
+
+        env.set_caller(merchant);
+        let record_id = client
+            .usage_meter()
+            .record_usage(subscription_id, plan_id, "api_calls", 5)
+            .unwrap();
+        let record: UsageRecord = client.usage_meter().get_record(record_id).unwrap().unwrap();
+        assert_eq!((record.subscription_id, record.units), (subscription_id, 5));
+
+        let events = client.events(Component::SubscriptionManager, 0).unwrap();
+        assert!(events.contains(&Event::SubscriptionManager(SubscriptionManagerEvent::PlanCreated(PlanCreated {
+            plan_id,
+            merchant,
+            name: "Pro".to_string(),
+            base_price: price,
+        }))));
+        assert!(events.contains(&Event::SubscriptionManager(SubscriptionManagerEvent::Subscribed(Subscribed {
+            subscription_id,
+            plan_id,
+            subscriber,
+        }))));
+        let usage = client.events(Component::UsageMeter, 0).unwrap();
+        assert!(matches!(
+            usage.last(),
+            Some(Event::UsageMeter(UsageMeterEvent::UsageRecorded(recorded))) if recorded.units == 5
+        ));
+    }
+}
@@ -0,0 +1,218 @@
+//! StakeToPay client.
+
+use casperflow_contracts::pagination::DateRange;
+use casperflow_contracts::stake_to_pay::{StakeConfig, StakePayment, StakePaymentPage};
+use odra::casper_types::U512;
+use odra::prelude::Address;
+
+use crate::error::Result;
+use crate::governance::Governance;
+use crate::transport::{Call, Transport};
+
+/// Client for the StakeToPay contract
+pub struct StakeToPayClient<'a, T> {
+    transport: &'a T,
+    address: Address,
+}
+
+impl<'a, T: Transport> StakeToPayClient<'a, T> {
+    pub fn new(transport: &'a T, address: Address) -> Self {
+        Self { transport, address }
+    }
+
+    pub fn address(&self) -> Address {
+        self.address
+    }
+
+    /// Roles, pausing, timelock and upgrade entry points
+    pub fn governance(&self) -> Governance<'a, T> {
+        Governance::new(self.transport, self.address)
+    }
+
+    fn call(&self, entry_point: &str) -> Call {
+        Call::new(self.address, entry_point)
+    }
+
+    fn view(&self, entry_point: &str) -> Call {
+        Call::view(self.address, entry_point)
+    }
+
+    // ============ STAKING FUNCTIONS ============
+
+    /// Stake `amount`
+    pub fn deposit(&self, amount: U512) -> Result<()> {
+        self.transport.call(self.call("deposit").with_amount(amount))
+    }
+
+    /// Withdraw staked principal
+    pub fn withdraw(&self, amount: U512) -> Result<()> {
+        self.transport.call(self.call("withdraw").arg("amount", amount))
+    }
+
+    /// Withdraw all principal while staking is paused; returns the amount
+    pub fn emergency_withdraw(&self) -> Result<U512> {
+        self.transport.call(self.call("emergency_withdraw"))
+    }
+
+    pub fn withdraw_rewards(&self, amount: U512) -> Result<()> {
+        self.transport
+            .call(self.call("withdraw_rewards").arg("amount", amount))
+    }
+
+    /// Withdraw all available rewards, keeping the principal staked
+    pub fn claim_rewards(&self) -> Result<U512> {
+        self.transport.call(self.call("claim_rewards"))
+    }
+
+    /// Add `amount` to the reserve rewards are paid from
+    pub fn fund_rewards(&self, amount: U512) -> Result<()> {
+        self.transport.call(self.call("fund_rewards").with_amount(amount))
+    }
+
+    /// Accrue a staker's rewards up to now
+    pub fn update_rewards(&self, user: Address) -> Result<()> {
+        self.transport.call(self.call("update_rewards").arg("user", user))
+    }
+
+    // ============ STAKE-TO-PAY FUNCTIONS ============
+
+    pub fn enable_stake_to_pay(&self) -> Result<()> {
+        self.transport.call(self.call("enable_stake_to_pay"))
+    }
+
+    pub fn disable_stake_to_pay(&self) -> Result<()> {
+        self.transport.call(self.call("disable_stake_to_pay"))
+    }
+
+    /// Let keepers pay a plan's invoices from the caller's rewards
+    pub fn enable_auto_pay(&self, plan_id: u64) -> Result<()> {
+        self.transport
+            .call(self.call("enable_auto_pay").arg("plan_id", plan_id))
+    }
+
+    pub fn disable_auto_pay(&self, plan_id: u64) -> Result<()> {
+        self.transport
+            .call(self.call("disable_auto_pay").arg("plan_id", plan_id))
+    }
+
+    /// Top up the caller's prepaid credit with a merchant from rewards, by at
+    /// least `amount` at a time
+    pub fn enable_auto_top_up(&self, merchant: Address, amount: U512) -> Result<()> {
+        self.transport.call(
+            self.call("enable_auto_top_up")
+                .arg("merchant", merchant)
+                .arg("amount", amount),
+        )
+    }
+
+    pub fn disable_auto_top_up(&self, merchant: Address) -> Result<()> {
+        self.transport
+            .call(self.call("disable_auto_top_up").arg("merchant", merchant))
+    }
+
+    /// Pay one of the caller's invoices from their rewards, through BillingEngine
+    pub fn pay_invoice_from_rewards(&self, invoice_id: u64) -> Result<()> {
+        self.transport
+            .call(self.call("pay_invoice_from_rewards").arg("invoice_id", invoice_id))
+    }
+
+    /// Pay pending invoices of auto-pay subscribers (Keeper); returns invoices paid
+    pub fn auto_pay_invoices(&self, invoice_ids: Vec<u64>) -> Result<u32> {
+        self.transport
+            .call(self.call("auto_pay_invoices").arg("invoice_ids", invoice_ids))
+    }
+
+    /// Migrate up to `limit` stake configs to the current schema (Keeper)
+    pub fn migrate_stake_configs(&self, limit: u32) -> Result<u32> {
+        self.transport
+            .call(self.call("migrate_stake_configs").arg("limit", limit))
+    }
+
+    // ============ VIEW FUNCTIONS ============
+
+    pub fn get_stake_config(&self, user: Address) -> Result<Option<StakeConfig>> {
+        self.transport
+            .call(self.view("get_stake_config").arg("user", user))
+    }
+
+    pub fn get_available_rewards(&self, user: Address) -> Result<U512> {
+        self.transport
+            .call(self.view("get_available_rewards").arg("user", user))
+    }
+
+    pub fn is_auto_pay_enabled(&self, user: Address, plan_id: u64) -> Result<bool> {
+        self.transport.call(
+            self.view("is_auto_pay_enabled")
+                .arg("user", user)
+                .arg("plan_id", plan_id),
+        )
+    }
+
+    pub fn get_auto_top_up(&self, user: Address, merchant: Address) -> Result<U512> {
+        self.transport.call(
+            self.view("get_auto_top_up")
+                .arg("user", user)
+                .arg("merchant", merchant),
+        )
+    }
+
+    pub fn estimate_yearly_rewards(&self, amount: U512) -> Result<U512> {
+        self.transport
+            .call(self.view("estimate_yearly_rewards").arg("amount", amount))
+    }
+
+    pub fn get_payment(&self, payment_id: u64) -> Result<Option<StakePayment>> {
+        self.transport
+            .call(self.view("get_payment").arg("payment_id", payment_id))
+    }
+
+    pub fn get_user_payment_count(&self, user: Address) -> Result<u32> {
+        self.transport
+            .call(self.view("get_user_payment_count").arg("user", user))
+    }
+
+    pub fn get_user_payments_page(
+        &self,
+        user: Address,
+        cursor: u32,
+        limit: u32,
+        paid: DateRange,
+    ) -> Result<StakePaymentPage> {
+        self.transport.call(
+            self.view("get_user_payments_page")
+                .arg("user", user)
+                .arg("cursor", cursor)
+                .arg("limit", limit)
+                .arg("paid", paid),
+        )
+    }
+
+    pub fn get_total_staked(&self) -> Result<U512> {
+        self.transport.call(self.view("get_total_staked"))
+    }
+
+    pub fn get_apy_bps(&self) -> Result<u64> {
+        self.transport.call(self.view("get_apy_bps"))
+    }
+
+    pub fn get_reward_reserve(&self) -> Result<U512> {
+        self.transport.call(self.view("get_reward_reserve"))
+    }
+
+    // ============ ADMIN FUNCTIONS ============
+
+    pub fn set_billing_engine(&self, address: Address) -> Result<()> {
+        self.transport
+            .call(self.call("set_billing_engine").arg("address", address))
+    }
+
+    pub fn set_subscription_manager(&self, address: Address) -> Result<()> {
+        self.transport
+            .call(self.call("set_subscription_manager").arg("address", address))
+    }
+
+    /// Queue a new reward rate (RateManager)
+    pub fn set_apy_bps(&self, apy: u64) -> Result<()> {
+        self.transport.call(self.call("set_apy_bps").arg("apy", apy))
+    }
+}
@@ -0,0 +1,427 @@
+//! SubscriptionManager client.
+
+use casperflow_contracts::analytics::{ChurnStats, SubscriberStats};
+use casperflow_contracts::subscription_manager::{
+    Entitlement, Plan, PlanPage, Stream, Subscription, SubscriptionFilter, SubscriptionPage,
+};
+use casperflow_contracts::time::Duration;
+use odra::casper_types::U512;
+use odra::prelude::Address;
+
+use crate::error::Result;
+use crate::governance::Governance;
+use crate::transport::{Call, Transport};
+
+/// How a subscription's invoices are paid
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PaymentMethod {
+    /// From the subscriber's wallet; the first cycle is paid on subscribing
+    Wallet = 0,
+    /// From staking rewards in StakeToPay
+    Staked = 1,
+    /// Per second from a deposit; start with `subscribe_streaming`, not `subscribe`
+    Streamed = 2,
+}
+
+/// Client for the SubscriptionManager contract
+pub struct SubscriptionManagerClient<'a, T> {
+    transport: &'a T,
+    address: Address,
+}
+
+impl<'a, T: Transport> SubscriptionManagerClient<'a, T> {
+    pub fn new(transport: &'a T, address: Address) -> Self {
+        Self { transport, address }
+    }
+
+    pub fn address(&self) -> Address {
+        self.address
+    }
+
+    /// Roles, pausing, timelock and upgrade entry points
+    pub fn governance(&self) -> Governance<'a, T> {
+        Governance::new(self.transport, self.address)
+    }
+
+    fn call(&self, entry_point: &str) -> Call {
+        Call::new(self.address, entry_point)
+    }
+
+    fn view(&self, entry_point: &str) -> Call {
+        Call::view(self.address, entry_point)
+    }
+
+    // ============ MERCHANT FUNCTIONS ============
+
+    /// Create a plan owned by the caller; returns the plan ID
+    pub fn create_plan(
+        &self,
+        name: &str,
+        base_price: U512,
+        usage_price: U512,
+        billing_cycle: Duration,
+    ) -> Result<u64> {
+        self.transport.call(
+            self.call("create_plan")
+                .arg("name", name.to_string())
+                .arg("base_price", base_price)
+                .arg("usage_price", usage_price)
+                .arg("billing_cycle", billing_cycle),
+        )
+    }
+
+    /// Update a plan's pricing (plan merchant)
+    pub fn update_plan(&self, plan_id: u64, base_price: U512, usage_price: U512) -> Result<()> {
+        self.transport.call(
+            self.call("update_plan")
+                .arg("plan_id", plan_id)
+                .arg("base_price", base_price)
+                .arg("usage_price", usage_price),
+        )
+    }
+
+    /// Stop accepting subscriptions to a plan (plan merchant)
+    pub fn deactivate_plan(&self, plan_id: u64) -> Result<()> {
+        self.transport
+            .call(self.call("deactivate_plan").arg("plan_id", plan_id))
+    }
+
+    /// Grant `key` to a plan's subscribers; `None` for on/off features (plan merchant)
+    pub fn set_entitlement(&self, plan_id: u64, key: &str, limit: Option<u64>) -> Result<()> {
+        self.transport.call(
+            self.call("set_entitlement")
+                .arg("plan_id", plan_id)
+                .arg("key", key.to_string())
+                .arg("limit", limit),
+        )
+    }
+
+    /// Stop granting `key` to a plan's subscribers (plan merchant)
+    pub fn remove_entitlement(&self, plan_id: u64, key: &str) -> Result<()> {
+        self.transport.call(
+            self.call("remove_entitlement")
+                .arg("plan_id", plan_id)
+                .arg("key", key.to_string()),
+        )
+    }
+
+    // ============ USER FUNCTIONS ============
+
+    /// Subscribe the caller to a plan, attaching `amount`; returns the subscription ID
+    ///
+    /// Wallet subscriptions must attach at least the plan's base price; any
+    /// excess is refunded.
+    pub fn subscribe(
+        &self,
+        plan_id: u64,
+        auto_renew: bool,
+        payment_method: PaymentMethod,
+        amount: U512,
+    ) -> Result<u64> {
+        self.transport.call(
+            self.call("subscribe")
+                .arg("plan_id", plan_id)
+                .arg("auto_renew", auto_renew)
+                .arg("payment_method", payment_method as u8)
+                .with_amount(amount),
+        )
+    }
+
+    /// Cancel one of the caller's subscriptions
+    pub fn unsubscribe(&self, subscription_id: u64) -> Result<()> {
+        self.transport
+            .call(self.call("unsubscribe").arg("subscription_id", subscription_id))
+    }
+
+    pub fn set_auto_renew(&self, subscription_id: u64, auto_renew: bool) -> Result<()> {
+        self.transport.call(
+            self.call("set_auto_renew")
+                .arg("subscription_id", subscription_id)
+                .arg("auto_renew", auto_renew),
+        )
+    }
+
+    // ============ STREAMING FUNCTIONS ============
+
+    /// Subscribe the caller to a plan paid by streaming `deposit` per second;
+    /// returns the subscription ID
+    pub fn subscribe_streaming(&self, plan_id: u64, deposit: U512) -> Result<u64> {
+        self.transport.call(
+            self.call("subscribe_streaming")
+                .arg("plan_id", plan_id)
+                .with_amount(deposit),
+        )
+    }
+
+    /// Add `amount` to one of the caller's running streams
+    pub fn top_up_stream(&self, subscription_id: u64, amount: U512) -> Result<()> {
+        self.transport.call(
+            self.call("top_up_stream")
+                .arg("subscription_id", subscription_id)
+                .with_amount(amount),
+        )
+    }
+
+    /// Pay out what has streamed, less the protocol fee (stream merchant); returns the payout
+    pub fn withdraw_streamed(&self, subscription_id: u64) -> Result<U512> {
+        self.transport
+            .call(self.call("withdraw_streamed").arg("subscription_id", subscription_id))
+    }
+
+    /// Deactivate subscriptions whose streams ran dry; returns how many were closed
+    pub fn close_depleted_streams(&self, subscription_ids: Vec<u64>) -> Result<u32> {
+        self.transport.call(
+            self.call("close_depleted_streams")
+                .arg("subscription_ids", subscription_ids),
+        )
+    }
+
+    // ============ RENEWAL FUNCTIONS ============
+
+    /// Invoice every due subscription in the batch (Keeper); returns invoice IDs
+    pub fn renew_subscriptions(&self, subscription_ids: Vec<u64>) -> Result<Vec<u64>> {
+        self.transport.call(
+            self.call("renew_subscriptions")
+                .arg("subscription_ids", subscription_ids),
+        )
+    }
+
+    // ============ MIGRATION ============
+
+    /// Import a plan from the legacy SubscriptionManager (Admin or plan merchant)
+    pub fn migrate_legacy_plan(&self, legacy_plan_id: u32) -> Result<u64> {
+        self.transport
+            .call(self.call("migrate_legacy_plan").arg("legacy_plan_id", legacy_plan_id))
+    }
+
+    /// Import a legacy subscription (Admin or subscriber)
+    pub fn migrate_legacy_subscription(&self, subscriber: Address, legacy_plan_id: u32) -> Result<u64> {
+        self.transport.call(
+            self.call("migrate_legacy_subscription")
+                .arg("subscriber", subscriber)
+                .arg("legacy_plan_id", legacy_plan_id),
+        )
+    }
+
+    pub fn get_migrated_plan_id(&self, legacy_plan_id: u32) -> Result<Option<u64>> {
+        self.transport
+            .call(self.view("get_migrated_plan_id").arg("legacy_plan_id", legacy_plan_id))
+    }
+
+    pub fn get_migrated_subscription_id(
+        &self,
+        subscriber: Address,
+        legacy_plan_id: u32,
+    ) -> Result<Option<u64>> {
+        self.transport.call(
+            self.view("get_migrated_subscription_id")
+                .arg("subscriber", subscriber)
+                .arg("legacy_plan_id", legacy_plan_id),
+        )
+    }
+
+    // ============ VIEW FUNCTIONS ============
+
+    pub fn get_plan(&self, plan_id: u64) -> Result<Option<Plan>> {
+        self.transport.call(self.view("get_plan").arg("plan_id", plan_id))
+    }
+
+    pub fn get_subscription(&self, subscription_id: u64) -> Result<Option<Subscription>> {
+        self.transport
+            .call(self.view("get_subscription").arg("subscription_id", subscription_id))
+    }
+
+    pub fn get_merchant_plan_count(&self, merchant: Address) -> Result<u32> {
+        self.transport
+            .call(self.view("get_merchant_plan_count").arg("merchant", merchant))
+    }
+
+    pub fn get_merchant_plans_page(
+        &self,
+        merchant: Address,
+        cursor: u32,
+        limit: u32,
+        is_active: Option<bool>,
+    ) -> Result<PlanPage> {
+        self.transport.call(
+            self.view("get_merchant_plans_page")
+                .arg("merchant", merchant)
+                .arg("cursor", cursor)
+                .arg("limit", limit)
+                .arg("is_active", is_active),
+        )
+    }
+
+    pub fn get_user_subscription_count(&self, user: Address) -> Result<u32> {
+        self.transport
+            .call(self.view("get_user_subscription_count").arg("user", user))
+    }
+
+    pub fn get_user_subscriptions_page(
+        &self,
+        user: Address,
+        cursor: u32,
+        limit: u32,
+        filter: SubscriptionFilter,
+    ) -> Result<SubscriptionPage> {
+        self.transport.call(
+            self.view("get_user_subscriptions_page")
+                .arg("user", user)
+                .arg("cursor", cursor)
+                .arg("limit", limit)
+                .arg("filter", filter),
+        )
+    }
+
+    pub fn get_user_plan_subscription(&self, user: Address, plan_id: u64) -> Result<Option<Subscription>> {
+        self.transport.call(
+            self.view("get_user_plan_subscription")
+                .arg("user", user)
+                .arg("plan_id", plan_id),
+        )
+    }
+
+    pub fn has_access(&self, subscriber: Address, plan_id: u64) -> Result<bool> {
+        self.transport.call(
+            self.view("has_access")
+                .arg("subscriber", subscriber)
+                .arg("plan_id", plan_id),
+        )
+    }
+
+    pub fn has_merchant_access(&self, subscriber: Address, merchant: Address) -> Result<bool> {
+        self.transport.call(
+            self.view("has_merchant_access")
+                .arg("subscriber", subscriber)
+                .arg("merchant", merchant),
+        )
+    }
+
+    pub fn get_plan_entitlements(&self, plan_id: u64) -> Result<Vec<Entitlement>> {
+        self.transport
+            .call(self.view("get_plan_entitlements").arg("plan_id", plan_id))
+    }
+
+    pub fn get_entitlements(&self, subscriber: Address, merchant: Address) -> Result<Vec<Entitlement>> {
+        self.transport.call(
+            self.view("get_entitlements")
+                .arg("subscriber", subscriber)
+                .arg("merchant", merchant),
+        )
+    }
+
+    pub fn has_entitlement(&self, subscriber: Address, merchant: Address, key: &str) -> Result<bool> {
+        self.transport.call(
+            self.view("has_entitlement")
+                .arg("subscriber", subscriber)
+                .arg("merchant", merchant)
+                .arg("key", key.to_string()),
+        )
+    }
+
+    pub fn get_stream(&self, subscription_id: u64) -> Result<Option<Stream>> {
+        self.transport
+            .call(self.view("get_stream").arg("subscription_id", subscription_id))
+    }
+
+    pub fn get_withdrawable_streamed(&self, subscription_id: u64) -> Result<U512> {
+        self.transport.call(
+            self.view("get_withdrawable_streamed")
+                .arg("subscription_id", subscription_id),
+        )
+    }
+
+    pub fn get_payment_grace(&self) -> Result<Duration> {
+        self.transport.call(self.view("get_payment_grace"))
+    }
+
+    pub fn total_plans(&self) -> Result<u64> {
+        self.transport.call(self.view("total_plans"))
+    }
+
+    pub fn total_subscriptions(&self) -> Result<u64> {
+        self.transport.call(self.view("total_subscriptions"))
+    }
+
+    // ============ ANALYTICS ============
+
+    pub fn get_plan_stats(&self, plan_id: u64) -> Result<SubscriberStats> {
+        self.transport
+            .call(self.view("get_plan_stats").arg("plan_id", plan_id))
+    }
+
+    pub fn get_merchant_stats(&self, merchant: Address) -> Result<SubscriberStats> {
+        self.transport
+            .call(self.view("get_merchant_stats").arg("merchant", merchant))
+    }
+
+    pub fn get_plan_period_stats(&self, plan_id: u64, period: u64) -> Result<ChurnStats> {
+        self.transport.call(
+            self.view("get_plan_period_stats")
+                .arg("plan_id", plan_id)
+                .arg("period", period),
+        )
+    }
+
+    pub fn get_merchant_period_stats(&self, merchant: Address, period: u64) -> Result<ChurnStats> {
+        self.transport.call(
+            self.view("get_merchant_period_stats")
+                .arg("merchant", merchant)
+                .arg("period", period),
+        )
+    }
+
+    pub fn get_merchant_mrr(&self, merchant: Address) -> Result<U512> {
+        self.transport
+            .call(self.view("get_merchant_mrr").arg("merchant", merchant))
+    }
+
+    pub fn get_merchant_arpu(&self, merchant: Address) -> Result<U512> {
+        self.transport
+            .call(self.view("get_merchant_arpu").arg("merchant", merchant))
+    }
+
+    pub fn get_plan_arpu(&self, plan_id: u64) -> Result<U512> {
+        self.transport
+            .call(self.view("get_plan_arpu").arg("plan_id", plan_id))
+    }
+
+    pub fn get_merchant_churn_bps(&self, merchant: Address, period: u64) -> Result<u64> {
+        self.transport.call(
+            self.view("get_merchant_churn_bps")
+                .arg("merchant", merchant)
+                .arg("period", period),
+        )
+    }
+
+    pub fn current_analytics_period(&self) -> Result<u64> {
+        self.transport.call(self.view("current_analytics_period"))
+    }
+
+    // ============ ADMIN FUNCTIONS ============
+
+    pub fn set_billing_engine(&self, address: Address) -> Result<()> {
+        self.transport
+            .call(self.call("set_billing_engine").arg("address", address))
+    }
+
+    pub fn set_usage_meter(&self, address: Address) -> Result<()> {
+        self.transport
+            .call(self.call("set_usage_meter").arg("address", address))
+    }
+
+    pub fn set_stake_to_pay(&self, address: Address) -> Result<()> {
+        self.transport
+            .call(self.call("set_stake_to_pay").arg("address", address))
+    }
+
+    pub fn set_legacy_manager(&self, address: Address) -> Result<()> {
+        self.transport
+            .call(self.call("set_legacy_manager").arg("address", address))
+    }
+
+    pub fn set_payment_grace(&self, grace: Duration) -> Result<()> {
+        self.transport
+            .call(self.call("set_payment_grace").arg("grace", grace))
+    }
+}
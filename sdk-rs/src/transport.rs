@@ -0,0 +1,137 @@
+//! Transports carrying entry point calls to the contracts.
+
+use odra::casper_types::bytesrepr::{Bytes, FromBytes, ToBytes};
+use odra::casper_types::{CLTyped, RuntimeArgs, U512};
+use odra::host::HostEnv;
+use odra::prelude::Address;
+use odra::CallDef;
+
+use crate::error::{Error, Result};
+
+/// Gas limit for state-changing calls unless set otherwise
+pub const DEFAULT_GAS: u64 = 10_000_000_000;
+
+/// Entry point call on one of the contracts
+#[derive(Clone, Debug, PartialEq)]
+pub struct Call {
+    pub contract: Address,
+    pub entry_point: String,
+    pub args: RuntimeArgs,
+    /// CSPR attached to a payable entry point, in motes
+    pub amount: U512,
+    /// Whether the call changes state; views are read without a transaction
+    pub is_mut: bool,
+}
+
+impl Call {
+    /// Call that changes state
+    pub fn new(contract: Address, entry_point: &str) -> Self {
+        Self {
+            contract,
+            entry_point: entry_point.to_string(),
+            args: RuntimeArgs::new(),
+            amount: U512::zero(),
+            is_mut: true,
+        }
+    }
+
+    /// Call to a view
+    pub fn view(contract: Address, entry_point: &str) -> Self {
+        Self {
+            is_mut: false,
+            ..Self::new(contract, entry_point)
+        }
+    }
+
+    /// Add a named argument
+    pub fn arg<V: CLTyped + ToBytes>(mut self, name: &str, value: V) -> Self {
+        self.args
+            .insert(name, value)
+            .expect("entry point arguments serialize");
+        self
+    }
+
+    /// Attach CSPR to a payable entry point
+    ///
+    /// Odra reads attached value from the `amount` argument, so it is passed
+    /// both as the call's value and as that argument.
+    pub fn with_amount(mut self, amount: U512) -> Self {
+        if !amount.is_zero() {
+            self = self.arg("amount", amount);
+        }
+        self.amount = amount;
+        self
+    }
+}
+
+/// Carries calls to the contracts and reads their events
+///
+/// [`OdraTransport`] covers Odra's in-memory VM and a live node; other
+/// backends only need to execute a [`Call`] and decode its return value.
+pub trait Transport {
+    /// Execute `call` and decode its return value
+    fn call<R: FromBytes + CLTyped>(&self, call: Call) -> Result<R>;
+
+    /// Number of events emitted by `contract`
+    fn event_count(&self, contract: Address) -> Result<u32>;
+
+    /// Raw bytes of the event at `index` emitted by `contract`
+    fn event_bytes(&self, contract: Address, index: u32) -> Result<Bytes>;
+}
+
+/// Transport over an Odra [`HostEnv`]
+///
+/// Wrap `odra_test::env()` to run against the in-memory VM in unit tests, or
+/// `odra_casper_livenet_env::env()` to send transactions to a node. Calls are
+/// made as `env.caller()`.
+#[derive(Clone)]
+pub struct OdraTransport {
+    env: HostEnv,
+    gas: u64,
+}
+
+impl OdraTransport {
+    pub fn new(env: HostEnv) -> Self {
+        Self {
+            env,
+            gas: DEFAULT_GAS,
+        }
+    }
+
+    /// Transport over Odra's livenet backend, configured from the
+    /// `ODRA_CASPER_LIVENET_*` environment variables
+    pub fn livenet() -> Self {
+        Self::new(odra_casper_livenet_env::env())
+    }
+
+    /// Gas limit for state-changing calls
+    pub fn with_gas(mut self, gas: u64) -> Self {
+        self.gas = gas;
+        self
+    }
+
+    pub fn env(&self) -> &HostEnv {
+        &self.env
+    }
+}
+
+impl Transport for OdraTransport {
+    fn call<R: FromBytes + CLTyped>(&self, call: Call) -> Result<R> {
+        if call.is_mut {
+            self.env.set_gas(self.gas);
+        }
+        let call_def =
+            CallDef::new(call.entry_point, call.is_mut, call.args).with_amount(call.amount);
+        Ok(self.env.call_contract(call.contract, call_def)?)
+    }
+
+    fn event_count(&self, contract: Address) -> Result<u32> {
+        Ok(self.env.events_count(&contract))
+    }
+
+    fn event_bytes(&self, contract: Address, index: u32) -> Result<Bytes> {
+        self.env
+            .get_event_bytes(&contract, index)
+            .map_err(|_| Error::EventNotFound { contract, index })
+    }
+}
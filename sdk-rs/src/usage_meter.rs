@@ -0,0 +1,418 @@
+//! UsageMeter client.
+
+use casperflow_contracts::time::{Duration, Timestamp};
+use casperflow_contracts::usage_meter::{
+    voucher_message, BillingPeriodUsage, CreditCharge, PaymentChannel, PendingAdjustments,
+    PeriodSchedule, UsageAdjustment, UsageAdjustmentPage, UsageRecord, UsageRecordFilter,
+    UsageRecordPage,
+};
+use odra::casper_types::bytesrepr::Bytes;
+use odra::casper_types::{PublicKey, U512};
+use odra::prelude::Address;
+
+use crate::error::Result;
+use crate::governance::Governance;
+use crate::transport::{Call, Transport};
+
+/// Client for the UsageMeter contract
+pub struct UsageMeterClient<'a, T> {
+    transport: &'a T,
+    address: Address,
+}
+
+impl<'a, T: Transport> UsageMeterClient<'a, T> {
+    pub fn new(transport: &'a T, address: Address) -> Self {
+        Self { transport, address }
+    }
+
+    pub fn address(&self) -> Address {
+        self.address
+    }
+
+    /// Roles, pausing, timelock and upgrade entry points
+    pub fn governance(&self) -> Governance<'a, T> {
+        Governance::new(self.transport, self.address)
+    }
+
+    fn call(&self, entry_point: &str) -> Call {
+        Call::new(self.address, entry_point)
+    }
+
+    fn view(&self, entry_point: &str) -> Call {
+        Call::view(self.address, entry_point)
+    }
+
+    // ============ RECORDER FUNCTIONS ============
+
+    /// Allow an address to record usage for a plan
+    pub fn authorize_recorder(&self, plan_id: u64, recorder: Address) -> Result<()> {
+        self.transport.call(
+            self.call("authorize_recorder")
+                .arg("plan_id", plan_id)
+                .arg("recorder", recorder),
+        )
+    }
+
+    pub fn revoke_recorder(&self, plan_id: u64, recorder: Address) -> Result<()> {
+        self.transport.call(
+            self.call("revoke_recorder")
+                .arg("plan_id", plan_id)
+                .arg("recorder", recorder),
+        )
+    }
+
+    // ============ USAGE FUNCTIONS ============
+
+    /// Record usage at the current block time; returns the record ID
+    pub fn record_usage(
+        &self,
+        subscription_id: u64,
+        plan_id: u64,
+        metric: &str,
+        units: u64,
+    ) -> Result<u64> {
+        self.transport.call(
+            self.call("record_usage")
+                .arg("subscription_id", subscription_id)
+                .arg("plan_id", plan_id)
+                .arg("metric", metric.to_string())
+                .arg("units", units),
+        )
+    }
+
+    /// Record usage that happened at `timestamp`, within the late-usage window
+    pub fn record_usage_at(
+        &self,
+        subscription_id: u64,
+        plan_id: u64,
+        metric: &str,
+        units: u64,
+        timestamp: Timestamp,
+    ) -> Result<u64> {
+        self.transport.call(
+            self.call("record_usage_at")
+                .arg("subscription_id", subscription_id)
+                .arg("plan_id", plan_id)
+                .arg("metric", metric.to_string())
+                .arg("units", units)
+                .arg("timestamp", timestamp),
+        )
+    }
+
+    /// Record one metric for several subscriptions of a plan
+    pub fn batch_record_usage(
+        &self,
+        subscription_ids: Vec<u64>,
+        plan_id: u64,
+        metric: &str,
+        units_list: Vec<u64>,
+    ) -> Result<()> {
+        self.transport.call(
+            self.call("batch_record_usage")
+                .arg("subscription_ids", subscription_ids)
+                .arg("plan_id", plan_id)
+                .arg("metric", metric.to_string())
+                .arg("units_list", units_list),
+        )
+    }
+
+    /// Credit or debit a posted record (Admin or plan merchant); returns the adjustment ID
+    pub fn adjust_usage(
+        &self,
+        original_record_id: u64,
+        plan_id: u64,
+        units: u64,
+        is_credit: bool,
+        reason_code: u8,
+    ) -> Result<u64> {
+        self.transport.call(
+            self.call("adjust_usage")
+                .arg("original_record_id", original_record_id)
+                .arg("plan_id", plan_id)
+                .arg("units", units)
+                .arg("is_credit", is_credit)
+                .arg("reason_code", reason_code),
+        )
+    }
+
+    // ============ PREPAID CREDIT ============
+
+    /// Pay a plan's usage from prepaid credit (Admin or plan merchant)
+    pub fn set_prepaid_plan(&self, plan_id: u64, prepaid: bool) -> Result<()> {
+        self.transport.call(
+            self.call("set_prepaid_plan")
+                .arg("plan_id", plan_id)
+                .arg("prepaid", prepaid),
+        )
+    }
+
+    /// Add `amount` to the caller's credit with a merchant
+    pub fn deposit_credit(&self, merchant: Address, amount: U512) -> Result<()> {
+        self.transport.call(
+            self.call("deposit_credit")
+                .arg("merchant", merchant)
+                .with_amount(amount),
+        )
+    }
+
+    /// Withdraw the caller's credit earnings; returns the amount after the protocol fee
+    pub fn withdraw_credit_earnings(&self) -> Result<U512> {
+        self.transport.call(self.call("withdraw_credit_earnings"))
+    }
+
+    pub fn is_prepaid_plan(&self, plan_id: u64) -> Result<bool> {
+        self.transport
+            .call(self.view("is_prepaid_plan").arg("plan_id", plan_id))
+    }
+
+    pub fn get_credit_balance(&self, subscriber: Address, merchant: Address) -> Result<U512> {
+        self.transport.call(
+            self.view("get_credit_balance")
+                .arg("subscriber", subscriber)
+                .arg("merchant", merchant),
+        )
+    }
+
+    pub fn get_credit_earnings(&self, merchant: Address) -> Result<U512> {
+        self.transport
+            .call(self.view("get_credit_earnings").arg("merchant", merchant))
+    }
+
+    pub fn get_credit_charge(&self, record_id: u64) -> Result<Option<CreditCharge>> {
+        self.transport
+            .call(self.view("get_credit_charge").arg("record_id", record_id))
+    }
+
+    // ============ PAYMENT CHANNELS ============
+
+    /// Lock `deposit` in a channel to the subscription's merchant; returns the channel ID
+    ///
+    /// `subscriber_key` must be the caller's account key; vouchers are signed with it.
+    pub fn open_channel(
+        &self,
+        subscription_id: u64,
+        metric: &str,
+        subscriber_key: PublicKey,
+        timeout: Duration,
+        deposit: U512,
+    ) -> Result<u64> {
+        self.transport.call(
+            self.call("open_channel")
+                .arg("subscription_id", subscription_id)
+                .arg("metric", metric.to_string())
+                .arg("subscriber_key", subscriber_key)
+                .arg("timeout", timeout)
+                .with_amount(deposit),
+        )
+    }
+
+    /// Add `amount` to one of the caller's open channels
+    pub fn top_up_channel(&self, channel_id: u64, amount: U512) -> Result<()> {
+        self.transport.call(
+            self.call("top_up_channel")
+                .arg("channel_id", channel_id)
+                .with_amount(amount),
+        )
+    }
+
+    /// Give the merchant the challenge period to close (channel subscriber);
+    /// returns when the deposit can be reclaimed
+    pub fn request_channel_close(&self, channel_id: u64) -> Result<Timestamp> {
+        self.transport
+            .call(self.call("request_channel_close").arg("channel_id", channel_id))
+    }
+
+    /// Settle the latest voucher (channel merchant or recorder); returns the
+    /// payout after the protocol fee
+    pub fn close_channel(&self, channel_id: u64, units: u64, signature: Bytes) -> Result<U512> {
+        self.transport.call(
+            self.call("close_channel")
+                .arg("channel_id", channel_id)
+                .arg("units", units)
+                .arg("signature", signature),
+        )
+    }
+
+    /// Take back the deposit of a channel the merchant did not close in time (channel subscriber)
+    pub fn reclaim_channel(&self, channel_id: u64) -> Result<U512> {
+        self.transport
+            .call(self.call("reclaim_channel").arg("channel_id", channel_id))
+    }
+
+    /// Message the subscriber signs off-chain for a voucher paying `units`
+    pub fn voucher_message(&self, channel_id: u64, units: u64) -> Bytes {
+        voucher_message(self.address, channel_id, units)
+    }
+
+    pub fn get_channel(&self, channel_id: u64) -> Result<Option<PaymentChannel>> {
+        self.transport
+            .call(self.view("get_channel").arg("channel_id", channel_id))
+    }
+
+    pub fn get_record_channel(&self, record_id: u64) -> Result<Option<u64>> {
+        self.transport
+            .call(self.view("get_record_channel").arg("record_id", record_id))
+    }
+
+    pub fn get_challenge_period(&self) -> Result<Duration> {
+        self.transport.call(self.view("get_challenge_period"))
+    }
+
+    pub fn total_channels(&self) -> Result<u64> {
+        self.transport.call(self.view("total_channels"))
+    }
+
+    // ============ BILLING FUNCTIONS ============
+
+    /// Take the adjustments waiting for a subscription's next invoice (Keeper or BillingEngine)
+    pub fn settle_adjustments(&self, subscription_id: u64) -> Result<PendingAdjustments> {
+        self.transport
+            .call(self.call("settle_adjustments").arg("subscription_id", subscription_id))
+    }
+
+    /// Close the period ending at `period_end` (keeper); returns its billable
+    /// units, excluding those paid from prepaid credit or channels
+    pub fn close_period(&self, subscription_id: u64, period_end: Timestamp) -> Result<u64> {
+        self.transport.call(
+            self.call("close_period")
+                .arg("subscription_id", subscription_id)
+                .arg("period_end", period_end),
+        )
+    }
+
+    // ============ VIEW FUNCTIONS ============
+
+    pub fn get_current_usage(&self, subscription_id: u64) -> Result<u64> {
+        self.transport
+            .call(self.view("get_current_usage").arg("subscription_id", subscription_id))
+    }
+
+    pub fn get_current_period_start(&self, subscription_id: u64) -> Result<Option<Timestamp>> {
+        self.transport.call(
+            self.view("get_current_period_start")
+                .arg("subscription_id", subscription_id),
+        )
+    }
+
+    pub fn get_period_schedule(&self, subscription_id: u64) -> Result<Option<PeriodSchedule>> {
+        self.transport
+            .call(self.view("get_period_schedule").arg("subscription_id", subscription_id))
+    }
+
+    pub fn get_late_usage_window(&self) -> Result<Duration> {
+        self.transport.call(self.view("get_late_usage_window"))
+    }
+
+    pub fn get_record(&self, record_id: u64) -> Result<Option<UsageRecord>> {
+        self.transport
+            .call(self.view("get_record").arg("record_id", record_id))
+    }
+
+    pub fn get_subscription_record_count(&self, subscription_id: u64) -> Result<u32> {
+        self.transport.call(
+            self.view("get_subscription_record_count")
+                .arg("subscription_id", subscription_id),
+        )
+    }
+
+    pub fn get_subscription_records_page(
+        &self,
+        subscription_id: u64,
+        cursor: u32,
+        limit: u32,
+        filter: UsageRecordFilter,
+    ) -> Result<UsageRecordPage> {
+        self.transport.call(
+            self.view("get_subscription_records_page")
+                .arg("subscription_id", subscription_id)
+                .arg("cursor", cursor)
+                .arg("limit", limit)
+                .arg("filter", filter),
+        )
+    }
+
+    pub fn get_period_usage(
+        &self,
+        subscription_id: u64,
+        period_start: Timestamp,
+    ) -> Result<Option<BillingPeriodUsage>> {
+        self.transport.call(
+            self.view("get_period_usage")
+                .arg("subscription_id", subscription_id)
+                .arg("period_start", period_start),
+        )
+    }
+
+    pub fn get_billable_units(&self, subscription_id: u64, period_start: Timestamp) -> Result<u64> {
+        self.transport.call(
+            self.view("get_billable_units")
+                .arg("subscription_id", subscription_id)
+                .arg("period_start", period_start),
+        )
+    }
+
+    pub fn get_adjustment(&self, adjustment_id: u64) -> Result<Option<UsageAdjustment>> {
+        self.transport
+            .call(self.view("get_adjustment").arg("adjustment_id", adjustment_id))
+    }
+
+    pub fn get_record_adjustment_count(&self, record_id: u64) -> Result<u32> {
+        self.transport
+            .call(self.view("get_record_adjustment_count").arg("record_id", record_id))
+    }
+
+    pub fn get_record_adjustments_page(
+        &self,
+        record_id: u64,
+        cursor: u32,
+        limit: u32,
+    ) -> Result<UsageAdjustmentPage> {
+        self.transport.call(
+            self.view("get_record_adjustments_page")
+                .arg("record_id", record_id)
+                .arg("cursor", cursor)
+                .arg("limit", limit),
+        )
+    }
+
+    pub fn get_pending_adjustments(&self, subscription_id: u64) -> Result<PendingAdjustments> {
+        self.transport.call(
+            self.view("get_pending_adjustments")
+                .arg("subscription_id", subscription_id),
+        )
+    }
+
+    pub fn is_authorized(&self, plan_id: u64, address: Address) -> Result<bool> {
+        self.transport.call(
+            self.view("is_authorized")
+                .arg("plan_id", plan_id)
+                .arg("address", address),
+        )
+    }
+
+    pub fn total_records(&self) -> Result<u64> {
+        self.transport.call(self.view("total_records"))
+    }
+
+    // ============ ADMIN FUNCTIONS ============
+
+    pub fn set_subscription_manager(&self, address: Address) -> Result<()> {
+        self.transport
+            .call(self.call("set_subscription_manager").arg("address", address))
+    }
+
+    pub fn set_billing_engine(&self, address: Address) -> Result<()> {
+        self.transport
+            .call(self.call("set_billing_engine").arg("address", address))
+    }
+
+    pub fn set_late_usage_window(&self, window: Duration) -> Result<()> {
+        self.transport
+            .call(self.call("set_late_usage_window").arg("window", window))
+    }
+
+    pub fn set_challenge_period(&self, period: Duration) -> Result<()> {
+        self.transport
+            .call(self.call("set_challenge_period").arg("period", period))
+    }
+}
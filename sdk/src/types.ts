@@ -48,6 +48,7 @@ export interface Subscription {
 export enum PaymentMethod {
     Wallet = 0,
     Staked = 1,
+    Streamed = 2,
 }
 
 /**
@@ -0,0 +1,710 @@
+//! Aggregates logged usage and reports it in `batch_record_usage` calls.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant};
+
+use casperflow_contracts::usage_meter::{Error as UsageError, UsageRecordFilter};
+use casperflow_sdk::{CasperFlow, Transport};
+use odra::prelude::Address;
+use serde::Deserialize;
+
+use crate::error::{Error, Result};
+use crate::wal::{Batch, Entry, Item, Wal};
+
+/// Records read per page when checking whether a batch landed
+const RECORD_PAGE: u32 = 50;
+
+/// Usage reported by a merchant service
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct UsageEvent {
+    pub subscription_id: u64,
+    pub metric: String,
+    pub units: u64,
+}
+
+/// Usage set aside after the contract rejected it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rejected {
+    pub subscription_id: u64,
+    pub metric: String,
+    pub units: u64,
+    pub reason: String,
+}
+
+/// What a flush reported
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FlushReport {
+    /// Batches recorded on chain, including ones found already recorded
+    pub batches: usize,
+    /// Units recorded
+    pub units: u64,
+    /// Usage set aside by this flush
+    pub rejected: Vec<Rejected>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BatchStatus {
+    Pending,
+    Committed,
+    Split,
+    Rejected,
+}
+
+#[derive(Debug)]
+struct Flush {
+    id: u64,
+    batches: Vec<Batch>,
+    status: Vec<BatchStatus>,
+}
+
+/// Agent state, rebuilt by replaying the log
+#[derive(Debug, Default)]
+struct State {
+    /// Units not yet in a flush, by subscription and metric
+    pending: BTreeMap<(u64, String), u64>,
+    flush: Option<Flush>,
+    next_flush_id: u64,
+    rejected: Vec<Rejected>,
+}
+
+impl State {
+    fn apply(&mut self, entry: &Entry) {
+        match entry {
+            Entry::Usage {
+                subscription_id,
+                metric,
+                units,
+            } => {
+                *self
+                    .pending
+                    .entry((*subscription_id, metric.clone()))
+                    .or_default() += units;
+            }
+            Entry::FlushStarted { flush_id, batches } => {
+                for batch in batches {
+                    for item in &batch.items {
+                        self.take(item.subscription_id, &batch.metric, item.units);
+                    }
+                }
+                self.flush = Some(Flush {
+                    id: *flush_id,
+                    batches: batches.clone(),
+                    status: vec![BatchStatus::Pending; batches.len()],
+                });
+                self.next_flush_id = flush_id + 1;
+            }
+            Entry::BatchCommitted { batch, .. } => self.set_status(*batch, BatchStatus::Committed),
+            Entry::BatchSplit { batch, .. } => {
+                self.set_status(*batch, BatchStatus::Split);
+                if let Some(flush) = &mut self.flush {
+                    let split = flush.batches[*batch].clone();
+                    for item in split.items {
+                        flush.batches.push(Batch {
+                            plan_id: split.plan_id,
+                            metric: split.metric.clone(),
+                            items: vec![item],
+                        });
+                        flush.status.push(BatchStatus::Pending);
+                    }
+                }
+            }
+            Entry::BatchRejected { batch, reason, .. } => {
+                self.set_status(*batch, BatchStatus::Rejected);
+                if let Some(flush) = &self.flush {
+                    let batch = &flush.batches[*batch];
+                    for item in &batch.items {
+                        self.rejected.push(Rejected {
+                            subscription_id: item.subscription_id,
+                            metric: batch.metric.clone(),
+                            units: item.units,
+                            reason: reason.clone(),
+                        });
+                    }
+                }
+            }
+            Entry::FlushCompleted { .. } => self.flush = None,
+            Entry::Rejected {
+                subscription_id,
+                metric,
+                units,
+                reason,
+            } => {
+                self.take(*subscription_id, metric, *units);
+                self.rejected.push(Rejected {
+                    subscription_id: *subscription_id,
+                    metric: metric.clone(),
+                    units: *units,
+                    reason: reason.clone(),
+                });
+            }
+            Entry::RejectedRequeued => {
+                for rejected in std::mem::take(&mut self.rejected) {
+                    *self
+                        .pending
+                        .entry((rejected.subscription_id, rejected.metric))
+                        .or_default() += rejected.units;
+                }
+            }
+        }
+    }
+
+    fn take(&mut self, subscription_id: u64, metric: &str, units: u64) {
+        let key = (subscription_id, metric.to_string());
+        if let Some(pending) = self.pending.get_mut(&key) {
+            *pending = pending.saturating_sub(units);
+            if *pending == 0 {
+                self.pending.remove(&key);
+            }
+        }
+    }
+
+    fn set_status(&mut self, batch: usize, status: BatchStatus) {
+        if let Some(flush) = &mut self.flush {
+            flush.status[batch] = status;
+        }
+    }
+
+    /// Entries that rebuild this state, for compacting the log
+    fn snapshot(&self) -> Vec<Entry> {
+        let pending = self.pending.iter().map(|((subscription_id, metric), units)| Entry::Usage {
+            subscription_id: *subscription_id,
+            metric: metric.clone(),
+            units: *units,
+        });
+        let rejected = self.rejected.iter().map(|rejected| Entry::Usage {
+            subscription_id: rejected.subscription_id,
+            metric: rejected.metric.clone(),
+            units: rejected.units,
+        });
+        // Rejected usage is logged as usage and set aside again
+        let set_aside = self.rejected.iter().map(|rejected| Entry::Rejected {
+            subscription_id: rejected.subscription_id,
+            metric: rejected.metric.clone(),
+            units: rejected.units,
+            reason: rejected.reason.clone(),
+        });
+        pending.chain(rejected).chain(set_aside).collect()
+    }
+}
+
+/// Whether a revert means the usage itself can never be recorded as sent
+fn is_rejection(err: &casperflow_sdk::Error) -> bool {
+    let rejections = [
+        UsageError::NotAuthorized,
+        UsageError::SubscriptionNotFound,
+        UsageError::PlanNotFound,
+        UsageError::PlanMismatch,
+        UsageError::NoBillingCycle,
+    ];
+    err.revert_code()
+        .is_some_and(|code| rejections.into_iter().any(|rejection| rejection as u16 == code))
+}
+
+/// Usage reporting agent for one recorder account
+///
+/// Usage is appended to the log before [`record`](Self::record) returns, so
+/// acknowledged usage survives a crash. A flush logs its batches before
+/// sending them. When an interrupted flush is resumed, the agent looks for
+/// the records each unsettled batch would create among the subscription's
+/// records since the batch was planned, so a batch that landed before a
+/// crash or a lost response is marked committed instead of being sent twice. Only one agent may record
+/// with a given account.
+pub struct Agent<T> {
+    client: CasperFlow<T>,
+    recorder: Address,
+    wal: Wal,
+    state: State,
+    max_batch_items: usize,
+    /// Plan of each subscription seen, `None` if it does not exist
+    plans: HashMap<u64, Option<u64>>,
+}
+
+impl<T: Transport> Agent<T> {
+    /// Open the agent's log at `path` and restore its state; `recorder` is
+    /// the account the transport sends calls from
+    pub fn open(client: CasperFlow<T>, recorder: Address, path: impl AsRef<Path>) -> Result<Self> {
+        let (wal, entries) = Wal::open(path)?;
+        let mut state = State::default();
+        for entry in &entries {
+            state.apply(entry);
+        }
+        Ok(Self {
+            client,
+            recorder,
+            wal,
+            state,
+            max_batch_items: 50,
+            plans: HashMap::new(),
+        })
+    }
+
+    /// Most subscriptions in one `batch_record_usage` call
+    pub fn with_max_batch_items(mut self, max_batch_items: usize) -> Self {
+        self.max_batch_items = max_batch_items.max(1);
+        self
+    }
+
+    /// Skip fsync on every append
+    pub fn without_sync(mut self) -> Self {
+        self.wal = self.wal.without_sync();
+        self
+    }
+
+    /// Log usage for the next flush
+    pub fn record(&mut self, event: UsageEvent) -> Result<()> {
+        if event.units == 0 {
+            return Err(Error::InvalidUsage("units must be positive"));
+        }
+        if event.metric.is_empty() {
+            return Err(Error::InvalidUsage("metric is empty"));
+        }
+        self.log(Entry::Usage {
+            subscription_id: event.subscription_id,
+            metric: event.metric,
+            units: event.units,
+        })
+    }
+
+    /// Units waiting for a flush
+    pub fn pending_units(&self, subscription_id: u64, metric: &str) -> u64 {
+        self.state
+            .pending
+            .get(&(subscription_id, metric.to_string()))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Usage the contract rejected
+    pub fn rejected(&self) -> &[Rejected] {
+        &self.state.rejected
+    }
+
+    /// Queue rejected usage again, e.g. after authorizing the recorder
+    pub fn requeue_rejected(&mut self) -> Result<()> {
+        if self.state.rejected.is_empty() {
+            return Ok(());
+        }
+        // Subscriptions not found before may exist now
+        self.plans.clear();
+        self.log(Entry::RejectedRequeued)
+    }
+
+    /// Report pending usage, finishing an interrupted flush first
+    ///
+    /// On a transport error the flush stays in the log and is resumed by the
+    /// next call, after checking which of its batches already landed.
+    pub fn flush(&mut self) -> Result<FlushReport> {
+        let mut report = FlushReport::default();
+        if self.state.flush.is_some() {
+            self.send_batches(&mut report, true)?;
+        }
+        if !self.state.pending.is_empty() {
+            self.plan(&mut report)?;
+            self.send_batches(&mut report, false)?;
+        }
+        Ok(report)
+    }
+
+    fn log(&mut self, entry: Entry) -> Result<()> {
+        self.wal.append(&entry)?;
+        self.state.apply(&entry);
+        Ok(())
+    }
+
+    fn plan_of(&mut self, subscription_id: u64) -> Result<Option<u64>> {
+        if let Some(plan_id) = self.plans.get(&subscription_id) {
+            return Ok(*plan_id);
+        }
+        let plan_id = self
+            .client
+            .subscription_manager()
+            .get_subscription(subscription_id)?
+            .map(|subscription| subscription.plan_id);
+        self.plans.insert(subscription_id, plan_id);
+        Ok(plan_id)
+    }
+
+    /// Group pending usage by plan and metric into batches and log the flush
+    fn plan(&mut self, report: &mut FlushReport) -> Result<()> {
+        let pending: Vec<_> = self.state.pending.clone().into_iter().collect();
+        let mut groups: BTreeMap<(u64, String), Vec<Item>> = BTreeMap::new();
+        for ((subscription_id, metric), units) in pending {
+            let Some(plan_id) = self.plan_of(subscription_id)? else {
+                let rejected = Rejected {
+                    subscription_id,
+                    metric,
+                    units,
+                    reason: "subscription not found".to_string(),
+                };
+                self.log(Entry::Rejected {
+                    subscription_id,
+                    metric: rejected.metric.clone(),
+                    units,
+                    reason: rejected.reason.clone(),
+                })?;
+                report.rejected.push(rejected);
+                continue;
+            };
+            let records_before = self
+                .client
+                .usage_meter()
+                .get_subscription_record_count(subscription_id)?;
+            groups.entry((plan_id, metric)).or_default().push(Item {
+                subscription_id,
+                units,
+                records_before,
+            });
+        }
+
+        let mut batches = Vec::new();
+        for ((plan_id, metric), items) in groups {
+            for chunk in items.chunks(self.max_batch_items) {
+                batches.push(Batch {
+                    plan_id,
+                    metric: metric.clone(),
+                    items: chunk.to_vec(),
+                });
+            }
+        }
+        if batches.is_empty() {
+            return Ok(());
+        }
+        let flush_id = self.state.next_flush_id;
+        self.log(Entry::FlushStarted { flush_id, batches })
+    }
+
+    /// Send every batch of the current flush that is not yet committed,
+    /// first checking whether it landed if the flush was interrupted
+    fn send_batches(&mut self, report: &mut FlushReport, resumed: bool) -> Result<()> {
+        let mut index = 0;
+        while let Some(flush) = &self.state.flush {
+            let flush_id = flush.id;
+            if index == flush.batches.len() {
+                self.log(Entry::FlushCompleted { flush_id })?;
+                self.compact()?;
+                return Ok(());
+            }
+            if flush.status[index] != BatchStatus::Pending {
+                index += 1;
+                continue;
+            }
+            let batch = flush.batches[index].clone();
+            let units: u64 = batch.items.iter().map(|item| item.units).sum();
+
+            if resumed && self.landed(&batch)? {
+                tracing::info!(flush_id, batch = index, "batch already recorded");
+                self.log(Entry::BatchCommitted { flush_id, batch: index })?;
+                report.batches += 1;
+                report.units += units;
+                continue;
+            }
+
+            let result = self.client.usage_meter().batch_record_usage(
+                batch.items.iter().map(|item| item.subscription_id).collect(),
+                batch.plan_id,
+                &batch.metric,
+                batch.items.iter().map(|item| item.units).collect(),
+            );
+            match result {
+                Ok(()) => {
+                    self.log(Entry::BatchCommitted { flush_id, batch: index })?;
+                    report.batches += 1;
+                    report.units += units;
+                }
+                Err(err) if is_rejection(&err) && batch.items.len() > 1 => {
+                    tracing::warn!(flush_id, batch = index, %err, "batch reverted, splitting");
+                    self.log(Entry::BatchSplit { flush_id, batch: index })?;
+                }
+                Err(err) if is_rejection(&err) => {
+                    tracing::warn!(flush_id, batch = index, %err, "usage rejected");
+                    let item = &batch.items[0];
+                    report.rejected.push(Rejected {
+                        subscription_id: item.subscription_id,
+                        metric: batch.metric.clone(),
+                        units: item.units,
+                        reason: err.to_string(),
+                    });
+                    self.log(Entry::BatchRejected {
+                        flush_id,
+                        batch: index,
+                        reason: err.to_string(),
+                    })?;
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the batch's records are already on chain
+    ///
+    /// Batches are recorded atomically, so finding the first item's record,
+    /// from this recorder with the same metric and units after the count
+    /// taken when the batch was planned, means the whole batch landed.
+    fn landed(&self, batch: &Batch) -> Result<bool> {
+        let item = &batch.items[0];
+        let filter = UsageRecordFilter {
+            metric: Some(batch.metric.clone()),
+            ..Default::default()
+        };
+        let meter = self.client.usage_meter();
+        let mut cursor = Some(item.records_before);
+        while let Some(at) = cursor {
+            let page = meter.get_subscription_records_page(item.subscription_id, at, RECORD_PAGE, filter.clone())?;
+            if page
+                .items
+                .iter()
+                .any(|record| record.recorded_by == self.recorder && record.units == item.units)
+            {
+                return Ok(true);
+            }
+            cursor = page.next_cursor;
+        }
+        Ok(false)
+    }
+
+    /// Rewrite the log as the pending and rejected usage alone
+    fn compact(&mut self) -> Result<()> {
+        self.wal.rewrite(&self.state.snapshot())
+    }
+
+    /// Serve [`Recorder`]s and flush every `interval` until every recorder
+    /// is dropped, then flush once more
+    ///
+    /// Odra host environments are tied to one thread, so the agent runs on
+    /// the thread that built its transport and other threads reach it
+    /// through recorders.
+    pub fn run(mut self, requests: Receiver<Request>, interval: Duration) {
+        let mut next_flush = Instant::now() + interval;
+        loop {
+            let timeout = next_flush.saturating_duration_since(Instant::now());
+            match requests.recv_timeout(timeout) {
+                Ok(Request { event, reply }) => {
+                    let _ = reply.send(self.record(event).map_err(|err| err.to_string()));
+                    continue;
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => {
+                    self.flush_logged();
+                    return;
+                }
+            }
+            self.flush_logged();
+            next_flush = Instant::now() + interval;
+        }
+    }
+
+    fn flush_logged(&mut self) {
+        match self.flush() {
+            Ok(report) => {
+                if report.batches > 0 || !report.rejected.is_empty() {
+                    tracing::info!(
+                        batches = report.batches,
+                        units = report.units,
+                        rejected = report.rejected.len(),
+                        "flushed usage"
+                    );
+                }
+            }
+            Err(err) => tracing::warn!(%err, "flush failed, retrying next interval"),
+        }
+    }
+}
+
+/// Usage event sent to a running agent, with a channel for the outcome
+pub struct Request {
+    pub event: UsageEvent,
+    pub reply: Sender<std::result::Result<(), String>>,
+}
+
+/// Cloneable handle for recording usage with an agent on another thread
+#[derive(Clone)]
+pub struct Recorder {
+    requests: Sender<Request>,
+}
+
+impl Recorder {
+    /// Recorder and the receiver to pass to [`Agent::run`]
+    pub fn channel() -> (Self, Receiver<Request>) {
+        let (requests, receiver) = mpsc::channel();
+        (Self { requests }, receiver)
+    }
+
+    /// Log usage, returning once it is durable
+    pub fn record(&self, subscription_id: u64, metric: &str, units: u64) -> Result<()> {
+        self.send(UsageEvent {
+            subscription_id,
+            metric: metric.to_string(),
+            units,
+        })
+    }
+
+    pub fn send(&self, event: UsageEvent) -> Result<()> {
+        let (reply, outcome) = mpsc::channel();
+        self.requests
+            .send(Request { event, reply })
+            .map_err(|_| Error::Stopped)?;
+        outcome.recv().map_err(|_| Error::Stopped)?.map_err(Error::Refused)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use casperflow_contracts::billing_engine::BillingEngine;
+    use casperflow_contracts::registry::{Component, Registry};
+    use casperflow_contracts::stake_to_pay::StakeToPay;
+    use casperflow_contracts::subscription_manager::SubscriptionManager;
+    use casperflow_contracts::time::Duration;
+    use casperflow_contracts::usage_meter::UsageMeter;
+    use casperflow_sdk::{Call, OdraTransport, PaymentMethod};
+    use odra::casper_types::bytesrepr::{Bytes, FromBytes};
+    use odra::casper_types::{CLTyped, U512};
+    use odra::host::{Deployer, HostEnv, HostRef, NoArgs};
+    use odra::prelude::{ExecutionError, OdraError};
+
+    use super::*;
+    use crate::wal::tests::temp_path;
+
+    /// Transport whose next `batch_record_usage` lands but reports a failure
+    struct LostResponse {
+        inner: OdraTransport,
+        lose_next: Cell<bool>,
+    }
+
+    impl Transport for LostResponse {
+        fn call<R: FromBytes + CLTyped>(&self, call: Call) -> casperflow_sdk::Result<R> {
+            let batch = call.entry_point == "batch_record_usage";
+            let result = self.inner.call(call);
+            if batch && result.is_ok() && self.lose_next.replace(false) {
+                return Err(casperflow_sdk::Error::Call(OdraError::ExecutionError(
+                    ExecutionError::UnwrapError,
+                )));
+            }
+            result
+        }
+
+        fn event_count(&self, contract: Address) -> casperflow_sdk::Result<u32> {
+            self.inner.event_count(contract)
+        }
+
+        fn event_bytes(&self, contract: Address, index: u32) -> casperflow_sdk::Result<Bytes> {
+            self.inner.event_bytes(contract, index)
+        }
+    }
+
+    /// Deploys the protocol with a plan whose recorder is account 0; returns
+    /// the registry and two subscriptions to it
+    fn deploy(env: &HostEnv) -> (Address, u64, u64) {
+        let mut registry = Registry::deploy(env, NoArgs);
+        let mut manager = SubscriptionManager::deploy(env, NoArgs);
+        let mut meter = UsageMeter::deploy(env, NoArgs);
+        let mut engine = BillingEngine::deploy(env, NoArgs);
+        let mut stake_to_pay = StakeToPay::deploy(env, NoArgs);
+        registry.register(Component::SubscriptionManager, manager.address());
+        registry.register(Component::UsageMeter, meter.address());
+        registry.register(Component::BillingEngine, engine.address());
+        registry.register(Component::StakeToPay, stake_to_pay.address());
+        manager.set_registry(registry.address());
+        meter.set_registry(registry.address());
+        engine.set_registry(registry.address());
+        stake_to_pay.set_registry(registry.address());
+
+        let client = CasperFlow::from_registry(OdraTransport::new(env.clone()), registry.address()).unwrap();
+        let price = U512::from(1_000u64);
+        let plan_id = client
+            .subscription_manager()
+            .create_plan("Pro", price, U512::one(), Duration::from_days(30))
+            .unwrap();
+        client.usage_meter().authorize_recorder(plan_id, env.get_account(0)).unwrap();
+        let subscribe = |account| {
+            env.set_caller(env.get_account(account));
+            client
+                .subscription_manager()
+                .subscribe(plan_id, true, PaymentMethod::Wallet, price)
+                .unwrap()
+        };
+        let subscriptions = (subscribe(1), subscribe(2));
+        env.set_caller(env.get_account(0));
+        (registry.address(), subscriptions.0, subscriptions.1)
+    }
+
+    fn usage(subscription_id: u64, units: u64) -> UsageEvent {
+        UsageEvent {
+            subscription_id,
+            metric: "api_calls".to_string(),
+            units,
+        }
+    }
+
+    #[test]
+    fn test_aggregates_and_flushes_in_batches() {
+        let env = odra_test::env();
+        let (registry, first, second) = deploy(&env);
+        let client = || CasperFlow::from_registry(OdraTransport::new(env.clone()), registry).unwrap();
+        let path = temp_path("agent-flush");
+        let mut agent = Agent::open(client(), env.get_account(0), &path).unwrap().without_sync();
+
+        agent.record(usage(first, 3)).unwrap();
+        agent.record(usage(first, 4)).unwrap();
+        agent.record(usage(second, 5)).unwrap();
+        agent.record(usage(99, 1)).unwrap();
+        assert!(agent.record(usage(first, 0)).is_err());
+        assert_eq!(agent.pending_units(first, "api_calls"), 7);
+
+        // Usage survives a restart before the flush
+        drop(agent);
+        let mut agent = Agent::open(client(), env.get_account(0), &path).unwrap().without_sync();
+        let report = agent.flush().unwrap();
+        assert_eq!((report.batches, report.units), (1, 12));
+        assert_eq!(report.rejected.len(), 1);
+        assert_eq!(agent.rejected()[0].subscription_id, 99);
+        assert_eq!(agent.pending_units(first, "api_calls"), 0);
+
+        let client = client();
+        let meter = client.usage_meter();
+        assert_eq!(meter.get_current_usage(first).unwrap(), 7);
+        assert_eq!(meter.get_current_usage(second).unwrap(), 5);
+        assert_eq!(meter.get_subscription_record_count(first).unwrap(), 1);
+        assert_eq!(agent.flush().unwrap(), FlushReport::default());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_lost_response_is_not_recorded_twice() {
+        let env = odra_test::env();
+        let (registry, first, second) = deploy(&env);
+        let path = temp_path("agent-retry");
+        let transport = LostResponse {
+            inner: OdraTransport::new(env.clone()),
+            lose_next: Cell::new(true),
+        };
+        let client = CasperFlow::from_registry(transport, registry).unwrap();
+        let mut agent = Agent::open(client, env.get_account(0), &path)
+            .unwrap()
+            .with_max_batch_items(1)
+            .without_sync();
+        agent.record(usage(first, 3)).unwrap();
+        agent.record(usage(second, 5)).unwrap();
+        // The first batch lands but the agent sees an error and stops
+        assert!(agent.flush().is_err());
+        drop(agent);
+
+        // After a restart the landed batch is found and only the second is sent
+        let client = CasperFlow::from_registry(OdraTransport::new(env.clone()), registry).unwrap();
+        let mut agent = Agent::open(client, env.get_account(0), &path).unwrap().without_sync();
+        agent.record(usage(first, 2)).unwrap();
+        let report = agent.flush().unwrap();
+        assert_eq!((report.batches, report.units), (3, 10));
+
+        let client = CasperFlow::from_registry(OdraTransport::new(env.clone()), registry).unwrap();
+        let meter = client.usage_meter();
+        assert_eq!(meter.get_current_usage(first).unwrap(), 5);
+        assert_eq!(meter.get_current_usage(second).unwrap(), 5);
+        assert_eq!(meter.get_subscription_record_count(first).unwrap(), 2);
+        std::fs::remove_file(path).unwrap();
+    }
+}
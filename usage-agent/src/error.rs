@@ -0,0 +1,27 @@
+//! Agent error type.
+
+use thiserror::Error;
+
+/// Result alias used throughout the agent
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Errors raised while logging, aggregating or reporting usage
+#[derive(Debug, Error)]
+pub enum Error {
+    /// Usage event is malformed
+    #[error("invalid usage: {0}")]
+    InvalidUsage(&'static str),
+    /// Contract call failed; the flush is retried later
+    #[error(transparent)]
+    Chain(#[from] casperflow_sdk::Error),
+    /// Running agent could not log the usage
+    #[error("usage refused: {0}")]
+    Refused(String),
+    /// The agent loop has stopped
+    #[error("agent is not running")]
+    Stopped,
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
@@ -0,0 +1,16 @@
+//! CasperFlow usage agent
+//!
+//! Merchants embed the [`Agent`] (or run the `casperflow-usage-agent` daemon)
+//! to report metered usage. Usage is written to a local write-ahead log as it
+//! arrives, summed per subscription and metric, and sent to the UsageMeter in
+//! `batch_record_usage` calls on a schedule. Flushes are logged batch by batch
+//! and checked against the chain before a retry, so a crash or a lost
+//! response neither drops nor double counts usage.
+
+pub mod agent;
+pub mod error;
+pub mod server;
+pub mod wal;
+
+pub use agent::{Agent, FlushReport, Recorder, Rejected, UsageEvent};
+pub use error::{Error, Result};
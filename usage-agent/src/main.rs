@@ -0,0 +1,70 @@
+//! Usage agent daemon: serves a local socket and flushes on an interval.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use casperflow_sdk::{CasperFlow, OdraTransport};
+use casperflow_usage_agent::{server, Agent, Recorder};
+use clap::Parser;
+use odra::prelude::Address;
+use tracing_subscriber::EnvFilter;
+
+/// Buffers usage reported over a Unix socket and records it on the
+/// CasperFlow UsageMeter in batches.
+///
+/// The node, chain name and recorder key are read from the
+/// `ODRA_CASPER_LIVENET_*` environment variables used by Odra's livenet
+/// backend. The recorder account must be authorized for each plan it reports
+/// usage for.
+#[derive(Parser)]
+#[command(version)]
+struct Args {
+    /// Registry contract (`hash-…`)
+    #[arg(long, env = "CASPERFLOW_REGISTRY")]
+    registry: String,
+    /// Write-ahead log file
+    #[arg(long, default_value = "usage-agent.wal")]
+    wal: PathBuf,
+    /// Unix socket clients write usage events to
+    #[arg(long, default_value = "/tmp/casperflow-usage.sock")]
+    socket: PathBuf,
+    /// Seconds between flushes; keep well below the plans' billing cycles
+    #[arg(long, default_value_t = 60)]
+    interval: u64,
+    /// Most subscriptions in one `batch_record_usage` call
+    #[arg(long, default_value_t = 50)]
+    max_batch_items: usize,
+    /// Gas attached to each call, in motes
+    #[arg(long, default_value_t = casperflow_sdk::transport::DEFAULT_GAS)]
+    gas: u64,
+}
+
+fn main() {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")))
+        .init();
+    let args = Args::parse();
+    let registry = Address::new(&args.registry).unwrap_or_else(|err| {
+        eprintln!("invalid --registry {}: {err:?}", args.registry);
+        std::process::exit(2);
+    });
+
+    let transport = OdraTransport::livenet().with_gas(args.gas);
+    let recorder = transport.env().caller();
+    let agent = CasperFlow::from_registry(transport, registry)
+        .map_err(Into::into)
+        .and_then(|client| Agent::open(client, recorder, &args.wal))
+        .unwrap_or_else(|err| {
+            eprintln!("cannot start agent: {err}");
+            std::process::exit(1);
+        })
+        .with_max_batch_items(args.max_batch_items);
+
+    let (handle, requests) = Recorder::channel();
+    if let Err(err) = server::serve(&args.socket, handle) {
+        eprintln!("cannot listen on {}: {err}", args.socket.display());
+        std::process::exit(1);
+    }
+    tracing::info!(?recorder, socket = %args.socket.display(), "starting");
+    agent.run(requests, Duration::from_secs(args.interval));
+}
@@ -0,0 +1,89 @@
+//! Local socket for services that report usage from another process.
+//!
+//! Clients write one JSON usage event per line, e.g.
+//! `{"subscription_id":4,"metric":"api_calls","units":3}`, and read back
+//! `ok` once the event is in the log, or `error: <reason>`.
+
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::thread;
+
+use crate::agent::{Recorder, UsageEvent};
+use crate::error::Result;
+
+/// Accept connections on a Unix socket at `path`, one thread per client
+pub fn serve(path: impl AsRef<Path>, recorder: Recorder) -> Result<thread::JoinHandle<()>> {
+    let path = path.as_ref();
+    // A socket left by a previous run would make bind fail
+    if path.exists() {
+        std::fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    Ok(thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let recorder = recorder.clone();
+                    thread::spawn(move || {
+                        if let Err(err) = handle(stream, &recorder) {
+                            tracing::debug!(%err, "usage client disconnected");
+                        }
+                    });
+                }
+                Err(err) => tracing::warn!(%err, "accepting usage client failed"),
+            }
+        }
+    }))
+}
+
+fn handle(stream: UnixStream, recorder: &Recorder) -> Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let outcome = serde_json::from_str::<UsageEvent>(&line)
+            .map_err(Into::into)
+            .and_then(|event| recorder.send(event));
+        match outcome {
+            Ok(()) => writer.write_all(b"ok\n")?,
+            Err(err) => writeln!(writer, "error: {err}")?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::Request;
+
+    #[test]
+    fn test_acknowledges_each_event() {
+        let path = std::env::temp_dir().join(format!("casperflow-usage-{}.sock", std::process::id()));
+        let (recorder, requests) = Recorder::channel();
+        serve(&path, recorder).unwrap();
+        // Stand-in for the agent loop
+        thread::spawn(move || {
+            for Request { event, reply } in requests {
+                let outcome = if event.units > 100 { Err("too much".to_string()) } else { Ok(()) };
+                reply.send(outcome).unwrap();
+            }
+        });
+
+        let mut client = UnixStream::connect(&path).unwrap();
+        client
+            .write_all(b"{\"subscription_id\":4,\"metric\":\"api_calls\",\"units\":3}\nnot json\n")
+            .unwrap();
+        client
+            .write_all(b"{\"subscription_id\":4,\"metric\":\"api_calls\",\"units\":300}\n")
+            .unwrap();
+        let mut replies = BufReader::new(client).lines();
+        assert_eq!(replies.next().unwrap().unwrap(), "ok");
+        assert!(replies.next().unwrap().unwrap().starts_with("error: "));
+        assert_eq!(replies.next().unwrap().unwrap(), "error: usage refused: too much");
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,180 @@
+//! Write-ahead log of usage and flush progress.
+//!
+//! Each entry is one JSON line, synced to disk before `append` returns. A
+//! line cut short by a crash was never acknowledged, so it is dropped when
+//! the log is opened.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+
+/// Usage of one subscription within a batch
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Item {
+    pub subscription_id: u64,
+    pub units: u64,
+    /// Subscription's on-chain record count before the batch was first sent
+    pub records_before: u32,
+}
+
+/// One `batch_record_usage` call: a plan, a metric and its subscriptions
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Batch {
+    pub plan_id: u64,
+    pub metric: String,
+    pub items: Vec<Item>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Entry {
+    /// Usage accepted from a client
+    Usage {
+        subscription_id: u64,
+        metric: String,
+        units: u64,
+    },
+    /// Pending usage moved into batches about to be sent
+    FlushStarted { flush_id: u64, batches: Vec<Batch> },
+    /// Batch is recorded on chain
+    BatchCommitted { flush_id: u64, batch: usize },
+    /// Batch reverted; each of its items is retried as a batch of its own
+    BatchSplit { flush_id: u64, batch: usize },
+    /// Single-item batch reverted and was set aside
+    BatchRejected {
+        flush_id: u64,
+        batch: usize,
+        reason: String,
+    },
+    /// Every batch of the flush is committed or rejected
+    FlushCompleted { flush_id: u64 },
+    /// Usage that could not be reported, kept for an operator to retry
+    Rejected {
+        subscription_id: u64,
+        metric: String,
+        units: u64,
+        reason: String,
+    },
+    /// Rejected usage was queued again
+    RejectedRequeued,
+}
+
+/// Append-only log file
+pub struct Wal {
+    path: PathBuf,
+    file: File,
+    sync: bool,
+}
+
+impl Wal {
+    /// Open or create the log and read its entries
+    pub fn open(path: impl AsRef<Path>) -> Result<(Self, Vec<Entry>)> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&path)?;
+
+        let mut entries = Vec::new();
+        let mut complete = 0u64;
+        let mut reader = BufReader::new(&file);
+        let mut line = String::new();
+        loop {
+            line.clear();
+            let read = reader.read_line(&mut line)?;
+            if read == 0 || !line.ends_with('\n') {
+                break;
+            }
+            entries.push(serde_json::from_str(line.trim_end())?);
+            complete += read as u64;
+        }
+        // Drop a torn final line so the next append starts on a fresh line
+        if file.metadata()?.len() > complete {
+            tracing::warn!(path = %path.display(), "dropping incomplete log entry");
+            file.set_len(complete)?;
+            file.seek(SeekFrom::End(0))?;
+        }
+
+        Ok((Self { path, file, sync: true }, entries))
+    }
+
+    /// Skip fsync after each append, for tests and throwaway logs
+    pub fn without_sync(mut self) -> Self {
+        self.sync = false;
+        self
+    }
+
+    pub fn append(&mut self, entry: &Entry) -> Result<()> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        self.file.write_all(&line)?;
+        if self.sync {
+            self.file.sync_data()?;
+        }
+        Ok(())
+    }
+
+    /// Replace the log with `entries`, atomically
+    pub fn rewrite(&mut self, entries: &[Entry]) -> Result<()> {
+        let tmp = self.path.with_extension("tmp");
+        {
+            let mut file = File::create(&tmp)?;
+            for entry in entries {
+                serde_json::to_writer(&mut file, entry)?;
+                file.write_all(b"\n")?;
+            }
+            file.sync_all()?;
+        }
+        fs::rename(&tmp, &self.path)?;
+        self.file = OpenOptions::new().append(true).open(&self.path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("casperflow-{name}-{}.wal", std::process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_drops_torn_entry_and_rewrites() {
+        let path = temp_path("wal");
+        let usage = |units| Entry::Usage {
+            subscription_id: 1,
+            metric: "api_calls".to_string(),
+            units,
+        };
+        let (mut wal, entries) = Wal::open(&path).unwrap();
+        assert!(entries.is_empty());
+        wal.append(&usage(3)).unwrap();
+        wal.append(&usage(4)).unwrap();
+        drop(wal);
+
+        // A crash mid-append leaves half a line
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(br#"{"type":"usage","subscr"#).unwrap();
+        drop(file);
+
+        let (mut wal, entries) = Wal::open(&path).unwrap();
+        assert_eq!(entries, [usage(3), usage(4)]);
+        wal.append(&usage(5)).unwrap();
+        let (mut wal, entries) = Wal::open(&path).unwrap();
+        assert_eq!(entries, [usage(3), usage(4), usage(5)]);
+
+        wal.rewrite(&[usage(12)]).unwrap();
+        wal.append(&Entry::RejectedRequeued).unwrap();
+        let (_, entries) = Wal::open(&path).unwrap();
+        assert_eq!(entries, [usage(12), Entry::RejectedRequeued]);
+        fs::remove_file(path).unwrap();
+    }
+}
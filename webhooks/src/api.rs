@@ -0,0 +1,220 @@
+//! Admin API for endpoints, the delivery log and replays.
+
+use std::sync::Arc;
+
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::dispatcher::{now_millis, Dispatcher};
+use crate::error::Error;
+use crate::store::{DeliveryFilter, NewEndpoint};
+
+type SharedDispatcher = Arc<Dispatcher>;
+
+/// Routes served by the dispatcher
+pub fn router(dispatcher: SharedDispatcher) -> Router {
+    Router::new()
+        .route("/endpoints", get(endpoints).post(add_endpoint))
+        .route("/endpoints/:id", get(endpoint).delete(remove_endpoint))
+        .route("/deliveries", get(deliveries))
+        .route("/deliveries/:id/attempts", get(attempts))
+        .route("/deliveries/:id/retry", post(retry))
+        .route("/replay", post(replay))
+        .with_state(dispatcher)
+}
+
+/// Error response with a JSON body
+enum ApiError {
+    NotFound,
+    BadRequest(String),
+    Internal(Error),
+}
+
+impl From<Error> for ApiError {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::InvalidEndpoint(_) => Self::BadRequest(err.to_string()),
+            err => Self::Internal(err),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, message) = match self {
+            Self::NotFound => (StatusCode::NOT_FOUND, "not found".to_string()),
+            Self::BadRequest(message) => (StatusCode::BAD_REQUEST, message),
+            Self::Internal(err) => {
+                tracing::error!(%err, "request failed");
+                (StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
+            }
+        };
+        (status, Json(json!({ "error": message }))).into_response()
+    }
+}
+
+type ApiResult = Result<Response, ApiError>;
+
+#[derive(Deserialize)]
+struct EndpointFilter {
+    merchant: Option<String>,
+}
+
+async fn endpoints(
+    State(dispatcher): State<SharedDispatcher>,
+    Query(filter): Query<EndpointFilter>,
+) -> ApiResult {
+    let endpoints = dispatcher.store().endpoints(filter.merchant.as_deref())?;
+    Ok(Json(endpoints).into_response())
+}
+
+/// Register an endpoint; the only response that includes its secret
+async fn add_endpoint(
+    State(dispatcher): State<SharedDispatcher>,
+    Json(endpoint): Json<NewEndpoint>,
+) -> ApiResult {
+    let endpoint = dispatcher.store().add_endpoint(endpoint, now_millis())?;
+    let mut body = json!(endpoint);
+    body["secret"] = json!(endpoint.secret);
+    Ok((StatusCode::CREATED, Json(body)).into_response())
+}
+
+async fn endpoint(State(dispatcher): State<SharedDispatcher>, Path(id): Path<u64>) -> ApiResult {
+    let endpoint = dispatcher.store().endpoint(id)?.ok_or(ApiError::NotFound)?;
+    Ok(Json(endpoint).into_response())
+}
+
+async fn remove_endpoint(
+    State(dispatcher): State<SharedDispatcher>,
+    Path(id): Path<u64>,
+) -> ApiResult {
+    if !dispatcher.store().disable_endpoint(id)? {
+        return Err(ApiError::NotFound);
+    }
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+async fn deliveries(
+    State(dispatcher): State<SharedDispatcher>,
+    Query(filter): Query<DeliveryFilter>,
+) -> ApiResult {
+    Ok(Json(dispatcher.store().deliveries(&filter)?).into_response())
+}
+
+async fn attempts(State(dispatcher): State<SharedDispatcher>, Path(id): Path<u64>) -> ApiResult {
+    Ok(Json(dispatcher.store().attempts(id)?).into_response())
+}
+
+async fn retry(State(dispatcher): State<SharedDispatcher>, Path(id): Path<u64>) -> ApiResult {
+    if !dispatcher.store().redeliver(id, now_millis())? {
+        return Err(ApiError::NotFound);
+    }
+    Ok(StatusCode::ACCEPTED.into_response())
+}
+
+#[derive(Deserialize)]
+struct Replay {
+    from_height: u64,
+    endpoint_id: Option<u64>,
+}
+
+async fn replay(
+    State(dispatcher): State<SharedDispatcher>,
+    Json(replay): Json<Replay>,
+) -> ApiResult {
+    let queued = dispatcher
+        .replay(replay.from_height, replay.endpoint_id)
+        .await?;
+    Ok((StatusCode::ACCEPTED, Json(json!({ "queued": queued }))).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use axum::body::Body;
+    use axum::http::Request;
+    use http_body_util::BodyExt;
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::source::IndexerClient;
+    use crate::store::Store;
+
+    async fn send(router: &Router, request: Request<Body>) -> (StatusCode, Value) {
+        let response = router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    fn post_json(uri: &str, body: Value) -> Request<Body> {
+        Request::post(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_endpoint_routes() {
+        let store = Arc::new(Mutex::new(Store::in_memory().unwrap()));
+        let dispatcher = Dispatcher::new(
+            store,
+            IndexerClient::new("http://127.0.0.1:9"),
+            Default::default(),
+        )
+        .unwrap();
+        let router = router(Arc::new(dispatcher));
+
+        let (status, created) = send(
+            &router,
+            post_json(
+                "/endpoints",
+                json!({"merchant": "m", "url": "https://m/hooks", "event_types": ["payment.failed"]}),
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert!(created["secret"].as_str().unwrap().starts_with("whsec_"));
+
+        let (status, _) = send(
+            &router,
+            post_json(
+                "/endpoints",
+                json!({"merchant": "m", "url": "m/hooks", "event_types": ["payment.failed"]}),
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (_, listed) = send(
+            &router,
+            Request::get("/endpoints?merchant=m")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(listed[0]["event_types"], json!(["payment.failed"]));
+        assert!(listed[0].get("secret").is_none());
+
+        let uri = format!("/endpoints/{}", created["id"]);
+        let (status, _) = send(&router, Request::delete(&uri).body(Body::empty()).unwrap()).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (_, endpoint) = send(&router, Request::get(&uri).body(Body::empty()).unwrap()).await;
+        assert_eq!(endpoint["active"], false);
+        let (status, _) = send(
+            &router,
+            Request::post("/deliveries/7/retry")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
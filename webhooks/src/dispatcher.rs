@@ -0,0 +1,523 @@
+//! Turns final contract events into deliveries and sends them.
+
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use reqwest::header::CONTENT_TYPE;
+use serde_json::Value;
+use tracing::{debug, info, warn};
+
+use crate::error::Result;
+use crate::event::{EventType, WebhookEvent};
+use crate::signing::{self, SIGNATURE_HEADER};
+use crate::source::{IndexedEvent, IndexerClient};
+use crate::store::{Attempt, DueDelivery, Outcome, Store};
+
+/// Header carrying the webhook event id
+pub const EVENT_ID_HEADER: &str = "CasperFlow-Event-Id";
+/// Header carrying the webhook event type
+pub const EVENT_TYPE_HEADER: &str = "CasperFlow-Event-Type";
+
+/// Milliseconds since the Unix epoch
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
+
+/// Exponential backoff between attempts of a delivery
+#[derive(Clone, Copy, Debug)]
+pub struct Retry {
+    /// Attempts before a delivery is marked failed
+    pub max_attempts: u32,
+    /// Delay after the first failed attempt, doubled after each one
+    pub initial: Duration,
+    pub max: Duration,
+}
+
+impl Default for Retry {
+    fn default() -> Self {
+        Self {
+            max_attempts: 8,
+            initial: Duration::from_secs(10),
+            max: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+impl Retry {
+    /// Delay after the `attempts`-th failed attempt
+    pub fn delay(&self, attempts: u32) -> Duration {
+        let doublings = attempts.saturating_sub(1).min(31);
+        self.initial.saturating_mul(1 << doublings).min(self.max)
+    }
+
+    fn outcome(&self, attempts: u32, now: u64) -> Outcome {
+        if attempts >= self.max_attempts {
+            Outcome::Failed
+        } else {
+            Outcome::Retry(now + self.delay(attempts).as_millis() as u64)
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub retry: Retry,
+    /// Time an endpoint has to answer
+    pub timeout: Duration,
+    /// Events read from the indexer, and deliveries sent, per round
+    pub batch: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            retry: Retry::default(),
+            timeout: Duration::from_secs(10),
+            batch: 100,
+        }
+    }
+}
+
+/// Deliveries made by one round
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Round {
+    pub delivered: usize,
+    pub retried: usize,
+    pub failed: usize,
+}
+
+/// Follows the indexer and delivers webhooks
+pub struct Dispatcher {
+    store: Arc<Mutex<Store>>,
+    indexer: IndexerClient,
+    http: reqwest::Client,
+    config: Config,
+}
+
+impl Dispatcher {
+    pub fn new(store: Arc<Mutex<Store>>, indexer: IndexerClient, config: Config) -> Result<Self> {
+        let http = reqwest::Client::builder().timeout(config.timeout).build()?;
+        Ok(Self {
+            store,
+            indexer,
+            http,
+            config,
+        })
+    }
+
+    pub fn store(&self) -> MutexGuard<'_, Store> {
+        self.store.lock().expect("store lock")
+    }
+
+    /// Queue deliveries for final events after the cursor; returns how many
+    ///
+    /// Only events at or below the indexer's checkpoint are read, so a
+    /// webhook is never sent for a block that is later rolled back.
+    pub async fn poll(&self) -> Result<usize> {
+        let Some(final_height) = self.indexer.final_height().await? else {
+            return Ok(0);
+        };
+        let mut queued = 0;
+        loop {
+            let cursor = self.store().cursor()?;
+            let events = self.indexer.events(cursor, None, self.config.batch).await?;
+            let mut last = cursor;
+            let mut reached_tip = (events.len() as u64) < self.config.batch;
+            for event in &events {
+                if event.block_height > final_height {
+                    reached_tip = true;
+                    break;
+                }
+                if let Some(webhook) = self.webhook(event).await? {
+                    queued += self.enqueue(&webhook, None, false)?;
+                }
+                last = event.id;
+            }
+            self.store().set_cursor(last)?;
+            if reached_tip {
+                return Ok(queued);
+            }
+        }
+    }
+
+    /// Queue every final event from `from_height` up to the cursor again,
+    /// for one endpoint or all of them; returns how many deliveries
+    pub async fn replay(&self, from_height: u64, endpoint_id: Option<u64>) -> Result<usize> {
+        let cursor = self.store().cursor()?;
+        let mut after = 0;
+        let mut queued = 0;
+        loop {
+            let events = self
+                .indexer
+                .events(after, Some(from_height), self.config.batch)
+                .await?;
+            for event in &events {
+                if event.id > cursor {
+                    return Ok(queued);
+                }
+                if let Some(webhook) = self.webhook(event).await? {
+                    queued += self.enqueue(&webhook, endpoint_id, true)?;
+                }
+                after = event.id;
+            }
+            if (events.len() as u64) < self.config.batch {
+                return Ok(queued);
+            }
+        }
+    }
+
+    /// Webhook for an indexed event, if it is a billing event with a known
+    /// merchant
+    async fn webhook(&self, event: &IndexedEvent) -> Result<Option<WebhookEvent>> {
+        let Some(event_type) = EventType::of_contract_event(&event.name) else {
+            return Ok(None);
+        };
+        let field = |name: &str| event.payload.get(name).and_then(Value::as_u64);
+        let owner = match event_type {
+            EventType::PaymentFailed => match field("invoice_id") {
+                Some(invoice_id) => self.indexer.invoice_owner(invoice_id).await?,
+                None => None,
+            },
+            _ => match field("subscription_id") {
+                Some(subscription_id) => self
+                    .indexer
+                    .subscription_merchant(subscription_id)
+                    .await?
+                    .map(|merchant| (subscription_id, merchant)),
+                None => None,
+            },
+        };
+        let Some((subscription_id, merchant)) = owner else {
+            debug!(id = event.id, name = %event.name, "no merchant for event");
+            return Ok(None);
+        };
+        Ok(Some(WebhookEvent {
+            id: format!("{}-{}", event.transaction_hash, event.event_index),
+            event_type,
+            merchant,
+            subscription_id,
+            block_height: event.block_height,
+            transaction_hash: event.transaction_hash.clone(),
+            data: event.payload.clone(),
+        }))
+    }
+
+    fn enqueue(&self, webhook: &WebhookEvent, only: Option<u64>, replay: bool) -> Result<usize> {
+        let mut store = self.store();
+        let now = now_millis();
+        let mut queued = 0;
+        for endpoint in store.subscribers(&webhook.merchant, webhook.event_type)? {
+            if only.is_some_and(|id| id != endpoint.id) {
+                continue;
+            }
+            queued += usize::from(store.enqueue(endpoint.id, webhook, now, replay)?);
+        }
+        Ok(queued)
+    }
+
+    /// Attempt every delivery that is due
+    pub async fn deliver_due(&self) -> Result<Round> {
+        let due = self.store().due(now_millis(), self.config.batch)?;
+        let mut round = Round::default();
+        for delivery in due {
+            let attempt = self.attempt(&delivery).await;
+            let outcome = match attempt.error {
+                None => Outcome::Delivered,
+                Some(_) => self
+                    .config
+                    .retry
+                    .outcome(delivery.attempts + 1, now_millis()),
+            };
+            match outcome {
+                Outcome::Delivered => round.delivered += 1,
+                Outcome::Retry(_) => round.retried += 1,
+                Outcome::Failed => {
+                    warn!(id = delivery.id, url = %delivery.url, "delivery failed, attempts exhausted");
+                    round.failed += 1;
+                }
+            }
+            self.store()
+                .record_attempt(delivery.id, &attempt, outcome)?;
+        }
+        Ok(round)
+    }
+
+    async fn attempt(&self, delivery: &DueDelivery) -> Attempt {
+        let attempted_at = now_millis();
+        let signature = signing::sign(
+            &delivery.secret,
+            attempted_at / 1_000,
+            delivery.payload.as_bytes(),
+        );
+        let started = Instant::now();
+        let response = self
+            .http
+            .post(&delivery.url)
+            .header(CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, signature)
+            .header(EVENT_ID_HEADER, &delivery.event_id)
+            .header(EVENT_TYPE_HEADER, &delivery.event_type)
+            .body(delivery.payload.clone())
+            .send()
+            .await;
+        let (status_code, error) = match response {
+            Ok(response) if response.status().is_success() => (Some(response.status()), None),
+            Ok(response) => (
+                Some(response.status()),
+                Some(format!("endpoint returned {}", response.status())),
+            ),
+            Err(err) => (err.status(), Some(err.to_string())),
+        };
+        Attempt {
+            attempted_at,
+            status_code: status_code.map(|status| status.as_u16()),
+            error,
+            duration_ms: started.elapsed().as_millis() as u64,
+        }
+    }
+
+    /// Poll and deliver every `interval`, forever
+    pub async fn run(&self, interval: Duration) {
+        loop {
+            match self.poll().await {
+                Ok(0) => {}
+                Ok(queued) => info!(queued, "queued deliveries"),
+                Err(err) => warn!(%err, "reading events failed"),
+            }
+            match self.deliver_due().await {
+                Ok(round) if round != Round::default() => info!(
+                    delivered = round.delivered,
+                    retried = round.retried,
+                    failed = round.failed,
+                    "delivery round"
+                ),
+                Ok(_) => {}
+                Err(err) => warn!(%err, "delivery round failed"),
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::IntoFuture;
+
+    use axum::extract::State;
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::routing::post;
+    use axum::Router;
+    use casperflow_indexer::ces::Event;
+    use casperflow_indexer::sse::Block;
+    use serde_json::json;
+
+    use super::*;
+    use crate::store::{DeliveryFilter, DeliveryStatus, NewEndpoint};
+
+    const MERCHANT: &str = "account-hash-01";
+
+    /// Serves `router` on a free local port; returns its base URL
+    async fn serve(router: Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(axum::serve(listener, router).into_future());
+        format!("http://{address}")
+    }
+
+    /// Indexer with a plan, a subscription, a renewal, a failed payment and a
+    /// cancellation; the cancellation's block is not final yet
+    async fn indexer() -> (String, Arc<Mutex<casperflow_indexer::Store>>) {
+        let mut store = casperflow_indexer::Store::in_memory().unwrap();
+        let event = |contract: &str, name: &str, payload: Value| Event {
+            contract: contract.into(),
+            name: name.into(),
+            payload,
+        };
+        for height in 1..=3 {
+            let block = Block {
+                hash: format!("a{height}"),
+                height,
+                parent_hash: format!("a{}", height - 1),
+            };
+            store.add_block(&block, Some(height)).unwrap();
+        }
+        store
+            .insert_events(
+                "a1",
+                "t1",
+                &[
+                    event("SubscriptionManager", "PlanCreated", json!({"plan_id": 1, "merchant": MERCHANT, "name": "Pro", "base_price": "100"})),
+                    event("SubscriptionManager", "Subscribed", json!({"subscription_id": 1, "plan_id": 1, "subscriber": "account-hash-02"})),
+                    event("BillingEngine", "InvoiceCreated", json!({"invoice_id": 1, "subscription_id": 1, "total_amount": "100"})),
+                ],
+            )
+            .unwrap();
+        store
+            .insert_events(
+                "a2",
+                "t2",
+                &[
+                    event(
+                        "SubscriptionManager",
+                        "SubscriptionRenewed",
+                        json!({"subscription_id": 1, "next_billing_at": {"millis": 5}}),
+                    ),
+                    event(
+                        "BillingEngine",
+                        "InvoiceFailed",
+                        json!({"invoice_id": 1, "reason": "Overdue"}),
+                    ),
+                ],
+            )
+            .unwrap();
+        store
+            .insert_events(
+                "a3",
+                "t3",
+                &[event(
+                    "SubscriptionManager",
+                    "Unsubscribed",
+                    json!({"subscription_id": 1, "subscriber": "account-hash-02"}),
+                )],
+            )
+            .unwrap();
+        store.finalize(2).unwrap();
+        let store = Arc::new(Mutex::new(store));
+        let url = serve(casperflow_indexer::api::router(store.clone())).await;
+        (url, store)
+    }
+
+    type Received = Arc<Mutex<Vec<(HeaderMap, String)>>>;
+
+    /// Webhook receiver that fails its first request
+    async fn receiver() -> (String, Received) {
+        let received = Received::default();
+        let router = Router::new()
+            .route(
+                "/hooks",
+                post(
+                    |State(received): State<Received>, headers: HeaderMap, body: String| async move {
+                        let mut received = received.lock().unwrap();
+                        received.push((headers, body));
+                        if received.len() == 1 {
+                            StatusCode::SERVICE_UNAVAILABLE
+                        } else {
+                            StatusCode::OK
+                        }
+                    },
+                ),
+            )
+            .with_state(received.clone());
+        (serve(router).await, received)
+    }
+
+    #[tokio::test]
+    async fn test_delivers_signed_events_with_retries_and_replay() {
+        let (indexer_url, indexer_store) = indexer().await;
+        let (receiver_url, received) = receiver().await;
+        let store = Arc::new(Mutex::new(Store::in_memory().unwrap()));
+        let config = Config {
+            retry: Retry {
+                max_attempts: 3,
+                initial: Duration::ZERO,
+                max: Duration::ZERO,
+            },
+            batch: 2,
+            ..Default::default()
+        };
+        let dispatcher =
+            Dispatcher::new(store.clone(), IndexerClient::new(&indexer_url), config).unwrap();
+        let endpoint = dispatcher
+            .store()
+            .add_endpoint(
+                NewEndpoint {
+                    merchant: MERCHANT.into(),
+                    url: format!("{receiver_url}/hooks"),
+                    event_types: vec![
+                        EventType::SubscriptionStarted,
+                        EventType::PaymentFailed,
+                        EventType::SubscriptionCancelled,
+                    ],
+                    secret: Some("whsec_test".into()),
+                },
+                0,
+            )
+            .unwrap();
+        // Another merchant's endpoint gets nothing
+        dispatcher
+            .store()
+            .add_endpoint(
+                NewEndpoint {
+                    merchant: "account-hash-09".into(),
+                    url: format!("{receiver_url}/other"),
+                    event_types: EventType::ALL.to_vec(),
+                    secret: None,
+                },
+                0,
+            )
+            .unwrap();
+
+        // The renewal is not subscribed to and the cancellation is not final
+        assert_eq!(dispatcher.poll().await.unwrap(), 2);
+        assert_eq!(dispatcher.poll().await.unwrap(), 0);
+        let round = dispatcher.deliver_due().await.unwrap();
+        assert_eq!((round.delivered, round.retried), (1, 1));
+        let round = dispatcher.deliver_due().await.unwrap();
+        assert_eq!((round.delivered, round.retried), (1, 0));
+
+        {
+            let received = received.lock().unwrap();
+            assert_eq!(received.len(), 3);
+            let (headers, body) = &received[2];
+            let signature = headers[SIGNATURE_HEADER].to_str().unwrap();
+            assert!(signing::verify(
+                "whsec_test",
+                signature,
+                body.as_bytes(),
+                now_millis() / 1_000,
+                60
+            ));
+            let webhook: WebhookEvent = serde_json::from_str(body).unwrap();
+            assert_eq!(headers[EVENT_ID_HEADER], webhook.id.as_str());
+            assert_eq!(webhook.merchant, MERCHANT);
+            assert_eq!(webhook.subscription_id, 1);
+        }
+        let delivered = DeliveryFilter {
+            endpoint_id: Some(endpoint.id),
+            status: Some(DeliveryStatus::Delivered),
+            ..Default::default()
+        };
+        let deliveries = dispatcher.store().deliveries(&delivered).unwrap();
+        assert_eq!(deliveries.len(), 2);
+        let attempts = dispatcher.store().attempts(deliveries[0].id).unwrap();
+        assert_eq!(attempts[0].status_code, Some(503));
+        assert_eq!(attempts[1].status_code, Some(200));
+
+        // Once its block is final the cancellation goes out
+        indexer_store.lock().unwrap().finalize(3).unwrap();
+        assert_eq!(dispatcher.poll().await.unwrap(), 1);
+        assert_eq!(dispatcher.deliver_due().await.unwrap().delivered, 1);
+
+        // Replaying from height 2 sends the failed payment and cancellation again
+        assert_eq!(dispatcher.replay(2, Some(endpoint.id)).await.unwrap(), 2);
+        assert_eq!(dispatcher.deliver_due().await.unwrap().delivered, 2);
+        assert_eq!(received.lock().unwrap().len(), 6);
+    }
+
+    #[test]
+    fn test_backoff() {
+        let retry = Retry {
+            max_attempts: 4,
+            initial: Duration::from_secs(10),
+            max: Duration::from_secs(30),
+        };
+        let delays: Vec<_> = (1..=4)
+            .map(|attempts| retry.delay(attempts).as_secs())
+            .collect();
+        assert_eq!(delays, [10, 20, 30, 30]);
+        assert_eq!(retry.outcome(1, 1_000), Outcome::Retry(11_000));
+        assert_eq!(retry.outcome(4, 1_000), Outcome::Failed);
+    }
+}
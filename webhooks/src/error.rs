@@ -0,0 +1,23 @@
+//! Dispatcher error type.
+
+use thiserror::Error;
+
+/// Result alias used throughout the dispatcher
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Errors raised while reading events, storing deliveries or serving the API
+#[derive(Debug, Error)]
+pub enum Error {
+    /// Endpoint registration was rejected
+    #[error("invalid endpoint: {0}")]
+    InvalidEndpoint(String),
+    /// Indexer answered with something the dispatcher does not understand
+    #[error("indexer: {0}")]
+    Indexer(String),
+    #[error("http: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("database: {0}")]
+    Database(#[from] rusqlite::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
@@ -0,0 +1,88 @@
+//! Webhook event types and the payload sent to endpoints.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Billing events merchants can subscribe an endpoint to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum EventType {
+    #[serde(rename = "subscription.started")]
+    SubscriptionStarted,
+    #[serde(rename = "subscription.renewed")]
+    SubscriptionRenewed,
+    #[serde(rename = "payment.failed")]
+    PaymentFailed,
+    #[serde(rename = "subscription.cancelled")]
+    SubscriptionCancelled,
+}
+
+impl EventType {
+    pub const ALL: [EventType; 4] = [
+        Self::SubscriptionStarted,
+        Self::SubscriptionRenewed,
+        Self::PaymentFailed,
+        Self::SubscriptionCancelled,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::SubscriptionStarted => "subscription.started",
+            Self::SubscriptionRenewed => "subscription.renewed",
+            Self::PaymentFailed => "payment.failed",
+            Self::SubscriptionCancelled => "subscription.cancelled",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|event_type| event_type.as_str() == value)
+    }
+
+    /// Webhook type of a contract event, by its CES name
+    pub fn of_contract_event(name: &str) -> Option<Self> {
+        match name {
+            "Subscribed" => Some(Self::SubscriptionStarted),
+            "SubscriptionRenewed" => Some(Self::SubscriptionRenewed),
+            "InvoiceFailed" => Some(Self::PaymentFailed),
+            "Unsubscribed" => Some(Self::SubscriptionCancelled),
+            _ => None,
+        }
+    }
+}
+
+/// Body POSTed to endpoints
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WebhookEvent {
+    /// Stable id, `<transaction hash>-<event index>`; receivers should
+    /// ignore ids they already handled
+    pub id: String,
+    #[serde(rename = "type")]
+    pub event_type: EventType,
+    pub merchant: String,
+    pub subscription_id: u64,
+    pub block_height: u64,
+    pub transaction_hash: String,
+    /// Fields of the contract event
+    pub data: Value,
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_type_names() {
+        for event_type in EventType::ALL {
+            assert_eq!(EventType::parse(event_type.as_str()), Some(event_type));
+            assert_eq!(json!(event_type), json!(event_type.as_str()));
+        }
+        assert_eq!(EventType::parse("invoice.paid"), None);
+        assert_eq!(
+            EventType::of_contract_event("InvoiceFailed"),
+            Some(EventType::PaymentFailed)
+        );
+    }
+}
@@ -0,0 +1,22 @@
+//! CasperFlow webhook dispatcher.
+//!
+//! Reads final contract events from the indexer's REST API and POSTs the
+//! billing ones (subscription started, renewed, cancelled and failed
+//! payments) to the endpoints merchants registered for them. Payloads are
+//! signed with HMAC-SHA256, failed deliveries are retried with exponential
+//! backoff, every attempt is kept in a SQLite delivery log and past events
+//! can be replayed from a block height.
+
+pub mod api;
+pub mod dispatcher;
+pub mod error;
+pub mod event;
+pub mod signing;
+pub mod source;
+pub mod store;
+
+pub use dispatcher::{Config, Dispatcher, Retry};
+pub use error::{Error, Result};
+pub use event::{EventType, WebhookEvent};
+pub use source::IndexerClient;
+pub use store::Store;
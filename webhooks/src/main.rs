@@ -0,0 +1,71 @@
+//! `casperflow_webhooks` binary.
+
+use std::future::IntoFuture;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use clap::Parser;
+use tracing::info;
+use tracing_subscriber::EnvFilter;
+
+use casperflow_webhooks::{api, Config, Dispatcher, IndexerClient, Retry, Store};
+
+#[derive(Parser)]
+#[command(about = "Delivers signed webhooks for CasperFlow billing events")]
+struct Args {
+    /// Indexer API, e.g. http://localhost:8080
+    #[arg(long, env = "CASPERFLOW_INDEXER")]
+    indexer: String,
+    #[arg(long, default_value = "casperflow_webhooks.db")]
+    database: PathBuf,
+    /// Admin API address; keep it private, it hands out endpoint secrets
+    #[arg(long, default_value = "127.0.0.1:8090")]
+    listen: SocketAddr,
+    /// Seconds between polling the indexer and sending due deliveries
+    #[arg(long, default_value_t = 5)]
+    interval: u64,
+    /// Attempts before a delivery is marked failed
+    #[arg(long, default_value_t = Retry::default().max_attempts)]
+    max_attempts: u32,
+    /// Seconds an endpoint has to answer
+    #[arg(long, default_value_t = 10)]
+    timeout: u64,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+        )
+        .init();
+    let args = Args::parse();
+
+    let store = Arc::new(Mutex::new(Store::open(&args.database)?));
+    let config = Config {
+        retry: Retry {
+            max_attempts: args.max_attempts,
+            ..Retry::default()
+        },
+        timeout: Duration::from_secs(args.timeout),
+        ..Config::default()
+    };
+    let dispatcher = Arc::new(Dispatcher::new(
+        store,
+        IndexerClient::new(&args.indexer),
+        config,
+    )?);
+
+    let listener = tokio::net::TcpListener::bind(args.listen).await?;
+    info!(address = %args.listen, "serving admin API");
+    let server = tokio::spawn(axum::serve(listener, api::router(dispatcher.clone())).into_future());
+
+    tokio::select! {
+        _ = dispatcher.run(Duration::from_secs(args.interval)) => {}
+        _ = tokio::signal::ctrl_c() => info!("interrupted"),
+    }
+    server.abort();
+    Ok(())
+}
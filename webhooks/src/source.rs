@@ -0,0 +1,110 @@
+//! Client for the indexer's REST API, the dispatcher's event source.
+
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::error::{Error, Result};
+
+/// Event as served by the indexer's `/events`
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct IndexedEvent {
+    pub id: u64,
+    pub contract: String,
+    pub name: String,
+    pub payload: Value,
+    pub block_hash: String,
+    pub block_height: u64,
+    pub transaction_hash: String,
+    pub event_index: u32,
+}
+
+#[derive(Deserialize)]
+struct Health {
+    checkpoint: Option<Checkpoint>,
+}
+
+#[derive(Deserialize)]
+struct Checkpoint {
+    height: u64,
+}
+
+#[derive(Deserialize)]
+struct Subscription {
+    merchant: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Invoice {
+    subscription_id: u64,
+    merchant: Option<String>,
+}
+
+/// Reads events and projections from a running indexer
+#[derive(Clone)]
+pub struct IndexerClient {
+    http: reqwest::Client,
+    base_url: String,
+}
+
+impl IndexerClient {
+    /// Client for the indexer API at `base_url`, e.g. `http://localhost:8080`
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<Option<T>> {
+        let response = self
+            .http
+            .get(format!("{}{path}", self.base_url))
+            .send()
+            .await?;
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            status if status.is_success() => Ok(Some(response.json().await?)),
+            status => Err(Error::Indexer(format!("GET {path} returned {status}"))),
+        }
+    }
+
+    /// Height of the indexer's finality checkpoint
+    pub async fn final_height(&self) -> Result<Option<u64>> {
+        let health: Health = self
+            .get("/health")
+            .await?
+            .ok_or_else(|| Error::Indexer("no /health".to_string()))?;
+        Ok(health.checkpoint.map(|checkpoint| checkpoint.height))
+    }
+
+    /// Events stored after event id `after`, from blocks at or above
+    /// `from_height`
+    pub async fn events(
+        &self,
+        after: u64,
+        from_height: Option<u64>,
+        limit: u64,
+    ) -> Result<Vec<IndexedEvent>> {
+        let mut path = format!("/events?after={after}&limit={limit}");
+        if let Some(height) = from_height {
+            path.push_str(&format!("&from_height={height}"));
+        }
+        Ok(self.get(&path).await?.unwrap_or_default())
+    }
+
+    /// Merchant of a subscription, from its plan's `PlanCreated`
+    pub async fn subscription_merchant(&self, subscription_id: u64) -> Result<Option<String>> {
+        let subscription: Option<Subscription> = self
+            .get(&format!("/subscriptions/{subscription_id}"))
+            .await?;
+        Ok(subscription.and_then(|subscription| subscription.merchant))
+    }
+
+    /// Subscription and merchant an invoice was issued for
+    pub async fn invoice_owner(&self, invoice_id: u64) -> Result<Option<(u64, String)>> {
+        let invoice: Option<Invoice> = self.get(&format!("/invoices/{invoice_id}")).await?;
+        Ok(invoice.and_then(|invoice| Some((invoice.subscription_id, invoice.merchant?))))
+    }
+}
@@ -0,0 +1,552 @@
+//! SQLite persistence of endpoints, deliveries and their attempts.
+//!
+//! A delivery is one event for one endpoint. It stays `pending` until an
+//! attempt gets a 2xx answer (`delivered`) or the attempts run out
+//! (`failed`); every attempt is kept as the delivery log.
+
+use std::path::Path;
+
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::event::{EventType, WebhookEvent};
+use crate::signing;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS endpoints (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    merchant TEXT NOT NULL,
+    url TEXT NOT NULL,
+    secret TEXT NOT NULL,
+    event_types TEXT NOT NULL,
+    active INTEGER NOT NULL DEFAULT 1,
+    created_at INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS endpoints_by_merchant ON endpoints (merchant);
+CREATE TABLE IF NOT EXISTS deliveries (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    endpoint_id INTEGER NOT NULL REFERENCES endpoints (id),
+    event_id TEXT NOT NULL,
+    event_type TEXT NOT NULL,
+    block_height INTEGER NOT NULL,
+    payload TEXT NOT NULL,
+    status TEXT NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at INTEGER,
+    created_at INTEGER NOT NULL,
+    delivered_at INTEGER,
+    UNIQUE (endpoint_id, event_id)
+);
+CREATE INDEX IF NOT EXISTS deliveries_due ON deliveries (status, next_attempt_at);
+CREATE TABLE IF NOT EXISTS attempts (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    delivery_id INTEGER NOT NULL REFERENCES deliveries (id),
+    attempted_at INTEGER NOT NULL,
+    status_code INTEGER,
+    error TEXT,
+    duration_ms INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS attempts_by_delivery ON attempts (delivery_id);
+CREATE TABLE IF NOT EXISTS cursor (
+    id INTEGER PRIMARY KEY CHECK (id = 0),
+    last_event_id INTEGER NOT NULL
+);
+";
+
+/// Default and maximum page sizes of list queries
+const DEFAULT_LIMIT: u64 = 50;
+const MAX_LIMIT: u64 = 500;
+
+fn limit(requested: Option<u64>) -> u64 {
+    requested.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT)
+}
+
+/// Endpoint registration request
+#[derive(Clone, Debug, Deserialize)]
+pub struct NewEndpoint {
+    /// Merchant account (`account-hash-…`) whose events are sent
+    pub merchant: String,
+    pub url: String,
+    pub event_types: Vec<EventType>,
+    /// Signing secret; generated when omitted
+    pub secret: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Endpoint {
+    pub id: u64,
+    pub merchant: String,
+    pub url: String,
+    /// Only returned when the endpoint is created
+    #[serde(skip_serializing)]
+    pub secret: String,
+    pub event_types: Vec<EventType>,
+    /// False once the endpoint was removed; its deliveries are kept
+    pub active: bool,
+    /// Milliseconds since the Unix epoch
+    pub created_at: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    Pending,
+    Delivered,
+    Failed,
+}
+
+impl DeliveryStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Delivered => "delivered",
+            Self::Failed => "failed",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "delivered" => Self::Delivered,
+            "failed" => Self::Failed,
+            _ => Self::Pending,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct DeliveryFilter {
+    pub endpoint_id: Option<u64>,
+    pub status: Option<DeliveryStatus>,
+    pub event_id: Option<String>,
+    #[serde(default)]
+    pub offset: u64,
+    pub limit: Option<u64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Delivery {
+    pub id: u64,
+    pub endpoint_id: u64,
+    pub event_id: String,
+    pub event_type: String,
+    pub block_height: u64,
+    pub status: DeliveryStatus,
+    pub attempts: u32,
+    pub next_attempt_at: Option<u64>,
+    pub created_at: u64,
+    pub delivered_at: Option<u64>,
+}
+
+/// Delivery due for an attempt, with what is needed to send it
+#[derive(Clone, Debug, PartialEq)]
+pub struct DueDelivery {
+    pub id: u64,
+    pub url: String,
+    pub secret: String,
+    pub event_id: String,
+    pub event_type: String,
+    pub payload: String,
+    pub attempts: u32,
+}
+
+/// One HTTP request made for a delivery
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Attempt {
+    pub attempted_at: u64,
+    /// Response status, `None` if no response arrived
+    pub status_code: Option<u16>,
+    pub error: Option<String>,
+    pub duration_ms: u64,
+}
+
+/// What happens to a delivery after an attempt
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Outcome {
+    Delivered,
+    /// Try again at this time
+    Retry(u64),
+    /// Attempts are exhausted
+    Failed,
+}
+
+/// Dispatcher database
+pub struct Store {
+    conn: Connection,
+}
+
+impl Store {
+    /// Open (or create) the database at `path`
+    pub fn open(path: &Path) -> Result<Self> {
+        Self::init(Connection::open(path)?)
+    }
+
+    /// Database that lives as long as the store
+    pub fn in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> Result<Self> {
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self { conn })
+    }
+
+    pub fn add_endpoint(&mut self, endpoint: NewEndpoint, now: u64) -> Result<Endpoint> {
+        if !endpoint.url.starts_with("http://") && !endpoint.url.starts_with("https://") {
+            return Err(Error::InvalidEndpoint(format!(
+                "{} is not an http(s) URL",
+                endpoint.url
+            )));
+        }
+        if endpoint.event_types.is_empty() {
+            return Err(Error::InvalidEndpoint("no event types".to_string()));
+        }
+        let secret = endpoint.secret.unwrap_or_else(signing::generate_secret);
+        self.conn.execute(
+            "INSERT INTO endpoints (merchant, url, secret, event_types, created_at)
+                VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                endpoint.merchant,
+                endpoint.url,
+                secret,
+                event_types_column(&endpoint.event_types),
+                now
+            ],
+        )?;
+        Ok(Endpoint {
+            id: self.conn.last_insert_rowid() as u64,
+            merchant: endpoint.merchant,
+            url: endpoint.url,
+            secret,
+            event_types: endpoint.event_types,
+            active: true,
+            created_at: now,
+        })
+    }
+
+    pub fn endpoint(&self, id: u64) -> Result<Option<Endpoint>> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT * FROM endpoints WHERE id = ?1",
+                [id],
+                endpoint_from_row,
+            )
+            .optional()?)
+    }
+
+    /// Endpoints, optionally of one merchant
+    pub fn endpoints(&self, merchant: Option<&str>) -> Result<Vec<Endpoint>> {
+        let mut statement = self.conn.prepare_cached(
+            "SELECT * FROM endpoints WHERE (?1 IS NULL OR merchant = ?1) ORDER BY id",
+        )?;
+        let rows = statement.query_map([merchant], endpoint_from_row)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Stop sending to an endpoint; returns false if it does not exist
+    pub fn disable_endpoint(&mut self, id: u64) -> Result<bool> {
+        Ok(self
+            .conn
+            .execute("UPDATE endpoints SET active = 0 WHERE id = ?1", [id])?
+            > 0)
+    }
+
+    /// Active endpoints of `merchant` subscribed to `event_type`
+    pub fn subscribers(&self, merchant: &str, event_type: EventType) -> Result<Vec<Endpoint>> {
+        Ok(self
+            .endpoints(Some(merchant))?
+            .into_iter()
+            .filter(|endpoint| endpoint.active && endpoint.event_types.contains(&event_type))
+            .collect())
+    }
+
+    /// Id of the last indexer event turned into deliveries
+    pub fn cursor(&self) -> Result<u64> {
+        Ok(self
+            .conn
+            .query_row("SELECT last_event_id FROM cursor", [], |row| row.get(0))
+            .optional()?
+            .unwrap_or(0))
+    }
+
+    pub fn set_cursor(&mut self, last_event_id: u64) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO cursor (id, last_event_id) VALUES (0, ?1)",
+            [last_event_id],
+        )?;
+        Ok(())
+    }
+
+    /// Queue `event` for an endpoint; returns false if it was already queued
+    ///
+    /// With `replay`, an existing delivery is sent again from its first
+    /// attempt, whatever its status.
+    pub fn enqueue(
+        &mut self,
+        endpoint_id: u64,
+        event: &WebhookEvent,
+        now: u64,
+        replay: bool,
+    ) -> Result<bool> {
+        let conflict = if replay {
+            "DO UPDATE SET status = 'pending', attempts = 0, next_attempt_at = excluded.next_attempt_at, delivered_at = NULL"
+        } else {
+            "DO NOTHING"
+        };
+        let inserted = self.conn.execute(
+            &format!(
+                "INSERT INTO deliveries
+                    (endpoint_id, event_id, event_type, block_height, payload, status, next_attempt_at, created_at)
+                    VALUES (?1, ?2, ?3, ?4, ?5, 'pending', ?6, ?6)
+                    ON CONFLICT (endpoint_id, event_id) {conflict}"
+            ),
+            params![
+                endpoint_id,
+                event.id,
+                event.event_type.as_str(),
+                event.block_height,
+                serde_json::to_string(event)?,
+                now
+            ],
+        )?;
+        Ok(inserted > 0)
+    }
+
+    /// Send a delivery again from its first attempt; returns false if it
+    /// does not exist
+    pub fn redeliver(&mut self, delivery_id: u64, now: u64) -> Result<bool> {
+        Ok(self.conn.execute(
+            "UPDATE deliveries SET status = 'pending', attempts = 0, next_attempt_at = ?2, delivered_at = NULL
+                WHERE id = ?1",
+            params![delivery_id, now],
+        )? > 0)
+    }
+
+    /// Pending deliveries to active endpoints due by `now`, oldest first
+    pub fn due(&self, now: u64, limit: u64) -> Result<Vec<DueDelivery>> {
+        let mut statement = self.conn.prepare_cached(
+            "SELECT d.id, e.url, e.secret, d.event_id, d.event_type, d.payload, d.attempts
+            FROM deliveries d JOIN endpoints e ON e.id = d.endpoint_id
+            WHERE d.status = 'pending' AND d.next_attempt_at <= ?1 AND e.active
+            ORDER BY d.next_attempt_at, d.id
+            LIMIT ?2",
+        )?;
+        let rows = statement.query_map(params![now, limit], |row| {
+            Ok(DueDelivery {
+                id: row.get("id")?,
+                url: row.get("url")?,
+                secret: row.get("secret")?,
+                event_id: row.get("event_id")?,
+                event_type: row.get("event_type")?,
+                payload: row.get("payload")?,
+                attempts: row.get("attempts")?,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Log an attempt and move the delivery on
+    pub fn record_attempt(
+        &mut self,
+        delivery_id: u64,
+        attempt: &Attempt,
+        outcome: Outcome,
+    ) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        tx.execute(
+            "INSERT INTO attempts (delivery_id, attempted_at, status_code, error, duration_ms)
+                VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                delivery_id,
+                attempt.attempted_at,
+                attempt.status_code,
+                attempt.error,
+                attempt.duration_ms
+            ],
+        )?;
+        let (status, next_attempt_at, delivered_at) = match outcome {
+            Outcome::Delivered => (DeliveryStatus::Delivered, None, Some(attempt.attempted_at)),
+            Outcome::Retry(at) => (DeliveryStatus::Pending, Some(at), None),
+            Outcome::Failed => (DeliveryStatus::Failed, None, None),
+        };
+        tx.execute(
+            "UPDATE deliveries SET attempts = attempts + 1, status = ?2, next_attempt_at = ?3, delivered_at = ?4
+                WHERE id = ?1",
+            params![delivery_id, status.as_str(), next_attempt_at, delivered_at],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn deliveries(&self, filter: &DeliveryFilter) -> Result<Vec<Delivery>> {
+        let mut statement = self.conn.prepare_cached(
+            "SELECT * FROM deliveries
+            WHERE (?1 IS NULL OR endpoint_id = ?1)
+            AND (?2 IS NULL OR status = ?2)
+            AND (?3 IS NULL OR event_id = ?3)
+            ORDER BY id
+            LIMIT ?4 OFFSET ?5",
+        )?;
+        let rows = statement.query_map(
+            params![
+                filter.endpoint_id,
+                filter.status.map(DeliveryStatus::as_str),
+                filter.event_id,
+                limit(filter.limit),
+                filter.offset
+            ],
+            |row| {
+                Ok(Delivery {
+                    id: row.get("id")?,
+                    endpoint_id: row.get("endpoint_id")?,
+                    event_id: row.get("event_id")?,
+                    event_type: row.get("event_type")?,
+                    block_height: row.get("block_height")?,
+                    status: DeliveryStatus::parse(&row.get::<_, String>("status")?),
+                    attempts: row.get("attempts")?,
+                    next_attempt_at: row.get("next_attempt_at")?,
+                    created_at: row.get("created_at")?,
+                    delivered_at: row.get("delivered_at")?,
+                })
+            },
+        )?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Attempts made for a delivery, oldest first
+    pub fn attempts(&self, delivery_id: u64) -> Result<Vec<Attempt>> {
+        let mut statement = self.conn.prepare_cached(
+            "SELECT attempted_at, status_code, error, duration_ms FROM attempts
+            WHERE delivery_id = ?1 ORDER BY id",
+        )?;
+        let rows = statement.query_map([delivery_id], |row| {
+            Ok(Attempt {
+                attempted_at: row.get("attempted_at")?,
+                status_code: row.get("status_code")?,
+                error: row.get("error")?,
+                duration_ms: row.get("duration_ms")?,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+}
+
+fn event_types_column(event_types: &[EventType]) -> String {
+    event_types
+        .iter()
+        .map(|event_type| event_type.as_str())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn endpoint_from_row(row: &Row) -> rusqlite::Result<Endpoint> {
+    let event_types: String = row.get("event_types")?;
+    Ok(Endpoint {
+        id: row.get("id")?,
+        merchant: row.get("merchant")?,
+        url: row.get("url")?,
+        secret: row.get("secret")?,
+        event_types: event_types
+            .split(',')
+            .filter_map(EventType::parse)
+            .collect(),
+        active: row.get("active")?,
+        created_at: row.get("created_at")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn event(id: &str) -> WebhookEvent {
+        WebhookEvent {
+            id: id.into(),
+            event_type: EventType::SubscriptionStarted,
+            merchant: "m".into(),
+            subscription_id: 1,
+            block_height: 4,
+            transaction_hash: "t1".into(),
+            data: json!({"subscription_id": 1}),
+        }
+    }
+
+    #[test]
+    fn test_delivery_lifecycle() {
+        let mut store = Store::in_memory().unwrap();
+        let new = |url: &str, event_types: Vec<EventType>| NewEndpoint {
+            merchant: "m".into(),
+            url: url.into(),
+            event_types,
+            secret: None,
+        };
+        assert!(store
+            .add_endpoint(new("ftp://m", vec![EventType::PaymentFailed]), 1)
+            .is_err());
+        assert!(store.add_endpoint(new("https://m", vec![]), 1).is_err());
+        let endpoint = store
+            .add_endpoint(
+                new("https://m/hooks", vec![EventType::SubscriptionStarted]),
+                1,
+            )
+            .unwrap();
+        assert!(endpoint.secret.starts_with("whsec_"));
+        assert_eq!(store.endpoint(endpoint.id).unwrap(), Some(endpoint.clone()));
+        assert_eq!(
+            store
+                .subscribers("m", EventType::SubscriptionStarted)
+                .unwrap(),
+            std::slice::from_ref(&endpoint)
+        );
+        assert!(store
+            .subscribers("m", EventType::PaymentFailed)
+            .unwrap()
+            .is_empty());
+
+        assert!(store
+            .enqueue(endpoint.id, &event("t1-0"), 10, false)
+            .unwrap());
+        assert!(!store
+            .enqueue(endpoint.id, &event("t1-0"), 10, false)
+            .unwrap());
+        let due = store.due(10, 10).unwrap();
+        assert_eq!((due.len(), due[0].attempts), (1, 0));
+        assert!(store.due(9, 10).unwrap().is_empty());
+
+        let attempt = |status_code| Attempt {
+            attempted_at: 10,
+            status_code,
+            error: None,
+            duration_ms: 3,
+        };
+        store
+            .record_attempt(due[0].id, &attempt(Some(503)), Outcome::Retry(20))
+            .unwrap();
+        assert!(store.due(19, 10).unwrap().is_empty());
+        store
+            .record_attempt(due[0].id, &attempt(Some(200)), Outcome::Delivered)
+            .unwrap();
+        let delivery = &store.deliveries(&Default::default()).unwrap()[0];
+        assert_eq!(
+            (delivery.status, delivery.attempts, delivery.delivered_at),
+            (DeliveryStatus::Delivered, 2, Some(10))
+        );
+        assert_eq!(store.attempts(delivery.id).unwrap().len(), 2);
+
+        // A replay sends a delivered event again
+        assert!(store
+            .enqueue(endpoint.id, &event("t1-0"), 30, true)
+            .unwrap());
+        assert_eq!(store.due(30, 10).unwrap()[0].attempts, 0);
+        // Removed endpoints get nothing
+        assert!(store.disable_endpoint(endpoint.id).unwrap());
+        assert!(store.due(30, 10).unwrap().is_empty());
+        assert!(!store.disable_endpoint(99).unwrap());
+
+        store.set_cursor(7).unwrap();
+        assert_eq!(store.cursor().unwrap(), 7);
+    }
+}